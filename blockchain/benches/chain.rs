@@ -0,0 +1,226 @@
+#![allow(clippy::needless_return)]
+
+use blockchain::{
+    block::{Block, MiningTemplate},
+    blockchain::Blockchain,
+    clock::{MockClock, SequentialNonces},
+    ledger::Ledger,
+    mempool::Mempool,
+    transaction::{OutPoint, Transaction, TxOutput},
+    types::{BlockHeight, Difficulty, Hash256, Nonce},
+};
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput,
+};
+use crypto::{hasher::Sha256Hasher, keys::KeyPair};
+use std::time::{Duration, SystemTime};
+
+const START_MS: u64 = 1_700_000_000_000;
+// Far enough apart that difficulty falls to its minimum and stays there.
+const BLOCK_INTERVAL_MS: u64 = 2_000;
+
+fn mine_at(last_block: &Block, data: String, timestamp: SystemTime) -> Block {
+    return Block::mine_block_using(
+        &Sha256Hasher,
+        last_block,
+        data,
+        None,
+        &MockClock::new(timestamp),
+        &mut SequentialNonces::default(),
+    );
+}
+
+fn mine_chain(length: usize) -> Vec<Block> {
+    let mut chain = vec![Block::genesis()];
+    let mut timestamp = SystemTime::UNIX_EPOCH + Duration::from_millis(START_MS);
+    while chain.len() < length {
+        let block = mine_at(
+            &chain[chain.len() - 1],
+            format!("block {}", chain.len()),
+            timestamp,
+        );
+        chain.push(block);
+        timestamp += Duration::from_millis(BLOCK_INTERVAL_MS);
+    }
+    return chain;
+}
+
+// Each run mines one block whose difficulty adjusts up to `difficulty`, with
+// a different payload per run so runs do not repeat one nonce search.
+fn mining(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mine_block");
+    group.sample_size(20);
+    for difficulty in [8, 12, 16].iter() {
+        let mut last_block = Block::genesis();
+        last_block.difficulty = Difficulty(difficulty - 1);
+        last_block.timestamp = SystemTime::UNIX_EPOCH + Duration::from_millis(START_MS);
+        let timestamp = last_block.timestamp + Duration::from_millis(1);
+        let mut run: u64 = 0;
+        group.bench_function(BenchmarkId::new("difficulty", difficulty), |b| {
+            b.iter(|| {
+                run += 1;
+                return mine_at(&last_block, format!("run {}", run), timestamp);
+            })
+        });
+    }
+    group.finish();
+}
+
+// One mining attempt: hashing the block again with only its nonce changed,
+// from scratch and from the template's midstate.
+fn attempts(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mining_attempt");
+    let mut block = mine_chain(2).pop().unwrap();
+    block.data = "ab".repeat(512);
+    block.state_root = Some(Hash256([3; 32]));
+    let template = MiningTemplate::new(
+        &Sha256Hasher,
+        &block.timestamp,
+        &block.last_hash,
+        &block.data,
+        block.difficulty,
+        &block.state_root,
+    );
+    let mut nonce: u64 = 0;
+    group.bench_function("compute_hash", |b| {
+        b.iter(|| {
+            nonce += 1;
+            block.nonce = Nonce(nonce);
+            return Block::compute_hash(&Sha256Hasher, black_box(&block));
+        })
+    });
+    group.bench_function("midstate", |b| {
+        b.iter(|| {
+            nonce += 1;
+            return template.hash(black_box(Nonce(nonce)));
+        })
+    });
+    group.finish();
+}
+
+fn validation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("is_valid_chain");
+    group.sample_size(10);
+    for length in [1_000, 10_000, 100_000].iter() {
+        let chain = mine_chain(*length);
+        group.throughput(Throughput::Elements(*length as u64));
+        group.bench_function(BenchmarkId::new("blocks", length), |b| {
+            b.iter(|| assert!(Blockchain::is_valid_chain(black_box(&chain))))
+        });
+    }
+    group.finish();
+}
+
+fn payments(count: usize) -> (Ledger, Vec<Transaction>) {
+    let key_pair = KeyPair::generate();
+    let outpoints: Vec<OutPoint> = (0..count)
+        .map(|i| OutPoint {
+            txid: [7; 32],
+            index: i as u32,
+        })
+        .collect();
+    let ledger = Ledger::from_utxos(outpoints.iter().map(|outpoint| {
+        let output = TxOutput {
+            amount: 100,
+            address: key_pair.address(),
+        };
+        return (*outpoint, output);
+    }));
+    let transactions = outpoints
+        .iter()
+        .map(|outpoint| {
+            let outputs = vec![TxOutput {
+                amount: 90,
+                address: "cd".repeat(32),
+            }];
+            return Transaction::new_signed(&[(*outpoint, &key_pair)], outputs, 0);
+        })
+        .collect();
+    return (ledger, transactions);
+}
+
+fn serialization(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialization");
+    let chain = mine_chain(100);
+    let block = &chain[chain.len() - 1];
+    let block_json = serde_json::to_string(block).unwrap();
+    let chain_json = serde_json::to_string(&chain).unwrap();
+    let (_, transactions) = payments(1);
+    let transaction = &transactions[0];
+    let transaction_hex = transaction.encode_hex();
+    group.bench_function("block_to_json", |b| {
+        b.iter(|| serde_json::to_string(black_box(block)).unwrap())
+    });
+    group.bench_function("block_from_json", |b| {
+        b.iter(|| serde_json::from_str::<Block>(black_box(&block_json)).unwrap())
+    });
+    group.throughput(Throughput::Elements(chain.len() as u64));
+    group.bench_function("chain_to_json", |b| {
+        b.iter(|| serde_json::to_string(black_box(&chain)).unwrap())
+    });
+    group.bench_function("chain_from_json", |b| {
+        b.iter(|| serde_json::from_str::<Vec<Block>>(black_box(&chain_json)).unwrap())
+    });
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("transaction_encode_hex", |b| {
+        b.iter(|| black_box(transaction).encode_hex())
+    });
+    group.bench_function("transaction_decode_hex", |b| {
+        b.iter(|| Transaction::decode_hex(black_box(&transaction_hex)).unwrap())
+    });
+    group.finish();
+}
+
+fn mempool(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mempool");
+    group.sample_size(10);
+    for count in [10, 100, 500].iter() {
+        let (ledger, transactions) = payments(*count);
+        let mut full = Mempool::new();
+        for transaction in transactions.iter() {
+            full.add(transaction.clone(), &ledger, BlockHeight(1))
+                .unwrap();
+        }
+        let last_txid = transactions[count - 1].txid();
+        group.throughput(Throughput::Elements(*count as u64));
+        group.bench_function(BenchmarkId::new("add", count), |b| {
+            b.iter_batched(
+                || (Mempool::new(), transactions.clone()),
+                |(mut mempool, transactions)| {
+                    for transaction in transactions {
+                        mempool.add(transaction, &ledger, BlockHeight(1)).unwrap();
+                    }
+                    return mempool;
+                },
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_function(BenchmarkId::new("contains", count), |b| {
+            b.iter(|| full.contains(black_box(&last_txid)))
+        });
+        group.bench_function(BenchmarkId::new("revalidate", count), |b| {
+            b.iter_batched(
+                || full.clone(),
+                |mut mempool| {
+                    mempool.revalidate(&ledger, BlockHeight(1));
+                    return mempool;
+                },
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_function(BenchmarkId::new("fees", count), |b| {
+            b.iter(|| full.fees(black_box(&ledger), BlockHeight(1)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    mining,
+    attempts,
+    validation,
+    serialization,
+    mempool
+);
+criterion_main!(benches);
@@ -0,0 +1,14 @@
+#![no_main]
+
+use blockchain::block::Block;
+use libfuzzer_sys::fuzz_target;
+
+// Any block the decoder accepts encodes back to the same block.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(block) = serde_json::from_slice::<Block>(data) {
+        let encoded = serde_json::to_vec(&block).unwrap();
+        let decoded: Block = serde_json::from_slice(&encoded).unwrap();
+        assert_eq!(decoded, block);
+        let _ = Block::is_valid_block(&block, &Block::genesis().hash, Block::genesis().difficulty);
+    }
+});
@@ -0,0 +1,14 @@
+#![no_main]
+
+use blockchain::{block::Block, blockchain::Blockchain};
+use libfuzzer_sys::fuzz_target;
+
+// Validating an untrusted chain never panics, and no chain that validates
+// starts anywhere but genesis.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(chain) = serde_json::from_slice::<Vec<Block>>(data) {
+        if Blockchain::is_valid_chain(&chain) {
+            assert_eq!(chain[0], Block::genesis());
+        }
+    }
+});
@@ -0,0 +1,19 @@
+#![no_main]
+
+use blockchain::transaction::Transaction;
+use libfuzzer_sys::fuzz_target;
+
+// Decoding untrusted transactions, as JSON or as a block payload, never
+// panics, and what decodes keeps its txid through a round trip.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(transaction) = serde_json::from_slice::<Transaction>(data) {
+        let decoded = Transaction::decode_hex(&transaction.encode_hex()).unwrap();
+        assert_eq!(decoded, transaction);
+        assert_eq!(decoded.txid(), transaction.txid());
+        let _ = transaction.sighash();
+        let _ = transaction.output_total();
+    }
+    if let Ok(payload) = std::str::from_utf8(data) {
+        let _ = Transaction::decode_payload(payload);
+    }
+});
@@ -0,0 +1,321 @@
+use crate::{
+    block::{Block, BlockHeader},
+    clock::{Clock, SystemClock},
+    config::MINE_RATE,
+    consensus::{self, BlockContents, ConsensusEngine},
+    ledger::Ledger,
+    transaction::Transaction,
+};
+
+use crypto::{
+    hasher::ChainHasher,
+    keys::{self, KeyPair},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::SystemTime,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthorityParams {
+    pub slot_ms: u64,
+    // Addresses that sign blocks until votes change the set.
+    pub authorities: Vec<String>,
+}
+
+impl Default for AuthorityParams {
+    fn default() -> AuthorityParams {
+        return AuthorityParams {
+            slot_ms: MINE_RATE,
+            authorities: Vec::new(),
+        };
+    }
+}
+
+impl AuthorityParams {
+    pub fn check(&self) -> Result<(), String> {
+        if self.slot_ms == 0 {
+            return Err(String::from("slot length must be positive"));
+        }
+        if self.authorities.is_empty() {
+            return Err(String::from(
+                "proof of authority needs at least one authority",
+            ));
+        }
+        for address in self.authorities.iter() {
+            if !keys::is_valid_address(address) {
+                return Err(format!("invalid authority {}", address));
+            }
+        }
+        return Ok(());
+    }
+}
+
+// An authority's vote to add `candidate` to the authority set or remove it.
+// Votes only count in the epoch they were cast for, which ends whenever the
+// set changes, so they cannot be replayed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthorityVote {
+    #[serde(with = "hex::serde")]
+    pub voter: [u8; keys::PUBLIC_KEY_LENGTH],
+    pub candidate: String,
+    pub add: bool,
+    pub epoch: u64,
+    #[serde(with = "hex::serde")]
+    pub signature: Vec<u8>,
+}
+
+impl AuthorityVote {
+    pub fn new_signed(key_pair: &KeyPair, candidate: &str, add: bool, epoch: u64) -> AuthorityVote {
+        let mut vote = AuthorityVote {
+            voter: key_pair.public_key(),
+            candidate: String::from(candidate),
+            add,
+            epoch,
+            signature: Vec::new(),
+        };
+        vote.signature = key_pair.sign(&vote.message()).to_vec();
+        return vote;
+    }
+
+    pub fn message(&self) -> Vec<u8> {
+        let mut message = b"authority vote".to_vec();
+        message.extend_from_slice(&self.epoch.to_be_bytes());
+        message.push(self.add as u8);
+        message.extend_from_slice(self.candidate.as_bytes());
+        return message;
+    }
+
+    pub fn voter_address(&self) -> String {
+        return keys::address(&self.voter);
+    }
+
+    pub fn verify(&self) -> bool {
+        return keys::verify(&self.voter, &self.message(), &self.signature);
+    }
+}
+
+// The authority votes in a block's transaction payload, in order.
+pub fn payload_votes(data: &str) -> Vec<AuthorityVote> {
+    return Transaction::decode_payload(data)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|transaction| transaction.vote)
+        .collect();
+}
+
+// The authorities after a sequence of votes. A proposal passes once more than
+// half of the current authorities vote for it; that starts a new epoch and
+// discards every open tally. Within an epoch a candidate can only be voted in
+// if it is not a member and out if it is, so tallies are kept per candidate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthoritySet {
+    members: BTreeSet<String>,
+    epoch: u64,
+    tallies: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl AuthoritySet {
+    pub fn new(authorities: &[String]) -> AuthoritySet {
+        return AuthoritySet {
+            members: authorities.iter().cloned().collect(),
+            epoch: 0,
+            tallies: BTreeMap::new(),
+        };
+    }
+
+    pub fn members(&self) -> Vec<String> {
+        return self.members.iter().cloned().collect();
+    }
+
+    pub fn contains(&self, address: &str) -> bool {
+        return self.members.contains(address);
+    }
+
+    pub fn epoch(&self) -> u64 {
+        return self.epoch;
+    }
+
+    // Authorities take turns in address order.
+    pub fn leader(&self, slot: u64) -> Option<String> {
+        if self.members.is_empty() {
+            return None;
+        }
+        let turn = (slot % self.members.len() as u64) as usize;
+        return self.members.iter().nth(turn).cloned();
+    }
+
+    // Counts a vote whose signature the ledger has already checked.
+    pub fn apply(&mut self, vote: &AuthorityVote) -> Result<(), String> {
+        let voter = vote.voter_address();
+        if !self.contains(&voter) {
+            return Err(format!("{} is not an authority", voter));
+        }
+        if vote.epoch != self.epoch {
+            return Err(format!(
+                "vote is for epoch {} but the authority set is at epoch {}",
+                vote.epoch, self.epoch
+            ));
+        }
+        if vote.add == self.contains(&vote.candidate) {
+            return Err(format!(
+                "vote would not change whether {} is an authority",
+                vote.candidate
+            ));
+        }
+        if !vote.add && self.members.len() == 1 {
+            return Err(String::from("cannot remove the last authority"));
+        }
+        let tally = self.tallies.entry(vote.candidate.clone()).or_default();
+        if !tally.insert(voter.clone()) {
+            return Err(format!("{} already voted on {}", voter, vote.candidate));
+        }
+        if tally.len() > self.members.len() / 2 {
+            if vote.add {
+                self.members.insert(vote.candidate.clone());
+            } else {
+                self.members.remove(&vote.candidate);
+            }
+            self.epoch += 1;
+            self.tallies.clear();
+        }
+        return Ok(());
+    }
+}
+
+// Blocks are sealed by the configured authorities in turn, one slot each, and
+// carry no proof of work.
+#[derive(Debug)]
+pub struct ProofOfAuthorityEngine {
+    hasher: Box<dyn ChainHasher>,
+    params: AuthorityParams,
+    clock: Arc<dyn Clock>,
+}
+
+impl ProofOfAuthorityEngine {
+    pub fn new(hasher: Box<dyn ChainHasher>, params: AuthorityParams) -> ProofOfAuthorityEngine {
+        return ProofOfAuthorityEngine::with_clock(hasher, params, Arc::new(SystemClock));
+    }
+
+    pub fn with_clock(
+        hasher: Box<dyn ChainHasher>,
+        params: AuthorityParams,
+        clock: Arc<dyn Clock>,
+    ) -> ProofOfAuthorityEngine {
+        return ProofOfAuthorityEngine {
+            hasher,
+            params,
+            clock,
+        };
+    }
+
+    // The set `ledger` has reached, or the configured authorities for a
+    // ledger that does not carry one.
+    pub fn authority_set(&self, ledger: &Ledger) -> AuthoritySet {
+        match ledger.authorities() {
+            Some(authorities) => return authorities.clone(),
+            None => return AuthoritySet::new(&self.params.authorities),
+        }
+    }
+}
+
+impl ConsensusEngine for ProofOfAuthorityEngine {
+    fn produce_block(
+        &self,
+        last_block: &Block,
+        ledger: &Ledger,
+        contents: BlockContents,
+        signer: Option<&KeyPair>,
+    ) -> Result<Block, String> {
+        if !contents.evidence.is_empty() {
+            return Err(String::from(
+                "proof of authority blocks cannot carry slashing evidence",
+            ));
+        }
+        let authorities = self.authority_set(ledger);
+        return consensus::produce_sealed(
+            self.hasher.as_ref(),
+            self.params.slot_ms,
+            self.clock.as_ref(),
+            last_block,
+            contents,
+            signer,
+            |slot| authorities.leader(slot),
+        );
+    }
+
+    fn next_block_time(
+        &self,
+        last_block: &Block,
+        ledger: &Ledger,
+        signer: Option<&KeyPair>,
+    ) -> Result<SystemTime, String> {
+        let authorities = self.authority_set(ledger);
+        let slot = consensus::next_leader_slot(
+            self.params.slot_ms,
+            self.clock.as_ref(),
+            last_block,
+            signer,
+            |slot| authorities.leader(slot),
+        )?;
+        return Ok(consensus::slot_start(self.params.slot_ms, slot));
+    }
+
+    fn validate_block(
+        &self,
+        block: &Block,
+        last_block: &Block,
+        ledger: &Ledger,
+    ) -> Result<(), String> {
+        self.validate_proof(block, last_block)?;
+        return self.validate_state(block, last_block, ledger);
+    }
+
+    fn validate_proof(&self, block: &Block, last_block: &Block) -> Result<(), String> {
+        consensus::validate_seal(
+            self.hasher.as_ref(),
+            self.params.slot_ms,
+            self.clock.as_ref(),
+            block,
+            last_block,
+        )?;
+        if !block.evidence.is_empty() {
+            return Err(String::from(
+                "proof of authority blocks cannot carry slashing evidence",
+            ));
+        }
+        return Ok(());
+    }
+
+    fn validate_state(
+        &self,
+        block: &Block,
+        _last_block: &Block,
+        ledger: &Ledger,
+    ) -> Result<(), String> {
+        let mut authorities = self.authority_set(ledger);
+        consensus::validate_leader(block, |slot| authorities.leader(slot))?;
+        for vote in payload_votes(&block.data).iter() {
+            authorities.apply(vote)?;
+        }
+        return Ok(());
+    }
+
+    fn validate_header(&self, header: &BlockHeader, last_header: &BlockHeader) -> bool {
+        return consensus::is_valid_sealed_header(self.hasher.as_ref(), header, last_header);
+    }
+
+    fn validate_transaction(
+        &self,
+        transaction: &Transaction,
+        ledger: &Ledger,
+    ) -> Result<(), String> {
+        match &transaction.vote {
+            Some(vote) => return self.authority_set(ledger).apply(vote),
+            None => return Ok(()),
+        }
+    }
+}
@@ -1,61 +1,175 @@
-use crypto::cryptohash;
+use crypto::{
+    cryptohash,
+    hasher::{ChainHasher, Midstate, Sha256Hasher},
+};
 
-use crate::config::*;
+use crate::{
+    clock::{Clock, MockClock, NonceSource, SequentialNonces, SystemClock},
+    config::*,
+    consensus::{BlockSeal, DoubleSign},
+    types::{Difficulty, Hash256, Nonce},
+};
 
-use std::{cmp, collections::BTreeMap, time::SystemTime};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::SystemTime};
 
-#[derive(Debug, PartialEq)]
+// How many nonces a miner tries for each reading of its clock.
+const NONCES_PER_TIMESTAMP: usize = 4096;
+const NONCE_KEY: &str = "nonce";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Block {
     pub timestamp: SystemTime,
-    pub last_hash: [u8; 32],
-    pub hash: [u8; 32],
+    pub last_hash: Hash256,
+    pub hash: Hash256,
     pub data: String,
-    pub nonce: usize,
-    pub difficulty: usize,
+    pub nonce: Nonce,
+    pub difficulty: Difficulty,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_root: Option<Hash256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seal: Option<BlockSeal>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub evidence: Vec<DoubleSign>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub timestamp: SystemTime,
+    pub last_hash: Hash256,
+    pub hash: Hash256,
+    // Commits to the block's data and evidence, so the header alone is
+    // enough to recompute the hash.
+    pub data_root: Hash256,
+    pub nonce: Nonce,
+    pub difficulty: Difficulty,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_root: Option<Hash256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seal: Option<BlockSeal>,
 }
 
 impl Block {
     pub fn genesis() -> Block {
         Block {
             timestamp: SystemTime::UNIX_EPOCH,
-            last_hash: [0; 32],
-            hash: [255; 32],
+            last_hash: Hash256::ZERO,
+            hash: Hash256([255; 32]),
             data: String::from("genesis block"),
-            nonce: 0,
-            difficulty: 8,
+            nonce: Nonce(0),
+            difficulty: Difficulty(8),
+            state_root: None,
+            seal: None,
+            evidence: Vec::new(),
         }
     }
 
+    pub fn header(&self) -> BlockHeader {
+        return BlockHeader {
+            timestamp: self.timestamp,
+            last_hash: self.last_hash,
+            hash: self.hash,
+            data_root: Block::compute_data_root(&self.data, &self.evidence),
+            nonce: self.nonce,
+            difficulty: self.difficulty,
+            state_root: self.state_root,
+            seal: self.seal.clone(),
+        };
+    }
+
     pub fn mine_block(last_block: &Block, data: String) -> Block {
-        let mut timestamp: SystemTime;
-        let mut difficulty: usize;
-        let mut nonce: usize = 0;
-        let last_hash: [u8; 32] = last_block.hash.clone();
-        let mut hash: [u8; 32] = [0; 32];
+        return Block::mine_block_with_state(last_block, data, None);
+    }
+
+    // `state_root` commits to the UTXO set after this block is applied and is
+    // covered by the block hash.
+    pub fn mine_block_with_state(
+        last_block: &Block,
+        data: String,
+        state_root: Option<Hash256>,
+    ) -> Block {
+        return Block::mine_block_with(&Sha256Hasher, last_block, data, state_root);
+    }
+
+    pub fn mine_block_with(
+        hasher: &dyn ChainHasher,
+        last_block: &Block,
+        data: String,
+        state_root: Option<Hash256>,
+    ) -> Block {
+        return Block::mine_block_using(
+            hasher,
+            last_block,
+            data,
+            state_root,
+            &SystemClock,
+            &mut SequentialNonces::default(),
+        );
+    }
+
+    // Mines with every attempt stamped `timestamp`, e.g. a simulated clock's
+    // time, so the same inputs always give the same block.
+    pub fn mine_block_at(
+        hasher: &dyn ChainHasher,
+        last_block: &Block,
+        data: String,
+        state_root: Option<Hash256>,
+        timestamp: SystemTime,
+    ) -> Block {
+        return Block::mine_block_using(
+            hasher,
+            last_block,
+            data,
+            state_root,
+            &MockClock::new(timestamp),
+            &mut SequentialNonces::default(),
+        );
+    }
+
+    // Attempts are stamped by `clock`, which also drives the difficulty
+    // adjustment and is read again every `NONCES_PER_TIMESTAMP` nonces drawn
+    // from `nonces`.
+    pub fn mine_block_using(
+        hasher: &dyn ChainHasher,
+        last_block: &Block,
+        data: String,
+        state_root: Option<Hash256>,
+        clock: &dyn Clock,
+        nonces: &mut dyn NonceSource,
+    ) -> Block {
+        let last_hash: Hash256 = last_block.hash;
         loop {
-            nonce += 1;
-            timestamp = SystemTime::now();
-            difficulty = Block::adjust_difficulty(&last_block, &timestamp);
-            cryptohash::hash(
-                &Block::get_data_map(&timestamp, &last_hash, &data, nonce, difficulty),
-                &mut hash,
+            let timestamp = clock.now();
+            let difficulty = Block::adjust_difficulty(last_block, &timestamp);
+            let template = MiningTemplate::new(
+                hasher,
+                &timestamp,
+                &last_hash,
+                &data,
+                difficulty,
+                &state_root,
             );
-            if cryptohash::is_valid_hash(&hash, difficulty) {
-                break;
+            for _ in 0..NONCES_PER_TIMESTAMP {
+                let nonce = nonces.next_nonce();
+                let hash = template.hash(nonce);
+                if cryptohash::is_valid_hash(hash.as_bytes(), difficulty.bits()) {
+                    return Block {
+                        timestamp,
+                        last_hash,
+                        hash,
+                        data,
+                        nonce,
+                        difficulty,
+                        state_root,
+                        seal: None,
+                        evidence: Vec::new(),
+                    };
+                }
             }
         }
-
-        Block {
-            timestamp,
-            last_hash,
-            hash,
-            data,
-            nonce,
-            difficulty,
-        }
     }
 
-    pub fn adjust_difficulty(last_block: &Block, new_timestamp: &SystemTime) -> usize {
+    pub fn adjust_difficulty(last_block: &Block, new_timestamp: &SystemTime) -> Difficulty {
         let mut difficulty = last_block.difficulty;
         if last_block.difficulty < DIFFICULTY_MIN {
             return DIFFICULTY_MIN;
@@ -65,20 +179,20 @@ impl Block {
         match new_timestamp.duration_since(last_block.timestamp) {
             Ok(elapsed) => {
                 if elapsed.as_millis() < MINE_RATE as u128 {
-                    difficulty += 1;
+                    difficulty = difficulty + 1;
                 } else {
-                    difficulty -= 1;
+                    difficulty = difficulty - 1;
                 }
             }
             Err(e) => {
                 println!("Error: {:?}", e);
-                difficulty += 1;
+                difficulty = difficulty + 1;
             }
         }
-        return cmp::max(cmp::min(DIFFICULTY_MAX, difficulty), DIFFICULTY_MIN);
+        return difficulty.clamp(DIFFICULTY_MIN, DIFFICULTY_MAX);
     }
 
-    pub fn is_valid_difficulty(last_difficulty: usize, new_difficulty: usize) -> bool {
+    pub fn is_valid_difficulty(last_difficulty: Difficulty, new_difficulty: Difficulty) -> bool {
         if last_difficulty < DIFFICULTY_MIN {
             return new_difficulty == DIFFICULTY_MIN;
         }
@@ -86,7 +200,7 @@ impl Block {
             return new_difficulty == DIFFICULTY_MAX;
         }
         if new_difficulty == last_difficulty + 1 || new_difficulty == last_difficulty - 1 {
-            return new_difficulty >= DIFFICULTY_MIN && new_difficulty <= DIFFICULTY_MAX;
+            return (DIFFICULTY_MIN..=DIFFICULTY_MAX).contains(&new_difficulty);
         }
         if new_difficulty == DIFFICULTY_MIN || new_difficulty == DIFFICULTY_MAX {
             return last_difficulty == new_difficulty;
@@ -96,52 +210,215 @@ impl Block {
 
     pub fn is_valid_block(
         block: &Block,
-        last_block_hash: &[u8],
-        last_block_difficulty: usize,
+        last_block_hash: &Hash256,
+        last_block_difficulty: Difficulty,
     ) -> bool {
-        let Block {
-            timestamp,
-            last_hash,
-            data,
-            nonce,
-            difficulty,
-            hash,
-        } = block;
-        if last_hash != last_block_hash {
+        return Block::is_valid_block_with(
+            &Sha256Hasher,
+            block,
+            last_block_hash,
+            last_block_difficulty,
+        );
+    }
+
+    pub fn is_valid_block_with(
+        hasher: &dyn ChainHasher,
+        block: &Block,
+        last_block_hash: &Hash256,
+        last_block_difficulty: Difficulty,
+    ) -> bool {
+        if block.last_hash != *last_block_hash {
             return false;
         }
         if !Block::is_valid_difficulty(last_block_difficulty, block.difficulty) {
             return false;
         }
-        let mut expected_hash: [u8; 32] = [0; 32];
-        cryptohash::hash(
-            &Block::get_data_map(timestamp, last_hash, data, *nonce, *difficulty),
-            &mut expected_hash,
-        );
-        if hash != &expected_hash {
+        if block.hash != Block::compute_hash(hasher, block) {
             return false;
         }
-        if !cryptohash::is_valid_hash(hash, *difficulty) {
+        if !cryptohash::is_valid_hash(block.hash.as_bytes(), block.difficulty.bits()) {
             return false;
         }
         return true;
     }
 
+    // Hashes everything in the block except the seal signature, which signs
+    // the hash. The body is covered through the header's data root.
+    pub fn compute_hash(hasher: &dyn ChainHasher, block: &Block) -> Hash256 {
+        return block.header().compute_hash(hasher);
+    }
+
+    // The data root of a block body: its data and any slashing evidence.
+    pub fn compute_data_root(data: &str, evidence: &[DoubleSign]) -> Hash256 {
+        let mut body_map = BTreeMap::<String, String>::new();
+        body_map.insert("data".to_string(), format!("{:?}", data));
+        if !evidence.is_empty() {
+            let evidence = serde_json::to_string(evidence).unwrap();
+            body_map.insert("evidence".to_string(), evidence);
+        }
+        let mut data_root: [u8; 32] = [0; 32];
+        cryptohash::hash(&body_map, &mut data_root);
+        return Hash256(data_root);
+    }
+
+    pub fn is_valid_header(
+        header: &BlockHeader,
+        last_block_hash: &Hash256,
+        last_block_difficulty: Difficulty,
+    ) -> bool {
+        return Block::is_valid_header_with(
+            &Sha256Hasher,
+            header,
+            last_block_hash,
+            last_block_difficulty,
+        );
+    }
+
+    // Checks what a header alone can prove once its block body is pruned: it
+    // links to the previous block, its difficulty follows on, its hash covers
+    // its fields and meets that difficulty.
+    pub fn is_valid_header_with(
+        hasher: &dyn ChainHasher,
+        header: &BlockHeader,
+        last_block_hash: &Hash256,
+        last_block_difficulty: Difficulty,
+    ) -> bool {
+        if header.last_hash != *last_block_hash {
+            return false;
+        }
+        if !Block::is_valid_difficulty(last_block_difficulty, header.difficulty) {
+            return false;
+        }
+        if header.hash != header.compute_hash(hasher) {
+            return false;
+        }
+        return cryptohash::is_valid_hash(header.hash.as_bytes(), header.difficulty.bits());
+    }
+
     pub fn get_data_map(
         timestamp: &SystemTime,
-        last_hash: &[u8; 32],
-        data: &String,
-        nonce: usize,
-        difficulty: usize,
+        last_hash: &Hash256,
+        data: &str,
+        nonce: Nonce,
+        difficulty: Difficulty,
+    ) -> BTreeMap<String, String> {
+        let data_root = Block::compute_data_root(data, &[]);
+        return Block::get_header_map(timestamp, last_hash, &data_root, nonce, difficulty);
+    }
+
+    fn get_header_map(
+        timestamp: &SystemTime,
+        last_hash: &Hash256,
+        data_root: &Hash256,
+        nonce: Nonce,
+        difficulty: Difficulty,
     ) -> BTreeMap<String, String> {
         let mut data_map = BTreeMap::<String, String>::new();
         data_map.insert("timestamp".to_string(), format!("{:?}", timestamp));
-        data_map.insert("last_hash".to_string(), format!("{:?}", last_hash));
-        data_map.insert("data".to_string(), format!("{:?}", data));
-        data_map.insert("nonce".to_string(), format!("{:?}", nonce));
-        data_map.insert("difficulty".to_string(), format!("{:?}", difficulty));
+        data_map.insert("last_hash".to_string(), format!("{:?}", last_hash.0));
+        data_map.insert("data_root".to_string(), format!("{:?}", data_root.0));
+        data_map.insert(NONCE_KEY.to_string(), format!("{:?}", nonce.0));
+        data_map.insert("difficulty".to_string(), format!("{:?}", difficulty.0));
         return data_map;
     }
+
+    // Blocks without a state root hash exactly as they did before it existed.
+    fn insert_state_root(data_map: &mut BTreeMap<String, String>, state_root: &Option<Hash256>) {
+        if let Some(state_root) = state_root {
+            data_map.insert("state_root".to_string(), format!("{:?}", state_root.0));
+        }
+    }
+}
+
+impl BlockHeader {
+    // Hashes every field but the hash itself and the seal signature, which
+    // signs the hash.
+    pub fn compute_hash(&self, hasher: &dyn ChainHasher) -> Hash256 {
+        let mut data_map = Block::get_header_map(
+            &self.timestamp,
+            &self.last_hash,
+            &self.data_root,
+            self.nonce,
+            self.difficulty,
+        );
+        Block::insert_state_root(&mut data_map, &self.state_root);
+        if let Some(seal) = &self.seal {
+            data_map.insert("validator".to_string(), hex::encode(seal.validator));
+        }
+        let mut hash: [u8; 32] = [0; 32];
+        cryptohash::hash_with(hasher, &data_map, &mut hash);
+        return Hash256(hash);
+    }
+}
+
+// The hash preimage of an unsealed block split around its nonce. Entries
+// sorted before the nonce are hashed once into a midstate, so each attempt
+// only hashes the nonce and the entries after it, without allocating. Hashes
+// match `Block::compute_hash` for the same block.
+pub struct MiningTemplate<'a> {
+    hasher: &'a dyn ChainHasher,
+    midstate: Option<Box<dyn Midstate>>,
+    prefix: Vec<u8>,
+    suffix: Vec<u8>,
+}
+
+impl<'a> MiningTemplate<'a> {
+    pub fn new(
+        hasher: &'a dyn ChainHasher,
+        timestamp: &SystemTime,
+        last_hash: &Hash256,
+        data: &str,
+        difficulty: Difficulty,
+        state_root: &Option<Hash256>,
+    ) -> MiningTemplate<'a> {
+        let mut data_map = Block::get_data_map(timestamp, last_hash, data, Nonce(0), difficulty);
+        Block::insert_state_root(&mut data_map, state_root);
+        data_map.remove(NONCE_KEY);
+        let mut prefix = b"|".to_vec();
+        let mut suffix = Vec::new();
+        for (key, value) in data_map.iter() {
+            let part = match key.as_str() < NONCE_KEY {
+                true => &mut prefix,
+                false => &mut suffix,
+            };
+            cryptohash::push_entry(part, key, value.as_bytes());
+        }
+        return MiningTemplate {
+            hasher,
+            midstate: hasher.midstate(&prefix),
+            prefix,
+            suffix,
+        };
+    }
+
+    pub fn hash(&self, nonce: Nonce) -> Hash256 {
+        let mut buffer = [0; 20];
+        let nonce = decimal(nonce.0, &mut buffer);
+        match &self.midstate {
+            Some(midstate) => {
+                return Hash256(midstate.finish(&[b" nonce:", nonce, b" |", &self.suffix]))
+            }
+            None => {
+                let mut preimage = self.prefix.clone();
+                cryptohash::push_entry(&mut preimage, NONCE_KEY, nonce);
+                preimage.extend_from_slice(&self.suffix);
+                return Hash256(self.hasher.digest(&preimage));
+            }
+        }
+    }
+}
+
+// Writes `n` as `format!("{:?}", n)` would, into the end of `buffer`.
+fn decimal(mut n: u64, buffer: &mut [u8; 20]) -> &[u8] {
+    let mut start = buffer.len();
+    loop {
+        start -= 1;
+        buffer[start] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            return &buffer[start..];
+        }
+    }
 }
 
 // impl PartialEq for Block {
@@ -158,12 +435,15 @@ impl Block {
 impl Clone for Block {
     fn clone(&self) -> Block {
         return Block {
-            timestamp: self.timestamp.clone(),
-            last_hash: self.last_hash.clone(),
-            hash: self.hash.clone(),
+            timestamp: self.timestamp,
+            last_hash: self.last_hash,
+            hash: self.hash,
             data: self.data.clone(),
             nonce: self.nonce,
             difficulty: self.difficulty,
+            state_root: self.state_root,
+            seal: self.seal.clone(),
+            evidence: self.evidence.clone(),
         };
     }
 }
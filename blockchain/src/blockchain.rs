@@ -1,37 +1,885 @@
-use crate::block::Block;
+use crate::{
+    authority::AuthoritySet,
+    block::{Block, BlockHeader},
+    clock::{Clock, SystemClock},
+    config::{MINING_REWARD, PARALLEL_VALIDATION_MIN},
+    consensus::{BlockContents, ConsensusEngine, DoubleSign, ProofOfWorkEngine},
+    events::{ChainEvent, EventBus},
+    finality::{FinalityCertificate, FinalityGadget, FinalityVote, ValidatorSet},
+    index::{ChainIndex, TxLocation},
+    ledger::Ledger,
+    mempool::Mempool,
+    params::{ChainParams, Consensus},
+    snapshot::Snapshot,
+    transaction::Transaction,
+    types::{BlockHeight, Hash256},
+};
+
+use crypto::{hasher::Sha256Hasher, keys::KeyPair};
+use rayon::prelude::*;
+use std::{sync::Arc, time::SystemTime};
 
 #[derive(Debug)]
 pub struct Blockchain {
     pub chain: Vec<Block>,
+    base: Option<Snapshot>,
+    base_ledger: Ledger,
+    // The ledger at the tip, or why the chain's blocks do not apply.
+    ledger: Result<Ledger, String>,
+    pruned_headers: Vec<BlockHeader>,
+    params: ChainParams,
+    engine: Box<dyn ConsensusEngine>,
+    clock: Arc<dyn Clock>,
+    validator_key: Option<KeyPair>,
+    pending_evidence: Vec<DoubleSign>,
+    finalized: Option<FinalityCertificate>,
+    finality_gadget: Option<FinalityGadget>,
+    index: ChainIndex,
+    mempool: Mempool,
+    events: Arc<EventBus>,
 }
 
 impl Blockchain {
     pub fn new() -> Blockchain {
+        return Blockchain::from_chain(vec![Block::genesis()]);
+    }
+
+    pub fn from_chain(chain: Vec<Block>) -> Blockchain {
+        return Blockchain::with_index(chain, ChainIndex::new());
+    }
+
+    pub fn with_index(chain: Vec<Block>, mut index: ChainIndex) -> Blockchain {
+        if index.base_height() != BlockHeight::GENESIS {
+            index = ChainIndex::new();
+        }
+        index.sync(&chain);
+        let base_ledger = Ledger::new();
+        let mut ledger = base_ledger.clone();
+        let ledger = ledger
+            .apply_chain(BlockHeight::GENESIS, &chain)
+            .map(|_| ledger);
         Blockchain {
-            chain: vec![Block::genesis()],
+            chain,
+            base: None,
+            base_ledger,
+            ledger,
+            pruned_headers: Vec::new(),
+            params: ChainParams::default(),
+            engine: Box::new(ProofOfWorkEngine::new(Box::new(Sha256Hasher))),
+            clock: Arc::new(SystemClock),
+            validator_key: None,
+            pending_evidence: Vec::new(),
+            finalized: None,
+            finality_gadget: None,
+            index,
+            mempool: Mempool::new(),
+            events: Arc::new(EventBus::new()),
+        }
+    }
+
+    // Starts from a verified snapshot instead of genesis; `chain` holds the
+    // snapshot block followed by the blocks validated on top of it.
+    pub fn from_snapshot(
+        snapshot: Snapshot,
+        chain: Vec<Block>,
+        mut index: ChainIndex,
+    ) -> Result<Blockchain, String> {
+        let base_ledger = snapshot.verify()?;
+        if chain.first() != Some(&snapshot.block) {
+            return Err(format!(
+                "chain does not start at snapshot block {}",
+                snapshot.height
+            ));
+        }
+        let mut ledger = base_ledger.clone();
+        ledger.apply_chain(snapshot.height, &chain)?;
+        if index.base_height() != snapshot.height {
+            index = ChainIndex::with_base(snapshot.height);
+        }
+        index.sync(&chain);
+        return Ok(Blockchain {
+            chain,
+            base: Some(snapshot),
+            base_ledger,
+            ledger: Ok(ledger),
+            pruned_headers: Vec::new(),
+            params: ChainParams::default(),
+            engine: Box::new(ProofOfWorkEngine::new(Box::new(Sha256Hasher))),
+            clock: Arc::new(SystemClock),
+            validator_key: None,
+            pending_evidence: Vec::new(),
+            finalized: None,
+            finality_gadget: None,
+            index,
+            mempool: Mempool::new(),
+            events: Arc::new(EventBus::new()),
+        });
+    }
+
+    pub fn params(&self) -> &ChainParams {
+        return &self.params;
+    }
+
+    // Fails, leaving the current params in place, if the consensus
+    // parameters are out of bounds.
+    pub fn set_params(&mut self, params: ChainParams) -> Result<(), String> {
+        if let Some(finality) = &params.finality {
+            finality.validator_set()?;
+        }
+        self.engine = params.engine_with_clock(self.clock.clone())?;
+        if self.base.is_none() {
+            self.base_ledger = params.genesis_ledger();
+            self.ledger = self.replay_ledger();
+        }
+        self.params = params;
+        self.reset_finality_gadget();
+        return Ok(());
+    }
+
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        return self.clock.clone();
+    }
+
+    // Makes mining and slot timing read the time from `clock`.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) -> Result<(), String> {
+        self.engine = self.params.engine_with_clock(clock.clone())?;
+        self.clock = clock;
+        return Ok(());
+    }
+
+    // Signs the blocks this node produces under proof of stake, and its
+    // finality votes if the key is a finality validator's.
+    pub fn set_validator_key(&mut self, key_pair: KeyPair) {
+        self.validator_key = Some(key_pair);
+        self.reset_finality_gadget();
+    }
+
+    // Double signs seen but not yet included in a block.
+    pub fn pending_evidence(&self) -> &[DoubleSign] {
+        return &self.pending_evidence;
+    }
+
+    pub fn report_double_sign(&mut self, evidence: DoubleSign) -> Result<(), String> {
+        if !matches!(self.params.consensus, Consensus::ProofOfStake(_)) {
+            return Err(String::from("only proof of stake slashes double signs"));
+        }
+        let offender = evidence.offender()?;
+        if self.tip_ledger()?.is_slashed(&offender) {
+            return Err(format!("{} is already slashed", offender));
+        }
+        let pending = self
+            .pending_evidence
+            .iter()
+            .any(|pending| pending.offender().as_ref() == Ok(&offender));
+        if !pending {
+            self.pending_evidence.push(evidence);
+        }
+        return Ok(());
+    }
+
+    // The current authorities and voting epoch, under proof of authority.
+    pub fn authority_set(&self) -> Result<AuthoritySet, String> {
+        match &self.params.consensus {
+            Consensus::ProofOfAuthority(params) => match self.tip_ledger()?.authorities() {
+                Some(authorities) => return Ok(authorities.clone()),
+                None => return Ok(AuthoritySet::new(&params.authorities)),
+            },
+            _ => return Err(String::from("chain does not use proof of authority")),
+        }
+    }
+
+    pub fn finality_validators(&self) -> Result<ValidatorSet, String> {
+        match &self.params.finality {
+            Some(finality) => return finality.validator_set(),
+            None => return Err(String::from("chain has no finality validators")),
+        }
+    }
+
+    // The certificate of the highest final block. Chains that do not contain
+    // that block are refused.
+    pub fn finalized(&self) -> Option<&FinalityCertificate> {
+        return self.finalized.as_ref();
+    }
+
+    pub fn finalize(&mut self, certificate: FinalityCertificate) -> Result<(), String> {
+        certificate.verify(&self.finality_validators()?)?;
+        self.commit_finality(certificate.clone())?;
+        if let Some(gadget) = &mut self.finality_gadget {
+            gadget.on_certificate(certificate)?;
+        }
+        self.vote_finality();
+        return Ok(());
+    }
+
+    // The finality votes counted at the height being voted on.
+    pub fn finality_votes(&self) -> Vec<FinalityVote> {
+        match &self.finality_gadget {
+            Some(gadget) => return gadget.votes(),
+            None => return Vec::new(),
+        }
+    }
+
+    // Counts a peer's finality vote, voting in turn and finalizing the block
+    // once a quorum precommits it.
+    pub fn add_finality_vote(&mut self, vote: FinalityVote) -> Result<(), String> {
+        match &mut self.finality_gadget {
+            Some(gadget) => gadget.on_vote(vote)?,
+            None => return Err(String::from("chain has no finality validators")),
+        };
+        self.vote_finality();
+        return Ok(());
+    }
+
+    fn commit_finality(&mut self, certificate: FinalityCertificate) -> Result<(), String> {
+        if let Some(finalized) = &self.finalized {
+            if certificate.height <= finalized.height {
+                return Err(format!("block {} is already final", certificate.height));
+            }
+        }
+        match self.header_at(certificate.height) {
+            Some(header) if header.hash == certificate.hash => {}
+            _ => {
+                return Err(format!(
+                    "block {} {} is not in the chain",
+                    certificate.height, certificate.hash
+                ))
+            }
+        }
+        let (height, hash) = (certificate.height, certificate.hash);
+        self.finalized = Some(certificate);
+        self.events.publish(ChainEvent::Finalized { height, hash });
+        return Ok(());
+    }
+
+    fn reset_finality_gadget(&mut self) {
+        let validators = match &self.params.finality {
+            Some(finality) => finality.validator_set().ok(),
+            None => None,
+        };
+        self.finality_gadget = validators.map(|validators| {
+            let key_pair = self
+                .validator_key
+                .as_ref()
+                .and_then(|key_pair| KeyPair::from_secret_bytes(&key_pair.secret_bytes()));
+            FinalityGadget::new(validators, key_pair, self.finalized.clone())
+        });
+        self.vote_finality();
+    }
+
+    // Prevotes this chain's blocks from the lowest one that is not final yet,
+    // after adopting what the gadget has finalized. A block the gadget
+    // finalized that this chain does not have holds voting back until the
+    // chain catches up.
+    fn vote_finality(&mut self) {
+        while let Some(gadget) = &self.finality_gadget {
+            let height = gadget.height();
+            let finalized = self
+                .finalized
+                .as_ref()
+                .map(|certificate| certificate.height);
+            if let Some(certificate) = gadget.finalized() {
+                if Some(certificate.height) > finalized
+                    && self.commit_finality(certificate.clone()).is_err()
+                {
+                    return;
+                }
+            }
+            let hash = match self.header_at(height) {
+                Some(header) => header.hash,
+                None => return,
+            };
+            match &mut self.finality_gadget {
+                Some(gadget) => {
+                    gadget.propose(hash);
+                    if gadget.height() == height {
+                        return;
+                    }
+                }
+                None => return,
+            }
+        }
+    }
+
+    pub fn base(&self) -> Option<&Snapshot> {
+        return self.base.as_ref();
+    }
+
+    pub fn base_height(&self) -> BlockHeight {
+        match &self.base {
+            Some(snapshot) => return snapshot.height,
+            None => return BlockHeight::GENESIS,
+        }
+    }
+
+    // Headers of the blocks below the base whose bodies were pruned, starting
+    // at genesis. Empty for chains fast synced from a snapshot.
+    pub fn pruned_headers(&self) -> &[BlockHeader] {
+        return &self.pruned_headers;
+    }
+
+    pub fn set_pruned_headers(&mut self, headers: Vec<BlockHeader>) -> Result<(), String> {
+        if !headers.is_empty() {
+            if headers.len() != self.base_height().index() {
+                return Err(format!(
+                    "expected {} pruned headers, found {}",
+                    self.base_height(),
+                    headers.len()
+                ));
+            }
+            let mut header_chain = headers.clone();
+            header_chain.push(self.chain[0].header());
+            let valid = header_chain[0] == Block::genesis().header()
+                && header_chain
+                    .windows(2)
+                    .all(|pair| self.engine.validate_header(&pair[1], &pair[0]));
+            if !valid {
+                return Err(String::from("pruned headers are invalid"));
+            }
+        }
+        self.pruned_headers = headers;
+        return Ok(());
+    }
+
+    pub fn header_at(&self, height: BlockHeight) -> Option<BlockHeader> {
+        match self.block_at(height) {
+            Some(block) => return Some(block.header()),
+            None => return self.pruned_headers.get(height.index()).cloned(),
+        }
+    }
+
+    pub fn is_pruned(&self, height: BlockHeight) -> bool {
+        return height.index() < self.pruned_headers.len();
+    }
+
+    pub fn height(&self) -> BlockHeight {
+        return self.base_height() + (self.chain.len() - 1) as u64;
+    }
+
+    pub fn index(&self) -> &ChainIndex {
+        return &self.index;
+    }
+
+    pub fn events(&self) -> Arc<EventBus> {
+        return Arc::clone(&self.events);
+    }
+
+    pub fn mempool(&self) -> &Mempool {
+        return &self.mempool;
+    }
+
+    pub fn ledger(&self) -> Result<Ledger, String> {
+        return self.tip_ledger().cloned();
+    }
+
+    // Borrows the ledger kept up to date as blocks are added.
+    pub fn tip_ledger(&self) -> Result<&Ledger, String> {
+        return self.ledger.as_ref().map_err(String::clone);
+    }
+
+    pub fn snapshot(&self, height: BlockHeight) -> Result<Snapshot, String> {
+        let block = match self.block_at(height) {
+            Some(block) => block.clone(),
+            None => return Err(format!("block {} is not in the chain", height)),
+        };
+        if block.state_root.is_none() {
+            return Err(format!("block {} has no state root", height));
         }
+        let mut ledger = self.base_ledger.clone();
+        let base_height = self.base_height();
+        ledger.apply_chain(base_height, &self.chain[..=(height - base_height) as usize])?;
+        return Ok(Snapshot::new(height, block, &ledger));
     }
 
-    pub fn add_block(&mut self, data: String) {
-        let new_block = Block::mine_block(&self.chain[self.chain.len() - 1], data);
-        self.chain.push(new_block);
+    // When this node may produce the next block. Engines that elect slot
+    // leaders refuse to produce one before the slot its validator key leads.
+    pub fn next_block_time(&self) -> Result<SystemTime, String> {
+        return self.engine.next_block_time(
+            self.last_block(),
+            self.tip_ledger()?,
+            self.validator_key.as_ref(),
+        );
     }
 
-    pub fn is_valid_chain(chain: &Vec<Block>) -> bool {
-        if chain[0] != Block::genesis() {
+    // Waits on the chain's clock until `next_block_time` and runs `produce`,
+    // trying again if the slot passed while waiting. Callers sharing the chain
+    // behind a lock read `next_block_time` and wait with the lock released
+    // instead.
+    pub fn in_slot<F>(&mut self, produce: F) -> Result<(), String>
+    where
+        F: Fn(&mut Blockchain) -> Result<(), String>,
+    {
+        loop {
+            self.clock.sleep_until(self.next_block_time()?);
+            let error = match produce(self) {
+                Ok(()) => return Ok(()),
+                Err(e) => e,
+            };
+            if self.next_block_time()? <= self.clock.now() {
+                return Err(error);
+            }
+        }
+    }
+
+    // Fails if the consensus engine cannot produce a block, e.g. under proof
+    // of stake without a validator key.
+    pub fn add_block(&mut self, data: String) -> Result<(), String> {
+        let height = self.height().next();
+        let ledger = self.ledger.as_mut().map_err(|e| e.clone())?;
+        let evidence: Vec<DoubleSign> = self
+            .pending_evidence
+            .iter()
+            .filter(|evidence| match evidence.offender() {
+                Ok(offender) => !ledger.is_slashed(&offender),
+                Err(_) => false,
+            })
+            .cloned()
+            .collect();
+        let contents = BlockContents {
+            state_root: ledger.commitment_after(height, &data, &evidence).ok(),
+            data,
+            evidence,
+        };
+        let block = self.engine.produce_block(
+            &self.chain[self.chain.len() - 1],
+            ledger,
+            contents,
+            self.validator_key.as_ref(),
+        )?;
+        let applied = ledger.apply_block(height, &block).map(|_| ());
+        self.pending_evidence.clear();
+        self.push_block(block, applied);
+        return Ok(());
+    }
+
+    pub fn submit_block(&mut self, block: Block) -> bool {
+        let valid = match &self.ledger {
+            Ok(ledger) => self
+                .engine
+                .validate_block(&block, self.last_block(), ledger)
+                .is_ok(),
+            Err(_) => return false,
+        };
+        if !valid {
+            self.detect_double_sign(&block);
+            return false;
+        }
+        let height = self.height().next();
+        if self.params.check_block(height, &block).is_err() {
+            return false;
+        }
+        let applied = match &mut self.ledger {
+            Ok(ledger) => ledger.apply_block(height, &block),
+            Err(_) => return false,
+        };
+        if applied.is_err() {
+            return false;
+        }
+        self.push_block(block, Ok(()));
+        return true;
+    }
+
+    // A sealed block for a slot this chain already has a block for, from the
+    // same validator, is evidence of a double sign.
+    fn detect_double_sign(&mut self, block: &Block) {
+        let seal = match &block.seal {
+            Some(seal) => seal,
+            None => return,
+        };
+        let conflicting = self.chain.iter().find(|existing| match &existing.seal {
+            Some(existing_seal) => {
+                existing_seal.validator == seal.validator
+                    && existing_seal.slot == seal.slot
+                    && existing.hash != block.hash
+            }
+            None => false,
+        });
+        if let Some(existing) = conflicting {
+            let evidence = DoubleSign {
+                first: existing.header(),
+                second: block.header(),
+            };
+            let _ = self.report_double_sign(evidence);
+        }
+    }
+
+    pub fn submit_transaction(&mut self, transaction: Transaction) -> Result<[u8; 32], String> {
+        let ledger = self.ledger.as_ref().map_err(String::clone)?;
+        self.engine
+            .validate_transaction(&transaction, &self.mempool.pending_ledger(ledger))?;
+        let height = self.height().next();
+        let txid = self.mempool.add(transaction.clone(), ledger, height)?;
+        self.events
+            .publish(ChainEvent::NewTransaction { transaction });
+        return Ok(txid);
+    }
+
+    pub fn mine_transactions(&mut self, reward_address: &str) -> Result<(), String> {
+        let ledger = self.ledger.as_ref().map_err(String::clone)?;
+        let height = self.height().next();
+        let engine = self.engine.as_ref();
+        self.mempool
+            .revalidate_with(ledger, height, |transaction, pending| {
+                engine.validate_transaction(transaction, pending)
+            });
+        let fees = self.mempool.fees(ledger, height);
+        let mut transactions = vec![Transaction::coinbase(
+            height,
+            reward_address,
+            MINING_REWARD + fees,
+        )];
+        transactions.extend(self.mempool.transactions().iter().cloned());
+        return self.add_block(Transaction::encode_payload(&transactions));
+    }
+
+    pub fn last_block(&self) -> &Block {
+        return &self.chain[self.chain.len() - 1];
+    }
+
+    pub fn block_at(&self, height: BlockHeight) -> Option<&Block> {
+        if height < self.base_height() {
+            return None;
+        }
+        return self.chain.get((height - self.base_height()) as usize);
+    }
+
+    pub fn find_header(&self, hash: &Hash256) -> Option<(BlockHeight, BlockHeader)> {
+        if let Some((height, block)) = self.find_block(hash) {
+            return Some((height, block.header()));
+        }
+        return self
+            .pruned_headers
+            .iter()
+            .position(|header| &header.hash == hash)
+            .map(|height| {
+                (
+                    BlockHeight::from(height),
+                    self.pruned_headers[height].clone(),
+                )
+            });
+    }
+
+    pub fn find_block(&self, hash: &Hash256) -> Option<(BlockHeight, &Block)> {
+        let height = self.index.block_height(hash)?;
+        match self.block_at(height) {
+            Some(block) if &block.hash == hash => return Some((height, block)),
+            _ => return None,
+        }
+    }
+
+    pub fn find_transaction(&self, txid: &[u8; 32]) -> Option<(TxLocation, Transaction)> {
+        let location = self.index.transaction_location(txid)?;
+        let transaction = self.transaction_at(location)?;
+        return Some((location, transaction));
+    }
+
+    pub fn address_transactions(&self, address: &str) -> Vec<(TxLocation, Transaction)> {
+        return self
+            .index
+            .address_transactions(address)
+            .iter()
+            .filter_map(|location| Some((*location, self.transaction_at(*location)?)))
+            .collect();
+    }
+
+    pub fn received_by_address(&self, address: &str) -> u64 {
+        return self
+            .address_transactions(address)
+            .into_iter()
+            .flat_map(|(_, transaction)| transaction.outputs)
+            .filter(|output| output.address == address)
+            .map(|output| output.amount)
+            .sum();
+    }
+
+    pub fn is_valid_chain(chain: &[Block]) -> bool {
+        return Blockchain::first_invalid_block(chain).is_none();
+    }
+
+    // The index of the first block that breaks a chain started from genesis.
+    // Long chains have their proofs checked in parallel.
+    pub fn first_invalid_block(chain: &[Block]) -> Option<usize> {
+        return Blockchain::first_invalid_from_genesis(
+            chain,
+            chain.len() >= PARALLEL_VALIDATION_MIN,
+        );
+    }
+
+    // Like `first_invalid_block`, but checks proofs in parallel whatever the
+    // chain's length.
+    pub fn first_invalid_block_parallel(chain: &[Block]) -> Option<usize> {
+        return Blockchain::first_invalid_from_genesis(chain, true);
+    }
+
+    fn first_invalid_from_genesis(chain: &[Block], parallel: bool) -> Option<usize> {
+        if chain.first() != Some(&Block::genesis()) {
+            return Some(0);
+        }
+        let engine = ProofOfWorkEngine::new(Box::new(Sha256Hasher));
+        return Blockchain::first_invalid_in_segment(
+            &engine,
+            BlockHeight::GENESIS,
+            &Ledger::new(),
+            chain,
+            parallel,
+        );
+    }
+
+    pub fn is_valid_header_chain(headers: &[BlockHeader]) -> bool {
+        if headers.is_empty() || headers[0] != Block::genesis().header() {
             return false;
         }
-        for i in 1..chain.len() {
-            if !Block::is_valid_block(&chain[i], &chain[i - 1].hash, chain[i - 1].difficulty) {
+        for i in 1..headers.len() {
+            if !Block::is_valid_header(&headers[i], &headers[i - 1].hash, headers[i - 1].difficulty)
+            {
                 return false;
             }
         }
         return true;
     }
 
-    pub fn replace_chain(&mut self, new_chain: Vec<Block>) {
-        if new_chain.len() > self.chain.len() && Blockchain::is_valid_chain(&new_chain) {
-            self.chain = new_chain;
+    // Drops the bodies of blocks more than `depth` below the tip by moving the
+    // base up to a snapshot of the newest block with a state root at or below
+    // that height. Returns the new base height, if it moved.
+    pub fn prune(&mut self, depth: usize) -> Result<Option<BlockHeight>, String> {
+        let base_height = self.base_height();
+        let target = match self.height().checked_sub(depth as u64) {
+            Some(target) if target > base_height => target,
+            _ => return Ok(None),
+        };
+        let height = match (base_height.0 + 1..=target.0)
+            .map(BlockHeight)
+            .rev()
+            .find(|height| {
+                self.chain[(*height - base_height) as usize]
+                    .state_root
+                    .is_some()
+            }) {
+            Some(height) => height,
+            None => return Ok(None),
+        };
+        let snapshot = self.snapshot(height)?;
+        let keeps_headers = self.pruned_headers.len() == base_height.index();
+        let pruned: Vec<Block> = self
+            .chain
+            .drain(..(height - base_height) as usize)
+            .collect();
+        if keeps_headers {
+            self.pruned_headers.extend(pruned.iter().map(Block::header));
+        }
+        self.base_ledger = snapshot.ledger();
+        self.base = Some(snapshot);
+        self.index = ChainIndex::with_base(height);
+        self.index.sync(&self.chain);
+        return Ok(Some(height));
+    }
+
+    // Like `is_valid_chain`, but for chains started from this chain's base
+    // block rather than genesis.
+    pub fn is_valid_extension(&self, chain: &[Block]) -> bool {
+        return self.first_invalid_extension(chain).is_none();
+    }
+
+    // Like `first_invalid_block`, but for chains started from this chain's
+    // base block rather than genesis.
+    pub fn first_invalid_extension(&self, chain: &[Block]) -> Option<usize> {
+        let engine = self.engine.as_ref();
+        let parallel = chain.len() >= PARALLEL_VALIDATION_MIN;
+        match &self.base {
+            Some(snapshot) => {
+                if chain.first() != Some(&snapshot.block) {
+                    return Some(0);
+                }
+                return Blockchain::first_invalid_in_segment(
+                    engine,
+                    snapshot.height,
+                    &self.base_ledger,
+                    chain,
+                    parallel,
+                );
+            }
+            None => {
+                if chain.first() != Some(&Block::genesis()) {
+                    return Some(0);
+                }
+                return Blockchain::first_invalid_in_segment(
+                    engine,
+                    BlockHeight::GENESIS,
+                    &self.base_ledger,
+                    chain,
+                    parallel,
+                );
+            }
         }
     }
+
+    // `new_chain` starts at the same base as this chain: genesis, or the
+    // snapshot block for chains started from a snapshot. Chains that conflict
+    // with a checkpoint, reorg deeper than the chain parameters allow or
+    // revert a final block are refused with the reason.
+    pub fn replace_chain(&mut self, new_chain: Vec<Block>) -> Result<(), String> {
+        if new_chain.len() <= self.chain.len() {
+            return Err(String::from(
+                "new chain is not longer than the current chain",
+            ));
+        }
+        let base_height = self.base_height();
+        self.params.check_chain(base_height, &new_chain)?;
+        let fork = self
+            .chain
+            .iter()
+            .zip(new_chain.iter())
+            .take_while(|(old, new)| old.hash == new.hash)
+            .count();
+        self.params.check_reorg_depth(self.chain.len() - fork)?;
+        if let Some(finalized) = &self.finalized {
+            if base_height + fork as u64 <= finalized.height {
+                return Err(format!(
+                    "new chain reverts final block {}",
+                    finalized.height
+                ));
+            }
+        }
+        if let Some(index) = self.first_invalid_extension(&new_chain) {
+            return Err(format!(
+                "new chain is invalid at height {}",
+                base_height + index as u64
+            ));
+        }
+        let old_chain = std::mem::replace(&mut self.chain, new_chain);
+        self.ledger = self.replay_ledger();
+        self.index.sync(&self.chain);
+        if fork < old_chain.len() {
+            self.restore_transactions(&old_chain[fork..]);
+            self.events.publish(ChainEvent::Reorg {
+                fork_height: base_height + fork as u64,
+                disconnected: old_chain[fork..].to_vec(),
+                connected: self.chain[fork..].to_vec(),
+            });
+        } else {
+            for position in fork..self.chain.len() {
+                self.events.publish(ChainEvent::NewBlock {
+                    height: base_height + position as u64,
+                    block: self.chain[position].clone(),
+                });
+            }
+        }
+        self.refresh_mempool();
+        self.publish_tip();
+        self.vote_finality();
+        return Ok(());
+    }
+
+    // Blocks are applied to the ledger in order. In parallel, every block's
+    // proof is checked against its parent first, so only the blocks before
+    // the first bad proof need the ledger.
+    fn first_invalid_in_segment(
+        engine: &dyn ConsensusEngine,
+        base_height: BlockHeight,
+        base_ledger: &Ledger,
+        chain: &[Block],
+        parallel: bool,
+    ) -> Option<usize> {
+        let bad_proof = match parallel {
+            true => (1..chain.len())
+                .into_par_iter()
+                .find_first(|i| engine.validate_proof(&chain[*i], &chain[*i - 1]).is_err()),
+            false => None,
+        };
+        let mut ledger = base_ledger.clone();
+        for i in 1..bad_proof.unwrap_or(chain.len()) {
+            let checked = match parallel {
+                true => engine.validate_state(&chain[i], &chain[i - 1], &ledger),
+                false => engine.validate_block(&chain[i], &chain[i - 1], &ledger),
+            };
+            if checked.is_err() {
+                return Some(i);
+            }
+            if ledger
+                .apply_block(base_height + i as u64, &chain[i])
+                .is_err()
+            {
+                return Some(i);
+            }
+        }
+        return bad_proof;
+    }
+
+    // Replays the chain on top of the base ledger, for when the tip moves by
+    // more than one block.
+    fn replay_ledger(&self) -> Result<Ledger, String> {
+        let mut ledger = self.base_ledger.clone();
+        ledger.apply_chain(self.base_height(), &self.chain)?;
+        return Ok(ledger);
+    }
+
+    // `applied` is how applying `block` to the tip ledger went. A block that
+    // did not apply leaves the chain without a ledger.
+    fn push_block(&mut self, block: Block, applied: Result<(), String>) {
+        self.chain.push(block);
+        if let Err(e) = applied {
+            self.ledger = Err(e);
+        }
+        self.index.sync(&self.chain);
+        self.events.publish(ChainEvent::NewBlock {
+            height: self.height(),
+            block: self.last_block().clone(),
+        });
+        self.refresh_mempool();
+        self.publish_tip();
+        self.vote_finality();
+    }
+
+    fn transaction_at(&self, location: TxLocation) -> Option<Transaction> {
+        let block = self.block_at(location.height)?;
+        let mut transactions = Transaction::decode_payload(&block.data)?;
+        if location.position >= transactions.len() {
+            return None;
+        }
+        return Some(transactions.swap_remove(location.position));
+    }
+
+    fn restore_transactions(&mut self, disconnected: &[Block]) {
+        let pending = self.mempool.transactions().to_vec();
+        let mut restored = Mempool::new();
+        let ledger = self.ledger().unwrap_or_default();
+        let height = self.height().next();
+        for block in disconnected {
+            for transaction in Transaction::decode_payload(&block.data).unwrap_or_default() {
+                if !transaction.is_coinbase() {
+                    let _ = restored.add(transaction, &ledger, height);
+                }
+            }
+        }
+        for transaction in pending {
+            let _ = restored.add(transaction, &ledger, height);
+        }
+        self.mempool = restored;
+    }
+
+    fn refresh_mempool(&mut self) {
+        if self.mempool.is_empty() {
+            return;
+        }
+        let empty = Ledger::new();
+        let ledger = self.ledger.as_ref().unwrap_or(&empty);
+        let height = self.height().next();
+        let engine = self.engine.as_ref();
+        self.mempool
+            .revalidate_with(ledger, height, |transaction, pending| {
+                engine.validate_transaction(transaction, pending)
+            });
+    }
+
+    fn publish_tip(&self) {
+        self.events.publish(ChainEvent::NewTip {
+            height: self.height(),
+            hash: self.last_block().hash,
+        });
+    }
+}
+
+impl Default for Blockchain {
+    fn default() -> Blockchain {
+        return Blockchain::new();
+    }
 }
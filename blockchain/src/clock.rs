@@ -0,0 +1,103 @@
+use crate::types::Nonce;
+
+use std::{
+    fmt,
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+// Where mining, difficulty adjustment and slot timing read the time from.
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> SystemTime;
+
+    // Blocks until `deadline`, returning at once if it has passed.
+    fn sleep_until(&self, deadline: SystemTime);
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        return SystemTime::now();
+    }
+
+    fn sleep_until(&self, deadline: SystemTime) {
+        if let Ok(wait) = deadline.duration_since(SystemTime::now()) {
+            thread::sleep(wait);
+        }
+    }
+}
+
+// A clock that only moves when told to, so tests never wait on real time.
+// Sleeping jumps straight to the deadline.
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<SystemTime>,
+}
+
+impl MockClock {
+    pub fn new(now: SystemTime) -> MockClock {
+        return MockClock {
+            now: Mutex::new(now),
+        };
+    }
+
+    // Starts `millis` after the Unix epoch.
+    pub fn at_millis(millis: u64) -> MockClock {
+        return MockClock::new(SystemTime::UNIX_EPOCH + Duration::from_millis(millis));
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now += by;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        return *self.now.lock().unwrap();
+    }
+
+    fn sleep_until(&self, deadline: SystemTime) {
+        let mut now = self.now.lock().unwrap();
+        if deadline > *now {
+            *now = deadline;
+        }
+    }
+}
+
+// Supplies the nonces a miner tries, in order.
+pub trait NonceSource {
+    fn next_nonce(&mut self) -> Nonce;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequentialNonces {
+    next: u64,
+}
+
+impl SequentialNonces {
+    pub fn starting_at(next: Nonce) -> SequentialNonces {
+        return SequentialNonces { next: next.0 };
+    }
+}
+
+impl Default for SequentialNonces {
+    fn default() -> SequentialNonces {
+        return SequentialNonces::starting_at(Nonce(1));
+    }
+}
+
+impl NonceSource for SequentialNonces {
+    fn next_nonce(&mut self) -> Nonce {
+        let nonce = Nonce(self.next);
+        self.next = self.next.wrapping_add(1);
+        return nonce;
+    }
+}
@@ -1,3 +1,8 @@
+use crate::types::Difficulty;
+
 pub const MINE_RATE: u64 = 1_000;
-pub const DIFFICULTY_MAX: usize = 256;
-pub const DIFFICULTY_MIN: usize = 4;
\ No newline at end of file
+pub const DIFFICULTY_MAX: Difficulty = Difficulty(256);
+pub const DIFFICULTY_MIN: Difficulty = Difficulty(4);
+pub const MINING_REWARD: u64 = 50;
+// Chains at least this long have their blocks' proofs checked in parallel.
+pub const PARALLEL_VALIDATION_MIN: usize = 64;
@@ -0,0 +1,647 @@
+use crate::{
+    authority,
+    block::{Block, BlockHeader},
+    clock::{Clock, SequentialNonces, SystemClock},
+    config::{MINE_RATE, MINING_REWARD},
+    ledger::Ledger,
+    transaction::Transaction,
+    types::{Hash256, Nonce},
+};
+
+use crypto::{
+    cryptohash,
+    hasher::ChainHasher,
+    keys::{self, KeyPair},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+// How far ahead a validator looks for a slot it leads before giving up.
+pub const MAX_LEADER_SEARCH_SLOTS: u64 = 10_000;
+
+// What a block producer wants sealed into the next block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockContents {
+    pub data: String,
+    pub state_root: Option<Hash256>,
+    pub evidence: Vec<DoubleSign>,
+}
+
+// Decides who may produce the next block and what makes a block valid.
+// `ledger` is always the state after `last_block`.
+pub trait ConsensusEngine: fmt::Debug + Send + Sync {
+    fn produce_block(
+        &self,
+        last_block: &Block,
+        ledger: &Ledger,
+        contents: BlockContents,
+        signer: Option<&KeyPair>,
+    ) -> Result<Block, String>;
+
+    // When `signer` may next produce a block on top of `last_block`. Engines
+    // that elect slot leaders refuse to produce one before then, so callers
+    // wait for it without holding up the chain.
+    fn next_block_time(
+        &self,
+        last_block: &Block,
+        ledger: &Ledger,
+        signer: Option<&KeyPair>,
+    ) -> Result<SystemTime, String>;
+
+    fn validate_block(
+        &self,
+        block: &Block,
+        last_block: &Block,
+        ledger: &Ledger,
+    ) -> Result<(), String>;
+
+    // The part of `validate_block` that needs no ledger, so every block of a
+    // chain can be checked at once. Engines whose rules all need the ledger
+    // only check the link to the parent here.
+    fn validate_proof(&self, block: &Block, last_block: &Block) -> Result<(), String> {
+        if block.last_hash != last_block.hash {
+            return Err(String::from("block does not extend its parent"));
+        }
+        return Ok(());
+    }
+
+    // The rest of `validate_block`, for a block whose proof already passed.
+    fn validate_state(
+        &self,
+        block: &Block,
+        last_block: &Block,
+        ledger: &Ledger,
+    ) -> Result<(), String> {
+        return self.validate_block(block, last_block, ledger);
+    }
+
+    // Checks what a header alone can prove, for chains whose older bodies
+    // were pruned.
+    fn validate_header(&self, header: &BlockHeader, last_header: &BlockHeader) -> bool;
+
+    // Consensus rules for a transaction entering the mempool, on top of the
+    // ledger's own checks. Only proof of authority accepts authority votes.
+    fn validate_transaction(
+        &self,
+        transaction: &Transaction,
+        _ledger: &Ledger,
+    ) -> Result<(), String> {
+        match &transaction.vote {
+            Some(_) => return Err(String::from(NO_VOTES)),
+            None => return Ok(()),
+        }
+    }
+}
+
+const NO_VOTES: &str = "authority votes need proof of authority consensus";
+
+#[derive(Debug)]
+pub struct ProofOfWorkEngine {
+    hasher: Box<dyn ChainHasher>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ProofOfWorkEngine {
+    pub fn new(hasher: Box<dyn ChainHasher>) -> ProofOfWorkEngine {
+        return ProofOfWorkEngine::with_clock(hasher, Arc::new(SystemClock));
+    }
+
+    pub fn with_clock(hasher: Box<dyn ChainHasher>, clock: Arc<dyn Clock>) -> ProofOfWorkEngine {
+        return ProofOfWorkEngine { hasher, clock };
+    }
+}
+
+impl ConsensusEngine for ProofOfWorkEngine {
+    fn produce_block(
+        &self,
+        last_block: &Block,
+        _ledger: &Ledger,
+        contents: BlockContents,
+        _signer: Option<&KeyPair>,
+    ) -> Result<Block, String> {
+        if !contents.evidence.is_empty() {
+            return Err(String::from(
+                "proof of work blocks cannot carry slashing evidence",
+            ));
+        }
+        return Ok(Block::mine_block_using(
+            self.hasher.as_ref(),
+            last_block,
+            contents.data,
+            contents.state_root,
+            self.clock.as_ref(),
+            &mut SequentialNonces::default(),
+        ));
+    }
+
+    fn next_block_time(
+        &self,
+        _last_block: &Block,
+        _ledger: &Ledger,
+        _signer: Option<&KeyPair>,
+    ) -> Result<SystemTime, String> {
+        return Ok(self.clock.now());
+    }
+
+    fn validate_block(
+        &self,
+        block: &Block,
+        last_block: &Block,
+        _ledger: &Ledger,
+    ) -> Result<(), String> {
+        return self.validate_proof(block, last_block);
+    }
+
+    fn validate_proof(&self, block: &Block, last_block: &Block) -> Result<(), String> {
+        if block.seal.is_some() || !block.evidence.is_empty() {
+            return Err(String::from(
+                "proof of work blocks cannot carry a seal or slashing evidence",
+            ));
+        }
+        if !authority::payload_votes(&block.data).is_empty() {
+            return Err(String::from(NO_VOTES));
+        }
+        if !Block::is_valid_block_with(
+            self.hasher.as_ref(),
+            block,
+            &last_block.hash,
+            last_block.difficulty,
+        ) {
+            return Err(String::from("invalid proof of work"));
+        }
+        return Ok(());
+    }
+
+    fn validate_state(
+        &self,
+        _block: &Block,
+        _last_block: &Block,
+        _ledger: &Ledger,
+    ) -> Result<(), String> {
+        return Ok(());
+    }
+
+    fn validate_header(&self, header: &BlockHeader, last_header: &BlockHeader) -> bool {
+        return header.seal.is_none()
+            && Block::is_valid_header_with(
+                self.hasher.as_ref(),
+                header,
+                &last_header.hash,
+                last_header.difficulty,
+            );
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StakeParams {
+    pub slot_ms: u64,
+    // Addresses holding at least this much are validators, weighted by balance.
+    pub min_stake: u64,
+    // Validators, with equal weight, while no address holds the minimum stake.
+    pub bootstrap_validators: Vec<String>,
+}
+
+impl Default for StakeParams {
+    fn default() -> StakeParams {
+        return StakeParams {
+            slot_ms: MINE_RATE,
+            min_stake: MINING_REWARD,
+            bootstrap_validators: Vec::new(),
+        };
+    }
+}
+
+impl StakeParams {
+    pub fn check(&self) -> Result<(), String> {
+        if self.slot_ms == 0 {
+            return Err(String::from("slot length must be positive"));
+        }
+        if self.min_stake == 0 {
+            return Err(String::from("minimum stake must be positive"));
+        }
+        for address in self.bootstrap_validators.iter() {
+            if !keys::is_valid_address(address) {
+                return Err(format!("invalid bootstrap validator {}", address));
+            }
+        }
+        return Ok(());
+    }
+}
+
+// A validator's signature over a block hash and the slot it was produced in.
+// The validator key is covered by the block hash; the signature is not.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockSeal {
+    #[serde(with = "hex::serde")]
+    pub validator: [u8; keys::PUBLIC_KEY_LENGTH],
+    pub slot: u64,
+    #[serde(with = "hex::serde")]
+    pub signature: Vec<u8>,
+}
+
+impl BlockSeal {
+    pub fn message(slot: u64, hash: &Hash256) -> Vec<u8> {
+        let mut message = slot.to_be_bytes().to_vec();
+        message.extend_from_slice(hash.as_bytes());
+        return message;
+    }
+
+    pub fn address(&self) -> String {
+        return keys::address(&self.validator);
+    }
+
+    pub fn verify(&self, hash: &Hash256) -> bool {
+        return keys::verify(
+            &self.validator,
+            &BlockSeal::message(self.slot, hash),
+            &self.signature,
+        );
+    }
+}
+
+// Proof that a validator sealed two different blocks for the same slot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DoubleSign {
+    pub first: BlockHeader,
+    pub second: BlockHeader,
+}
+
+impl DoubleSign {
+    // The address of the validator to slash, if the evidence holds up.
+    pub fn offender(&self) -> Result<String, String> {
+        let (first, second) = match (&self.first.seal, &self.second.seal) {
+            (Some(first), Some(second)) => (first, second),
+            _ => {
+                return Err(String::from(
+                    "double sign evidence needs two sealed headers",
+                ))
+            }
+        };
+        if first.validator != second.validator || first.slot != second.slot {
+            return Err(String::from(
+                "double sign evidence must come from one validator and slot",
+            ));
+        }
+        if self.first.hash == self.second.hash {
+            return Err(String::from("double sign evidence repeats one block"));
+        }
+        if !first.verify(&self.first.hash) || !second.verify(&self.second.hash) {
+            return Err(String::from("double sign evidence has a bad signature"));
+        }
+        return Ok(first.address());
+    }
+}
+
+#[derive(Debug)]
+pub struct ProofOfStakeEngine {
+    hasher: Box<dyn ChainHasher>,
+    params: StakeParams,
+    clock: Arc<dyn Clock>,
+}
+
+impl ProofOfStakeEngine {
+    pub fn new(hasher: Box<dyn ChainHasher>, params: StakeParams) -> ProofOfStakeEngine {
+        return ProofOfStakeEngine::with_clock(hasher, params, Arc::new(SystemClock));
+    }
+
+    pub fn with_clock(
+        hasher: Box<dyn ChainHasher>,
+        params: StakeParams,
+        clock: Arc<dyn Clock>,
+    ) -> ProofOfStakeEngine {
+        return ProofOfStakeEngine {
+            hasher,
+            params,
+            clock,
+        };
+    }
+
+    // Validators and their stakes, in address order. Slashed validators have
+    // forfeited their stake and never lead again.
+    pub fn validators(&self, ledger: &Ledger) -> Vec<(String, u64)> {
+        let mut balances = BTreeMap::<&str, u64>::new();
+        for output in ledger.utxos().values() {
+            let balance = balances.entry(&output.address).or_insert(0);
+            *balance = balance.saturating_add(output.amount);
+        }
+        let staked: Vec<(String, u64)> = balances
+            .into_iter()
+            .filter(|(address, stake)| {
+                *stake >= self.params.min_stake && !ledger.is_slashed(address)
+            })
+            .map(|(address, stake)| (address.to_string(), stake))
+            .collect();
+        if !staked.is_empty() {
+            return staked;
+        }
+        let mut bootstrap: Vec<(String, u64)> = self
+            .params
+            .bootstrap_validators
+            .iter()
+            .filter(|address| !ledger.is_slashed(address))
+            .map(|address| (address.clone(), 1))
+            .collect();
+        bootstrap.sort();
+        bootstrap.dedup();
+        return bootstrap;
+    }
+
+    // Picks the slot leader with probability proportional to stake, seeded by
+    // the parent block so every node draws the same leader.
+    pub fn leader(&self, last_hash: &Hash256, slot: u64, ledger: &Ledger) -> Option<String> {
+        let validators = self.validators(ledger);
+        let total: u128 = validators.iter().map(|(_, stake)| *stake as u128).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut seed = last_hash.as_bytes().to_vec();
+        seed.extend_from_slice(&slot.to_be_bytes());
+        let mut draw_bytes: [u8; 16] = [0; 16];
+        draw_bytes.copy_from_slice(&cryptohash::sha256(&seed)[..16]);
+        let mut draw = u128::from_be_bytes(draw_bytes) % total;
+        for (address, stake) in validators {
+            if draw < stake as u128 {
+                return Some(address);
+            }
+            draw -= stake as u128;
+        }
+        return None;
+    }
+
+    pub fn slot_start(&self, slot: u64) -> SystemTime {
+        return slot_start(self.params.slot_ms, slot);
+    }
+
+    pub fn current_slot(&self) -> u64 {
+        return current_slot(self.clock.as_ref(), self.params.slot_ms);
+    }
+}
+
+pub(crate) fn slot_start(slot_ms: u64, slot: u64) -> SystemTime {
+    return SystemTime::UNIX_EPOCH + Duration::from_millis(slot * slot_ms);
+}
+
+pub(crate) fn current_slot(clock: &dyn Clock, slot_ms: u64) -> u64 {
+    let elapsed = match clock.now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_millis(),
+        Err(_) => 0,
+    };
+    return (elapsed / slot_ms as u128) as u64;
+}
+
+fn slot_of(block: &Block) -> u64 {
+    match &block.seal {
+        Some(seal) => return seal.slot,
+        None => return 0,
+    }
+}
+
+fn validator(signer: Option<&KeyPair>) -> Result<&KeyPair, String> {
+    match signer {
+        Some(signer) => return Ok(signer),
+        None => {
+            return Err(String::from(
+                "this consensus needs a validator key to produce blocks",
+            ))
+        }
+    }
+}
+
+// The first slot from now that `signer` leads on top of `last_block`.
+// Shared by the engines that elect slot leaders.
+pub(crate) fn next_leader_slot<F>(
+    slot_ms: u64,
+    clock: &dyn Clock,
+    last_block: &Block,
+    signer: Option<&KeyPair>,
+    leader: F,
+) -> Result<u64, String>
+where
+    F: Fn(u64) -> Option<String>,
+{
+    let address = validator(signer)?.address();
+    let first_slot = current_slot(clock, slot_ms).max(slot_of(last_block) + 1);
+    match (first_slot..first_slot + MAX_LEADER_SEARCH_SLOTS)
+        .find(|slot| leader(*slot).as_ref() == Some(&address))
+    {
+        Some(slot) => return Ok(slot),
+        None => {
+            return Err(format!(
+                "{} does not lead any of the next {} slots",
+                address, MAX_LEADER_SEARCH_SLOTS
+            ))
+        }
+    }
+}
+
+// Seals the next block in the slot that `signer` leads, if that slot has
+// started. It never waits for the slot: callers wait for `next_block_time`
+// first.
+pub(crate) fn produce_sealed<F>(
+    hasher: &dyn ChainHasher,
+    slot_ms: u64,
+    clock: &dyn Clock,
+    last_block: &Block,
+    contents: BlockContents,
+    signer: Option<&KeyPair>,
+    leader: F,
+) -> Result<Block, String>
+where
+    F: Fn(u64) -> Option<String>,
+{
+    let signer = validator(signer)?;
+    let slot = next_leader_slot(slot_ms, clock, last_block, Some(signer), leader)?;
+    if slot > current_slot(clock, slot_ms) {
+        return Err(format!(
+            "{} is not the slot leader until slot {}",
+            signer.address(),
+            slot
+        ));
+    }
+    let timestamp = slot_start(slot_ms, slot);
+    let mut block = Block {
+        timestamp,
+        last_hash: last_block.hash,
+        hash: Hash256::ZERO,
+        data: contents.data,
+        nonce: Nonce(0),
+        difficulty: last_block.difficulty,
+        state_root: contents.state_root,
+        seal: Some(BlockSeal {
+            validator: signer.public_key(),
+            slot,
+            signature: Vec::new(),
+        }),
+        evidence: contents.evidence,
+    };
+    block.hash = Block::compute_hash(hasher, &block);
+    if let Some(seal) = block.seal.as_mut() {
+        seal.signature = signer.sign(&BlockSeal::message(slot, &block.hash)).to_vec();
+    }
+    return Ok(block);
+}
+
+// Checks a sealed block's link, slot, hash and signature: everything about
+// the seal that needs no ledger.
+pub(crate) fn validate_seal(
+    hasher: &dyn ChainHasher,
+    slot_ms: u64,
+    clock: &dyn Clock,
+    block: &Block,
+    last_block: &Block,
+) -> Result<(), String> {
+    let seal = match &block.seal {
+        Some(seal) => seal,
+        None => return Err(String::from("blocks must be sealed by their slot leader")),
+    };
+    if block.last_hash != last_block.hash {
+        return Err(String::from("block does not extend its parent"));
+    }
+    if block.nonce != Nonce(0) || block.difficulty != last_block.difficulty {
+        return Err(String::from(
+            "sealed blocks keep nonce and difficulty fixed",
+        ));
+    }
+    if seal.slot <= slot_of(last_block) || seal.slot > current_slot(clock, slot_ms) + 1 {
+        return Err(format!("slot {} is out of order", seal.slot));
+    }
+    if block.timestamp != slot_start(slot_ms, seal.slot) {
+        return Err(format!("timestamp is not the start of slot {}", seal.slot));
+    }
+    if block.hash != Block::compute_hash(hasher, block) {
+        return Err(String::from("block hash does not match its contents"));
+    }
+    if !seal.verify(&block.hash) {
+        return Err(String::from("invalid block seal"));
+    }
+    return Ok(());
+}
+
+// Checks that the validator who sealed `block` leads its slot.
+pub(crate) fn validate_leader<F>(block: &Block, leader: F) -> Result<(), String>
+where
+    F: Fn(u64) -> Option<String>,
+{
+    let seal = match &block.seal {
+        Some(seal) => seal,
+        None => return Err(String::from("blocks must be sealed by their slot leader")),
+    };
+    if leader(seal.slot) != Some(seal.address()) {
+        return Err(format!(
+            "{} is not the leader of slot {}",
+            seal.address(),
+            seal.slot
+        ));
+    }
+    return Ok(());
+}
+
+// Checks a sealed header's link, slot, hash and signature. The hash is
+// recomputed from the header, whose data root stands in for the pruned body.
+pub(crate) fn is_valid_sealed_header(
+    hasher: &dyn ChainHasher,
+    header: &BlockHeader,
+    last_header: &BlockHeader,
+) -> bool {
+    let last_slot = match &last_header.seal {
+        Some(seal) => seal.slot,
+        None => 0,
+    };
+    match &header.seal {
+        Some(seal) => {
+            return header.last_hash == last_header.hash
+                && header.difficulty == last_header.difficulty
+                && seal.slot > last_slot
+                && header.hash == header.compute_hash(hasher)
+                && seal.verify(&header.hash);
+        }
+        None => return false,
+    }
+}
+
+impl ConsensusEngine for ProofOfStakeEngine {
+    fn produce_block(
+        &self,
+        last_block: &Block,
+        ledger: &Ledger,
+        contents: BlockContents,
+        signer: Option<&KeyPair>,
+    ) -> Result<Block, String> {
+        return produce_sealed(
+            self.hasher.as_ref(),
+            self.params.slot_ms,
+            self.clock.as_ref(),
+            last_block,
+            contents,
+            signer,
+            |slot| self.leader(&last_block.hash, slot, ledger),
+        );
+    }
+
+    fn next_block_time(
+        &self,
+        last_block: &Block,
+        ledger: &Ledger,
+        signer: Option<&KeyPair>,
+    ) -> Result<SystemTime, String> {
+        let slot = next_leader_slot(
+            self.params.slot_ms,
+            self.clock.as_ref(),
+            last_block,
+            signer,
+            |slot| self.leader(&last_block.hash, slot, ledger),
+        )?;
+        return Ok(self.slot_start(slot));
+    }
+
+    fn validate_block(
+        &self,
+        block: &Block,
+        last_block: &Block,
+        ledger: &Ledger,
+    ) -> Result<(), String> {
+        self.validate_proof(block, last_block)?;
+        return self.validate_state(block, last_block, ledger);
+    }
+
+    fn validate_proof(&self, block: &Block, last_block: &Block) -> Result<(), String> {
+        return validate_seal(
+            self.hasher.as_ref(),
+            self.params.slot_ms,
+            self.clock.as_ref(),
+            block,
+            last_block,
+        );
+    }
+
+    fn validate_state(
+        &self,
+        block: &Block,
+        last_block: &Block,
+        ledger: &Ledger,
+    ) -> Result<(), String> {
+        validate_leader(block, |slot| self.leader(&last_block.hash, slot, ledger))?;
+        if !authority::payload_votes(&block.data).is_empty() {
+            return Err(String::from(NO_VOTES));
+        }
+        let mut offenders = Vec::new();
+        for evidence in block.evidence.iter() {
+            let offender = evidence.offender()?;
+            if ledger.is_slashed(&offender) || offenders.contains(&offender) {
+                return Err(format!("{} is already slashed", offender));
+            }
+            offenders.push(offender);
+        }
+        return Ok(());
+    }
+
+    fn validate_header(&self, header: &BlockHeader, last_header: &BlockHeader) -> bool {
+        return is_valid_sealed_header(self.hasher.as_ref(), header, last_header);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{
+    block::Block,
+    transaction::Transaction,
+    types::{BlockHeight, Hash256},
+};
+
+use std::{
+    fmt,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainEvent {
+    NewTip {
+        height: BlockHeight,
+        hash: Hash256,
+    },
+    NewBlock {
+        height: BlockHeight,
+        block: Block,
+    },
+    Reorg {
+        fork_height: BlockHeight,
+        disconnected: Vec<Block>,
+        connected: Vec<Block>,
+    },
+    NewTransaction {
+        transaction: Transaction,
+    },
+    Finalized {
+        height: BlockHeight,
+        hash: Hash256,
+    },
+}
+
+pub struct EventBus {
+    subscribers: Mutex<Vec<Sender<ChainEvent>>>,
+}
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        return EventBus {
+            subscribers: Mutex::new(Vec::new()),
+        };
+    }
+
+    pub fn subscribe(&self) -> Receiver<ChainEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.lock().unwrap().push(sender);
+        return receiver;
+    }
+
+    pub fn publish(&self, event: ChainEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        return self.subscribers.lock().unwrap().len();
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> EventBus {
+        return EventBus::new();
+    }
+}
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return f
+            .debug_struct("EventBus")
+            .field("subscribers", &self.subscriber_count())
+            .finish();
+    }
+}
@@ -0,0 +1,393 @@
+use crate::types::{BlockHeight, Hash256};
+
+use crypto::keys::{self, KeyPair};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FinalityParams {
+    // Hex public keys of the validators that vote blocks final.
+    pub validators: Vec<String>,
+}
+
+impl FinalityParams {
+    pub fn validator_set(&self) -> Result<ValidatorSet, String> {
+        if self.validators.is_empty() {
+            return Err(String::from("finality needs at least one validator"));
+        }
+        let mut validators = Vec::new();
+        for validator in self.validators.iter() {
+            match hex::decode(validator) {
+                Ok(key) if key.len() == keys::PUBLIC_KEY_LENGTH => {
+                    let mut public_key = [0; keys::PUBLIC_KEY_LENGTH];
+                    public_key.copy_from_slice(&key);
+                    validators.push(public_key);
+                }
+                _ => return Err(format!("invalid finality validator {}", validator)),
+            }
+        }
+        return Ok(ValidatorSet::new(&validators));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VoteKind {
+    Prevote,
+    Precommit,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FinalityVote {
+    pub kind: VoteKind,
+    pub height: BlockHeight,
+    pub round: u64,
+    pub hash: Hash256,
+    #[serde(with = "hex::serde")]
+    pub validator: [u8; keys::PUBLIC_KEY_LENGTH],
+    #[serde(with = "hex::serde")]
+    pub signature: Vec<u8>,
+}
+
+impl FinalityVote {
+    pub fn new_signed(
+        key_pair: &KeyPair,
+        kind: VoteKind,
+        height: BlockHeight,
+        round: u64,
+        hash: Hash256,
+    ) -> FinalityVote {
+        let mut vote = FinalityVote {
+            kind,
+            height,
+            round,
+            hash,
+            validator: key_pair.public_key(),
+            signature: Vec::new(),
+        };
+        vote.signature = key_pair.sign(&vote.message()).to_vec();
+        return vote;
+    }
+
+    pub fn message(&self) -> Vec<u8> {
+        let mut message = b"finality vote".to_vec();
+        message.push(self.kind as u8);
+        message.extend_from_slice(&self.height.0.to_be_bytes());
+        message.extend_from_slice(&self.round.to_be_bytes());
+        message.extend_from_slice(self.hash.as_bytes());
+        return message;
+    }
+
+    pub fn verify(&self) -> bool {
+        return keys::verify(&self.validator, &self.message(), &self.signature);
+    }
+}
+
+// Tolerates `max_faulty` byzantine validators: any two quorums share more
+// than that many validators, so they share an honest one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorSet {
+    validators: BTreeSet<[u8; keys::PUBLIC_KEY_LENGTH]>,
+}
+
+impl ValidatorSet {
+    pub fn new(validators: &[[u8; keys::PUBLIC_KEY_LENGTH]]) -> ValidatorSet {
+        return ValidatorSet {
+            validators: validators.iter().cloned().collect(),
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.validators.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.validators.is_empty();
+    }
+
+    pub fn contains(&self, validator: &[u8]) -> bool {
+        return self
+            .validators
+            .iter()
+            .any(|member| member[..] == *validator);
+    }
+
+    pub fn quorum(&self) -> usize {
+        return self.len() * 2 / 3 + 1;
+    }
+
+    pub fn max_faulty(&self) -> usize {
+        return self.len().saturating_sub(1) / 3;
+    }
+}
+
+// A quorum of precommits for one block, which makes it and its ancestors final.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FinalityCertificate {
+    pub height: BlockHeight,
+    pub round: u64,
+    pub hash: Hash256,
+    pub precommits: Vec<FinalityVote>,
+}
+
+impl FinalityCertificate {
+    pub fn verify(&self, validators: &ValidatorSet) -> Result<(), String> {
+        let mut signers = BTreeSet::new();
+        for vote in self.precommits.iter() {
+            if vote.kind != VoteKind::Precommit
+                || vote.height != self.height
+                || vote.round != self.round
+                || vote.hash != self.hash
+            {
+                return Err(String::from("certificate holds a vote for something else"));
+            }
+            if !validators.contains(&vote.validator) {
+                return Err(format!(
+                    "{} is not a finality validator",
+                    hex::encode(vote.validator)
+                ));
+            }
+            if !vote.verify() {
+                return Err(format!(
+                    "invalid precommit signature from {}",
+                    hex::encode(vote.validator)
+                ));
+            }
+            signers.insert(vote.validator);
+        }
+        if signers.len() < validators.quorum() {
+            return Err(format!(
+                "certificate has {} of the {} precommits needed",
+                signers.len(),
+                validators.quorum()
+            ));
+        }
+        return Ok(());
+    }
+}
+
+// One validator's view of the vote on the next block to finalize, following
+// Tendermint: prevote the proposed block, or the block locked on; precommit
+// once a quorum prevotes the same block in the current round, locking on it;
+// finalize once a quorum precommits the same block in any round. A round
+// that stalls is abandoned with `next_round`, keeping the lock.
+#[derive(Debug)]
+pub struct FinalityGadget {
+    validators: ValidatorSet,
+    key_pair: Option<KeyPair>,
+    height: BlockHeight,
+    round: u64,
+    locked: Option<(u64, Hash256)>,
+    prevoted: bool,
+    precommitted: bool,
+    votes: BTreeMap<(u64, VoteKind), BTreeMap<[u8; keys::PUBLIC_KEY_LENGTH], FinalityVote>>,
+    finalized: Option<FinalityCertificate>,
+}
+
+impl FinalityGadget {
+    // Without a key, or with a key outside the set, the gadget only follows
+    // the votes of others.
+    pub fn new(
+        validators: ValidatorSet,
+        key_pair: Option<KeyPair>,
+        finalized: Option<FinalityCertificate>,
+    ) -> FinalityGadget {
+        let height = match &finalized {
+            Some(certificate) => certificate.height.next(),
+            None => BlockHeight(1),
+        };
+        return FinalityGadget {
+            validators,
+            key_pair,
+            height,
+            round: 0,
+            locked: None,
+            prevoted: false,
+            precommitted: false,
+            votes: BTreeMap::new(),
+            finalized,
+        };
+    }
+
+    // The height being voted on.
+    pub fn height(&self) -> BlockHeight {
+        return self.height;
+    }
+
+    pub fn round(&self) -> u64 {
+        return self.round;
+    }
+
+    pub fn locked(&self) -> Option<Hash256> {
+        return self.locked.map(|(_, hash)| hash);
+    }
+
+    pub fn finalized(&self) -> Option<&FinalityCertificate> {
+        return self.finalized.as_ref();
+    }
+
+    // Every vote counted at the current height, this node's included, for
+    // peers that have not seen them yet.
+    pub fn votes(&self) -> Vec<FinalityVote> {
+        return self
+            .votes
+            .values()
+            .flat_map(|votes| votes.values().cloned())
+            .collect();
+    }
+
+    // Prevotes `hash`, this node's block at the current height, unless it
+    // already prevoted this round. Returns the votes to broadcast.
+    pub fn propose(&mut self, hash: Hash256) -> Vec<FinalityVote> {
+        if self.prevoted {
+            return Vec::new();
+        }
+        let hash = self.locked().unwrap_or(hash);
+        let prevote = match self.sign(VoteKind::Prevote, hash) {
+            Some(prevote) => prevote,
+            None => return Vec::new(),
+        };
+        self.prevoted = true;
+        self.record(prevote.clone());
+        let mut outgoing = vec![prevote];
+        outgoing.extend(self.advance());
+        return outgoing;
+    }
+
+    // Counts a vote from another validator and returns the votes it leads
+    // this node to broadcast. Votes for other heights are ignored; a second,
+    // different vote from a validator in the same step is refused.
+    pub fn on_vote(&mut self, vote: FinalityVote) -> Result<Vec<FinalityVote>, String> {
+        if vote.height != self.height {
+            return Ok(Vec::new());
+        }
+        if !self.validators.contains(&vote.validator) {
+            return Err(format!(
+                "{} is not a finality validator",
+                hex::encode(vote.validator)
+            ));
+        }
+        if !vote.verify() {
+            return Err(String::from("invalid finality vote signature"));
+        }
+        let cast = self
+            .votes
+            .get(&(vote.round, vote.kind))
+            .and_then(|votes| votes.get(&vote.validator));
+        match cast {
+            Some(cast) if cast.hash == vote.hash => return Ok(Vec::new()),
+            Some(_) => {
+                return Err(format!(
+                    "{} equivocated in round {}",
+                    hex::encode(vote.validator),
+                    vote.round
+                ))
+            }
+            None => {}
+        }
+        self.record(vote);
+        return Ok(self.advance());
+    }
+
+    // Adopts a certificate finalized without this node's votes, e.g. while it
+    // was catching up. Returns whether it moved the gadget forward.
+    pub fn on_certificate(&mut self, certificate: FinalityCertificate) -> Result<bool, String> {
+        if certificate.height < self.height {
+            return Ok(false);
+        }
+        certificate.verify(&self.validators)?;
+        self.finalize(certificate);
+        return Ok(true);
+    }
+
+    // Gives up on the current round, e.g. after a timeout without a quorum.
+    pub fn next_round(&mut self) {
+        self.round += 1;
+        self.prevoted = false;
+        self.precommitted = false;
+    }
+
+    fn sign(&self, kind: VoteKind, hash: Hash256) -> Option<FinalityVote> {
+        let key_pair = self.key_pair.as_ref()?;
+        if !self.validators.contains(&key_pair.public_key()) {
+            return None;
+        }
+        return Some(FinalityVote::new_signed(
+            key_pair,
+            kind,
+            self.height,
+            self.round,
+            hash,
+        ));
+    }
+
+    fn record(&mut self, vote: FinalityVote) {
+        self.votes
+            .entry((vote.round, vote.kind))
+            .or_default()
+            .insert(vote.validator, vote);
+    }
+
+    fn quorum_hash(&self, round: u64, kind: VoteKind) -> Option<Hash256> {
+        let votes = self.votes.get(&(round, kind))?;
+        let mut tallies: BTreeMap<Hash256, usize> = BTreeMap::new();
+        for vote in votes.values() {
+            *tallies.entry(vote.hash).or_default() += 1;
+        }
+        return tallies
+            .into_iter()
+            .find(|(_, count)| *count >= self.validators.quorum())
+            .map(|(hash, _)| hash);
+    }
+
+    fn advance(&mut self) -> Vec<FinalityVote> {
+        let mut outgoing = Vec::new();
+        let rounds: BTreeSet<u64> = self.votes.keys().map(|(round, _)| *round).collect();
+        for round in rounds.iter() {
+            if let Some(hash) = self.quorum_hash(*round, VoteKind::Prevote) {
+                let relock = match self.locked {
+                    Some((locked_round, _)) => *round > locked_round,
+                    None => true,
+                };
+                if relock {
+                    self.locked = Some((*round, hash));
+                }
+                if *round == self.round && !self.precommitted {
+                    if let Some(precommit) = self.sign(VoteKind::Precommit, hash) {
+                        self.precommitted = true;
+                        self.record(precommit.clone());
+                        outgoing.push(precommit);
+                    }
+                }
+            }
+        }
+        for round in rounds.iter() {
+            if let Some(hash) = self.quorum_hash(*round, VoteKind::Precommit) {
+                let precommits = self.votes[&(*round, VoteKind::Precommit)]
+                    .values()
+                    .filter(|vote| vote.hash == hash)
+                    .cloned()
+                    .collect();
+                self.finalize(FinalityCertificate {
+                    height: self.height,
+                    round: *round,
+                    hash,
+                    precommits,
+                });
+                break;
+            }
+        }
+        return outgoing;
+    }
+
+    fn finalize(&mut self, certificate: FinalityCertificate) {
+        self.height = certificate.height.next();
+        self.round = 0;
+        self.locked = None;
+        self.prevoted = false;
+        self.precommitted = false;
+        self.votes.clear();
+        self.finalized = Some(certificate);
+    }
+}
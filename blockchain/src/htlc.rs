@@ -0,0 +1,171 @@
+use crate::{
+    script::{
+        self, Instruction, OP_0, OP_1, OP_CHECKLOCKTIMEVERIFY, OP_CHECKSIG, OP_DROP, OP_ELSE,
+        OP_ENDIF, OP_EQUALVERIFY, OP_IF, OP_SHA256,
+    },
+    transaction::{OutPoint, Transaction, TxOutput},
+};
+
+use crypto::{
+    cryptohash,
+    keys::{KeyPair, PUBLIC_KEY_LENGTH},
+};
+use std::convert::TryFrom;
+
+// OP_IF OP_SHA256 <hash> OP_EQUALVERIFY <recipient> OP_CHECKSIG
+// OP_ELSE <timeout> OP_CHECKLOCKTIMEVERIFY OP_DROP <refund> OP_CHECKSIG OP_ENDIF
+#[derive(Debug, Clone, PartialEq)]
+pub struct Htlc {
+    pub hash: [u8; 32],
+    pub recipient: [u8; PUBLIC_KEY_LENGTH],
+    pub refund: [u8; PUBLIC_KEY_LENGTH],
+    pub timeout: u64,
+}
+
+impl Htlc {
+    pub fn new(
+        preimage: &[u8],
+        recipient: [u8; PUBLIC_KEY_LENGTH],
+        refund: [u8; PUBLIC_KEY_LENGTH],
+        timeout: u64,
+    ) -> Htlc {
+        return Htlc {
+            hash: cryptohash::sha256(preimage),
+            recipient,
+            refund,
+            timeout,
+        };
+    }
+
+    pub fn from_script(locking_script: &[u8]) -> Option<Htlc> {
+        let instructions = script::parse(locking_script).ok()?;
+        if instructions.len() != 13 {
+            return None;
+        }
+        let push = |index: usize| match &instructions[index] {
+            Instruction::Push(data) => Some(data.clone()),
+            Instruction::Op(_) => None,
+        };
+        let timeout = script::decode_number(&push(7)?).ok()?;
+        if timeout < 0 {
+            return None;
+        }
+        let htlc = Htlc {
+            hash: to_array(&push(2)?)?,
+            recipient: to_array(&push(4)?)?,
+            refund: to_array(&push(10)?)?,
+            timeout: timeout as u64,
+        };
+        if htlc.locking_script() != locking_script {
+            return None;
+        }
+        return Some(htlc);
+    }
+
+    pub fn locking_script(&self) -> Vec<u8> {
+        let mut locking_script = vec![OP_IF, OP_SHA256];
+        script::push_data(&mut locking_script, &self.hash);
+        locking_script.push(OP_EQUALVERIFY);
+        script::push_data(&mut locking_script, &self.recipient);
+        locking_script.extend_from_slice(&[OP_CHECKSIG, OP_ELSE]);
+        // Script numbers are signed; saturate rather than wrap to a negative lock height.
+        let timeout = i64::try_from(self.timeout).unwrap_or(i64::MAX);
+        script::push_number(&mut locking_script, timeout);
+        locking_script.extend_from_slice(&[OP_CHECKLOCKTIMEVERIFY, OP_DROP]);
+        script::push_data(&mut locking_script, &self.refund);
+        locking_script.extend_from_slice(&[OP_CHECKSIG, OP_ENDIF]);
+        return locking_script;
+    }
+
+    pub fn address(&self) -> String {
+        return script::address(&self.locking_script());
+    }
+
+    pub fn claim(
+        &self,
+        outpoints: &[OutPoint],
+        outputs: Vec<TxOutput>,
+        key_pair: &KeyPair,
+        preimage: &[u8],
+    ) -> Result<Transaction, String> {
+        if key_pair.public_key() != self.recipient {
+            return Err(String::from("key is not the HTLC recipient"));
+        }
+        if cryptohash::sha256(preimage) != self.hash {
+            return Err(String::from("preimage does not match the HTLC hash"));
+        }
+        let mut transaction = self.spend(outpoints, outputs, 0);
+        let signature = key_pair.sign(&transaction.sighash());
+        let mut unlocking_script = Vec::new();
+        script::push_data(&mut unlocking_script, &signature);
+        script::push_data(&mut unlocking_script, preimage);
+        unlocking_script.push(OP_1);
+        for index in 0..outpoints.len() {
+            transaction.set_unlocking_script(index, unlocking_script.clone());
+        }
+        return Ok(transaction);
+    }
+
+    pub fn refund(
+        &self,
+        outpoints: &[OutPoint],
+        outputs: Vec<TxOutput>,
+        key_pair: &KeyPair,
+    ) -> Result<Transaction, String> {
+        if key_pair.public_key() != self.refund {
+            return Err(String::from("key is not the HTLC refund key"));
+        }
+        let mut transaction = self.spend(outpoints, outputs, self.timeout);
+        let signature = key_pair.sign(&transaction.sighash());
+        let mut unlocking_script = Vec::new();
+        script::push_data(&mut unlocking_script, &signature);
+        unlocking_script.push(OP_0);
+        for index in 0..outpoints.len() {
+            transaction.set_unlocking_script(index, unlocking_script.clone());
+        }
+        return Ok(transaction);
+    }
+
+    pub fn extract_preimage(&self, transaction: &Transaction) -> Option<Vec<u8>> {
+        let locking_script = self.locking_script();
+        for input in transaction.inputs.iter() {
+            let spend = match &input.script {
+                Some(spend) if spend.locking_script == locking_script => spend,
+                _ => continue,
+            };
+            if let Ok(instructions) = script::parse(&spend.unlocking_script) {
+                if let [Instruction::Push(_), Instruction::Push(preimage), Instruction::Push(flag)] =
+                    instructions.as_slice()
+                {
+                    if script::cast_to_bool(flag) && cryptohash::sha256(preimage) == self.hash {
+                        return Some(preimage.clone());
+                    }
+                }
+            }
+        }
+        return None;
+    }
+
+    fn spend(
+        &self,
+        outpoints: &[OutPoint],
+        outputs: Vec<TxOutput>,
+        lock_height: u64,
+    ) -> Transaction {
+        let locking_script = self.locking_script();
+        let spends: Vec<(OutPoint, Vec<u8>)> = outpoints
+            .iter()
+            .map(|outpoint| (*outpoint, locking_script.clone()))
+            .collect();
+        return Transaction::new_script_spend(&spends, outputs, lock_height);
+    }
+}
+
+fn to_array(bytes: &[u8]) -> Option<[u8; 32]> {
+    if bytes.len() != 32 {
+        return None;
+    }
+    let mut array = [0; 32];
+    array.copy_from_slice(bytes);
+    return Some(array);
+}
@@ -0,0 +1,182 @@
+use crate::{
+    block::Block,
+    transaction::{Transaction, TxInput},
+    types::{BlockHeight, Hash256},
+};
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxLocation {
+    pub height: BlockHeight,
+    pub position: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct IndexedBlock {
+    pub(crate) hash: String,
+    pub(crate) txids: Vec<String>,
+    pub(crate) addresses: Vec<String>,
+}
+
+// Hashes, txids and addresses are keyed by their hex encoding so the index
+// serializes to plain JSON objects. `blocks[0]` sits at `base_height`, which
+// is non-zero for chains started from a snapshot.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChainIndex {
+    #[serde(default)]
+    pub(crate) base_height: BlockHeight,
+    pub(crate) blocks: Vec<IndexedBlock>,
+    pub(crate) heights: HashMap<String, BlockHeight>,
+    pub(crate) transactions: HashMap<String, TxLocation>,
+    pub(crate) addresses: HashMap<String, Vec<TxLocation>>,
+}
+
+impl ChainIndex {
+    pub fn new() -> ChainIndex {
+        return ChainIndex::default();
+    }
+
+    pub fn with_base(base_height: BlockHeight) -> ChainIndex {
+        return ChainIndex {
+            base_height,
+            ..ChainIndex::default()
+        };
+    }
+
+    pub fn build(chain: &[Block]) -> ChainIndex {
+        let mut index = ChainIndex::new();
+        index.sync(chain);
+        return index;
+    }
+
+    pub fn base_height(&self) -> BlockHeight {
+        return self.base_height;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.blocks.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.blocks.is_empty();
+    }
+
+    // Walks back from the indexed tip to the last block shared with `chain`,
+    // disconnects everything above it and connects the rest of `chain`.
+    // `chain[0]` must sit at the index's base height.
+    pub fn sync(&mut self, chain: &[Block]) {
+        let mut fork = self.blocks.len().min(chain.len());
+        while fork > 0 && self.blocks[fork - 1].hash != chain[fork - 1].hash.to_string() {
+            fork -= 1;
+        }
+        while self.blocks.len() > fork {
+            self.disconnect_tip();
+        }
+        for position in fork..chain.len() {
+            self.connect_block(chain, position);
+        }
+    }
+
+    pub fn block_height(&self, hash: &Hash256) -> Option<BlockHeight> {
+        return self.heights.get(&hash.to_string()).copied();
+    }
+
+    pub fn transaction_location(&self, txid: &[u8; 32]) -> Option<TxLocation> {
+        return self.transactions.get(&hex::encode(txid)).copied();
+    }
+
+    pub fn address_transactions(&self, address: &str) -> &[TxLocation] {
+        match self.addresses.get(address) {
+            Some(locations) => return locations,
+            None => return &[],
+        }
+    }
+
+    pub(crate) fn indexed_block(&self, height: BlockHeight) -> Option<&IndexedBlock> {
+        if height < self.base_height {
+            return None;
+        }
+        return self.blocks.get((height - self.base_height) as usize);
+    }
+
+    fn connect_block(&mut self, chain: &[Block], position: usize) {
+        let block = &chain[position];
+        let height = self.base_height + position as u64;
+        let transactions = Transaction::decode_payload(&block.data).unwrap_or_default();
+        let mut decoded: HashMap<BlockHeight, Vec<Transaction>> = HashMap::new();
+        decoded.insert(height, transactions.clone());
+        let mut indexed = IndexedBlock {
+            hash: block.hash.to_string(),
+            txids: Vec::new(),
+            addresses: Vec::new(),
+        };
+        let mut block_addresses = BTreeSet::new();
+        for (position, transaction) in transactions.iter().enumerate() {
+            let location = TxLocation { height, position };
+            let txid = hex::encode(transaction.txid());
+            self.transactions.insert(txid.clone(), location);
+            indexed.txids.push(txid);
+
+            let mut addresses: BTreeSet<String> = transaction
+                .outputs
+                .iter()
+                .map(|output| output.address.clone())
+                .collect();
+            for input in transaction.inputs.iter() {
+                if let Some(address) = self.spent_address(chain, &mut decoded, input) {
+                    addresses.insert(address);
+                }
+            }
+            for address in addresses {
+                self.addresses
+                    .entry(address.clone())
+                    .or_default()
+                    .push(location);
+                block_addresses.insert(address);
+            }
+        }
+        indexed.addresses = block_addresses.into_iter().collect();
+        self.heights.insert(indexed.hash.clone(), height);
+        self.blocks.push(indexed);
+    }
+
+    fn disconnect_tip(&mut self) {
+        let height = self.base_height + (self.blocks.len() - 1) as u64;
+        let indexed = self.blocks.pop().unwrap();
+        self.heights.remove(&indexed.hash);
+        for txid in indexed.txids.iter() {
+            self.transactions.remove(txid);
+        }
+        for address in indexed.addresses.iter() {
+            if let Some(locations) = self.addresses.get_mut(address) {
+                locations.retain(|location| location.height < height);
+                if locations.is_empty() {
+                    self.addresses.remove(address);
+                }
+            }
+        }
+    }
+
+    fn spent_address(
+        &self,
+        chain: &[Block],
+        decoded: &mut HashMap<BlockHeight, Vec<Transaction>>,
+        input: &TxInput,
+    ) -> Option<String> {
+        let location = self.transaction_location(&input.previous_output.txid)?;
+        if location.height < self.base_height {
+            return None;
+        }
+        let block = chain.get((location.height - self.base_height) as usize)?;
+        let transactions = decoded.entry(location.height).or_insert_with(|| {
+            return Transaction::decode_payload(&block.data).unwrap_or_default();
+        });
+        let output = transactions
+            .get(location.position)?
+            .outputs
+            .get(input.previous_output.index as usize)?;
+        return Some(output.address.clone());
+    }
+}
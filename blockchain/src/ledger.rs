@@ -0,0 +1,489 @@
+use crate::{
+    authority::AuthoritySet,
+    block::Block,
+    config::MINING_REWARD,
+    consensus::DoubleSign,
+    script::{self, ScriptContext},
+    transaction::{OutPoint, Transaction, TxInput, TxOutput},
+    types::{BlockHeight, Hash256},
+};
+
+use crypto::{cryptohash, keys};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Ledger {
+    utxos: BTreeMap<OutPoint, TxOutput>,
+    // The sum, modulo 2^256, of the hashes of every unspent output. Each
+    // output created or spent adds or subtracts its own hash, so committing
+    // to the UTXO set never rehashes all of it.
+    utxo_digest: [u8; 32],
+    // Validators that lost their stake for double signing; their outputs at
+    // the time of slashing are burned.
+    slashed: BTreeSet<String>,
+    // The proof of authority set after every vote so far; `None` on chains
+    // without authorities.
+    authorities: Option<AuthoritySet>,
+}
+
+// Everything applying a block changed in a ledger, so `Ledger::undo_block`
+// can take the block back off, like the undo data `SledStore` keeps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LedgerUndo {
+    // Outputs created and spent, oldest first.
+    changes: Vec<UtxoChange>,
+    slashed: Vec<String>,
+    authorities: Option<AuthoritySet>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum UtxoChange {
+    Created(OutPoint),
+    Spent(OutPoint, TxOutput),
+}
+
+impl Ledger {
+    pub fn new() -> Ledger {
+        return Ledger {
+            utxos: BTreeMap::new(),
+            utxo_digest: [0; 32],
+            slashed: BTreeSet::new(),
+            authorities: None,
+        };
+    }
+
+    pub fn from_chain(chain: &[Block]) -> Result<Ledger, String> {
+        let mut ledger = Ledger::new();
+        ledger.apply_chain(BlockHeight::GENESIS, chain)?;
+        return Ok(ledger);
+    }
+
+    // Applies every block after `chain[0]`, which sits at `base_height` and is
+    // already reflected in this ledger.
+    pub fn apply_chain(&mut self, base_height: BlockHeight, chain: &[Block]) -> Result<(), String> {
+        for (position, block) in chain.iter().enumerate().skip(1) {
+            self.apply_block(base_height + position as u64, block)?;
+        }
+        return Ok(());
+    }
+
+    pub fn from_utxos<I>(utxos: I) -> Ledger
+    where
+        I: IntoIterator<Item = (OutPoint, TxOutput)>,
+    {
+        let mut ledger = Ledger::new();
+        for (outpoint, output) in utxos {
+            ledger.insert_utxo(outpoint, output);
+        }
+        return ledger;
+    }
+
+    pub fn with_slashed<I>(mut self, slashed: I) -> Ledger
+    where
+        I: IntoIterator<Item = String>,
+    {
+        self.slashed.extend(slashed);
+        return self;
+    }
+
+    pub fn with_authorities(mut self, authorities: Option<AuthoritySet>) -> Ledger {
+        self.authorities = authorities;
+        return self;
+    }
+
+    pub fn authorities(&self) -> Option<&AuthoritySet> {
+        return self.authorities.as_ref();
+    }
+
+    pub fn slashed(&self) -> &BTreeSet<String> {
+        return &self.slashed;
+    }
+
+    pub fn is_slashed(&self, address: &str) -> bool {
+        return self.slashed.contains(address);
+    }
+
+    pub fn utxos(&self) -> &BTreeMap<OutPoint, TxOutput> {
+        return &self.utxos;
+    }
+
+    pub fn get(&self, outpoint: &OutPoint) -> Option<&TxOutput> {
+        return self.utxos.get(outpoint);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.utxos.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.utxos.is_empty();
+    }
+
+    pub fn unspent(&self, address: &str) -> Vec<(OutPoint, TxOutput)> {
+        return self
+            .utxos
+            .iter()
+            .filter(|(_, output)| output.address == address)
+            .map(|(outpoint, output)| (*outpoint, output.clone()))
+            .collect();
+    }
+
+    pub fn balance(&self, address: &str) -> u64 {
+        return self
+            .unspent(address)
+            .iter()
+            .map(|(_, output)| output.amount)
+            .sum();
+    }
+
+    // Hashes the digest of the unspent outputs, then any slashed validators
+    // and the authority set, so two ledgers share a commitment exactly when
+    // they hold the same state.
+    pub fn commitment(&self) -> Hash256 {
+        let mut bytes = self.utxo_digest.to_vec();
+        if !self.slashed.is_empty() {
+            bytes.extend_from_slice(b"slashed");
+            for address in self.slashed.iter() {
+                bytes.extend_from_slice(&(address.len() as u64).to_be_bytes());
+                bytes.extend_from_slice(address.as_bytes());
+            }
+        }
+        if let Some(authorities) = &self.authorities {
+            bytes.extend_from_slice(b"authorities");
+            bytes.extend_from_slice(&serde_json::to_vec(authorities).unwrap());
+        }
+        return Hash256(cryptohash::sha256(&bytes));
+    }
+
+    // The commitment a block with `data` and `evidence` would leave at
+    // `height`. The block is applied in place and undone again.
+    pub fn commitment_after(
+        &mut self,
+        height: BlockHeight,
+        data: &str,
+        evidence: &[DoubleSign],
+    ) -> Result<Hash256, String> {
+        let mut undo = self.start_undo();
+        let commitment = self
+            .apply_contents(height, data, evidence, &mut undo)
+            .map(|()| self.commitment());
+        self.undo_block(undo);
+        return commitment;
+    }
+
+    // Applies `block` in place. A block that fails leaves the ledger as it
+    // was; one that applies returns what `undo_block` needs to remove it.
+    pub fn apply_block(
+        &mut self,
+        height: BlockHeight,
+        block: &Block,
+    ) -> Result<LedgerUndo, String> {
+        let mut undo = self.start_undo();
+        let applied = self
+            .apply_contents(height, &block.data, &block.evidence, &mut undo)
+            .and_then(|()| match block.state_root {
+                Some(state_root) if state_root != self.commitment() => {
+                    Err(format!("block {}: state root mismatch", height))
+                }
+                _ => Ok(()),
+            });
+        match applied {
+            Ok(()) => return Ok(undo),
+            Err(e) => {
+                self.undo_block(undo);
+                return Err(e);
+            }
+        }
+    }
+
+    pub fn undo_block(&mut self, undo: LedgerUndo) {
+        for change in undo.changes.into_iter().rev() {
+            match change {
+                UtxoChange::Created(outpoint) => {
+                    self.remove_utxo(&outpoint);
+                }
+                UtxoChange::Spent(outpoint, output) => {
+                    self.insert_utxo(outpoint, output);
+                }
+            }
+        }
+        for address in undo.slashed.iter() {
+            self.slashed.remove(address);
+        }
+        self.authorities = undo.authorities;
+    }
+
+    fn start_undo(&self) -> LedgerUndo {
+        return LedgerUndo {
+            changes: Vec::new(),
+            slashed: Vec::new(),
+            authorities: self.authorities.clone(),
+        };
+    }
+
+    fn apply_contents(
+        &mut self,
+        height: BlockHeight,
+        data: &str,
+        evidence: &[DoubleSign],
+        undo: &mut LedgerUndo,
+    ) -> Result<(), String> {
+        self.apply_payload(height, data, undo)?;
+        return self.apply_evidence(height, evidence, undo);
+    }
+
+    fn apply_evidence(
+        &mut self,
+        height: BlockHeight,
+        evidence: &[DoubleSign],
+        undo: &mut LedgerUndo,
+    ) -> Result<(), String> {
+        for double_sign in evidence.iter() {
+            let offender = double_sign.offender()?;
+            if !self.slashed.insert(offender.clone()) {
+                return Err(format!("block {}: {} is already slashed", height, offender));
+            }
+            undo.slashed.push(offender.clone());
+            // Burn the offender's stake so it can be neither spent nor staked again.
+            let burned: Vec<OutPoint> = self
+                .utxos
+                .iter()
+                .filter(|(_, output)| output.address == offender)
+                .map(|(outpoint, _)| *outpoint)
+                .collect();
+            for outpoint in burned.iter() {
+                if let Some(output) = self.remove_utxo(outpoint) {
+                    undo.changes.push(UtxoChange::Spent(*outpoint, output));
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    fn apply_payload(
+        &mut self,
+        height: BlockHeight,
+        data: &str,
+        undo: &mut LedgerUndo,
+    ) -> Result<(), String> {
+        let transactions = match Transaction::decode_payload(data) {
+            Some(transactions) => transactions,
+            None => return Ok(()),
+        };
+        let mut fees: u64 = 0;
+        for (position, transaction) in transactions.iter().enumerate() {
+            if transaction.is_coinbase() {
+                if position != 0 {
+                    return Err(format!("block {}: coinbase must come first", height));
+                }
+                continue;
+            }
+            let fee = self.validate_transaction(transaction, height)?;
+            fees = match fees.checked_add(fee) {
+                Some(fees) => fees,
+                None => return Err(format!("block {}: fee overflow", height)),
+            };
+            self.record_transaction(transaction, &mut undo.changes);
+        }
+        if let Some(coinbase) = transactions.first().filter(|tx| tx.is_coinbase()) {
+            if coinbase.lock_height != height.0 {
+                return Err(format!("block {}: coinbase height mismatch", height));
+            }
+            if coinbase
+                .outputs
+                .iter()
+                .any(|output| !keys::is_valid_address(&output.address))
+            {
+                return Err(format!("block {}: invalid coinbase output", height));
+            }
+            match coinbase.output_total() {
+                Some(total) if total <= MINING_REWARD.saturating_add(fees) => {}
+                _ => return Err(format!("block {}: coinbase pays too much", height)),
+            }
+            self.record_transaction(coinbase, &mut undo.changes);
+        }
+        return Ok(());
+    }
+
+    pub fn validate_transaction(
+        &self,
+        transaction: &Transaction,
+        height: BlockHeight,
+    ) -> Result<u64, String> {
+        let txid = hex::encode(transaction.txid());
+        if transaction.is_coinbase() {
+            return Err(format!("{}: coinbase outside of a block", txid));
+        }
+        if transaction.lock_height > height.0 {
+            return Err(format!(
+                "{}: locked until height {}",
+                txid, transaction.lock_height
+            ));
+        }
+        if let Some(vote) = &transaction.vote {
+            if !transaction.inputs.is_empty() || !transaction.outputs.is_empty() {
+                return Err(format!("{}: votes cannot move funds", txid));
+            }
+            if !vote.verify() {
+                return Err(format!("{}: invalid vote signature", txid));
+            }
+            let mut authorities = match &self.authorities {
+                Some(authorities) => authorities.clone(),
+                None => return Err(format!("{}: chain has no authority set", txid)),
+            };
+            if let Err(e) = authorities.apply(vote) {
+                return Err(format!("{}: {}", txid, e));
+            }
+            return Ok(0);
+        }
+        if transaction.outputs.is_empty() {
+            return Err(format!("{}: no outputs", txid));
+        }
+        for output in transaction.outputs.iter() {
+            if output.amount == 0 || !keys::is_valid_address(&output.address) {
+                return Err(format!("{}: invalid output", txid));
+            }
+        }
+        let output_total = match transaction.output_total() {
+            Some(total) => total,
+            None => return Err(format!("{}: output overflow", txid)),
+        };
+        let sighash = transaction.sighash();
+        let mut spent = BTreeSet::new();
+        let mut input_total: u64 = 0;
+        for input in transaction.inputs.iter() {
+            if !spent.insert(input.previous_output) {
+                return Err(format!("{}: duplicate input", txid));
+            }
+            let previous = match self.utxos.get(&input.previous_output) {
+                Some(previous) => previous,
+                None => return Err(format!("{}: input is missing or spent", txid)),
+            };
+            if let Err(e) = verify_input(input, previous, &sighash, transaction.lock_height) {
+                return Err(format!("{}: {}", txid, e));
+            }
+            input_total = match input_total.checked_add(previous.amount) {
+                Some(total) => total,
+                None => return Err(format!("{}: input overflow", txid)),
+            };
+        }
+        if input_total < output_total {
+            return Err(format!("{}: outputs exceed inputs", txid));
+        }
+        return Ok(input_total - output_total);
+    }
+
+    pub fn apply_transaction(&mut self, transaction: &Transaction) {
+        self.record_transaction(transaction, &mut Vec::new());
+    }
+
+    // Applies `transaction`, appending the outputs it spent and created to
+    // `changes`.
+    fn record_transaction(&mut self, transaction: &Transaction, changes: &mut Vec<UtxoChange>) {
+        if let (Some(vote), Some(authorities)) = (&transaction.vote, &mut self.authorities) {
+            // Checked by `validate_transaction` before the vote was applied.
+            let _ = authorities.apply(vote);
+        }
+        for input in transaction.inputs.iter() {
+            if let Some(output) = self.remove_utxo(&input.previous_output) {
+                changes.push(UtxoChange::Spent(input.previous_output, output));
+            }
+        }
+        let txid = transaction.txid();
+        for (index, output) in transaction.outputs.iter().enumerate() {
+            let outpoint = OutPoint {
+                txid,
+                index: index as u32,
+            };
+            if let Some(replaced) = self.insert_utxo(outpoint, output.clone()) {
+                changes.push(UtxoChange::Spent(outpoint, replaced));
+            }
+            changes.push(UtxoChange::Created(outpoint));
+        }
+    }
+
+    fn insert_utxo(&mut self, outpoint: OutPoint, output: TxOutput) -> Option<TxOutput> {
+        add_digest(&mut self.utxo_digest, &utxo_hash(&outpoint, &output));
+        let replaced = self.utxos.insert(outpoint, output);
+        if let Some(replaced) = &replaced {
+            sub_digest(&mut self.utxo_digest, &utxo_hash(&outpoint, replaced));
+        }
+        return replaced;
+    }
+
+    fn remove_utxo(&mut self, outpoint: &OutPoint) -> Option<TxOutput> {
+        let removed = self.utxos.remove(outpoint);
+        if let Some(output) = &removed {
+            sub_digest(&mut self.utxo_digest, &utxo_hash(outpoint, output));
+        }
+        return removed;
+    }
+}
+
+fn utxo_hash(outpoint: &OutPoint, output: &TxOutput) -> [u8; 32] {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&outpoint.txid);
+    bytes.extend_from_slice(&outpoint.index.to_be_bytes());
+    bytes.extend_from_slice(&output.amount.to_be_bytes());
+    bytes.extend_from_slice(&(output.address.len() as u64).to_be_bytes());
+    bytes.extend_from_slice(output.address.as_bytes());
+    return cryptohash::sha256(&bytes);
+}
+
+// Big-endian addition and subtraction modulo 2^256.
+fn add_digest(digest: &mut [u8; 32], hash: &[u8; 32]) {
+    let mut carry = 0;
+    for i in (0..32).rev() {
+        let sum = digest[i] as u16 + hash[i] as u16 + carry;
+        digest[i] = sum as u8;
+        carry = sum >> 8;
+    }
+}
+
+fn sub_digest(digest: &mut [u8; 32], hash: &[u8; 32]) {
+    let mut borrow = 0;
+    for i in (0..32).rev() {
+        let difference = digest[i] as i16 - hash[i] as i16 - borrow;
+        digest[i] = difference as u8;
+        borrow = (difference < 0) as i16;
+    }
+}
+
+fn verify_input(
+    input: &TxInput,
+    previous: &TxOutput,
+    sighash: &[u8; 32],
+    lock_height: u64,
+) -> Result<(), String> {
+    match (&input.script, &input.multisig) {
+        (Some(spend), None) => {
+            if script::address(&spend.locking_script) != previous.address {
+                return Err(String::from("locking script does not own output"));
+            }
+            let context = ScriptContext {
+                sighash: *sighash,
+                lock_height,
+            };
+            match script::verify_spend(&spend.unlocking_script, &spend.locking_script, &context) {
+                Ok(()) => return Ok(()),
+                Err(e) => return Err(format!("script failed: {}", e)),
+            }
+        }
+        (None, Some(spend)) => {
+            if spend.policy.address() != previous.address {
+                return Err(String::from("multisig policy does not own output"));
+            }
+            return spend.verify(sighash);
+        }
+        (None, None) => {
+            if keys::address(&input.public_key) != previous.address {
+                return Err(String::from("input key does not own output"));
+            }
+            if !keys::verify(&input.public_key, sighash, &input.signature) {
+                return Err(String::from("invalid signature"));
+            }
+            return Ok(());
+        }
+        (Some(_), Some(_)) => return Err(String::from("input mixes script and multisig spends")),
+    }
+}
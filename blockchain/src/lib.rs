@@ -1,5 +1,25 @@
+#![allow(clippy::needless_return)]
+
+pub mod authority;
 pub mod blockchain;
-mod block;
+pub mod block;
+pub mod clock;
+pub mod consensus;
+pub mod events;
+pub mod finality;
+pub mod htlc;
+pub mod index;
+pub mod ledger;
+pub mod mempool;
+pub mod multisig;
+pub mod params;
+pub mod script;
+pub mod simulator;
+pub mod sled_store;
+pub mod snapshot;
+pub mod store;
+pub mod transaction;
+pub mod types;
 mod config;
 
 #[cfg(test)]
@@ -0,0 +1,99 @@
+use crate::{ledger::Ledger, transaction::Transaction, types::BlockHeight};
+
+#[derive(Debug, Clone, Default)]
+pub struct Mempool {
+    transactions: Vec<Transaction>,
+}
+
+impl Mempool {
+    pub fn new() -> Mempool {
+        return Mempool {
+            transactions: Vec::new(),
+        };
+    }
+
+    pub fn transactions(&self) -> &[Transaction] {
+        return &self.transactions;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.transactions.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.transactions.is_empty();
+    }
+
+    pub fn size_bytes(&self) -> usize {
+        return self
+            .transactions
+            .iter()
+            .map(|transaction| transaction.encode().len())
+            .sum();
+    }
+
+    pub fn contains(&self, txid: &[u8; 32]) -> bool {
+        return self
+            .transactions
+            .iter()
+            .any(|transaction| &transaction.txid() == txid);
+    }
+
+    pub fn add(
+        &mut self,
+        transaction: Transaction,
+        ledger: &Ledger,
+        height: BlockHeight,
+    ) -> Result<[u8; 32], String> {
+        let txid = transaction.txid();
+        if self.contains(&txid) {
+            return Err(format!("{}: already in mempool", hex::encode(txid)));
+        }
+        self.pending_ledger(ledger)
+            .validate_transaction(&transaction, height)?;
+        self.transactions.push(transaction);
+        return Ok(txid);
+    }
+
+    pub fn pending_ledger(&self, ledger: &Ledger) -> Ledger {
+        let mut pending = ledger.clone();
+        for transaction in self.transactions.iter() {
+            pending.apply_transaction(transaction);
+        }
+        return pending;
+    }
+
+    pub fn revalidate(&mut self, ledger: &Ledger, height: BlockHeight) {
+        self.revalidate_with(ledger, height, |_, _| Ok(()));
+    }
+
+    // Like `revalidate`, also dropping transactions `check` refuses on top of
+    // the transactions kept before them.
+    pub fn revalidate_with<F>(&mut self, ledger: &Ledger, height: BlockHeight, check: F)
+    where
+        F: Fn(&Transaction, &Ledger) -> Result<(), String>,
+    {
+        let mut pending = ledger.clone();
+        self.transactions.retain(|transaction| {
+            if pending.validate_transaction(transaction, height).is_err()
+                || check(transaction, &pending).is_err()
+            {
+                return false;
+            }
+            pending.apply_transaction(transaction);
+            return true;
+        });
+    }
+
+    pub fn fees(&self, ledger: &Ledger, height: BlockHeight) -> u64 {
+        let mut pending = ledger.clone();
+        let mut fees: u64 = 0;
+        for transaction in self.transactions.iter() {
+            if let Ok(fee) = pending.validate_transaction(transaction, height) {
+                fees = fees.saturating_add(fee);
+                pending.apply_transaction(transaction);
+            }
+        }
+        return fees;
+    }
+}
@@ -0,0 +1,134 @@
+use crypto::{
+    cryptohash,
+    keys::{self, PUBLIC_KEY_LENGTH},
+};
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+pub const MAX_MULTISIG_KEYS: usize = 15;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MultisigPolicy {
+    pub threshold: usize,
+    pub public_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MultisigSignature {
+    pub key_index: usize,
+    #[serde(with = "hex::serde")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MultisigSpend {
+    pub policy: MultisigPolicy,
+    pub signatures: Vec<MultisigSignature>,
+}
+
+impl MultisigPolicy {
+    pub fn new(threshold: usize, public_keys: &[[u8; PUBLIC_KEY_LENGTH]]) -> MultisigPolicy {
+        return MultisigPolicy {
+            threshold,
+            public_keys: public_keys.iter().map(hex::encode).collect(),
+        };
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let key_count = self.public_keys.len();
+        if key_count == 0 || key_count > MAX_MULTISIG_KEYS {
+            return Err(format!(
+                "multisig needs between 1 and {} keys",
+                MAX_MULTISIG_KEYS
+            ));
+        }
+        if self.threshold == 0 || self.threshold > key_count {
+            return Err(format!(
+                "threshold {} is not between 1 and {}",
+                self.threshold, key_count
+            ));
+        }
+        let mut seen = BTreeSet::new();
+        for public_key in self.public_keys.iter() {
+            match hex::decode(public_key) {
+                Ok(bytes) if bytes.len() == PUBLIC_KEY_LENGTH => {}
+                _ => return Err(format!("invalid public key {}", public_key)),
+            }
+            if !seen.insert(public_key.to_lowercase()) {
+                return Err(format!("duplicate public key {}", public_key));
+            }
+        }
+        return Ok(());
+    }
+
+    // Hex case is not significant in a policy, so the address commits to the lowercase keys.
+    pub fn address(&self) -> String {
+        let canonical = MultisigPolicy {
+            threshold: self.threshold,
+            public_keys: self
+                .public_keys
+                .iter()
+                .map(|key| key.to_lowercase())
+                .collect(),
+        };
+        return hex::encode(cryptohash::sha256(&serde_json::to_vec(&canonical).unwrap()));
+    }
+
+    pub fn key_index(&self, public_key: &[u8]) -> Option<usize> {
+        let public_key = hex::encode(public_key);
+        return self
+            .public_keys
+            .iter()
+            .position(|listed| listed.to_lowercase() == public_key);
+    }
+}
+
+impl MultisigSpend {
+    pub fn new(policy: MultisigPolicy) -> MultisigSpend {
+        return MultisigSpend {
+            policy,
+            signatures: Vec::new(),
+        };
+    }
+
+    pub fn add_signature(&mut self, key_index: usize, signature: Vec<u8>) -> bool {
+        if self.signatures.iter().any(|s| s.key_index == key_index) {
+            return false;
+        }
+        self.signatures.push(MultisigSignature {
+            key_index,
+            signature,
+        });
+        self.signatures.sort_by_key(|signature| signature.key_index);
+        return true;
+    }
+
+    pub fn verify(&self, sighash: &[u8]) -> Result<(), String> {
+        self.policy.validate()?;
+        let mut signers = BTreeSet::new();
+        for signature in self.signatures.iter() {
+            let public_key = match self.policy.public_keys.get(signature.key_index) {
+                Some(public_key) => hex::decode(public_key).unwrap_or_default(),
+                None => return Err(format!("no key at index {}", signature.key_index)),
+            };
+            if !signers.insert(signature.key_index) {
+                return Err(format!(
+                    "duplicate signature for key {}",
+                    signature.key_index
+                ));
+            }
+            if !keys::verify(&public_key, sighash, &signature.signature) {
+                return Err(format!("invalid signature for key {}", signature.key_index));
+            }
+        }
+        if signers.len() < self.policy.threshold {
+            return Err(format!(
+                "{} of {} required signatures",
+                signers.len(),
+                self.policy.threshold
+            ));
+        }
+        return Ok(());
+    }
+}
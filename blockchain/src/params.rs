@@ -0,0 +1,242 @@
+use crate::{
+    authority::{AuthorityParams, AuthoritySet, ProofOfAuthorityEngine},
+    block::Block,
+    clock::{Clock, SystemClock},
+    consensus::{ConsensusEngine, ProofOfStakeEngine, ProofOfWorkEngine, StakeParams},
+    finality::FinalityParams,
+    ledger::Ledger,
+    types::{BlockHeight, Hash256},
+};
+
+use crypto::hasher::{Argon2Hasher, Argon2Params, ChainHasher, HashAlgorithm};
+use serde::{Deserialize, Serialize};
+use std::{str::FromStr, sync::Arc};
+
+pub const MAX_REORG_DEPTH: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub height: BlockHeight,
+    pub hash: Hash256,
+}
+
+// Parses HEIGHT:HASH.
+impl FromStr for Checkpoint {
+    type Err = String;
+
+    fn from_str(checkpoint: &str) -> Result<Checkpoint, String> {
+        let mut parts = checkpoint.splitn(2, ':');
+        let height = parts
+            .next()
+            .and_then(|height| height.parse::<BlockHeight>().ok());
+        let hash = parts.next().and_then(|hash| hash.parse::<Hash256>().ok());
+        match (height, hash) {
+            (Some(height), Some(hash)) => return Ok(Checkpoint { height, hash }),
+            _ => {
+                return Err(format!(
+                    "invalid checkpoint {} (expected HEIGHT:HASH)",
+                    checkpoint
+                ))
+            }
+        }
+    }
+}
+
+// One HEIGHT:HASH checkpoint per line; blank lines and lines starting with #
+// are skipped.
+pub fn parse_checkpoints(contents: &str) -> Result<Vec<Checkpoint>, String> {
+    return contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::parse)
+        .collect();
+}
+
+// How a block's hash is derived from its contents, and so what mining it costs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ProofOfWork {
+    // The block hash is `hash_algorithm` over the block contents.
+    Hash,
+    // The block hash is memory-hard Argon2id over a `hash_algorithm` digest.
+    Argon2(Argon2Params),
+}
+
+impl Default for ProofOfWork {
+    fn default() -> ProofOfWork {
+        return ProofOfWork::Hash;
+    }
+}
+
+// Who produces blocks: miners, validators chosen by stake, or a fixed set of
+// authorities taking turns.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Consensus {
+    ProofOfWork,
+    ProofOfStake(StakeParams),
+    ProofOfAuthority(AuthorityParams),
+}
+
+impl Default for Consensus {
+    fn default() -> Consensus {
+        return Consensus::ProofOfWork;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChainParams {
+    // Replacements that disconnect more blocks than this are refused; `None`
+    // allows reorgs of any depth.
+    pub max_reorg_depth: Option<usize>,
+    pub checkpoints: Vec<Checkpoint>,
+    // Hashes blocks for their hashes and proof of work. Fixed for the life of
+    // a chain: blocks hashed with another algorithm do not validate.
+    pub hash_algorithm: HashAlgorithm,
+    // Only used under proof of work.
+    pub proof_of_work: ProofOfWork,
+    pub consensus: Consensus,
+    // Validators whose certificates make blocks final; `None` leaves chains
+    // to the longest chain rule alone.
+    pub finality: Option<FinalityParams>,
+}
+
+impl Default for ChainParams {
+    fn default() -> ChainParams {
+        return ChainParams {
+            max_reorg_depth: Some(MAX_REORG_DEPTH),
+            checkpoints: vec![Checkpoint {
+                height: BlockHeight::GENESIS,
+                hash: Block::genesis().hash,
+            }],
+            hash_algorithm: HashAlgorithm::default(),
+            proof_of_work: ProofOfWork::default(),
+            consensus: Consensus::default(),
+            finality: None,
+        };
+    }
+}
+
+impl ChainParams {
+    pub fn hasher(&self) -> Result<Box<dyn ChainHasher>, String> {
+        match &self.proof_of_work {
+            ProofOfWork::Hash => return Ok(Box::new(self.hash_algorithm)),
+            ProofOfWork::Argon2(params) => {
+                return Ok(Box::new(Argon2Hasher::new(self.hash_algorithm, params)?))
+            }
+        }
+    }
+
+    // The ledger before the first block: empty, but for the initial
+    // authorities under proof of authority.
+    pub fn genesis_ledger(&self) -> Ledger {
+        match &self.consensus {
+            Consensus::ProofOfAuthority(params) => {
+                let authorities = AuthoritySet::new(&params.authorities);
+                return Ledger::new().with_authorities(Some(authorities));
+            }
+            _ => return Ledger::new(),
+        }
+    }
+
+    pub fn engine(&self) -> Result<Box<dyn ConsensusEngine>, String> {
+        return self.engine_with_clock(Arc::new(SystemClock));
+    }
+
+    // Builds the engine reading the time from `clock`, e.g. a mock in tests.
+    pub fn engine_with_clock(
+        &self,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Box<dyn ConsensusEngine>, String> {
+        match &self.consensus {
+            Consensus::ProofOfWork => {
+                return Ok(Box::new(ProofOfWorkEngine::with_clock(
+                    self.hasher()?,
+                    clock,
+                )));
+            }
+            Consensus::ProofOfStake(params) => {
+                params.check()?;
+                let hasher = Box::new(self.hash_algorithm);
+                return Ok(Box::new(ProofOfStakeEngine::with_clock(
+                    hasher,
+                    params.clone(),
+                    clock,
+                )));
+            }
+            Consensus::ProofOfAuthority(params) => {
+                params.check()?;
+                let hasher = Box::new(self.hash_algorithm);
+                return Ok(Box::new(ProofOfAuthorityEngine::with_clock(
+                    hasher,
+                    params.clone(),
+                    clock,
+                )));
+            }
+        }
+    }
+
+    // Adds checkpoints from another source, refusing any that disagree with a
+    // checkpoint already set for the same height.
+    pub fn add_checkpoints(&mut self, checkpoints: Vec<Checkpoint>) -> Result<(), String> {
+        for checkpoint in checkpoints {
+            match self.checkpoint_at(checkpoint.height) {
+                Some(existing) if existing.hash != checkpoint.hash => {
+                    return Err(format!(
+                        "checkpoint {}:{} conflicts with checkpoint {}:{}",
+                        checkpoint.height, checkpoint.hash, existing.height, existing.hash
+                    ))
+                }
+                Some(_) => {}
+                None => self.checkpoints.push(checkpoint),
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn checkpoint_at(&self, height: BlockHeight) -> Option<&Checkpoint> {
+        return self
+            .checkpoints
+            .iter()
+            .find(|checkpoint| checkpoint.height == height);
+    }
+
+    pub fn check_block(&self, height: BlockHeight, block: &Block) -> Result<(), String> {
+        match self.checkpoint_at(height) {
+            Some(checkpoint) if checkpoint.hash != block.hash => {
+                return Err(format!(
+                    "block {} {} conflicts with checkpoint {}",
+                    height, block.hash, checkpoint.hash
+                ))
+            }
+            _ => return Ok(()),
+        }
+    }
+
+    // `chain[0]` sits at `base_height`.
+    pub fn check_chain(&self, base_height: BlockHeight, chain: &[Block]) -> Result<(), String> {
+        for checkpoint in self.checkpoints.iter() {
+            if checkpoint.height < base_height {
+                continue;
+            }
+            if let Some(block) = chain.get((checkpoint.height - base_height) as usize) {
+                self.check_block(checkpoint.height, block)?;
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn check_reorg_depth(&self, depth: usize) -> Result<(), String> {
+        match self.max_reorg_depth {
+            Some(max_depth) if depth > max_depth => {
+                return Err(format!(
+                    "reorg of {} blocks exceeds the maximum depth of {}",
+                    depth, max_depth
+                ))
+            }
+            _ => return Ok(()),
+        }
+    }
+}
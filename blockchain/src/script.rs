@@ -0,0 +1,560 @@
+use crypto::{cryptohash, keys};
+
+pub const OP_0: u8 = 0x00;
+pub const OP_PUSHDATA1: u8 = 0x4c;
+pub const OP_PUSHDATA2: u8 = 0x4d;
+pub const OP_1NEGATE: u8 = 0x4f;
+pub const OP_1: u8 = 0x51;
+pub const OP_16: u8 = 0x60;
+pub const OP_NOP: u8 = 0x61;
+pub const OP_IF: u8 = 0x63;
+pub const OP_NOTIF: u8 = 0x64;
+pub const OP_ELSE: u8 = 0x67;
+pub const OP_ENDIF: u8 = 0x68;
+pub const OP_VERIFY: u8 = 0x69;
+pub const OP_RETURN: u8 = 0x6a;
+pub const OP_DEPTH: u8 = 0x74;
+pub const OP_DROP: u8 = 0x75;
+pub const OP_DUP: u8 = 0x76;
+pub const OP_OVER: u8 = 0x78;
+pub const OP_SWAP: u8 = 0x7c;
+pub const OP_SIZE: u8 = 0x82;
+pub const OP_EQUAL: u8 = 0x87;
+pub const OP_EQUALVERIFY: u8 = 0x88;
+pub const OP_1ADD: u8 = 0x8b;
+pub const OP_1SUB: u8 = 0x8c;
+pub const OP_NEGATE: u8 = 0x8f;
+pub const OP_ABS: u8 = 0x90;
+pub const OP_NOT: u8 = 0x91;
+pub const OP_0NOTEQUAL: u8 = 0x92;
+pub const OP_ADD: u8 = 0x93;
+pub const OP_SUB: u8 = 0x94;
+pub const OP_BOOLAND: u8 = 0x9a;
+pub const OP_BOOLOR: u8 = 0x9b;
+pub const OP_NUMEQUAL: u8 = 0x9c;
+pub const OP_NUMEQUALVERIFY: u8 = 0x9d;
+pub const OP_NUMNOTEQUAL: u8 = 0x9e;
+pub const OP_LESSTHAN: u8 = 0x9f;
+pub const OP_GREATERTHAN: u8 = 0xa0;
+pub const OP_LESSTHANOREQUAL: u8 = 0xa1;
+pub const OP_GREATERTHANOREQUAL: u8 = 0xa2;
+pub const OP_MIN: u8 = 0xa3;
+pub const OP_MAX: u8 = 0xa4;
+pub const OP_WITHIN: u8 = 0xa5;
+pub const OP_SHA256: u8 = 0xa8;
+pub const OP_CHECKSIG: u8 = 0xac;
+pub const OP_CHECKSIGVERIFY: u8 = 0xad;
+pub const OP_CHECKLOCKTIMEVERIFY: u8 = 0xb1;
+
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+pub const MAX_ELEMENT_SIZE: usize = 520;
+pub const MAX_OPS: usize = 201;
+pub const MAX_STACK_SIZE: usize = 1_000;
+pub const MAX_NUM_SIZE: usize = 8;
+
+const OPCODE_NAMES: &[(u8, &str)] = &[
+    (OP_0, "OP_0"),
+    (OP_1NEGATE, "OP_1NEGATE"),
+    (OP_NOP, "OP_NOP"),
+    (OP_IF, "OP_IF"),
+    (OP_NOTIF, "OP_NOTIF"),
+    (OP_ELSE, "OP_ELSE"),
+    (OP_ENDIF, "OP_ENDIF"),
+    (OP_VERIFY, "OP_VERIFY"),
+    (OP_RETURN, "OP_RETURN"),
+    (OP_DEPTH, "OP_DEPTH"),
+    (OP_DROP, "OP_DROP"),
+    (OP_DUP, "OP_DUP"),
+    (OP_OVER, "OP_OVER"),
+    (OP_SWAP, "OP_SWAP"),
+    (OP_SIZE, "OP_SIZE"),
+    (OP_EQUAL, "OP_EQUAL"),
+    (OP_EQUALVERIFY, "OP_EQUALVERIFY"),
+    (OP_1ADD, "OP_1ADD"),
+    (OP_1SUB, "OP_1SUB"),
+    (OP_NEGATE, "OP_NEGATE"),
+    (OP_ABS, "OP_ABS"),
+    (OP_NOT, "OP_NOT"),
+    (OP_0NOTEQUAL, "OP_0NOTEQUAL"),
+    (OP_ADD, "OP_ADD"),
+    (OP_SUB, "OP_SUB"),
+    (OP_BOOLAND, "OP_BOOLAND"),
+    (OP_BOOLOR, "OP_BOOLOR"),
+    (OP_NUMEQUAL, "OP_NUMEQUAL"),
+    (OP_NUMEQUALVERIFY, "OP_NUMEQUALVERIFY"),
+    (OP_NUMNOTEQUAL, "OP_NUMNOTEQUAL"),
+    (OP_LESSTHAN, "OP_LESSTHAN"),
+    (OP_GREATERTHAN, "OP_GREATERTHAN"),
+    (OP_LESSTHANOREQUAL, "OP_LESSTHANOREQUAL"),
+    (OP_GREATERTHANOREQUAL, "OP_GREATERTHANOREQUAL"),
+    (OP_MIN, "OP_MIN"),
+    (OP_MAX, "OP_MAX"),
+    (OP_WITHIN, "OP_WITHIN"),
+    (OP_SHA256, "OP_SHA256"),
+    (OP_CHECKSIG, "OP_CHECKSIG"),
+    (OP_CHECKSIGVERIFY, "OP_CHECKSIGVERIFY"),
+    (OP_CHECKLOCKTIMEVERIFY, "OP_CHECKLOCKTIMEVERIFY"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    Push(Vec<u8>),
+    Op(u8),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptContext {
+    pub sighash: [u8; 32],
+    pub lock_height: u64,
+}
+
+pub fn address(script: &[u8]) -> String {
+    return hex::encode(cryptohash::sha256(script));
+}
+
+pub fn push_data(script: &mut Vec<u8>, data: &[u8]) {
+    if data.is_empty() {
+        script.push(OP_0);
+    } else if data.len() < OP_PUSHDATA1 as usize {
+        script.push(data.len() as u8);
+    } else if data.len() <= 0xff {
+        script.push(OP_PUSHDATA1);
+        script.push(data.len() as u8);
+    } else {
+        script.push(OP_PUSHDATA2);
+        script.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    }
+    script.extend_from_slice(data);
+}
+
+pub fn push_number(script: &mut Vec<u8>, number: i64) {
+    match number {
+        -1 => script.push(OP_1NEGATE),
+        1..=16 => script.push(OP_1 + number as u8 - 1),
+        _ => push_data(script, &encode_number(number)),
+    }
+}
+
+// Minimal little-endian sign-magnitude encoding, as used by Bitcoin script.
+pub fn encode_number(number: i64) -> Vec<u8> {
+    if number == 0 {
+        return Vec::new();
+    }
+    let negative = number < 0;
+    let mut magnitude = number.unsigned_abs();
+    let mut bytes = Vec::new();
+    while magnitude > 0 {
+        bytes.push((magnitude & 0xff) as u8);
+        magnitude >>= 8;
+    }
+    if bytes[bytes.len() - 1] & 0x80 != 0 {
+        bytes.push(if negative { 0x80 } else { 0 });
+    } else if negative {
+        let last = bytes.len() - 1;
+        bytes[last] |= 0x80;
+    }
+    return bytes;
+}
+
+pub fn decode_number(bytes: &[u8]) -> Result<i64, String> {
+    if bytes.len() > MAX_NUM_SIZE {
+        return Err(format!("number longer than {} bytes", MAX_NUM_SIZE));
+    }
+    if let Some(last) = bytes.last() {
+        if last & 0x7f == 0 && (bytes.len() == 1 || bytes[bytes.len() - 2] & 0x80 == 0) {
+            return Err(String::from("number is not minimally encoded"));
+        }
+    }
+    let mut magnitude: u64 = 0;
+    for (i, byte) in bytes.iter().enumerate() {
+        let byte = if i == bytes.len() - 1 {
+            byte & 0x7f
+        } else {
+            *byte
+        };
+        magnitude |= (byte as u64) << (8 * i);
+    }
+    if magnitude > i64::MAX as u64 {
+        return Err(String::from("number out of range"));
+    }
+    match bytes.last() {
+        Some(last) if last & 0x80 != 0 => return Ok(-(magnitude as i64)),
+        _ => return Ok(magnitude as i64),
+    }
+}
+
+pub fn cast_to_bool(bytes: &[u8]) -> bool {
+    for (i, byte) in bytes.iter().enumerate() {
+        if *byte != 0 {
+            return !(i == bytes.len() - 1 && *byte == 0x80);
+        }
+    }
+    return false;
+}
+
+pub fn parse(script: &[u8]) -> Result<Vec<Instruction>, String> {
+    if script.len() > MAX_SCRIPT_SIZE {
+        return Err(format!("script longer than {} bytes", MAX_SCRIPT_SIZE));
+    }
+    let mut instructions = Vec::new();
+    let mut position = 0;
+    while position < script.len() {
+        let instruction = match read_instruction(script, &mut position)? {
+            (_, Some(data)) => Instruction::Push(data.to_vec()),
+            (OP_0, None) => Instruction::Push(Vec::new()),
+            (OP_1NEGATE, None) => Instruction::Push(encode_number(-1)),
+            (opcode @ OP_1..=OP_16, None) => Instruction::Push(vec![opcode - OP_1 + 1]),
+            (opcode, None) => Instruction::Op(opcode),
+        };
+        instructions.push(instruction);
+    }
+    return Ok(instructions);
+}
+
+pub fn is_push_only(script: &[u8]) -> bool {
+    match parse(script) {
+        Ok(instructions) => {
+            return instructions
+                .iter()
+                .all(|instruction| matches!(instruction, Instruction::Push(_)))
+        }
+        Err(_) => return false,
+    }
+}
+
+pub fn disassemble(script: &[u8]) -> Result<String, String> {
+    let mut position = 0;
+    let mut words = Vec::new();
+    while position < script.len() {
+        let word = match read_instruction(script, &mut position)? {
+            (_, Some([])) => String::from("OP_0"),
+            (_, Some(data)) => hex::encode(data),
+            (opcode @ OP_1..=OP_16, None) => format!("OP_{}", opcode - OP_1 + 1),
+            (opcode, None) => match opcode_name(opcode) {
+                Some(name) => String::from(name),
+                None => format!("OP_UNKNOWN_0x{:02x}", opcode),
+            },
+        };
+        words.push(word);
+    }
+    return Ok(words.join(" "));
+}
+
+pub fn assemble(asm: &str) -> Result<Vec<u8>, String> {
+    let mut script = Vec::new();
+    for word in asm.split_whitespace() {
+        if let Some(number) = word.strip_prefix("OP_") {
+            if let Ok(number) = number.parse::<u8>() {
+                if (1..=16).contains(&number) {
+                    script.push(OP_1 + number - 1);
+                    continue;
+                }
+            }
+            match OPCODE_NAMES.iter().find(|(_, name)| *name == word) {
+                Some((opcode, _)) => script.push(*opcode),
+                None => return Err(format!("unknown opcode {}", word)),
+            }
+            continue;
+        }
+        match hex::decode(word) {
+            Ok(data) if !data.is_empty() => push_data(&mut script, &data),
+            _ => return Err(format!("invalid push data {}", word)),
+        }
+    }
+    return Ok(script);
+}
+
+pub fn verify_spend(
+    unlocking_script: &[u8],
+    locking_script: &[u8],
+    context: &ScriptContext,
+) -> Result<(), String> {
+    if !is_push_only(unlocking_script) {
+        return Err(String::from("unlocking script must only push data"));
+    }
+    let mut stack = Vec::new();
+    execute(unlocking_script, &mut stack, context)?;
+    execute(locking_script, &mut stack, context)?;
+    match stack.last() {
+        Some(top) if cast_to_bool(top) => return Ok(()),
+        _ => return Err(String::from("script evaluated to false")),
+    }
+}
+
+pub fn execute(
+    script: &[u8],
+    stack: &mut Vec<Vec<u8>>,
+    context: &ScriptContext,
+) -> Result<(), String> {
+    let instructions = parse(script)?;
+    let mut conditions: Vec<bool> = Vec::new();
+    let mut op_count = 0;
+    for instruction in instructions {
+        let executing = conditions.iter().all(|condition| *condition);
+        let opcode = match instruction {
+            Instruction::Push(data) => {
+                if data.len() > MAX_ELEMENT_SIZE {
+                    return Err(format!("push larger than {} bytes", MAX_ELEMENT_SIZE));
+                }
+                if executing {
+                    stack.push(data);
+                }
+                check_stack_size(stack)?;
+                continue;
+            }
+            Instruction::Op(opcode) => opcode,
+        };
+        if opcode_name(opcode).is_none() {
+            return Err(format!("unknown opcode 0x{:02x}", opcode));
+        }
+        op_count += 1;
+        if op_count > MAX_OPS {
+            return Err(format!("more than {} operations", MAX_OPS));
+        }
+        match opcode {
+            OP_IF | OP_NOTIF => {
+                let mut condition = false;
+                if executing {
+                    condition = cast_to_bool(&pop(stack)?);
+                    if opcode == OP_NOTIF {
+                        condition = !condition;
+                    }
+                }
+                conditions.push(condition);
+                continue;
+            }
+            OP_ELSE => {
+                match conditions.last_mut() {
+                    Some(condition) => *condition = !*condition,
+                    None => return Err(String::from("OP_ELSE without OP_IF")),
+                }
+                continue;
+            }
+            OP_ENDIF => {
+                if conditions.pop().is_none() {
+                    return Err(String::from("OP_ENDIF without OP_IF"));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        if !executing {
+            continue;
+        }
+        execute_opcode(opcode, stack, context)?;
+        check_stack_size(stack)?;
+    }
+    if !conditions.is_empty() {
+        return Err(String::from("unbalanced conditional"));
+    }
+    return Ok(());
+}
+
+fn execute_opcode(
+    opcode: u8,
+    stack: &mut Vec<Vec<u8>>,
+    context: &ScriptContext,
+) -> Result<(), String> {
+    match opcode {
+        OP_NOP => {}
+        OP_VERIFY => verify(stack)?,
+        OP_RETURN => return Err(String::from("OP_RETURN")),
+        OP_DEPTH => {
+            let depth = stack.len() as i64;
+            stack.push(encode_number(depth));
+        }
+        OP_DROP => {
+            pop(stack)?;
+        }
+        OP_DUP => {
+            let top = peek(stack, 0)?.to_vec();
+            stack.push(top);
+        }
+        OP_OVER => {
+            let second = peek(stack, 1)?.to_vec();
+            stack.push(second);
+        }
+        OP_SWAP => {
+            peek(stack, 1)?;
+            let length = stack.len();
+            stack.swap(length - 1, length - 2);
+        }
+        OP_SIZE => {
+            let size = peek(stack, 0)?.len() as i64;
+            stack.push(encode_number(size));
+        }
+        OP_EQUAL | OP_EQUALVERIFY => {
+            let a = pop(stack)?;
+            let b = pop(stack)?;
+            push_bool(stack, a == b);
+            if opcode == OP_EQUALVERIFY {
+                verify(stack)?;
+            }
+        }
+        OP_1ADD | OP_1SUB | OP_NEGATE | OP_ABS | OP_NOT | OP_0NOTEQUAL => {
+            let a = pop_number(stack)?;
+            let result = match opcode {
+                OP_1ADD => a.checked_add(1),
+                OP_1SUB => a.checked_sub(1),
+                OP_NEGATE => a.checked_neg(),
+                OP_ABS => a.checked_abs(),
+                OP_NOT => Some((a == 0) as i64),
+                _ => Some((a != 0) as i64),
+            };
+            push_checked(stack, result)?;
+        }
+        OP_ADD
+        | OP_SUB
+        | OP_BOOLAND
+        | OP_BOOLOR
+        | OP_NUMEQUAL
+        | OP_NUMEQUALVERIFY
+        | OP_NUMNOTEQUAL
+        | OP_LESSTHAN
+        | OP_GREATERTHAN
+        | OP_LESSTHANOREQUAL
+        | OP_GREATERTHANOREQUAL
+        | OP_MIN
+        | OP_MAX => {
+            let b = pop_number(stack)?;
+            let a = pop_number(stack)?;
+            let result = match opcode {
+                OP_ADD => a.checked_add(b),
+                OP_SUB => a.checked_sub(b),
+                OP_BOOLAND => Some((a != 0 && b != 0) as i64),
+                OP_BOOLOR => Some((a != 0 || b != 0) as i64),
+                OP_NUMEQUAL | OP_NUMEQUALVERIFY => Some((a == b) as i64),
+                OP_NUMNOTEQUAL => Some((a != b) as i64),
+                OP_LESSTHAN => Some((a < b) as i64),
+                OP_GREATERTHAN => Some((a > b) as i64),
+                OP_LESSTHANOREQUAL => Some((a <= b) as i64),
+                OP_GREATERTHANOREQUAL => Some((a >= b) as i64),
+                OP_MIN => Some(a.min(b)),
+                _ => Some(a.max(b)),
+            };
+            push_checked(stack, result)?;
+            if opcode == OP_NUMEQUALVERIFY {
+                verify(stack)?;
+            }
+        }
+        OP_WITHIN => {
+            let max = pop_number(stack)?;
+            let min = pop_number(stack)?;
+            let x = pop_number(stack)?;
+            push_bool(stack, min <= x && x < max);
+        }
+        OP_SHA256 => {
+            let data = pop(stack)?;
+            stack.push(cryptohash::sha256(&data).to_vec());
+        }
+        OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+            let public_key = pop(stack)?;
+            let signature = pop(stack)?;
+            push_bool(
+                stack,
+                keys::verify(&public_key, &context.sighash, &signature),
+            );
+            if opcode == OP_CHECKSIGVERIFY {
+                verify(stack)?;
+            }
+        }
+        OP_CHECKLOCKTIMEVERIFY => {
+            let lock_height = decode_number(peek(stack, 0)?)?;
+            if lock_height < 0 {
+                return Err(String::from("negative lock height"));
+            }
+            if context.lock_height < lock_height as u64 {
+                return Err(format!(
+                    "transaction lock height {} is below {}",
+                    context.lock_height, lock_height
+                ));
+            }
+        }
+        _ => return Err(format!("unknown opcode 0x{:02x}", opcode)),
+    }
+    return Ok(());
+}
+
+fn opcode_name(opcode: u8) -> Option<&'static str> {
+    return OPCODE_NAMES
+        .iter()
+        .find(|(code, _)| *code == opcode)
+        .map(|(_, name)| *name);
+}
+
+fn read_instruction<'a>(
+    script: &'a [u8],
+    position: &mut usize,
+) -> Result<(u8, Option<&'a [u8]>), String> {
+    let opcode = script[*position];
+    *position += 1;
+    let length = match opcode {
+        0x01..=0x4b => opcode as usize,
+        OP_PUSHDATA1 => read_bytes(script, position, 1)?[0] as usize,
+        OP_PUSHDATA2 => {
+            let length = read_bytes(script, position, 2)?;
+            u16::from_le_bytes([length[0], length[1]]) as usize
+        }
+        _ => return Ok((opcode, None)),
+    };
+    return Ok((opcode, Some(read_bytes(script, position, length)?)));
+}
+
+fn read_bytes<'a>(
+    script: &'a [u8],
+    position: &mut usize,
+    length: usize,
+) -> Result<&'a [u8], String> {
+    if script.len() - *position < length {
+        return Err(String::from("push past end of script"));
+    }
+    let bytes = &script[*position..*position + length];
+    *position += length;
+    return Ok(bytes);
+}
+
+fn check_stack_size(stack: &[Vec<u8>]) -> Result<(), String> {
+    if stack.len() > MAX_STACK_SIZE {
+        return Err(format!("stack larger than {} items", MAX_STACK_SIZE));
+    }
+    return Ok(());
+}
+
+fn pop(stack: &mut Vec<Vec<u8>>) -> Result<Vec<u8>, String> {
+    match stack.pop() {
+        Some(top) => return Ok(top),
+        None => return Err(String::from("stack underflow")),
+    }
+}
+
+fn peek(stack: &[Vec<u8>], depth: usize) -> Result<&[u8], String> {
+    if stack.len() <= depth {
+        return Err(String::from("stack underflow"));
+    }
+    return Ok(&stack[stack.len() - 1 - depth]);
+}
+
+fn pop_number(stack: &mut Vec<Vec<u8>>) -> Result<i64, String> {
+    return decode_number(&pop(stack)?);
+}
+
+fn push_bool(stack: &mut Vec<Vec<u8>>, value: bool) {
+    stack.push(if value { vec![1] } else { Vec::new() });
+}
+
+fn push_checked(stack: &mut Vec<Vec<u8>>, result: Option<i64>) -> Result<(), String> {
+    match result {
+        Some(number) if number != i64::MIN => {
+            stack.push(encode_number(number));
+            return Ok(());
+        }
+        _ => return Err(String::from("arithmetic overflow")),
+    }
+}
+
+fn verify(stack: &mut Vec<Vec<u8>>) -> Result<(), String> {
+    if !cast_to_bool(&pop(stack)?) {
+        return Err(String::from("verify failed"));
+    }
+    return Ok(());
+}
@@ -0,0 +1,616 @@
+use crate::{
+    block::Block,
+    blockchain::Blockchain,
+    events::ChainEvent,
+    types::{BlockHeight, Hash256},
+};
+
+use crypto::hasher::Sha256Hasher;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    rc::Rc,
+    sync::mpsc::Receiver,
+    time::{Duration, SystemTime},
+};
+
+// Simulated time starts here, so block timestamps look like real ones.
+const START_MS: u64 = 1_700_000_000_000;
+
+// SplitMix64: small and fast, and a seed always replays the same run.
+#[derive(Debug, Clone)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> SimRng {
+        return SimRng { state: seed };
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        return z ^ (z >> 31);
+    }
+
+    // Uniform in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        return (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+    }
+
+    // Uniform in [min, max].
+    pub fn range(&mut self, min: u64, max: u64) -> u64 {
+        return min + self.next_u64() % (max - min + 1);
+    }
+
+    pub fn chance(&mut self, probability: f64) -> bool {
+        return self.next_f64() < probability;
+    }
+
+    // Exponentially distributed with the given mean, and at least 1: the
+    // waiting time between events of a Poisson process such as block finds.
+    pub fn exponential(&mut self, mean: u64) -> u64 {
+        let uniform = 1.0 - self.next_f64();
+        return ((-uniform.ln() * mean as f64).round() as u64).max(1);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    // Mines on the longest chain it has seen and relays new tips at once.
+    Honest,
+    // Withholds the blocks it mines and publishes them only to orphan honest
+    // blocks, as in Eyal and Sirer's selfish mining.
+    Selfish,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimNode {
+    // Relative share of the network's hash power.
+    pub hash_power: f64,
+    pub strategy: Strategy,
+}
+
+impl SimNode {
+    pub fn honest(hash_power: f64) -> SimNode {
+        return SimNode {
+            hash_power,
+            strategy: Strategy::Honest,
+        };
+    }
+
+    pub fn selfish(hash_power: f64) -> SimNode {
+        return SimNode {
+            hash_power,
+            strategy: Strategy::Selfish,
+        };
+    }
+}
+
+// While active, messages between nodes in different groups are dropped.
+// Nodes left out of every group form one more group. Every node sends its
+// chain to its peers when the partition heals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Partition {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub groups: Vec<Vec<usize>>,
+}
+
+impl Partition {
+    pub fn separates(&self, at_ms: u64, from: usize, to: usize) -> bool {
+        if at_ms < self.start_ms || at_ms >= self.end_ms {
+            return false;
+        }
+        return self.group_of(from) != self.group_of(to);
+    }
+
+    fn group_of(&self, node: usize) -> Option<usize> {
+        return self.groups.iter().position(|group| group.contains(&node));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationConfig {
+    pub seed: u64,
+    // Mean time between blocks across the whole network.
+    pub block_interval_ms: u64,
+    // Each message arrives after a uniformly random delay in this range.
+    pub min_latency_ms: u64,
+    pub max_latency_ms: u64,
+    // Chance that any one message is lost.
+    pub loss: f64,
+    // How often every node sends its chain to its peers, which recovers from
+    // lost messages; `None` only relays new tips.
+    pub sync_interval_ms: Option<u64>,
+    pub nodes: Vec<SimNode>,
+    pub partitions: Vec<Partition>,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> SimulationConfig {
+        return SimulationConfig {
+            seed: 0,
+            block_interval_ms: 10_000,
+            min_latency_ms: 50,
+            max_latency_ms: 200,
+            loss: 0.0,
+            sync_interval_ms: None,
+            nodes: vec![SimNode::honest(1.0); 4],
+            partitions: Vec::new(),
+        };
+    }
+}
+
+impl SimulationConfig {
+    pub fn check(&self) -> Result<(), String> {
+        if self.block_interval_ms == 0 {
+            return Err(String::from("block interval must be positive"));
+        }
+        if self.min_latency_ms > self.max_latency_ms {
+            return Err(String::from("minimum latency is above the maximum latency"));
+        }
+        if !(0.0..1.0).contains(&self.loss) {
+            return Err(String::from("message loss must be in [0, 1)"));
+        }
+        if self.sync_interval_ms == Some(0) {
+            return Err(String::from("sync interval must be positive"));
+        }
+        if !self
+            .nodes
+            .iter()
+            .any(|node| node.strategy == Strategy::Honest)
+        {
+            return Err(String::from("simulation needs at least one honest node"));
+        }
+        if !self.nodes.iter().all(|node| node.hash_power > 0.0) {
+            return Err(String::from("hash power must be positive"));
+        }
+        for partition in self.partitions.iter() {
+            if partition.start_ms >= partition.end_ms {
+                return Err(String::from("partition must end after it starts"));
+            }
+            let unknown = partition
+                .groups
+                .iter()
+                .flatten()
+                .find(|node| **node >= self.nodes.len());
+            if let Some(node) = unknown {
+                return Err(format!("partition names unknown node {}", node));
+            }
+        }
+        return Ok(());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinedBlock {
+    pub miner: usize,
+    pub height: BlockHeight,
+    pub at_ms: u64,
+    pub hash: Hash256,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReorgRecord {
+    pub node: usize,
+    pub at_ms: u64,
+    pub fork_height: BlockHeight,
+    pub depth: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationReport {
+    pub elapsed_ms: u64,
+    pub blocks_mined: usize,
+    pub best_height: BlockHeight,
+    pub best_hash: Hash256,
+    pub orphan_rate: f64,
+    pub reorgs: usize,
+    pub max_reorg_depth: usize,
+    pub messages_sent: usize,
+    pub messages_lost: usize,
+    pub converged: bool,
+}
+
+enum Action {
+    Mine,
+    Deliver { to: usize, chain: Rc<Vec<Block>> },
+    Sync { node: usize, repeat: bool },
+}
+
+struct Peer {
+    config: SimNode,
+    blockchain: Blockchain,
+    events: Receiver<ChainEvent>,
+    // The longest chain this node knows the network has seen. Honest nodes
+    // publish every block, so it is their own chain.
+    public: Rc<Vec<Block>>,
+    // Whether a selfish miner published a branch as long as the honest one
+    // and races it.
+    racing: bool,
+}
+
+// A discrete-event simulation of nodes mining and gossiping whole chains,
+// the way nodes sync, over a lossy network with latency and partitions.
+// Everything random comes from the seed and blocks are stamped with the
+// simulated time, so a seed always replays the same run.
+pub struct Simulator {
+    config: SimulationConfig,
+    rng: SimRng,
+    now_ms: u64,
+    next_seq: u64,
+    queue: BTreeMap<(u64, u64), Action>,
+    peers: Vec<Peer>,
+    mining: bool,
+    mined: Vec<MinedBlock>,
+    reorgs: Vec<ReorgRecord>,
+    messages_sent: usize,
+    messages_lost: usize,
+}
+
+impl Simulator {
+    pub fn new(config: SimulationConfig) -> Result<Simulator, String> {
+        config.check()?;
+        let peers = config
+            .nodes
+            .iter()
+            .map(|node| {
+                let blockchain = Blockchain::new();
+                return Peer {
+                    config: node.clone(),
+                    events: blockchain.events().subscribe(),
+                    public: Rc::new(blockchain.chain.clone()),
+                    blockchain,
+                    racing: false,
+                };
+            })
+            .collect();
+        let mut simulator = Simulator {
+            rng: SimRng::new(config.seed),
+            config,
+            now_ms: 0,
+            next_seq: 0,
+            queue: BTreeMap::new(),
+            peers,
+            mining: true,
+            mined: Vec::new(),
+            reorgs: Vec::new(),
+            messages_sent: 0,
+            messages_lost: 0,
+        };
+        let first_block = simulator
+            .rng
+            .exponential(simulator.config.block_interval_ms);
+        simulator.schedule(first_block, Action::Mine);
+        for node in 0..simulator.peers.len() {
+            if let Some(interval) = simulator.config.sync_interval_ms {
+                simulator.schedule(interval, Action::Sync { node, repeat: true });
+            }
+            let heals: Vec<u64> = simulator
+                .config
+                .partitions
+                .iter()
+                .map(|partition| partition.end_ms)
+                .collect();
+            for end_ms in heals {
+                simulator.schedule(
+                    end_ms,
+                    Action::Sync {
+                        node,
+                        repeat: false,
+                    },
+                );
+            }
+        }
+        return Ok(simulator);
+    }
+
+    pub fn now_ms(&self) -> u64 {
+        return self.now_ms;
+    }
+
+    // Handles every event up to `end_ms`, then advances the clock to it.
+    pub fn run_until(&mut self, end_ms: u64) {
+        loop {
+            match self.queue.keys().next() {
+                Some((at_ms, _)) if *at_ms <= end_ms => {}
+                _ => break,
+            }
+            let ((at_ms, _), action) = self.queue.pop_first().unwrap();
+            self.now_ms = at_ms;
+            match action {
+                Action::Mine => self.mine(),
+                Action::Deliver { to, chain } => self.deliver(to, chain),
+                Action::Sync { node, repeat } => self.sync(node, repeat),
+            }
+        }
+        self.now_ms = self.now_ms.max(end_ms);
+    }
+
+    pub fn run_for(&mut self, duration_ms: u64) {
+        self.run_until(self.now_ms + duration_ms);
+    }
+
+    // Stops all mining, e.g. to let messages in flight settle before
+    // checking convergence.
+    pub fn stop_mining(&mut self) {
+        self.mining = false;
+    }
+
+    pub fn blockchain(&self, node: usize) -> &Blockchain {
+        return &self.peers[node].blockchain;
+    }
+
+    pub fn mined(&self) -> &[MinedBlock] {
+        return &self.mined;
+    }
+
+    pub fn reorgs(&self) -> &[ReorgRecord] {
+        return &self.reorgs;
+    }
+
+    // The longest chain among the honest nodes, the first node's on a tie.
+    pub fn best_chain(&self) -> &[Block] {
+        let mut best: &[Block] = &[];
+        for peer in self.honest_peers() {
+            if peer.blockchain.chain.len() > best.len() {
+                best = &peer.blockchain.chain;
+            }
+        }
+        return best;
+    }
+
+    // Whether every honest node has the same tip. Nodes keep the first of two
+    // equally long chains, so a tie only resolves with the next block.
+    pub fn converged(&self) -> bool {
+        let mut tips = self
+            .honest_peers()
+            .map(|peer| peer.blockchain.last_block().hash);
+        let first = tips.next();
+        return tips.all(|tip| Some(tip) == first);
+    }
+
+    // The share of mined blocks that did not make the best chain.
+    pub fn orphan_rate(&self) -> f64 {
+        if self.mined.is_empty() {
+            return 0.0;
+        }
+        let best: BTreeSet<Hash256> = self.best_chain().iter().map(|block| block.hash).collect();
+        let orphans = self
+            .mined
+            .iter()
+            .filter(|mined| !best.contains(&mined.hash))
+            .count();
+        return orphans as f64 / self.mined.len() as f64;
+    }
+
+    // The share of the best chain's blocks after genesis that `node` mined.
+    pub fn share_of(&self, node: usize) -> f64 {
+        let best = self.best_chain();
+        if best.len() < 2 {
+            return 0.0;
+        }
+        let mined: BTreeSet<Hash256> = self
+            .mined
+            .iter()
+            .filter(|mined| mined.miner == node)
+            .map(|mined| mined.hash)
+            .collect();
+        let count = best
+            .iter()
+            .filter(|block| mined.contains(&block.hash))
+            .count();
+        return count as f64 / (best.len() - 1) as f64;
+    }
+
+    pub fn max_reorg_depth(&self) -> usize {
+        return self
+            .reorgs
+            .iter()
+            .map(|reorg| reorg.depth)
+            .max()
+            .unwrap_or(0);
+    }
+
+    pub fn report(&self) -> SimulationReport {
+        let best = self.best_chain();
+        return SimulationReport {
+            elapsed_ms: self.now_ms,
+            blocks_mined: self.mined.len(),
+            best_height: BlockHeight::from(best.len() - 1),
+            best_hash: best[best.len() - 1].hash,
+            orphan_rate: self.orphan_rate(),
+            reorgs: self.reorgs.len(),
+            max_reorg_depth: self.max_reorg_depth(),
+            messages_sent: self.messages_sent,
+            messages_lost: self.messages_lost,
+            converged: self.converged(),
+        };
+    }
+
+    fn honest_peers(&self) -> impl Iterator<Item = &Peer> {
+        return self
+            .peers
+            .iter()
+            .filter(|peer| peer.config.strategy == Strategy::Honest);
+    }
+
+    fn schedule(&mut self, at_ms: u64, action: Action) {
+        self.queue.insert((at_ms, self.next_seq), action);
+        self.next_seq += 1;
+    }
+
+    fn mine(&mut self) {
+        if !self.mining {
+            return;
+        }
+        let total: f64 = self.peers.iter().map(|peer| peer.config.hash_power).sum();
+        let mut target = self.rng.next_f64() * total;
+        let mut miner = self.peers.len() - 1;
+        for (node, peer) in self.peers.iter().enumerate() {
+            if target < peer.config.hash_power {
+                miner = node;
+                break;
+            }
+            target -= peer.config.hash_power;
+        }
+        let timestamp = SystemTime::UNIX_EPOCH + Duration::from_millis(START_MS + self.now_ms);
+        let data = format!("block {} by node {}", self.mined.len(), miner);
+        let peer = &mut self.peers[miner];
+        let block = Block::mine_block_at(
+            &Sha256Hasher,
+            peer.blockchain.last_block(),
+            data,
+            None,
+            timestamp,
+        );
+        let hash = block.hash;
+        if peer.blockchain.submit_block(block) {
+            self.mined.push(MinedBlock {
+                miner,
+                height: peer.blockchain.height(),
+                at_ms: self.now_ms,
+                hash,
+            });
+            self.drain_events(miner);
+            let peer = &mut self.peers[miner];
+            match peer.config.strategy {
+                Strategy::Honest => {
+                    let chain = Rc::new(peer.blockchain.chain.clone());
+                    self.publish(miner, chain);
+                }
+                Strategy::Selfish => {
+                    if peer.racing {
+                        peer.racing = false;
+                        let chain = Rc::new(peer.blockchain.chain.clone());
+                        self.publish(miner, chain);
+                    }
+                }
+            }
+        }
+        let next = self.now_ms + self.rng.exponential(self.config.block_interval_ms);
+        self.schedule(next, Action::Mine);
+    }
+
+    fn deliver(&mut self, to: usize, chain: Rc<Vec<Block>>) {
+        if chain.len() <= self.peers[to].public.len() {
+            return;
+        }
+        match self.peers[to].config.strategy {
+            Strategy::Honest => {
+                if self.adopt(to, &chain) {
+                    self.publish(to, chain);
+                }
+            }
+            Strategy::Selfish => {
+                self.peers[to].public = chain;
+                self.withhold_or_publish(to);
+            }
+        }
+    }
+
+    // A selfish miner that falls behind gives up its private branch. Once
+    // the honest chain catches up with it, it publishes the branch to race
+    // or beat the honest one; further ahead, it reveals just enough blocks
+    // to match the honest chain and keeps the rest.
+    fn withhold_or_publish(&mut self, node: usize) {
+        let peer = &mut self.peers[node];
+        let public = Rc::clone(&peer.public);
+        let private = peer.blockchain.chain.len();
+        peer.racing = false;
+        if private < public.len() {
+            self.adopt(node, &public);
+        } else if private == public.len() {
+            if peer.blockchain.last_block().hash != public[public.len() - 1].hash {
+                peer.racing = true;
+                let chain = Rc::new(peer.blockchain.chain.clone());
+                self.publish(node, chain);
+            }
+        } else if private == public.len() + 1 {
+            let chain = Rc::new(peer.blockchain.chain.clone());
+            self.publish(node, chain);
+        } else {
+            let chain = Rc::new(peer.blockchain.chain[..public.len()].to_vec());
+            self.publish(node, chain);
+        }
+    }
+
+    // Switches `node` to `chain` if it is longer than its own.
+    fn adopt(&mut self, node: usize, chain: &Rc<Vec<Block>>) -> bool {
+        let blockchain = &mut self.peers[node].blockchain;
+        if chain.len() <= blockchain.chain.len() {
+            return false;
+        }
+        let extends_tip = chain.len() == blockchain.chain.len() + 1
+            && chain[chain.len() - 2].hash == blockchain.last_block().hash;
+        let adopted = if extends_tip {
+            blockchain.submit_block(chain[chain.len() - 1].clone())
+        } else {
+            blockchain.replace_chain(chain.to_vec()).is_ok()
+        };
+        if adopted {
+            self.peers[node].public = Rc::clone(chain);
+        }
+        self.drain_events(node);
+        return adopted;
+    }
+
+    fn publish(&mut self, from: usize, chain: Rc<Vec<Block>>) {
+        if chain.len() > self.peers[from].public.len() {
+            self.peers[from].public = Rc::clone(&chain);
+        }
+        self.broadcast(from, chain);
+    }
+
+    fn sync(&mut self, node: usize, repeat: bool) {
+        let chain = Rc::clone(&self.peers[node].public);
+        self.broadcast(node, chain);
+        if let (true, Some(interval)) = (repeat, self.config.sync_interval_ms) {
+            self.schedule(self.now_ms + interval, Action::Sync { node, repeat });
+        }
+    }
+
+    fn broadcast(&mut self, from: usize, chain: Rc<Vec<Block>>) {
+        for to in 0..self.peers.len() {
+            if to == from {
+                continue;
+            }
+            self.messages_sent += 1;
+            let partitioned = self
+                .config
+                .partitions
+                .iter()
+                .any(|partition| partition.separates(self.now_ms, from, to));
+            if partitioned || self.rng.chance(self.config.loss) {
+                self.messages_lost += 1;
+                continue;
+            }
+            let latency = self
+                .rng
+                .range(self.config.min_latency_ms, self.config.max_latency_ms);
+            let chain = Rc::clone(&chain);
+            self.schedule(self.now_ms + latency, Action::Deliver { to, chain });
+        }
+    }
+
+    fn drain_events(&mut self, node: usize) {
+        while let Ok(event) = self.peers[node].events.try_recv() {
+            if let ChainEvent::Reorg {
+                fork_height,
+                disconnected,
+                ..
+            } = event
+            {
+                self.reorgs.push(ReorgRecord {
+                    node,
+                    at_ms: self.now_ms,
+                    fork_height,
+                    depth: disconnected.len(),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,605 @@
+use crate::{
+    authority,
+    block::{Block, BlockHeader},
+    finality::FinalityCertificate,
+    index::{ChainIndex, IndexedBlock, TxLocation},
+    ledger::Ledger,
+    snapshot::Snapshot,
+    store::ChainStore,
+    transaction::{OutPoint, Transaction, TxOutput},
+    types::BlockHeight,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use sled::{
+    transaction::{ConflictableTransactionError, TransactionError, TransactionalTree},
+    Db, Transactional, Tree,
+};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+pub const SLED_DIR: &str = "chain.sled";
+
+const TREES: [&str; 9] = [
+    "blocks",
+    "headers",
+    "utxos",
+    "undo",
+    "index_blocks",
+    "index_heights",
+    "index_transactions",
+    "index_addresses",
+    "meta",
+];
+const BLOCKS: usize = 0;
+const HEADERS: usize = 1;
+const UTXOS: usize = 2;
+const UNDO: usize = 3;
+const INDEX_BLOCKS: usize = 4;
+const INDEX_HEIGHTS: usize = 5;
+const INDEX_TRANSACTIONS: usize = 6;
+const INDEX_ADDRESSES: usize = 7;
+const META: usize = 8;
+
+const SNAPSHOT_KEY: &[u8] = b"snapshot";
+const SLASHED_KEY: &[u8] = b"slashed";
+const FINALITY_KEY: &[u8] = b"finality";
+
+type Abortable<T> = Result<T, ConflictableTransactionError<String>>;
+
+// Blocks, headers, UTXOs and index entries live in separate trees keyed by
+// big-endian height, so each block is connected or disconnected in one
+// transaction. `undo` keeps the outputs a block spent to roll back reorgs.
+// Chains started from a snapshot keep it in `meta` and begin at its height.
+// Validators slashed by evidence in connected blocks, the authority votes
+// they carry and the latest finality certificate are also kept in `meta`.
+pub struct SledStore {
+    path: PathBuf,
+    db: Db,
+    trees: Vec<Tree>,
+}
+
+impl SledStore {
+    pub fn open(data_dir: &Path) -> io::Result<SledStore> {
+        let path = data_dir.join(SLED_DIR);
+        let db = sled::open(&path)?;
+        let mut trees = Vec::new();
+        for name in TREES.iter() {
+            trees.push(db.open_tree(name)?);
+        }
+        return Ok(SledStore { path, db, trees });
+    }
+
+    // The height the next block is stored at.
+    pub fn height(&self) -> io::Result<BlockHeight> {
+        match self.trees[BLOCKS].last()? {
+            Some((key, _)) => return Ok(decode_height(&key)?.next()),
+            None => return Ok(BlockHeight::GENESIS),
+        }
+    }
+
+    pub fn base_height(&self) -> io::Result<BlockHeight> {
+        match self.load_snapshot()? {
+            Some(snapshot) => return Ok(snapshot.height),
+            None => return Ok(BlockHeight::GENESIS),
+        }
+    }
+
+    fn header_at(&self, height: BlockHeight) -> io::Result<BlockHeader> {
+        match self.trees[HEADERS].get(height_key(height))? {
+            Some(header) => return decode(&header),
+            None => return Err(missing(height)),
+        }
+    }
+
+    fn connect(&self, height: BlockHeight, block: &Block, index: &ChainIndex) -> io::Result<()> {
+        let transactions = Transaction::decode_payload(&block.data).unwrap_or_default();
+        let txids: Vec<[u8; 32]> = transactions.iter().map(Transaction::txid).collect();
+        let indexed = match index.indexed_block(height) {
+            Some(indexed) if indexed.hash == block.hash.to_string() => indexed,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("index does not contain block {}", height),
+                ))
+            }
+        };
+        let block_json = encode(block)?;
+        let header_json = encode(&block.header())?;
+        let indexed_json = encode(indexed)?;
+        let locations = self.address_entries(indexed, index)?;
+        let offenders: Vec<String> = block
+            .evidence
+            .iter()
+            .filter_map(|evidence| evidence.offender().ok())
+            .collect();
+        let burned = self.outpoints_of(&offenders)?;
+        let result = self.trees[..].transaction(|trees| {
+            let key = height_key(height);
+            trees[BLOCKS].insert(&key, block_json.as_slice())?;
+            trees[HEADERS].insert(&key, header_json.as_slice())?;
+            let mut spent: Vec<(OutPoint, TxOutput)> = Vec::new();
+            for (transaction, txid) in transactions.iter().zip(txids.iter()) {
+                for input in transaction.inputs.iter() {
+                    let previous = input.previous_output;
+                    let removed = trees[UTXOS].remove(outpoint_key(&previous))?;
+                    // Outputs created and spent within this block never
+                    // existed before it, so disconnecting must not restore them.
+                    if txids.contains(&previous.txid) {
+                        continue;
+                    }
+                    if let Some(output) = removed {
+                        spent.push((previous, decode_in(&output)?));
+                    }
+                }
+                for (position, output) in transaction.outputs.iter().enumerate() {
+                    let outpoint = OutPoint {
+                        txid: *txid,
+                        index: position as u32,
+                    };
+                    // Evidence in this block burns whatever it pays the offenders.
+                    if offenders.contains(&output.address) {
+                        continue;
+                    }
+                    trees[UTXOS].insert(outpoint_key(&outpoint), encode_in(output)?)?;
+                }
+            }
+            for outpoint in burned.iter() {
+                if let Some(output) = trees[UTXOS].remove(outpoint_key(outpoint))? {
+                    spent.push((*outpoint, decode_in(&output)?));
+                }
+            }
+            trees[UNDO].insert(&key, encode_in(&spent)?)?;
+            update_slashed(&trees[META], block, true)?;
+
+            trees[INDEX_BLOCKS].insert(&key, indexed_json.as_slice())?;
+            trees[INDEX_HEIGHTS].insert(indexed.hash.as_bytes(), &key)?;
+            for (position, txid) in indexed.txids.iter().enumerate() {
+                let location = TxLocation { height, position };
+                trees[INDEX_TRANSACTIONS].insert(txid.as_bytes(), encode_in(&location)?)?;
+            }
+            write_addresses(&trees[INDEX_ADDRESSES], &locations)?;
+            return Ok(());
+        });
+        return finish(result);
+    }
+
+    fn disconnect_tip(&self, index: &ChainIndex) -> io::Result<()> {
+        let height = match self.height()?.checked_sub(1) {
+            Some(height) => height,
+            None => return Ok(()),
+        };
+        let indexed: IndexedBlock = match self.trees[INDEX_BLOCKS].get(height_key(height))? {
+            Some(indexed) => decode(&indexed)?,
+            None => return Err(missing(height)),
+        };
+        let locations = self.address_entries(&indexed, index)?;
+        let result = self.trees[..].transaction(|trees| {
+            let key = height_key(height);
+            let block: Block = match trees[BLOCKS].get(key)? {
+                Some(block) => decode_in(&block)?,
+                None => {
+                    return Err(ConflictableTransactionError::Abort(
+                        missing(height).to_string(),
+                    ))
+                }
+            };
+            let spent: Vec<(OutPoint, TxOutput)> = match trees[UNDO].get(key)? {
+                Some(spent) => decode_in(&spent)?,
+                None => Vec::new(),
+            };
+            for transaction in Transaction::decode_payload(&block.data).unwrap_or_default() {
+                let txid = transaction.txid();
+                for position in 0..transaction.outputs.len() {
+                    let outpoint = OutPoint {
+                        txid,
+                        index: position as u32,
+                    };
+                    trees[UTXOS].remove(outpoint_key(&outpoint))?;
+                }
+            }
+            for (outpoint, output) in spent.iter() {
+                trees[UTXOS].insert(outpoint_key(outpoint), encode_in(output)?)?;
+            }
+            update_slashed(&trees[META], &block, false)?;
+            trees[BLOCKS].remove(&key)?;
+            trees[HEADERS].remove(&key)?;
+            trees[UNDO].remove(&key)?;
+
+            trees[INDEX_BLOCKS].remove(&key)?;
+            trees[INDEX_HEIGHTS].remove(indexed.hash.as_bytes())?;
+            for txid in indexed.txids.iter() {
+                trees[INDEX_TRANSACTIONS].remove(txid.as_bytes())?;
+            }
+            write_addresses(&trees[INDEX_ADDRESSES], &locations)?;
+            return Ok(());
+        });
+        return finish(result);
+    }
+
+    // Address histories are written in full from `index`, which already
+    // reflects the chain after this block is connected or disconnected.
+    fn address_entries(
+        &self,
+        indexed: &IndexedBlock,
+        index: &ChainIndex,
+    ) -> io::Result<Vec<(String, Option<Vec<u8>>)>> {
+        let mut entries = Vec::new();
+        for address in indexed.addresses.iter() {
+            let locations = match index.addresses.get(address) {
+                Some(locations) => Some(encode(locations)?),
+                None => None,
+            };
+            entries.push((address.clone(), locations));
+        }
+        return Ok(entries);
+    }
+
+    // Unspent outputs paid to any of `addresses`, scanned before a block's
+    // transaction since sled cannot iterate a tree inside one.
+    fn outpoints_of(&self, addresses: &[String]) -> io::Result<Vec<OutPoint>> {
+        let mut outpoints = Vec::new();
+        if addresses.is_empty() {
+            return Ok(outpoints);
+        }
+        for entry in self.trees[UTXOS].iter() {
+            let (key, output) = entry?;
+            let output: TxOutput = decode(&output)?;
+            if addresses.contains(&output.address) {
+                outpoints.push(decode_outpoint(&key)?);
+            }
+        }
+        return Ok(outpoints);
+    }
+
+    fn values<T: DeserializeOwned>(&self, tree: usize) -> io::Result<Vec<T>> {
+        let mut values = Vec::new();
+        for entry in self.trees[tree].iter() {
+            let (_, value) = entry?;
+            values.push(decode(&value)?);
+        }
+        return Ok(values);
+    }
+}
+
+impl ChainStore for SledStore {
+    fn location(&self) -> &Path {
+        return &self.path;
+    }
+
+    fn exists(&self) -> bool {
+        return !self.trees[BLOCKS].is_empty();
+    }
+
+    fn load(&self) -> io::Result<Vec<Block>> {
+        return self.values(BLOCKS);
+    }
+
+    fn load_headers(&self) -> io::Result<Vec<BlockHeader>> {
+        return self.values(HEADERS);
+    }
+
+    fn load_index(&self) -> io::Result<ChainIndex> {
+        let mut index = ChainIndex::with_base(self.base_height()?);
+        index.blocks = self.values(INDEX_BLOCKS)?;
+        for entry in self.trees[INDEX_HEIGHTS].iter() {
+            let (hash, height) = entry?;
+            index
+                .heights
+                .insert(decode_string(&hash)?, decode_height(&height)?);
+        }
+        for entry in self.trees[INDEX_TRANSACTIONS].iter() {
+            let (txid, location) = entry?;
+            index
+                .transactions
+                .insert(decode_string(&txid)?, decode(&location)?);
+        }
+        for entry in self.trees[INDEX_ADDRESSES].iter() {
+            let (address, locations) = entry?;
+            index
+                .addresses
+                .insert(decode_string(&address)?, decode(&locations)?);
+        }
+        return Ok(index);
+    }
+
+    // The store does not know the initial authorities, so the authority set
+    // is rebuilt from `genesis`, or the snapshot, and the stored blocks' votes.
+    fn load_ledger(&self, genesis: &Ledger) -> io::Result<Ledger> {
+        let mut utxos = Vec::new();
+        for entry in self.trees[UTXOS].iter() {
+            let (key, output) = entry?;
+            utxos.push((decode_outpoint(&key)?, decode(&output)?));
+        }
+        let slashed: Vec<String> = match self.trees[META].get(SLASHED_KEY)? {
+            Some(slashed) => decode(&slashed)?,
+            None => Vec::new(),
+        };
+        let (mut authorities, base_height) = match self.load_snapshot()? {
+            Some(snapshot) => (snapshot.authorities, snapshot.height),
+            None => (genesis.authorities().cloned(), BlockHeight::GENESIS),
+        };
+        if let Some(authorities) = authorities.as_mut() {
+            for entry in self.trees[BLOCKS].range(height_key(base_height.next())..) {
+                let block: Block = decode(&entry?.1)?;
+                for vote in authority::payload_votes(&block.data).iter() {
+                    if let Err(e) = authorities.apply(vote) {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, e));
+                    }
+                }
+            }
+        }
+        return Ok(Ledger::from_utxos(utxos)
+            .with_slashed(slashed)
+            .with_authorities(authorities));
+    }
+
+    fn load_snapshot(&self) -> io::Result<Option<Snapshot>> {
+        match self.trees[META].get(SNAPSHOT_KEY)? {
+            Some(snapshot) => return Ok(Some(decode(&snapshot)?)),
+            None => return Ok(None),
+        }
+    }
+
+    fn commit_block(&self, block: &Block, index: &ChainIndex) -> io::Result<()> {
+        let height = self.height()?;
+        if let Some(tip) = height.checked_sub(1) {
+            if self.header_at(tip)?.hash != block.last_hash {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "block does not extend the stored tip",
+                ));
+            }
+        }
+        self.connect(height, block, index)?;
+        self.db.flush()?;
+        return Ok(());
+    }
+
+    fn commit_chain(&self, chain: &[Block], index: &ChainIndex) -> io::Result<()> {
+        let base_height = self.base_height()?;
+        let height = self.height()?;
+        let mut fork = ((height - base_height) as usize).min(chain.len());
+        while fork > 0
+            && self.header_at(base_height + (fork - 1) as u64)?.hash != chain[fork - 1].hash
+        {
+            fork -= 1;
+        }
+        for _ in (base_height + fork as u64).0..height.0 {
+            self.disconnect_tip(index)?;
+        }
+        for (position, block) in chain.iter().enumerate().skip(fork) {
+            self.connect(base_height + position as u64, block, index)?;
+        }
+        self.db.flush()?;
+        return Ok(());
+    }
+
+    fn commit_snapshot(&self, snapshot: &Snapshot, index: &ChainIndex) -> io::Result<()> {
+        for tree in self.trees.iter() {
+            tree.clear()?;
+        }
+        self.connect(snapshot.height, &snapshot.block, index)?;
+        self.trees[UTXOS].clear()?;
+        for (outpoint, output) in snapshot.utxos.iter() {
+            self.trees[UTXOS].insert(outpoint_key(outpoint), encode(output)?)?;
+        }
+        self.trees[META].insert(SNAPSHOT_KEY, encode(snapshot)?)?;
+        self.trees[META].insert(SLASHED_KEY, encode(&snapshot.slashed)?)?;
+        self.db.flush()?;
+        return Ok(());
+    }
+
+    // Headers stay in their tree and the UTXO tree already reflects the tip,
+    // so only bodies and undo data go. A store that started from a snapshot
+    // has no headers below it, so like `FileStore` it keeps none below the
+    // new base either. The index is rewritten from `index`, which was rebuilt
+    // from the new base.
+    fn prune(&self, snapshot: &Snapshot, index: &ChainIndex) -> io::Result<()> {
+        let key = height_key(snapshot.height);
+        match self.trees[BLOCKS].get(key)? {
+            Some(block) if decode::<Block>(&block)? == snapshot.block => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("block {} is not in the stored chain", snapshot.height),
+                ))
+            }
+        }
+        let mut pruned = Vec::new();
+        for entry in self.trees[BLOCKS].range(..key) {
+            pruned.push(entry?.0);
+        }
+        let keeps_headers = self.trees[HEADERS].contains_key(height_key(BlockHeight::GENESIS))?;
+        let mut stale = Vec::new();
+        for tree in INDEX_BLOCKS..=INDEX_ADDRESSES {
+            for entry in self.trees[tree].iter() {
+                stale.push((tree, entry?.0));
+            }
+        }
+        let mut entries = Vec::new();
+        for (position, indexed) in index.blocks.iter().enumerate() {
+            let height = height_key(index.base_height() + position as u64);
+            entries.push((INDEX_BLOCKS, height.to_vec(), encode(indexed)?));
+            entries.push((
+                INDEX_HEIGHTS,
+                indexed.hash.clone().into_bytes(),
+                height.to_vec(),
+            ));
+        }
+        for (txid, location) in index.transactions.iter() {
+            entries.push((
+                INDEX_TRANSACTIONS,
+                txid.clone().into_bytes(),
+                encode(location)?,
+            ));
+        }
+        for (address, locations) in index.addresses.iter() {
+            entries.push((
+                INDEX_ADDRESSES,
+                address.clone().into_bytes(),
+                encode(locations)?,
+            ));
+        }
+        let snapshot_json = encode(snapshot)?;
+        let result = self.trees[..].transaction(|trees| {
+            for height in pruned.iter() {
+                trees[BLOCKS].remove(height)?;
+                trees[UNDO].remove(height)?;
+                if !keeps_headers {
+                    trees[HEADERS].remove(height)?;
+                }
+            }
+            for (tree, key) in stale.iter() {
+                trees[*tree].remove(key)?;
+            }
+            for (tree, key, value) in entries.iter() {
+                trees[*tree].insert(key.as_slice(), value.as_slice())?;
+            }
+            trees[META].insert(SNAPSHOT_KEY, snapshot_json.as_slice())?;
+            return Ok(());
+        });
+        finish(result)?;
+        self.db.flush()?;
+        return Ok(());
+    }
+
+    fn load_finality(&self) -> io::Result<Option<FinalityCertificate>> {
+        match self.trees[META].get(FINALITY_KEY)? {
+            Some(certificate) => return Ok(Some(decode(&certificate)?)),
+            None => return Ok(None),
+        }
+    }
+
+    fn commit_finality(&self, certificate: &FinalityCertificate) -> io::Result<()> {
+        self.trees[META].insert(FINALITY_KEY, encode(certificate)?)?;
+        self.db.flush()?;
+        return Ok(());
+    }
+}
+
+fn write_addresses(
+    tree: &TransactionalTree,
+    entries: &[(String, Option<Vec<u8>>)],
+) -> Abortable<()> {
+    for (address, locations) in entries.iter() {
+        match locations {
+            Some(locations) => {
+                tree.insert(address.as_bytes(), locations.as_slice())?;
+            }
+            None => {
+                tree.remove(address.as_bytes())?;
+            }
+        }
+    }
+    return Ok(());
+}
+
+// Records or forgets the validators slashed by `block`'s evidence.
+fn update_slashed(tree: &TransactionalTree, block: &Block, connect: bool) -> Abortable<()> {
+    if block.evidence.is_empty() {
+        return Ok(());
+    }
+    let mut slashed: Vec<String> = match tree.get(SLASHED_KEY)? {
+        Some(slashed) => decode_in(&slashed)?,
+        None => Vec::new(),
+    };
+    for evidence in block.evidence.iter() {
+        if let Ok(offender) = evidence.offender() {
+            slashed.retain(|address| address != &offender);
+            if connect {
+                slashed.push(offender);
+            }
+        }
+    }
+    slashed.sort();
+    tree.insert(SLASHED_KEY, encode_in(&slashed)?)?;
+    return Ok(());
+}
+
+fn finish(result: Result<(), TransactionError<String>>) -> io::Result<()> {
+    match result {
+        Ok(()) => return Ok(()),
+        Err(TransactionError::Abort(e)) => {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+        Err(TransactionError::Storage(e)) => return Err(e.into()),
+    }
+}
+
+fn missing(height: BlockHeight) -> io::Error {
+    return io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("block {} is missing from the store", height),
+    );
+}
+
+fn height_key(height: BlockHeight) -> [u8; 8] {
+    return height.0.to_be_bytes();
+}
+
+fn decode_height(key: &[u8]) -> io::Result<BlockHeight> {
+    let mut bytes = [0; 8];
+    if key.len() != bytes.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid height key",
+        ));
+    }
+    bytes.copy_from_slice(key);
+    return Ok(BlockHeight(u64::from_be_bytes(bytes)));
+}
+
+fn outpoint_key(outpoint: &OutPoint) -> Vec<u8> {
+    let mut key = outpoint.txid.to_vec();
+    key.extend_from_slice(&outpoint.index.to_be_bytes());
+    return key;
+}
+
+fn decode_outpoint(key: &[u8]) -> io::Result<OutPoint> {
+    if key.len() != 36 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid outpoint key",
+        ));
+    }
+    let mut txid = [0; 32];
+    let mut index = [0; 4];
+    txid.copy_from_slice(&key[..32]);
+    index.copy_from_slice(&key[32..]);
+    return Ok(OutPoint {
+        txid,
+        index: u32::from_be_bytes(index),
+    });
+}
+
+fn decode_string(bytes: &[u8]) -> io::Result<String> {
+    match String::from_utf8(bytes.to_vec()) {
+        Ok(string) => return Ok(string),
+        Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+    }
+}
+
+fn encode<T: Serialize>(value: &T) -> io::Result<Vec<u8>> {
+    return Ok(serde_json::to_vec(value)?);
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> io::Result<T> {
+    return Ok(serde_json::from_slice(bytes)?);
+}
+
+fn encode_in<T: Serialize>(value: &T) -> Abortable<Vec<u8>> {
+    match serde_json::to_vec(value) {
+        Ok(bytes) => return Ok(bytes),
+        Err(e) => return Err(ConflictableTransactionError::Abort(e.to_string())),
+    }
+}
+
+fn decode_in<T: DeserializeOwned>(bytes: &[u8]) -> Abortable<T> {
+    match serde_json::from_slice(bytes) {
+        Ok(value) => return Ok(value),
+        Err(e) => return Err(ConflictableTransactionError::Abort(e.to_string())),
+    }
+}
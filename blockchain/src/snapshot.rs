@@ -0,0 +1,100 @@
+use crate::{
+    authority::AuthoritySet,
+    block::Block,
+    ledger::Ledger,
+    transaction::{OutPoint, TxOutput},
+    types::{BlockHeight, Hash256},
+};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+// The ledger state as of `block`, which commits to it through its state root.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub height: BlockHeight,
+    pub block: Block,
+    pub utxos: Vec<(OutPoint, TxOutput)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub slashed: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorities: Option<AuthoritySet>,
+}
+
+impl Snapshot {
+    pub fn new(height: BlockHeight, block: Block, ledger: &Ledger) -> Snapshot {
+        let utxos = ledger
+            .utxos()
+            .iter()
+            .map(|(outpoint, output)| (*outpoint, output.clone()))
+            .collect();
+        return Snapshot {
+            height,
+            block,
+            utxos,
+            slashed: ledger.slashed().iter().cloned().collect(),
+            authorities: ledger.authorities().cloned(),
+        };
+    }
+
+    pub fn ledger(&self) -> Ledger {
+        return Ledger::from_utxos(self.utxos.iter().cloned())
+            .with_slashed(self.slashed.iter().cloned())
+            .with_authorities(self.authorities.clone());
+    }
+
+    pub fn verify(&self) -> Result<Ledger, String> {
+        let state_root = match self.block.state_root {
+            Some(state_root) => state_root,
+            None => {
+                return Err(format!(
+                    "block {} has no state root to check the snapshot against",
+                    self.height
+                ))
+            }
+        };
+        let ledger = self.ledger();
+        if ledger.len() != self.utxos.len() {
+            return Err(String::from("snapshot lists an output twice"));
+        }
+        if ledger.slashed().len() != self.slashed.len() {
+            return Err(String::from("snapshot lists a slashed validator twice"));
+        }
+        if ledger.commitment() != state_root {
+            return Err(format!(
+                "snapshot does not match the state root of block {}",
+                self.height
+            ));
+        }
+        return Ok(ledger);
+    }
+
+    pub fn verify_checkpoint(&self, height: BlockHeight, hash: &Hash256) -> Result<Ledger, String> {
+        if self.height != height || &self.block.hash != hash {
+            return Err(format!(
+                "snapshot block {} {} is not the checkpoint {} {}",
+                self.height, self.block.hash, height, hash
+            ));
+        }
+        return self.verify();
+    }
+
+    pub fn load(path: &Path) -> io::Result<Snapshot> {
+        let reader = BufReader::new(File::open(path)?);
+        return Ok(serde_json::from_reader(reader)?);
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp_path = path.with_extension("json.tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            serde_json::to_writer(&mut writer, self)?;
+            writer.flush()?;
+        }
+        return fs::rename(tmp_path, path);
+    }
+}
@@ -0,0 +1,295 @@
+use crate::{
+    block::{Block, BlockHeader},
+    finality::FinalityCertificate,
+    index::ChainIndex,
+    ledger::Ledger,
+    sled_store::SledStore,
+    snapshot::Snapshot,
+    types::BlockHeight,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+pub const CHAIN_FILE: &str = "chain.jsonl";
+pub const INDEX_FILE: &str = "index.json";
+pub const SNAPSHOT_FILE: &str = "snapshot.json";
+pub const HEADERS_FILE: &str = "headers.jsonl";
+pub const FINALITY_FILE: &str = "finality.json";
+
+pub trait ChainStore: Send {
+    fn location(&self) -> &Path;
+    fn exists(&self) -> bool;
+    fn load(&self) -> io::Result<Vec<Block>>;
+    // Headers of every stored block, including those whose bodies were pruned.
+    fn load_headers(&self) -> io::Result<Vec<BlockHeader>>;
+    fn load_index(&self) -> io::Result<ChainIndex>;
+    // The ledger after the stored chain. `genesis` is the ledger before its
+    // first block, which holds any initial authority set.
+    fn load_ledger(&self, genesis: &Ledger) -> io::Result<Ledger>;
+    // The snapshot the stored chain starts from, if it does not start at genesis.
+    fn load_snapshot(&self) -> io::Result<Option<Snapshot>>;
+    // Persists `block` as the new tip; `index` must already include it.
+    fn commit_block(&self, block: &Block, index: &ChainIndex) -> io::Result<()>;
+    // Replaces the stored chain, e.g. after a reorg.
+    fn commit_chain(&self, chain: &[Block], index: &ChainIndex) -> io::Result<()>;
+    // Replaces the stored chain with `snapshot`'s block and UTXO set.
+    fn commit_snapshot(&self, snapshot: &Snapshot, index: &ChainIndex) -> io::Result<()>;
+    // Deletes the bodies of stored blocks below `snapshot`, keeping their
+    // headers if the store has them back to genesis, and makes `snapshot`
+    // the new base of the stored chain.
+    fn prune(&self, snapshot: &Snapshot, index: &ChainIndex) -> io::Result<()>;
+    // The certificate of the highest final block, if any.
+    fn load_finality(&self) -> io::Result<Option<FinalityCertificate>>;
+    fn commit_finality(&self, certificate: &FinalityCertificate) -> io::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreBackend {
+    File,
+    Sled,
+}
+
+impl Default for StoreBackend {
+    fn default() -> StoreBackend {
+        return StoreBackend::File;
+    }
+}
+
+impl FromStr for StoreBackend {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<StoreBackend, String> {
+        match name {
+            "file" => return Ok(StoreBackend::File),
+            "sled" => return Ok(StoreBackend::Sled),
+            _ => {
+                return Err(format!(
+                    "unknown store backend {} (expected file or sled)",
+                    name
+                ))
+            }
+        }
+    }
+}
+
+impl fmt::Display for StoreBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreBackend::File => return write!(f, "file"),
+            StoreBackend::Sled => return write!(f, "sled"),
+        }
+    }
+}
+
+pub fn open_store(backend: StoreBackend, data_dir: &Path) -> io::Result<Box<dyn ChainStore>> {
+    match backend {
+        StoreBackend::File => return Ok(Box::new(FileStore::new(data_dir))),
+        StoreBackend::Sled => return Ok(Box::new(SledStore::open(data_dir)?)),
+    }
+}
+
+pub struct FileStore {
+    path: PathBuf,
+    index_path: PathBuf,
+    snapshot_path: PathBuf,
+    headers_path: PathBuf,
+    finality_path: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(data_dir: &Path) -> FileStore {
+        return FileStore {
+            path: data_dir.join(CHAIN_FILE),
+            index_path: data_dir.join(INDEX_FILE),
+            snapshot_path: data_dir.join(SNAPSHOT_FILE),
+            headers_path: data_dir.join(HEADERS_FILE),
+            finality_path: data_dir.join(FINALITY_FILE),
+        };
+    }
+
+    pub fn path(&self) -> &Path {
+        return &self.path;
+    }
+
+    pub fn index_path(&self) -> &Path {
+        return &self.index_path;
+    }
+
+    pub fn snapshot_path(&self) -> &Path {
+        return &self.snapshot_path;
+    }
+
+    pub fn headers_path(&self) -> &Path {
+        return &self.headers_path;
+    }
+
+    pub fn finality_path(&self) -> &Path {
+        return &self.finality_path;
+    }
+
+    pub fn save(&self, chain: &[Block]) -> io::Result<()> {
+        let tmp_path = self.path.with_extension("jsonl.tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            for block in chain {
+                serde_json::to_writer(&mut writer, block)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+        }
+        return fs::rename(tmp_path, &self.path);
+    }
+
+    pub fn append(&self, block: &Block) -> io::Result<()> {
+        let mut file = OpenOptions::new().append(true).open(&self.path)?;
+        let mut line = serde_json::to_vec(block)?;
+        line.push(b'\n');
+        return file.write_all(&line);
+    }
+
+    pub fn save_index(&self, index: &ChainIndex) -> io::Result<()> {
+        let tmp_path = self.index_path.with_extension("json.tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            serde_json::to_writer(&mut writer, index)?;
+            writer.flush()?;
+        }
+        return fs::rename(tmp_path, &self.index_path);
+    }
+}
+
+impl ChainStore for FileStore {
+    fn location(&self) -> &Path {
+        return &self.path;
+    }
+
+    fn exists(&self) -> bool {
+        return self.path.exists();
+    }
+
+    fn load(&self) -> io::Result<Vec<Block>> {
+        return read_lines(&self.path);
+    }
+
+    fn load_headers(&self) -> io::Result<Vec<BlockHeader>> {
+        let mut headers = Vec::new();
+        if self.headers_path.exists() {
+            headers = read_lines(&self.headers_path)?;
+        }
+        headers.extend(self.load()?.iter().map(Block::header));
+        return Ok(headers);
+    }
+
+    fn load_index(&self) -> io::Result<ChainIndex> {
+        let reader = BufReader::new(File::open(&self.index_path)?);
+        return Ok(serde_json::from_reader(reader)?);
+    }
+
+    fn load_ledger(&self, genesis: &Ledger) -> io::Result<Ledger> {
+        let (mut ledger, base_height) = match self.load_snapshot()? {
+            Some(snapshot) => (snapshot.ledger(), snapshot.height),
+            None => (genesis.clone(), BlockHeight::GENESIS),
+        };
+        match ledger.apply_chain(base_height, &self.load()?) {
+            Ok(()) => return Ok(ledger),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+
+    fn load_snapshot(&self) -> io::Result<Option<Snapshot>> {
+        if !self.snapshot_path.exists() {
+            return Ok(None);
+        }
+        return Ok(Some(Snapshot::load(&self.snapshot_path)?));
+    }
+
+    fn commit_block(&self, block: &Block, index: &ChainIndex) -> io::Result<()> {
+        self.append(block)?;
+        return self.save_index(index);
+    }
+
+    fn commit_chain(&self, chain: &[Block], index: &ChainIndex) -> io::Result<()> {
+        self.save(chain)?;
+        return self.save_index(index);
+    }
+
+    fn commit_snapshot(&self, snapshot: &Snapshot, index: &ChainIndex) -> io::Result<()> {
+        if self.headers_path.exists() {
+            fs::remove_file(&self.headers_path)?;
+        }
+        snapshot.save(&self.snapshot_path)?;
+        self.save(std::slice::from_ref(&snapshot.block))?;
+        return self.save_index(index);
+    }
+
+    fn prune(&self, snapshot: &Snapshot, index: &ChainIndex) -> io::Result<()> {
+        let base_height = match self.load_snapshot()? {
+            Some(base) => base.height,
+            None => BlockHeight::GENESIS,
+        };
+        let chain = self.load()?;
+        let split = match snapshot.height.0.checked_sub(base_height.0) {
+            Some(split) if chain.get(split as usize) == Some(&snapshot.block) => split as usize,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("block {} is not in the stored chain", snapshot.height),
+                ))
+            }
+        };
+        if base_height == BlockHeight::GENESIS || self.headers_path.exists() {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.headers_path)?;
+            let mut lines = Vec::new();
+            for block in chain[..split].iter() {
+                serde_json::to_writer(&mut lines, &block.header())?;
+                lines.push(b'\n');
+            }
+            file.write_all(&lines)?;
+        }
+        snapshot.save(&self.snapshot_path)?;
+        self.save(&chain[split..])?;
+        return self.save_index(index);
+    }
+
+    fn load_finality(&self) -> io::Result<Option<FinalityCertificate>> {
+        if !self.finality_path.exists() {
+            return Ok(None);
+        }
+        let reader = BufReader::new(File::open(&self.finality_path)?);
+        return Ok(Some(serde_json::from_reader(reader)?));
+    }
+
+    fn commit_finality(&self, certificate: &FinalityCertificate) -> io::Result<()> {
+        let tmp_path = self.finality_path.with_extension("json.tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            serde_json::to_writer(&mut writer, certificate)?;
+            writer.flush()?;
+        }
+        return fs::rename(tmp_path, &self.finality_path);
+    }
+}
+
+fn read_lines<T: DeserializeOwned>(path: &Path) -> io::Result<Vec<T>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut values = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        values.push(serde_json::from_str(&line)?);
+    }
+    return Ok(values);
+}
@@ -0,0 +1,247 @@
+use crate::{
+    authority::AuthorityVote,
+    multisig::{MultisigPolicy, MultisigSpend},
+    types::BlockHeight,
+};
+
+use crypto::{cryptohash, keys::KeyPair};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct OutPoint {
+    #[serde(with = "hex::serde")]
+    pub txid: [u8; 32],
+    pub index: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TxInput {
+    pub previous_output: OutPoint,
+    #[serde(with = "hex::serde")]
+    pub public_key: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub signature: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multisig: Option<MultisigSpend>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<ScriptSpend>,
+}
+
+// Spends an output whose address is the hash of `locking_script`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScriptSpend {
+    #[serde(with = "hex::serde")]
+    pub locking_script: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub unlocking_script: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TxOutput {
+    pub amount: u64,
+    pub address: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transaction {
+    pub inputs: Vec<TxInput>,
+    pub outputs: Vec<TxOutput>,
+    pub lock_height: u64,
+    // Set on proof of authority votes, which move no funds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vote: Option<AuthorityVote>,
+}
+
+impl Transaction {
+    pub fn coinbase(height: BlockHeight, address: &str, amount: u64) -> Transaction {
+        return Transaction {
+            inputs: Vec::new(),
+            outputs: vec![TxOutput {
+                amount,
+                address: String::from(address),
+            }],
+            lock_height: height.0,
+            vote: None,
+        };
+    }
+
+    pub fn new_signed(
+        spends: &[(OutPoint, &KeyPair)],
+        outputs: Vec<TxOutput>,
+        lock_height: u64,
+    ) -> Transaction {
+        let mut transaction = Transaction {
+            inputs: spends
+                .iter()
+                .map(|(previous_output, key_pair)| TxInput {
+                    previous_output: *previous_output,
+                    public_key: key_pair.public_key().to_vec(),
+                    signature: Vec::new(),
+                    multisig: None,
+                    script: None,
+                })
+                .collect(),
+            outputs,
+            lock_height,
+            vote: None,
+        };
+        let sighash = transaction.sighash();
+        for (input, (_, key_pair)) in transaction.inputs.iter_mut().zip(spends.iter()) {
+            input.signature = key_pair.sign(&sighash).to_vec();
+        }
+        return transaction;
+    }
+
+    pub fn new_multisig(
+        spends: &[(OutPoint, MultisigPolicy)],
+        outputs: Vec<TxOutput>,
+        lock_height: u64,
+    ) -> Transaction {
+        return Transaction {
+            inputs: spends
+                .iter()
+                .map(|(previous_output, policy)| TxInput {
+                    previous_output: *previous_output,
+                    public_key: Vec::new(),
+                    signature: Vec::new(),
+                    multisig: Some(MultisigSpend::new(policy.clone())),
+                    script: None,
+                })
+                .collect(),
+            outputs,
+            lock_height,
+            vote: None,
+        };
+    }
+
+    pub fn new_script_spend(
+        spends: &[(OutPoint, Vec<u8>)],
+        outputs: Vec<TxOutput>,
+        lock_height: u64,
+    ) -> Transaction {
+        return Transaction {
+            inputs: spends
+                .iter()
+                .map(|(previous_output, locking_script)| TxInput {
+                    previous_output: *previous_output,
+                    public_key: Vec::new(),
+                    signature: Vec::new(),
+                    multisig: None,
+                    script: Some(ScriptSpend {
+                        locking_script: locking_script.clone(),
+                        unlocking_script: Vec::new(),
+                    }),
+                })
+                .collect(),
+            outputs,
+            lock_height,
+            vote: None,
+        };
+    }
+
+    pub fn set_unlocking_script(&mut self, index: usize, unlocking_script: Vec<u8>) -> bool {
+        match self
+            .inputs
+            .get_mut(index)
+            .and_then(|input| input.script.as_mut())
+        {
+            Some(spend) => {
+                spend.unlocking_script = unlocking_script;
+                return true;
+            }
+            None => return false,
+        }
+    }
+
+    pub fn sign_multisig(&mut self, key_pair: &KeyPair) -> usize {
+        let sighash = self.sighash();
+        let public_key = key_pair.public_key();
+        let mut signed = 0;
+        for input in self.inputs.iter_mut() {
+            if let Some(spend) = input.multisig.as_mut() {
+                if let Some(key_index) = spend.policy.key_index(&public_key) {
+                    if spend.add_signature(key_index, key_pair.sign(&sighash).to_vec()) {
+                        signed += 1;
+                    }
+                }
+            }
+        }
+        return signed;
+    }
+
+    pub fn combine_signatures(&mut self, other: &Transaction) -> Result<(), String> {
+        if other.sighash() != self.sighash() {
+            return Err(String::from("transactions differ in more than signatures"));
+        }
+        for (input, other_input) in self.inputs.iter_mut().zip(other.inputs.iter()) {
+            if let (Some(spend), Some(other_spend)) =
+                (input.multisig.as_mut(), other_input.multisig.as_ref())
+            {
+                for signature in other_spend.signatures.iter() {
+                    spend.add_signature(signature.key_index, signature.signature.clone());
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn new_vote(vote: AuthorityVote) -> Transaction {
+        return Transaction {
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            lock_height: 0,
+            vote: Some(vote),
+        };
+    }
+
+    pub fn is_coinbase(&self) -> bool {
+        return self.inputs.is_empty() && self.vote.is_none();
+    }
+
+    pub fn txid(&self) -> [u8; 32] {
+        return cryptohash::sha256(&self.encode());
+    }
+
+    pub fn sighash(&self) -> [u8; 32] {
+        let mut unsigned = self.clone();
+        for input in unsigned.inputs.iter_mut() {
+            input.signature.clear();
+            if let Some(spend) = input.multisig.as_mut() {
+                spend.signatures.clear();
+            }
+            if let Some(spend) = input.script.as_mut() {
+                spend.unlocking_script.clear();
+            }
+        }
+        return cryptohash::sha256(&unsigned.encode());
+    }
+
+    pub fn output_total(&self) -> Option<u64> {
+        return self
+            .outputs
+            .iter()
+            .try_fold(0u64, |total, output| total.checked_add(output.amount));
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        return serde_json::to_vec(self).unwrap();
+    }
+
+    pub fn encode_hex(&self) -> String {
+        return hex::encode(self.encode());
+    }
+
+    pub fn decode_hex(raw: &str) -> Option<Transaction> {
+        let bytes = hex::decode(raw).ok()?;
+        return serde_json::from_slice(&bytes).ok();
+    }
+
+    pub fn encode_payload(transactions: &[Transaction]) -> String {
+        return serde_json::to_string(transactions).unwrap();
+    }
+
+    pub fn decode_payload(data: &str) -> Option<Vec<Transaction>> {
+        return serde_json::from_str(data).ok();
+    }
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    ops::{Add, Sub},
+    str::FromStr,
+};
+
+pub use crypto::hash256::Hash256;
+
+// A block's distance from genesis, which is height 0.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct BlockHeight(pub u64);
+
+impl BlockHeight {
+    pub const GENESIS: BlockHeight = BlockHeight(0);
+
+    // The height as an offset into a chain of blocks in memory.
+    pub fn index(self) -> usize {
+        return self.0 as usize;
+    }
+
+    pub fn next(self) -> BlockHeight {
+        return BlockHeight(self.0 + 1);
+    }
+
+    pub fn checked_sub(self, blocks: u64) -> Option<BlockHeight> {
+        return self.0.checked_sub(blocks).map(BlockHeight);
+    }
+}
+
+impl From<usize> for BlockHeight {
+    fn from(height: usize) -> BlockHeight {
+        return BlockHeight(height as u64);
+    }
+}
+
+impl Add<u64> for BlockHeight {
+    type Output = BlockHeight;
+
+    fn add(self, blocks: u64) -> BlockHeight {
+        return BlockHeight(self.0 + blocks);
+    }
+}
+
+impl Sub<BlockHeight> for BlockHeight {
+    type Output = u64;
+
+    // The number of blocks from `other` up to this height.
+    fn sub(self, other: BlockHeight) -> u64 {
+        return self.0 - other.0;
+    }
+}
+
+// The number of leading zero bits a block hash needs.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Difficulty(pub u32);
+
+impl Difficulty {
+    pub fn bits(self) -> usize {
+        return self.0 as usize;
+    }
+}
+
+impl Add<u32> for Difficulty {
+    type Output = Difficulty;
+
+    fn add(self, steps: u32) -> Difficulty {
+        return Difficulty(self.0 + steps);
+    }
+}
+
+impl Sub<u32> for Difficulty {
+    type Output = Difficulty;
+
+    fn sub(self, steps: u32) -> Difficulty {
+        return Difficulty(self.0 - steps);
+    }
+}
+
+// A proof of work nonce, 64 bits wide on every platform so blocks hash and
+// encode the same everywhere.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Nonce(pub u64);
+
+macro_rules! decimal_newtype {
+    ($name:ident, $inner:ty, $label:expr) => {
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                return write!(f, "{}", self.0);
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = String;
+
+            fn from_str(value: &str) -> Result<$name, String> {
+                match value.parse::<$inner>() {
+                    Ok(value) => return Ok($name(value)),
+                    Err(e) => return Err(format!("invalid {} {:?}: {}", $label, value, e)),
+                }
+            }
+        }
+    };
+}
+
+decimal_newtype!(BlockHeight, u64, "block height");
+decimal_newtype!(Difficulty, u32, "difficulty");
+decimal_newtype!(Nonce, u64, "nonce");
@@ -0,0 +1,455 @@
+use crate::{
+    authority::*,
+    block::Block,
+    blockchain::Blockchain,
+    clock::{Clock, MockClock},
+    consensus::{self, BlockContents, BlockSeal, ConsensusEngine, ProofOfWorkEngine},
+    index::ChainIndex,
+    ledger::Ledger,
+    params::{ChainParams, Consensus},
+    sled_store::SledStore,
+    store::ChainStore,
+    transaction::Transaction,
+    types::{BlockHeight, Nonce},
+};
+
+use crypto::{
+    hasher::{HashAlgorithm, Sha256Hasher},
+    keys::KeyPair,
+};
+use std::sync::Arc;
+use tempfile::tempdir;
+
+fn addresses(authorities: &[&KeyPair]) -> Vec<String> {
+    let mut addresses: Vec<String> = authorities.iter().map(|key| key.address()).collect();
+    addresses.sort();
+    return addresses;
+}
+
+fn authority_params(authorities: &[&KeyPair]) -> AuthorityParams {
+    return AuthorityParams {
+        slot_ms: 5,
+        authorities: addresses(authorities),
+    };
+}
+
+fn engine(authorities: &[&KeyPair]) -> ProofOfAuthorityEngine {
+    return ProofOfAuthorityEngine::new(
+        Box::new(HashAlgorithm::Sha256),
+        authority_params(authorities),
+    );
+}
+
+// An engine whose clock only moves when it waits for a slot.
+fn engine_with_mock_clock(authorities: &[&KeyPair]) -> (ProofOfAuthorityEngine, Arc<MockClock>) {
+    let clock = Arc::new(MockClock::at_millis(1_700_000_000_000));
+    let engine = ProofOfAuthorityEngine::with_clock(
+        Box::new(HashAlgorithm::Sha256),
+        authority_params(authorities),
+        clock.clone(),
+    );
+    return (engine, clock);
+}
+
+// Waits on `clock` for the slot `signer` leads, as a node does before
+// producing a block.
+fn produce_in_slot(
+    engine: &dyn ConsensusEngine,
+    clock: &dyn Clock,
+    last_block: &Block,
+    contents: BlockContents,
+    signer: &KeyPair,
+) -> Result<Block, String> {
+    let ledger = Ledger::new();
+    clock.sleep_until(engine.next_block_time(last_block, &ledger, Some(signer))?);
+    return engine.produce_block(last_block, &ledger, contents, Some(signer));
+}
+
+fn authority_chain(authorities: &[&KeyPair], signer: &KeyPair) -> Blockchain {
+    let mut blockchain = Blockchain::new();
+    blockchain
+        .set_params(ChainParams {
+            consensus: Consensus::ProofOfAuthority(authority_params(authorities)),
+            ..ChainParams::default()
+        })
+        .unwrap();
+    blockchain.set_validator_key(copy(signer));
+    return blockchain;
+}
+
+fn copy(key_pair: &KeyPair) -> KeyPair {
+    return KeyPair::from_secret_bytes(&key_pair.secret_bytes()).unwrap();
+}
+
+fn vote(voter: &KeyPair, candidate: &KeyPair, add: bool, epoch: u64) -> AuthorityVote {
+    return AuthorityVote::new_signed(voter, &candidate.address(), add, epoch);
+}
+
+fn contents(transactions: &[Transaction]) -> BlockContents {
+    return BlockContents {
+        data: Transaction::encode_payload(transactions),
+        state_root: None,
+        evidence: Vec::new(),
+    };
+}
+
+mod authority_set {
+    use super::*;
+
+    #[test]
+    fn takes_turns_in_address_order() {
+        let (a, b, c) = (
+            KeyPair::generate(),
+            KeyPair::generate(),
+            KeyPair::generate(),
+        );
+        let order = addresses(&[&a, &b, &c]);
+        let set = AuthoritySet::new(&order);
+        for slot in 0..6 {
+            assert_eq!(set.leader(slot), Some(order[slot as usize % 3].clone()));
+        }
+        assert_eq!(AuthoritySet::new(&[]).leader(0), None);
+    }
+
+    #[test]
+    fn changes_once_a_majority_votes_and_starts_a_new_epoch() {
+        let (a, b, c, d) = (
+            KeyPair::generate(),
+            KeyPair::generate(),
+            KeyPair::generate(),
+            KeyPair::generate(),
+        );
+        let mut set = AuthoritySet::new(&addresses(&[&a, &b, &c]));
+        set.apply(&vote(&a, &d, true, 0)).unwrap();
+        set.apply(&vote(&a, &c, false, 0)).unwrap();
+        assert!(!set.contains(&d.address()));
+        set.apply(&vote(&b, &d, true, 0)).unwrap();
+        assert!(set.contains(&d.address()));
+        assert_eq!(set.epoch(), 1);
+
+        // The open vote to remove c was discarded with the old epoch.
+        set.apply(&vote(&b, &c, false, 1)).unwrap();
+        set.apply(&vote(&d, &c, false, 1)).unwrap();
+        assert!(set.contains(&c.address()));
+        set.apply(&vote(&a, &c, false, 1)).unwrap();
+        assert_eq!(set.members(), addresses(&[&a, &b, &d]));
+        assert_eq!(set.epoch(), 2);
+    }
+
+    #[test]
+    fn rejects_votes_that_cannot_count() {
+        let (a, b, outsider) = (
+            KeyPair::generate(),
+            KeyPair::generate(),
+            KeyPair::generate(),
+        );
+        let mut set = AuthoritySet::new(&addresses(&[&a, &b]));
+        assert!(set.apply(&vote(&outsider, &outsider, true, 0)).is_err());
+        assert!(set.apply(&vote(&a, &outsider, true, 1)).is_err());
+        assert!(set.apply(&vote(&a, &b, true, 0)).is_err());
+        assert!(set.apply(&vote(&a, &outsider, false, 0)).is_err());
+        set.apply(&vote(&a, &outsider, true, 0)).unwrap();
+        assert!(set.apply(&vote(&a, &outsider, true, 0)).is_err());
+
+        let mut single = AuthoritySet::new(&addresses(&[&a]));
+        assert!(single.apply(&vote(&a, &a, false, 0)).is_err());
+    }
+
+    #[test]
+    fn votes_are_bound_to_their_signature() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let mut forged = vote(&a, &b, true, 0);
+        assert!(forged.verify());
+        forged.add = false;
+        assert!(!forged.verify());
+    }
+}
+
+mod params {
+    use super::*;
+
+    #[test]
+    fn need_valid_authorities() {
+        let a = KeyPair::generate();
+        assert!(authority_params(&[&a]).check().is_ok());
+        assert!(AuthorityParams::default().check().is_err());
+        let invalid = AuthorityParams {
+            authorities: vec![String::from("not an address")],
+            ..AuthorityParams::default()
+        };
+        assert!(invalid.check().is_err());
+    }
+
+    #[test]
+    fn read_proof_of_authority_consensus() {
+        let a = KeyPair::generate();
+        let json = format!(
+            r#"{{"consensus":{{"type":"proof-of-authority","slot_ms":5,"authorities":["{}"]}}}}"#,
+            a.address()
+        );
+        let params: ChainParams = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            params.consensus,
+            Consensus::ProofOfAuthority(authority_params(&[&a]))
+        );
+    }
+}
+
+mod engine {
+    use super::*;
+
+    #[test]
+    fn authorities_seal_blocks_in_turn_without_proof_of_work() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let (engine, clock) = engine_with_mock_clock(&[&a, &b]);
+        let set = AuthoritySet::new(&addresses(&[&a, &b]));
+        let mut last_block = Block::genesis();
+        for signer in [&a, &b, &a].iter() {
+            let block =
+                produce_in_slot(&engine, clock.as_ref(), &last_block, contents(&[]), signer)
+                    .unwrap();
+            let seal = block.seal.as_ref().unwrap();
+            assert_eq!(seal.address(), signer.address());
+            assert_eq!(set.leader(seal.slot), Some(signer.address()));
+            assert_eq!(block.nonce, Nonce(0));
+            assert!(engine
+                .validate_block(&block, &last_block, &Ledger::new())
+                .is_ok());
+            assert!(engine.validate_header(&block.header(), &last_block.header()));
+            last_block = block;
+        }
+    }
+
+    #[test]
+    fn rejects_blocks_from_outsiders_and_out_of_turn() {
+        let (a, b, outsider) = (
+            KeyPair::generate(),
+            KeyPair::generate(),
+            KeyPair::generate(),
+        );
+        let genesis = Block::genesis();
+        assert!(engine(&[&a])
+            .produce_block(&genesis, &Ledger::new(), contents(&[]), Some(&outsider))
+            .is_err());
+        assert!(engine(&[&a])
+            .produce_block(&genesis, &Ledger::new(), contents(&[]), None)
+            .is_err());
+
+        // The same block moved into b's turn, still sealed by a.
+        let (shared, clock) = engine_with_mock_clock(&[&a, &b]);
+        let block = produce_in_slot(&shared, clock.as_ref(), &genesis, contents(&[]), &a).unwrap();
+        let mut out_of_turn = block.clone();
+        let slot = block.seal.as_ref().unwrap().slot + 1;
+        out_of_turn.timestamp = consensus::slot_start(5, slot);
+        out_of_turn.seal.as_mut().unwrap().slot = slot;
+        out_of_turn.hash = Block::compute_hash(&HashAlgorithm::Sha256, &out_of_turn);
+        out_of_turn.seal.as_mut().unwrap().signature = a
+            .sign(&BlockSeal::message(slot, &out_of_turn.hash))
+            .to_vec();
+        let error = shared
+            .validate_block(&out_of_turn, &genesis, &Ledger::new())
+            .unwrap_err();
+        assert!(error.contains("is not the leader"));
+        assert!(ProofOfWorkEngine::new(Box::new(Sha256Hasher))
+            .validate_block(&block, &genesis, &Ledger::new())
+            .is_err());
+    }
+
+    #[test]
+    fn checks_votes_against_the_authority_set() {
+        let (a, outsider) = (KeyPair::generate(), KeyPair::generate());
+        let engine = engine(&[&a]);
+        let genesis = Block::genesis();
+        let votes = [Transaction::new_vote(vote(&outsider, &outsider, true, 0))];
+        let block = engine
+            .produce_block(&genesis, &Ledger::new(), contents(&votes), Some(&a))
+            .unwrap();
+        let error = engine
+            .validate_block(&block, &genesis, &Ledger::new())
+            .unwrap_err();
+        assert!(error.contains("is not an authority"));
+    }
+
+    #[test]
+    fn other_engines_refuse_votes() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let engine = ProofOfWorkEngine::new(Box::new(Sha256Hasher));
+        let votes = [Transaction::new_vote(vote(&a, &b, true, 0))];
+        let genesis = Block::genesis();
+        let block = engine
+            .produce_block(&genesis, &Ledger::new(), contents(&votes), None)
+            .unwrap();
+        assert!(engine
+            .validate_block(&block, &genesis, &Ledger::new())
+            .is_err());
+        assert!(engine
+            .validate_transaction(&votes[0], &Ledger::new())
+            .is_err());
+    }
+}
+
+mod voting {
+    use super::*;
+
+    // Chain on which the single authority `a` voted `b` in at height 1.
+    fn voted_chain(a: &KeyPair, b: &KeyPair) -> Blockchain {
+        let mut blockchain = authority_chain(&[a], a);
+        let ballot = Transaction::new_vote(vote(a, b, true, 0));
+        blockchain.submit_transaction(ballot).unwrap();
+        blockchain
+            .in_slot(|blockchain| blockchain.mine_transactions(&a.address()))
+            .unwrap();
+        return blockchain;
+    }
+
+    #[test]
+    fn mined_votes_change_the_authority_set() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let mut blockchain = voted_chain(&a, &b);
+        let set = blockchain.authority_set().unwrap();
+        assert_eq!(set.members(), addresses(&[&a, &b]));
+        assert_eq!(set.epoch(), 1);
+        assert_eq!(blockchain.ledger().unwrap().authorities(), Some(&set));
+        assert_eq!(
+            blockchain.last_block().state_root,
+            Some(blockchain.ledger().unwrap().commitment())
+        );
+
+        blockchain.set_validator_key(copy(&b));
+        blockchain
+            .in_slot(|blockchain| blockchain.add_block(String::from("Raccoons")))
+            .unwrap();
+        let seal = blockchain.last_block().seal.as_ref().unwrap();
+        assert_eq!(seal.address(), b.address());
+    }
+
+    #[test]
+    fn ledgers_commit_to_the_set_rather_than_the_votes() {
+        let (a, b, c, d) = (
+            KeyPair::generate(),
+            KeyPair::generate(),
+            KeyPair::generate(),
+            KeyPair::generate(),
+        );
+        let blockchain = authority_chain(&[&a, &b, &c], &a);
+        let genesis = blockchain.params().genesis_ledger();
+        let apply = |ballots: &[AuthorityVote]| {
+            let mut ledger = genesis.clone();
+            for ballot in ballots.iter() {
+                let transaction = Transaction::new_vote(ballot.clone());
+                assert_eq!(
+                    ledger.validate_transaction(&transaction, BlockHeight(1)),
+                    Ok(0)
+                );
+                ledger.apply_transaction(&transaction);
+            }
+            return ledger;
+        };
+        let forward = apply(&[vote(&a, &d, true, 0), vote(&b, &d, true, 0)]);
+        let backward = apply(&[vote(&b, &d, true, 0), vote(&a, &d, true, 0)]);
+        assert_eq!(forward, backward);
+        assert_eq!(forward.commitment(), backward.commitment());
+        let set = forward.authorities().unwrap();
+        assert_eq!(set.epoch(), 1);
+        assert!(set.contains(&d.address()));
+        assert_ne!(forward.commitment(), genesis.commitment());
+    }
+
+    #[test]
+    fn votes_cannot_be_replayed() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let mut blockchain = voted_chain(&a, &b);
+        let replay = Transaction::new_vote(vote(&a, &b, true, 0));
+        assert!(blockchain.submit_transaction(replay).is_err());
+        let mut forged = vote(&a, &b, false, 1);
+        forged.candidate = a.address();
+        assert!(blockchain
+            .submit_transaction(Transaction::new_vote(forged))
+            .is_err());
+    }
+
+    #[test]
+    fn mempool_votes_are_checked_against_the_pending_set() {
+        let (a, b, c, d) = (
+            KeyPair::generate(),
+            KeyPair::generate(),
+            KeyPair::generate(),
+            KeyPair::generate(),
+        );
+        let mut blockchain = authority_chain(&[&a, &b, &c], &a);
+        blockchain
+            .submit_transaction(Transaction::new_vote(vote(&a, &d, true, 0)))
+            .unwrap();
+        blockchain
+            .submit_transaction(Transaction::new_vote(vote(&b, &d, true, 0)))
+            .unwrap();
+        let stale = Transaction::new_vote(vote(&c, &b, false, 0));
+        assert!(blockchain.submit_transaction(stale).is_err());
+        blockchain
+            .in_slot(|blockchain| blockchain.mine_transactions(&a.address()))
+            .unwrap();
+        assert!(blockchain.authority_set().unwrap().contains(&d.address()));
+    }
+
+    #[test]
+    fn proof_of_work_chains_refuse_votes() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let mut blockchain = Blockchain::new();
+        let ballot = Transaction::new_vote(vote(&a, &b, true, 0));
+        assert!(blockchain.submit_transaction(ballot).is_err());
+        assert!(blockchain.authority_set().is_err());
+    }
+
+    #[test]
+    fn peers_replay_votes_when_syncing() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let mut blockchain = voted_chain(&a, &b);
+        blockchain.set_validator_key(copy(&b));
+        blockchain
+            .in_slot(|blockchain| blockchain.add_block(String::from("Raccoons")))
+            .unwrap();
+        let mut peer = authority_chain(&[&a], &a);
+        assert!(peer.is_valid_extension(&blockchain.chain));
+        peer.replace_chain(blockchain.chain.clone()).unwrap();
+        assert_eq!(
+            peer.authority_set().unwrap(),
+            blockchain.authority_set().unwrap()
+        );
+        assert!(!authority_chain(&[&b], &b).is_valid_extension(&blockchain.chain));
+    }
+
+    #[test]
+    fn snapshots_carry_the_authority_set() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let blockchain = voted_chain(&a, &b);
+        let snapshot = blockchain.snapshot(BlockHeight(1)).unwrap();
+        assert_eq!(
+            snapshot.authorities,
+            Some(blockchain.authority_set().unwrap())
+        );
+        assert_eq!(snapshot.verify().unwrap(), blockchain.ledger().unwrap());
+    }
+
+    #[test]
+    fn sled_store_tracks_votes_across_reorgs() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let blockchain = voted_chain(&a, &b);
+        store
+            .commit_chain(&blockchain.chain, blockchain.index())
+            .unwrap();
+        let genesis = blockchain.params().genesis_ledger();
+        assert_eq!(
+            store.load_ledger(&genesis).unwrap(),
+            blockchain.ledger().unwrap()
+        );
+        store
+            .commit_chain(&blockchain.chain[..1], &ChainIndex::new())
+            .unwrap();
+        assert_eq!(
+            store.load_ledger(&genesis).unwrap().authorities(),
+            genesis.authorities()
+        );
+    }
+}
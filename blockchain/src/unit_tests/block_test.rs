@@ -1,11 +1,27 @@
 use crypto::cryptohash::*;
 
 use crate::{
-    block::Block,
-    config::*
+    block::{Block, BlockHeader},
+    clock::*,
+    config::*,
+    types::*
 };
 
-use std::{time::SystemTime};
+use crypto::hasher::Sha256Hasher;
+use std::time::{Duration, SystemTime};
+
+const START_MS: u64 = 1_700_000_000_000;
+
+// Mines on a clock `elapsed_ms` past `last_block`, trying nonces from `nonces`.
+fn mine_after(last_block: &Block, elapsed_ms: u64, nonces: &mut dyn NonceSource) -> Block {
+    let clock = MockClock::new(last_block.timestamp + Duration::from_millis(elapsed_ms));
+    return Block::mine_block_using(&Sha256Hasher, last_block, String::from("some data"), None, &clock, nonces);
+}
+
+fn mined() -> Block {
+    let genesis = Block::genesis();
+    return mine_after(&genesis, START_MS, &mut SequentialNonces::default());
+}
 
 
 mod block_struct_data {
@@ -13,19 +29,22 @@ mod block_struct_data {
 
     #[test]
     fn struct_has_proper_fields() {
-        let timestamp = SystemTime::now();
-        let last_hash: [u8; 32] = [0; 32];
-        let hash: [u8; 32] = [1; 32];
+        let timestamp = SystemTime::UNIX_EPOCH + Duration::from_millis(START_MS);
+        let last_hash = Hash256([0; 32]);
+        let hash = Hash256([1; 32]);
         let data = String::from("mock data");
-        let nonce: usize = 128;
-        let difficulty: usize = 0;
+        let nonce = Nonce(128);
+        let difficulty = Difficulty(0);
         let block = Block {
             timestamp,
-            last_hash: last_hash.clone(),
-            hash: hash.clone(),
+            last_hash,
+            hash,
             data: data.clone(),
             nonce,
             difficulty,
+            state_root: None,
+            seal: None,
+            evidence: Vec::new(),
         };
         assert_eq!(block.timestamp, timestamp);
         assert_eq!(block.last_hash, last_hash);
@@ -38,11 +57,11 @@ mod block_struct_data {
     #[test]
     fn genesis_creates_block_instance_with_expected_data() {
         let timestamp = SystemTime::UNIX_EPOCH;
-        let last_hash: [u8; 32] = [0; 32];
-        let hash: [u8; 32] = [255; 32];
+        let last_hash = Hash256::ZERO;
+        let hash = Hash256([255; 32]);
         let data = String::from("genesis block");
-        let nonce = 0;
-        let difficulty = 8;
+        let nonce = Nonce(0);
+        let difficulty = Difficulty(8);
         let genesis_block = Block::genesis();
         assert_eq!(genesis_block.timestamp, timestamp);
         assert_eq!(genesis_block.last_hash, last_hash);
@@ -93,8 +112,8 @@ mod mine_block {
             mined_block.nonce,
             mined_block.difficulty
         );
-        let mut expected_hash: [u8; 32] = [0; 32];
-        hash(&data_map, &mut expected_hash);
+        let mut expected_hash = Hash256::ZERO;
+        hash(&data_map, &mut expected_hash.0);
         assert_eq!(mined_block.hash, expected_hash);
     }
 
@@ -102,19 +121,115 @@ mod mine_block {
     fn hash_has_256_bits_and_matches_difficulty_constraint() {
         let (_, _, mined_block) = setup();
         assert!(is_valid_hash(
-            &mined_block.hash,
-            mined_block.difficulty
+            mined_block.hash.as_bytes(),
+            mined_block.difficulty.bits()
         ));
     }
 }
 
+mod mine_block_at {
+    use super::*;
+
+    #[test]
+    fn stamps_the_block_and_repeats_for_the_same_inputs() {
+        let last_block = Block::genesis();
+        let timestamp = SystemTime::UNIX_EPOCH + Duration::from_millis(START_MS);
+        let mine = || Block::mine_block_at(&Sha256Hasher, &last_block, String::from("data"), None, timestamp);
+        let block = mine();
+        assert_eq!(block.timestamp, timestamp);
+        assert_eq!(block.difficulty, Block::adjust_difficulty(&last_block, &timestamp));
+        assert_eq!(block, mine());
+        assert!(Block::is_valid_block(&block, &last_block.hash, last_block.difficulty));
+    }
+}
+
+mod mine_block_using {
+    use super::*;
+
+    #[test]
+    fn raises_difficulty_when_the_clock_shows_a_quick_block() {
+        let block = mined();
+        let next = mine_after(&block, MINE_RATE - 100, &mut SequentialNonces::default());
+        assert_eq!(next.timestamp, block.timestamp + Duration::from_millis(MINE_RATE - 100));
+        assert_eq!(next.difficulty, block.difficulty + 1);
+        assert!(Block::is_valid_block(&next, &block.hash, block.difficulty));
+    }
+
+    #[test]
+    fn lowers_difficulty_when_the_clock_shows_a_slow_block() {
+        let block = mined();
+        let next = mine_after(&block, MINE_RATE + 100, &mut SequentialNonces::default());
+        assert_eq!(next.difficulty, block.difficulty - 1);
+        assert!(Block::is_valid_block(&next, &block.hash, block.difficulty));
+    }
+
+    #[test]
+    fn repeats_for_the_same_clock_and_nonces() {
+        assert_eq!(mined(), mined());
+    }
+
+    #[test]
+    fn takes_nonces_from_the_source() {
+        let genesis = Block::genesis();
+        let block = mine_after(&genesis, START_MS, &mut SequentialNonces::starting_at(Nonce(1_000)));
+        assert!(block.nonce >= Nonce(1_000));
+        let skipped = mine_after(&genesis, START_MS, &mut SequentialNonces::starting_at(block.nonce));
+        assert_eq!(skipped, block);
+        assert!(Block::is_valid_block(&block, &genesis.hash, genesis.difficulty));
+    }
+}
+
+mod mining_template {
+    use super::*;
+    use crate::block::MiningTemplate;
+    use crypto::hasher::{Argon2Hasher, Argon2Params, ChainHasher, HashAlgorithm};
+
+    fn block(data: &str, nonce: Nonce, state_root: Option<Hash256>) -> Block {
+        let mut block = mined();
+        block.data = String::from(data);
+        block.nonce = nonce;
+        block.state_root = state_root;
+        return block;
+    }
+
+    #[test]
+    fn hashes_like_compute_hash() {
+        let argon2 = Argon2Hasher::new(HashAlgorithm::Sha256, &Argon2Params { memory_kib: 64, ..Argon2Params::default() }).unwrap();
+        let mut hashers: Vec<&dyn ChainHasher> = HashAlgorithm::ALL.iter().map(|algorithm| algorithm.hasher()).collect();
+        hashers.push(&argon2);
+        let datas = ["", "some data", "quotes \" and \\ and\nnewlines", "ünïcødé 🦝"];
+        let nonces = [0, 1, 9, 10, 4_294_967_296, u64::MAX].map(Nonce);
+        for hasher in hashers {
+            for data in datas.iter() {
+                for state_root in [None, Some(Hash256([7; 32]))].iter() {
+                    let reference = block(data, Nonce(0), *state_root);
+                    let template = MiningTemplate::new(hasher, &reference.timestamp, &reference.last_hash, &reference.data, reference.difficulty, state_root);
+                    for nonce in nonces.iter() {
+                        let reference = block(data, *nonce, *state_root);
+                        assert_eq!(template.hash(*nonce), Block::compute_hash(hasher, &reference), "{:?} {:?} {}", hasher, data, nonce);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mines_valid_blocks_with_every_algorithm() {
+        let genesis = Block::genesis();
+        let clock = MockClock::new(genesis.timestamp + Duration::from_millis(START_MS));
+        for algorithm in HashAlgorithm::ALL.iter() {
+            let block = Block::mine_block_using(algorithm, &genesis, String::from("some data"), Some(Hash256([3; 32])), &clock, &mut SequentialNonces::default());
+            assert!(Block::is_valid_block_with(algorithm, &block, &genesis.hash, genesis.difficulty), "{}", algorithm);
+        }
+    }
+}
+
 mod adjust_difficulty {
     use super::*;
-    use std::time::Duration;
 
     #[test]
     fn raises_difficulty_for_quickly_mined_block() {
-        let block = Block::mine_block(&Block::genesis(), String::from("some data"));
+        let block = mined();
         let new_timestamp = block.timestamp + Duration::from_millis(MINE_RATE - 100);
         assert_eq!(
             Block::adjust_difficulty(&block, &new_timestamp),
@@ -124,7 +239,7 @@ mod adjust_difficulty {
 
     #[test]
     fn lowers_difficulty_for_slowly_mined_block() {
-        let block = Block::mine_block(&Block::genesis(), String::from("some data"));
+        let block = mined();
         let new_timestamp = block.timestamp + Duration::from_millis(MINE_RATE + 100);
         assert_eq!(
             Block::adjust_difficulty(&block, &new_timestamp),
@@ -134,7 +249,7 @@ mod adjust_difficulty {
 
     #[test]
     fn increases_difficulty_if_elapsed_time_is_negative() {
-        let block = Block::mine_block(&Block::genesis(), String::from("some data"));
+        let block = mined();
         let new_timestamp = block.timestamp - Duration::from_millis(MINE_RATE);
         assert_eq!(
             Block::adjust_difficulty(&block, &new_timestamp),
@@ -144,7 +259,7 @@ mod adjust_difficulty {
 
     #[test]
     fn has_correct_lower_limit() {
-        let mut block = Block::mine_block(&Block::genesis(), String::from("some data"));
+        let mut block = mined();
         block.difficulty = DIFFICULTY_MIN;
         let new_timestamp = block.timestamp + Duration::from_millis(MINE_RATE + 100);
         assert_eq!(
@@ -155,7 +270,7 @@ mod adjust_difficulty {
 
     #[test]
     fn has_correct_upper_limit() {
-        let mut block = Block::mine_block(&Block::genesis(), String::from("some data"));
+        let mut block = mined();
         block.difficulty = DIFFICULTY_MAX;
         let new_timestamp = block.timestamp + Duration::from_millis(MINE_RATE - 100);
         assert_eq!(
@@ -166,14 +281,14 @@ mod adjust_difficulty {
 
     #[test]
     fn adjusts_difficulty_if_out_of_bounds() {
-        let mut block = Block::mine_block(&Block::genesis(), String::from("some data"));
-        block.difficulty = 0;
+        let mut block = mined();
+        block.difficulty = Difficulty(0);
         assert_eq!(
             Block::adjust_difficulty(&block, &block.timestamp),
             DIFFICULTY_MIN
         );
 
-        block.difficulty = std::usize::MAX;
+        block.difficulty = Difficulty(u32::MAX);
         assert_eq!(
             Block::adjust_difficulty(&block, &block.timestamp),
             DIFFICULTY_MAX
@@ -189,8 +304,8 @@ mod is_valid_difficulty {
 
         #[test]
         fn true_when_new_difficulty_within_bounds() {
-            assert!(Block::is_valid_difficulty(8, 9));
-            assert!(Block::is_valid_difficulty(12, 11));
+            assert!(Block::is_valid_difficulty(Difficulty(8), Difficulty(9)));
+            assert!(Block::is_valid_difficulty(Difficulty(12), Difficulty(11)));
         }
         #[test]
         fn false_when_new_difficulty_outside_of_bounds() {
@@ -214,7 +329,7 @@ mod is_valid_difficulty {
 
         #[test]
         fn false_if_not_min_or_max() {
-            assert!(!Block::is_valid_difficulty(12, 12));
+            assert!(!Block::is_valid_difficulty(Difficulty(12), Difficulty(12)));
             assert!(!Block::is_valid_difficulty(
                 DIFFICULTY_MIN - 1,
                 DIFFICULTY_MIN - 1
@@ -235,13 +350,13 @@ mod is_valid_difficulty {
         use super::*;
         #[test]
         fn false_if_both_difficulties_within_bounds() {
-            assert!(!Block::is_valid_difficulty(24, 26))
+            assert!(!Block::is_valid_difficulty(Difficulty(24), Difficulty(26)))
         }
 
         #[test]
         fn false_if_new_difficulty_out_of_bounds() {
-            assert!(!Block::is_valid_difficulty(25, DIFFICULTY_MIN - 1));
-            assert!(!Block::is_valid_difficulty(25, DIFFICULTY_MAX + 1));
+            assert!(!Block::is_valid_difficulty(Difficulty(25), DIFFICULTY_MIN - 1));
+            assert!(!Block::is_valid_difficulty(Difficulty(25), DIFFICULTY_MAX + 1));
         }
 
         #[test]
@@ -257,7 +372,7 @@ mod is_valid_difficulty {
 
             #[test]
             fn true_if_new_difficulty_equals_closest_bound() {
-                assert!(Block::is_valid_difficulty(0, DIFFICULTY_MIN));
+                assert!(Block::is_valid_difficulty(Difficulty(0), DIFFICULTY_MIN));
                 assert!(Block::is_valid_difficulty(
                     DIFFICULTY_MAX + 4,
                     DIFFICULTY_MAX
@@ -266,12 +381,12 @@ mod is_valid_difficulty {
 
             #[test]
             fn false_if_new_difficulty_not_equal_to_closest_bound() {
-                assert!(!Block::is_valid_difficulty(0, DIFFICULTY_MAX));
+                assert!(!Block::is_valid_difficulty(Difficulty(0), DIFFICULTY_MAX));
                 assert!(!Block::is_valid_difficulty(
                     DIFFICULTY_MAX + 1,
                     DIFFICULTY_MIN
                 ));
-                assert!(!Block::is_valid_difficulty(0, 12));
+                assert!(!Block::is_valid_difficulty(Difficulty(0), Difficulty(12)));
             }
         }
     }
@@ -285,7 +400,7 @@ mod is_valid_block {
         let mut last_block: Block =
             Block::mine_block(&Block::genesis(), String::from("dummy data 1"));
         let new_block = Block::mine_block(&last_block, String::from("dummy data 2"));
-        last_block.hash = [13; 32];
+        last_block.hash = Hash256([13; 32]);
         assert!(!Block::is_valid_block(
             &new_block,
             &last_block.hash,
@@ -298,7 +413,7 @@ mod is_valid_block {
         let mut last_block: Block =
             Block::mine_block(&Block::genesis(), String::from("dummy data 1"));
         let new_block = Block::mine_block(&last_block, String::from("dummy data 2"));
-        last_block.difficulty = 20;
+        last_block.difficulty = Difficulty(20);
         assert!(!Block::is_valid_block(
             &new_block,
             &last_block.hash,
@@ -322,20 +437,23 @@ mod is_valid_block {
     fn false_if_new_block_hash_violates_difficulty_constraint() {
         let last_block: Block = Block::genesis();
         let timestamp: SystemTime = SystemTime::UNIX_EPOCH;
-        let last_hash: [u8; 32] = last_block.hash.clone();
+        let last_hash: Hash256 = last_block.hash;
         let data: String = String::from("dummy data!");
-        let nonce: usize = 0;
-        let difficulty: usize = last_block.difficulty + 1;
+        let nonce = Nonce(0);
+        let difficulty: Difficulty = last_block.difficulty + 1;
         let data_map = Block::get_data_map(&timestamp, &last_hash, &data, nonce, difficulty);
-        let mut bad_hash: [u8; 32] = [0; 32];
-        hash(&data_map, &mut bad_hash);
+        let mut bad_hash = Hash256::ZERO;
+        hash(&data_map, &mut bad_hash.0);
         let new_block = Block {
             timestamp,
-            last_hash: last_hash,
-            hash: bad_hash.clone(),
+            last_hash,
+            hash: bad_hash,
             data: data.clone(),
             nonce,
             difficulty,
+            state_root: None,
+            seal: None,
+            evidence: Vec::new(),
         };
         assert!(!Block::is_valid_block(
             &new_block,
@@ -355,3 +473,111 @@ mod is_valid_block {
         ));
     }
 }
+
+mod is_valid_block_with {
+    use super::*;
+    use crypto::hasher::HashAlgorithm;
+
+    #[test]
+    fn checks_hash_with_the_hasher_that_mined_the_block() {
+        let last_block = Block::genesis();
+        for mined_with in HashAlgorithm::ALL.iter() {
+            let block = Block::mine_block_with(
+                mined_with.hasher(),
+                &last_block,
+                String::from("dummy data"),
+                None,
+            );
+            for checked_with in HashAlgorithm::ALL.iter() {
+                let valid = Block::is_valid_block_with(
+                    checked_with.hasher(),
+                    &block,
+                    &last_block.hash,
+                    last_block.difficulty,
+                );
+                assert_eq!(valid, mined_with == checked_with);
+            }
+        }
+    }
+
+    #[test]
+    fn sha256_is_the_default() {
+        let last_block = Block::genesis();
+        let block = Block::mine_block(&last_block, String::from("dummy data"));
+        let hasher = HashAlgorithm::Sha256.hasher();
+        assert!(Block::is_valid_block_with(
+            hasher,
+            &block,
+            &last_block.hash,
+            last_block.difficulty
+        ));
+    }
+}
+
+mod is_valid_header {
+    use super::*;
+
+    #[test]
+    fn true_for_header_of_valid_block() {
+        let last_block = Block::mine_block(&Block::genesis(), String::from("dummy data 1"));
+        let new_block = Block::mine_block(&last_block, String::from("dummy data 2"));
+        assert!(Block::is_valid_header(
+            &new_block.header(),
+            &last_block.hash,
+            last_block.difficulty
+        ));
+    }
+
+    #[test]
+    fn false_if_header_does_not_link_or_meet_its_difficulty() {
+        let last_block = Block::mine_block(&Block::genesis(), String::from("dummy data 1"));
+        let new_block = Block::mine_block(&last_block, String::from("dummy data 2"));
+        let header = new_block.header();
+        assert!(!Block::is_valid_header(&header, &Hash256([13; 32]), last_block.difficulty));
+        assert!(!Block::is_valid_header(&header, &last_block.hash, Difficulty(20)));
+
+        let mut forged = header.clone();
+        forged.difficulty = Difficulty(255);
+        assert!(!Block::is_valid_header(&forged, &Hash256::ZERO, Difficulty(255)));
+    }
+
+    #[test]
+    fn false_if_hash_does_not_cover_the_header() {
+        let last_block = Block::mine_block(&Block::genesis(), String::from("dummy data 1"));
+        let new_block = Block::mine_block(&last_block, String::from("dummy data 2"));
+        let valid = |header: &BlockHeader| {
+            return Block::is_valid_header(header, &last_block.hash, last_block.difficulty);
+        };
+
+        let mut zero_hash = new_block.header();
+        zero_hash.hash = Hash256::ZERO;
+        assert!(!valid(&zero_hash));
+        let mut other_body = new_block.header();
+        other_body.data_root = Block::compute_data_root("forged data", &[]);
+        assert!(!valid(&other_body));
+        let mut retimed = new_block.header();
+        retimed.timestamp += Duration::from_millis(1);
+        assert!(!valid(&retimed));
+        assert!(valid(&new_block.header()));
+    }
+}
+
+mod serialization {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_json() {
+        let block = Block::mine_block(&Block::genesis(), String::from("some data"));
+        let encoded = serde_json::to_string(&block).unwrap();
+        let decoded: Block = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(decoded, block);
+    }
+
+    #[test]
+    fn encodes_hashes_as_hex() {
+        let block = Block::genesis();
+        let encoded = serde_json::to_value(&block).unwrap();
+        assert_eq!(encoded["hash"], "ff".repeat(32));
+        assert_eq!(encoded["last_hash"], "00".repeat(32));
+    }
+}
@@ -2,9 +2,14 @@ use crypto::cryptohash;
 
 use crate::{
     block::Block,
-    blockchain::Blockchain
+    blockchain::Blockchain,
+    clock::{Clock, MockClock},
+    config::MINE_RATE,
+    types::{BlockHeight, Difficulty, Hash256, Nonce},
 };
 
+use std::{sync::Arc, time::Duration};
+
 mod blockchain_struct_data {
     use super::*;
     #[test]
@@ -14,6 +19,30 @@ mod blockchain_struct_data {
     }
 }
 
+mod set_clock {
+    use super::*;
+
+    #[test]
+    fn mines_blocks_stamped_by_the_clock() {
+        let clock = Arc::new(MockClock::at_millis(1_700_000_000_000));
+        let mut blockchain = Blockchain::new();
+        blockchain.set_clock(clock.clone()).unwrap();
+        blockchain.add_block(String::from("Raccoons are cool")).unwrap();
+        assert_eq!(blockchain.last_block().timestamp, clock.now());
+        assert_eq!(blockchain.last_block().difficulty, Difficulty(7));
+
+        clock.advance(Duration::from_millis(MINE_RATE + 1));
+        blockchain.add_block(String::from("Skunks smell bad")).unwrap();
+        assert_eq!(blockchain.last_block().timestamp, clock.now());
+        assert_eq!(blockchain.last_block().difficulty, Difficulty(6));
+
+        clock.advance(Duration::from_millis(MINE_RATE - 1));
+        blockchain.add_block(String::from("Bears are big")).unwrap();
+        assert_eq!(blockchain.last_block().difficulty, Difficulty(7));
+        assert!(Blockchain::is_valid_chain(&blockchain.chain));
+    }
+}
+
 mod add_block {
     use super::*;
     #[test]
@@ -21,7 +50,7 @@ mod add_block {
         let mut blockchain = Blockchain::new();
         let initial_length = blockchain.chain.len();
         let new_block_data = String::from("some important data");
-        blockchain.add_block(new_block_data.clone());
+        blockchain.add_block(new_block_data.clone()).unwrap();
         assert_eq!(blockchain.chain.len(), initial_length + 1);
     }
 
@@ -29,7 +58,7 @@ mod add_block {
     fn new_block_is_valid() {
         let mut blockchain = Blockchain::new();
         let new_block_data = String::from("some important data");
-        blockchain.add_block(new_block_data.clone());
+        blockchain.add_block(new_block_data.clone()).unwrap();
         let len = blockchain.chain.len();
         assert!(Block::is_valid_block(
             &blockchain.chain[len - 1],
@@ -44,9 +73,9 @@ mod is_valid_chain {
 
     fn setup() -> Blockchain {
         let mut blockchain = Blockchain::new();
-        blockchain.add_block(String::from("Raccoons are cool"));
-        blockchain.add_block(String::from("Skunks smell bad"));
-        blockchain.add_block(String::from("Bears are big"));
+        blockchain.add_block(String::from("Raccoons are cool")).unwrap();
+        blockchain.add_block(String::from("Skunks smell bad")).unwrap();
+        blockchain.add_block(String::from("Bears are big")).unwrap();
         return blockchain;
     }
 
@@ -60,28 +89,31 @@ mod is_valid_chain {
     #[test]
     fn false_if_a_last_hash_reference_has_changed() {
         let mut blockchain = setup();
-        blockchain.chain[2].last_hash = [13; 32];
+        blockchain.chain[2].last_hash = Hash256([13; 32]);
         assert!(!Blockchain::is_valid_chain(&blockchain.chain));
     }
 
     #[test]
     fn false_if_chain_contains_block_with_jumped_difficulty() {
         let mut blockchain = setup();
-        let timestamp = std::time::SystemTime::now();
+        let timestamp = blockchain.chain[blockchain.chain.len() - 1].timestamp;
         let data = String::new();
-        let last_hash = blockchain.chain[blockchain.chain.len() - 1].hash.clone();
+        let last_hash = blockchain.chain[blockchain.chain.len() - 1].hash;
         let nonce = 0;
         let difficulty = blockchain.chain[blockchain.chain.len() - 1].difficulty + 3;
-        let mut data_map = Block::get_data_map(&timestamp, &last_hash, &data, nonce, difficulty); 
+        let data_map = Block::get_data_map(&timestamp, &last_hash, &data, Nonce(nonce), difficulty); 
         let mut hash: [u8; 32] = [13; 32];
         cryptohash::hash(&data_map, &mut hash);
         blockchain.chain.push(Block {
             timestamp,
             last_hash,
-            hash,
+            hash: Hash256(hash),
             data,
-            nonce,
+            nonce: Nonce(nonce),
             difficulty,
+            state_root: None,
+            seal: None,
+            evidence: Vec::new(),
         });
         assert!(!Blockchain::is_valid_chain(&blockchain.chain));
     }
@@ -96,21 +128,24 @@ mod is_valid_chain {
     #[test]
     fn false_if_chain_contains_block_with_difficulty_constraint_violated() {
         let mut blockchain = setup();
-        let timestamp = std::time::SystemTime::now();
+        let timestamp = blockchain.chain[blockchain.chain.len() - 1].timestamp;
         let data = String::new();
-        let last_hash = blockchain.chain[blockchain.chain.len() - 1].hash.clone();
+        let last_hash = blockchain.chain[blockchain.chain.len() - 1].hash;
         let nonce = 0;
         let difficulty = blockchain.chain[blockchain.chain.len() - 1].difficulty + 1;
-        let data_map = Block::get_data_map(&timestamp, &last_hash, &data, nonce, difficulty);
+        let data_map = Block::get_data_map(&timestamp, &last_hash, &data, Nonce(nonce), difficulty);
         let mut hash: [u8; 32] = [0; 32];
         cryptohash::hash(&data_map, &mut hash);
         blockchain.chain.push(Block {
             timestamp,
             last_hash,
-            hash,
+            hash: Hash256(hash),
             data,
-            nonce,
+            nonce: Nonce(nonce),
             difficulty,
+            state_root: None,
+            seal: None,
+            evidence: Vec::new(),
         });
         assert!(!Blockchain::is_valid_chain(&blockchain.chain));
     }
@@ -118,19 +153,90 @@ mod is_valid_chain {
     #[test]
     fn true_if_chain_contains_only_valid_block() {
         let mut blockchain = setup();
-        blockchain.add_block(String::from("This chain is valid!"));
+        blockchain.add_block(String::from("This chain is valid!")).unwrap();
         assert!(Blockchain::is_valid_chain(&blockchain.chain));
     }
 }
 
+mod first_invalid_block {
+    use super::*;
+    use crate::config::PARALLEL_VALIDATION_MIN;
+
+    // Slow blocks keep the difficulty, and so the mining time, low.
+    fn setup(length: usize) -> Blockchain {
+        let clock = Arc::new(MockClock::at_millis(1_700_000_000_000));
+        let mut blockchain = Blockchain::new();
+        blockchain.set_clock(clock.clone()).unwrap();
+        while blockchain.chain.len() < length {
+            clock.advance(Duration::from_millis(MINE_RATE + 1));
+            blockchain.add_block(format!("Block {}", blockchain.chain.len())).unwrap();
+        }
+        return blockchain;
+    }
+
+    fn both_ways(chain: &[Block]) -> Option<usize> {
+        let parallel = Blockchain::first_invalid_block_parallel(chain);
+        assert_eq!(Blockchain::first_invalid_block(chain), parallel);
+        return parallel;
+    }
+
+    #[test]
+    fn none_for_a_valid_chain() {
+        let blockchain = setup(6);
+        assert_eq!(both_ways(&blockchain.chain), None);
+    }
+
+    #[test]
+    fn zero_without_genesis() {
+        let blockchain = setup(3);
+        assert_eq!(both_ways(&blockchain.chain[1..]), Some(0));
+        assert_eq!(both_ways(&[]), Some(0));
+    }
+
+    #[test]
+    fn reports_the_earliest_bad_block() {
+        let mut blockchain = setup(8);
+        blockchain.chain[6].data = String::from("Owls are loud");
+        blockchain.chain[3].last_hash = Hash256([13; 32]);
+        assert_eq!(both_ways(&blockchain.chain), Some(3));
+    }
+
+    #[test]
+    fn applies_the_ledger_before_a_later_bad_proof() {
+        let mut blockchain = setup(4);
+        let block = Block::mine_block_with_state(
+            blockchain.last_block(),
+            String::from("Foxes"),
+            Some(Hash256([9; 32])),
+        );
+        let next = Block::mine_block(&block, String::from("Badgers"));
+        blockchain.chain.push(block);
+        blockchain.chain.push(next.clone());
+        blockchain.chain.push(next);
+        assert_eq!(both_ways(&blockchain.chain), Some(4));
+    }
+
+    #[test]
+    fn validates_long_chains_in_parallel() {
+        let mut blockchain = setup(PARALLEL_VALIDATION_MIN + 1);
+        assert!(Blockchain::is_valid_chain(&blockchain.chain));
+        blockchain.chain[PARALLEL_VALIDATION_MIN - 1].data = String::from("Moles dig");
+        assert_eq!(
+            Blockchain::first_invalid_block(&blockchain.chain),
+            Some(PARALLEL_VALIDATION_MIN - 1)
+        );
+    }
+}
+
 mod replace_chain {
-    use super::Blockchain;
+    use super::{BlockHeight, Blockchain, Hash256};
+    use crate::params::{ChainParams, Checkpoint};
 
     fn setup() -> Blockchain {
         let mut blockchain = Blockchain::new();
-        blockchain.add_block(String::from("Raccoons are cool"));
-        blockchain.add_block(String::from("Skunks smell bad"));
-        blockchain.add_block(String::from("Bears are big"));
+        blockchain.add_block(String::from("Raccoons are cool")).unwrap();
+        blockchain.add_block(String::from("Skunks smell bad")).unwrap();
+        blockchain.add_block(String::from("Bears are big")).unwrap();
         return blockchain;
     }
 
@@ -139,7 +245,7 @@ mod replace_chain {
         let mut blockchain = setup();
         let new_blockchain = Blockchain::new();
         let original_chain = blockchain.chain.clone();
-        blockchain.replace_chain(new_blockchain.chain);
+        assert!(blockchain.replace_chain(new_blockchain.chain).is_err());
         assert_eq!(blockchain.chain, original_chain);
     }
 
@@ -149,7 +255,10 @@ mod replace_chain {
         let mut new_blockchain = setup();
         new_blockchain.chain[2].data = String::from("Skunks smell good");
         let original_chain = blockchain.chain.clone();
-        blockchain.replace_chain(new_blockchain.chain);
+        assert_eq!(
+            blockchain.replace_chain(new_blockchain.chain),
+            Err(String::from("new chain is invalid at height 2"))
+        );
         assert_eq!(blockchain.chain, original_chain);
     }
 
@@ -158,7 +267,444 @@ mod replace_chain {
         let mut blockchain = Blockchain::new();
         let new_blockchain = setup();
         let original_chain = blockchain.chain.clone();
-        blockchain.replace_chain(new_blockchain.chain);
+        blockchain.replace_chain(new_blockchain.chain).unwrap();
         assert_ne!(blockchain.chain, original_chain);
     }
+
+    fn fork_of(blockchain: &Blockchain, shared: usize, length: usize) -> Vec<super::Block> {
+        let mut fork = Blockchain::from_chain(blockchain.chain[..shared].to_vec());
+        while fork.chain.len() < length {
+            fork.add_block(String::from("Owls are wise")).unwrap();
+        }
+        return fork.chain;
+    }
+
+    #[test]
+    fn refuses_reorg_deeper_than_the_maximum() {
+        let mut blockchain = setup();
+        blockchain.set_params(ChainParams {
+            max_reorg_depth: Some(2),
+            ..ChainParams::default()
+        }).unwrap();
+        let original_chain = blockchain.chain.clone();
+        let deep = fork_of(&blockchain, 1, 6);
+        let error = blockchain.replace_chain(deep).unwrap_err();
+        assert!(error.contains("reorg of 3 blocks exceeds the maximum depth of 2"));
+        assert_eq!(blockchain.chain, original_chain);
+
+        let shallow = fork_of(&blockchain, 2, 6);
+        blockchain.replace_chain(shallow.clone()).unwrap();
+        assert_eq!(blockchain.chain, shallow);
+    }
+
+    #[test]
+    fn refuses_chain_that_conflicts_with_a_checkpoint() {
+        let mut blockchain = setup();
+        let mut params = ChainParams::default();
+        params.checkpoints.push(Checkpoint {
+            height: BlockHeight(2),
+            hash: blockchain.chain[2].hash,
+        });
+        blockchain.set_params(params).unwrap();
+        let conflicting = fork_of(&blockchain, 2, 6);
+        let error = blockchain.replace_chain(conflicting).unwrap_err();
+        assert!(error.contains("conflicts with checkpoint"));
+
+        let extending = fork_of(&blockchain, 3, 6);
+        blockchain.replace_chain(extending).unwrap();
+        assert_eq!(blockchain.chain.len(), 6);
+    }
+
+    #[test]
+    fn submit_block_rejects_block_that_conflicts_with_a_checkpoint() {
+        let mut blockchain = setup();
+        let mut params = ChainParams::default();
+        params.checkpoints.push(Checkpoint {
+            height: BlockHeight(4),
+            hash: Hash256([1; 32]),
+        });
+        blockchain.set_params(params).unwrap();
+        let block = super::Block::mine_block(blockchain.last_block(), String::from("Foxes"));
+        assert!(!blockchain.submit_block(block));
+    }
+}
+
+mod submit_block {
+    use super::*;
+
+    #[test]
+    fn appends_block_mined_on_top_of_the_chain() {
+        let mut blockchain = Blockchain::new();
+        let new_block = Block::mine_block(blockchain.last_block(), String::from("submitted"));
+        assert!(blockchain.submit_block(new_block.clone()));
+        assert_eq!(blockchain.chain.len(), 2);
+        assert_eq!(blockchain.last_block(), &new_block);
+    }
+
+    #[test]
+    fn rejects_block_that_does_not_extend_the_tip() {
+        let mut blockchain = Blockchain::new();
+        let stale_block = Block::mine_block(&Block::genesis(), String::from("stale"));
+        blockchain.add_block(String::from("tip")).unwrap();
+        assert!(!blockchain.submit_block(stale_block));
+        assert_eq!(blockchain.chain.len(), 2);
+    }
+
+    #[test]
+    fn rejects_tampered_block() {
+        let mut blockchain = Blockchain::new();
+        let mut new_block = Block::mine_block(blockchain.last_block(), String::from("submitted"));
+        new_block.data = String::from("tampered");
+        assert!(!blockchain.submit_block(new_block));
+        assert_eq!(blockchain.chain.len(), 1);
+    }
+}
+
+mod find_block {
+    use super::*;
+
+    #[test]
+    fn returns_height_and_block_for_known_hash() {
+        let mut blockchain = Blockchain::new();
+        blockchain.add_block(String::from("one")).unwrap();
+        blockchain.add_block(String::from("two")).unwrap();
+        let hash = blockchain.chain[1].hash;
+        let (height, block) = blockchain.find_block(&hash).unwrap();
+        assert_eq!(height, BlockHeight(1));
+        assert_eq!(block, &blockchain.chain[1]);
+    }
+
+    #[test]
+    fn returns_none_for_unknown_hash() {
+        let blockchain = Blockchain::new();
+        assert!(blockchain.find_block(&Hash256([13; 32])).is_none());
+    }
+}
+
+mod events {
+    use super::*;
+    use crate::events::ChainEvent;
+
+    fn build_chain(data: &[&str]) -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        for item in data {
+            blockchain.add_block(String::from(*item)).unwrap();
+        }
+        return blockchain;
+    }
+
+    #[test]
+    fn add_block_publishes_new_block_and_new_tip() {
+        let mut blockchain = Blockchain::new();
+        let receiver = blockchain.events().subscribe();
+        blockchain.add_block(String::from("Raccoons are cool")).unwrap();
+        let block = blockchain.chain[1].clone();
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<ChainEvent>>(),
+            vec![
+                ChainEvent::NewBlock { height: BlockHeight(1), block: block.clone() },
+                ChainEvent::NewTip { height: BlockHeight(1), hash: block.hash },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejected_submission_publishes_nothing() {
+        let mut blockchain = Blockchain::new();
+        let receiver = blockchain.events().subscribe();
+        let mut block = Block::mine_block(blockchain.last_block(), String::from("data"));
+        block.nonce = Nonce(block.nonce.0 + 1);
+        assert!(!blockchain.submit_block(block));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn replace_chain_publishes_new_blocks_when_extending_the_tip() {
+        let mut blockchain = build_chain(&["Raccoons are cool"]);
+        let mut longer = Blockchain::new();
+        longer.chain = blockchain.chain.clone();
+        longer.add_block(String::from("Skunks smell bad")).unwrap();
+        longer.add_block(String::from("Bears are big")).unwrap();
+        let receiver = blockchain.events().subscribe();
+        blockchain.replace_chain(longer.chain.clone()).unwrap();
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<ChainEvent>>(),
+            vec![
+                ChainEvent::NewBlock { height: BlockHeight(2), block: longer.chain[2].clone() },
+                ChainEvent::NewBlock { height: BlockHeight(3), block: longer.chain[3].clone() },
+                ChainEvent::NewTip { height: BlockHeight(3), hash: longer.chain[3].hash },
+            ]
+        );
+    }
+
+    #[test]
+    fn replace_chain_publishes_reorg_with_disconnected_and_connected_blocks() {
+        let mut blockchain = build_chain(&["Raccoons are cool", "Skunks smell bad"]);
+        let fork = build_chain(&["Bears are big", "Owls are wise", "Foxes are quick"]);
+        let original = blockchain.chain.clone();
+        let receiver = blockchain.events().subscribe();
+        blockchain.replace_chain(fork.chain.clone()).unwrap();
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<ChainEvent>>(),
+            vec![
+                ChainEvent::Reorg {
+                    fork_height: BlockHeight(1),
+                    disconnected: original[1..].to_vec(),
+                    connected: fork.chain[1..].to_vec(),
+                },
+                ChainEvent::NewTip { height: BlockHeight(3), hash: fork.chain[3].hash },
+            ]
+        );
+    }
+
+    #[test]
+    fn refused_replacement_publishes_nothing() {
+        let mut blockchain = build_chain(&["Raccoons are cool", "Skunks smell bad"]);
+        let receiver = blockchain.events().subscribe();
+        assert!(blockchain.replace_chain(Blockchain::new().chain).is_err());
+        assert!(receiver.try_recv().is_err());
+    }
+}
+
+mod transactions {
+    use super::*;
+    use crate::{
+        config::MINING_REWARD,
+        events::ChainEvent,
+        ledger::Ledger,
+        transaction::{OutPoint, Transaction, TxOutput},
+    };
+    use crypto::keys::KeyPair;
+
+    fn funded(key_pair: &KeyPair) -> (Blockchain, OutPoint) {
+        let mut blockchain = Blockchain::new();
+        blockchain.mine_transactions(&key_pair.address()).unwrap();
+        let (outpoint, _) = blockchain.ledger().unwrap().unspent(&key_pair.address())[0].clone();
+        return (blockchain, outpoint);
+    }
+
+    fn payment(outpoint: OutPoint, key_pair: &KeyPair, to: &str, amount: u64) -> Transaction {
+        let outputs = vec![TxOutput {
+            amount,
+            address: String::from(to),
+        }];
+        return Transaction::new_signed(&[(outpoint, key_pair)], outputs, 0);
+    }
+
+    #[test]
+    fn submit_transaction_adds_to_mempool_and_publishes_event() {
+        let key_pair = KeyPair::generate();
+        let (mut blockchain, outpoint) = funded(&key_pair);
+        let receiver = blockchain.events().subscribe();
+        let transaction = payment(outpoint, &key_pair, &"cd".repeat(32), 50);
+        assert_eq!(
+            blockchain.submit_transaction(transaction.clone()),
+            Ok(transaction.txid())
+        );
+        assert_eq!(blockchain.mempool().len(), 1);
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            ChainEvent::NewTransaction { transaction }
+        );
+    }
+
+    #[test]
+    fn mine_transactions_confirms_mempool_and_pays_fees() {
+        let key_pair = KeyPair::generate();
+        let miner = KeyPair::generate();
+        let (mut blockchain, outpoint) = funded(&key_pair);
+        let recipient = "cd".repeat(32);
+        blockchain
+            .submit_transaction(payment(outpoint, &key_pair, &recipient, 45))
+            .unwrap();
+        blockchain.mine_transactions(&miner.address()).unwrap();
+        let ledger = blockchain.ledger().unwrap();
+        assert!(blockchain.mempool().is_empty());
+        assert_eq!(ledger.balance(&recipient), 45);
+        assert_eq!(ledger.balance(&key_pair.address()), 0);
+        assert_eq!(ledger.balance(&miner.address()), MINING_REWARD + 5);
+        assert!(Blockchain::is_valid_chain(&blockchain.chain));
+    }
+
+    #[test]
+    fn is_valid_chain_rejects_invalid_transaction_payload() {
+        let key_pair = KeyPair::generate();
+        let (mut blockchain, outpoint) = funded(&key_pair);
+        let thief = KeyPair::generate();
+        let theft = payment(outpoint, &thief, &thief.address(), 50);
+        blockchain.add_block(Transaction::encode_payload(&[theft])).unwrap();
+        assert!(!Blockchain::is_valid_chain(&blockchain.chain));
+    }
+
+    #[test]
+    fn submit_block_rejects_invalid_transaction_payload() {
+        let mut blockchain = Blockchain::new();
+        let coinbase = Transaction::coinbase(BlockHeight(1), &"ab".repeat(32), MINING_REWARD * 2);
+        let block = Block::mine_block(
+            blockchain.last_block(),
+            Transaction::encode_payload(&[coinbase]),
+        );
+        assert!(!blockchain.submit_block(block));
+    }
+
+    #[test]
+    fn reorg_returns_disconnected_transactions_to_mempool() {
+        let key_pair = KeyPair::generate();
+        let (mut blockchain, outpoint) = funded(&key_pair);
+        let mut fork = Blockchain::from_chain(blockchain.chain.clone());
+        let transaction = payment(outpoint, &key_pair, &"cd".repeat(32), 50);
+        blockchain.submit_transaction(transaction.clone()).unwrap();
+        blockchain.mine_transactions(&"ab".repeat(32)).unwrap();
+        assert!(blockchain.mempool().is_empty());
+
+        fork.add_block(String::from("Bears are big")).unwrap();
+        fork.add_block(String::from("Owls are wise")).unwrap();
+        fork.add_block(String::from("Foxes are quick")).unwrap();
+        blockchain.replace_chain(fork.chain).unwrap();
+        assert_eq!(blockchain.mempool().transactions(), &[transaction]);
+    }
+
+    #[test]
+    fn keeps_the_tip_ledger_in_step_with_the_chain() {
+        let key_pair = KeyPair::generate();
+        let (mut blockchain, outpoint) = funded(&key_pair);
+        let mut fork = Blockchain::from_chain(blockchain.chain.clone());
+        let transaction = payment(outpoint, &key_pair, &"cd".repeat(32), 50);
+        blockchain.submit_transaction(transaction).unwrap();
+        blockchain.mine_transactions(&"ab".repeat(32)).unwrap();
+        let block = Block::mine_block(blockchain.last_block(), String::from("Bears are big"));
+        assert!(blockchain.submit_block(block));
+        assert_eq!(blockchain.ledger(), Ledger::from_chain(&blockchain.chain));
+
+        for _ in 0..3 {
+            fork.mine_transactions(&"ef".repeat(32)).unwrap();
+        }
+        blockchain.replace_chain(fork.chain.clone()).unwrap();
+        assert_eq!(blockchain.ledger(), fork.ledger());
+        assert_eq!(blockchain.ledger(), Ledger::from_chain(&fork.chain));
+    }
+
+    #[test]
+    fn find_transaction_and_address_transactions_use_the_index() {
+        let key_pair = KeyPair::generate();
+        let (mut blockchain, outpoint) = funded(&key_pair);
+        let recipient = "cd".repeat(32);
+        let transaction = payment(outpoint, &key_pair, &recipient, 50);
+        blockchain.submit_transaction(transaction.clone()).unwrap();
+        assert_eq!(blockchain.find_transaction(&transaction.txid()), None);
+        blockchain.mine_transactions(&"ab".repeat(32)).unwrap();
+
+        let (location, found) = blockchain.find_transaction(&transaction.txid()).unwrap();
+        assert_eq!((location.height, location.position), (BlockHeight(2), 1));
+        assert_eq!(found, transaction);
+        let history = blockchain.address_transactions(&key_pair.address());
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].0.height, BlockHeight(1));
+        assert_eq!(history[1].1, transaction);
+        assert_eq!(
+            blockchain.address_transactions(&recipient),
+            vec![(location, transaction)]
+        );
+        assert_eq!(blockchain.block_at(BlockHeight(2)), Some(blockchain.last_block()));
+        assert_eq!(blockchain.block_at(BlockHeight(3)), None);
+    }
+}
+
+mod received_by_address {
+    use super::*;
+    use crate::config::MINING_REWARD;
+    use crypto::hd::{self, discover_accounts, DerivationPath, ExtendedKey};
+
+    #[test]
+    fn sums_outputs_and_drives_hd_account_discovery() {
+        let seed = hd::mnemonic_to_seed(&hd::generate_mnemonic(12).unwrap(), "").unwrap();
+        let master = ExtendedKey::master(&seed);
+        let path = DerivationPath::for_address(0, 1);
+        let address = master.derive_path(&path).unwrap().key_pair().address();
+        let mut blockchain = Blockchain::new();
+        blockchain.mine_transactions(&address).unwrap();
+        blockchain.mine_transactions(&address).unwrap();
+        assert_eq!(blockchain.received_by_address(&address), MINING_REWARD * 2);
+
+        let accounts = discover_accounts(&master, 5, |address| {
+            blockchain.received_by_address(address) > 0
+        });
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].used[0].path, path);
+        assert_eq!(accounts[0].next_index, 2);
+    }
+}
+
+mod prune {
+    use super::*;
+
+    fn mined(blocks: usize) -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        for _ in 0..blocks {
+            blockchain.mine_transactions(&"ab".repeat(32)).unwrap();
+        }
+        return blockchain;
+    }
+
+    #[test]
+    fn drops_bodies_below_the_depth_and_keeps_headers() {
+        let mut blockchain = mined(4);
+        let chain = blockchain.chain.clone();
+        let ledger = blockchain.ledger().unwrap();
+        assert_eq!(blockchain.prune(1).unwrap(), Some(BlockHeight(3)));
+        assert_eq!(blockchain.base_height(), BlockHeight(3));
+        assert_eq!(blockchain.height(), BlockHeight(4));
+        assert_eq!(blockchain.chain.len(), 2);
+        assert_eq!(blockchain.pruned_headers().len(), 3);
+        assert_eq!(blockchain.ledger().unwrap(), ledger);
+
+        assert!(blockchain.block_at(BlockHeight(1)).is_none());
+        assert!(blockchain.is_pruned(BlockHeight(1)));
+        assert!(!blockchain.is_pruned(BlockHeight(3)));
+        for (height, block) in chain.iter().enumerate() {
+            assert_eq!(blockchain.header_at(BlockHeight::from(height)), Some(block.header()));
+        }
+        assert_eq!(
+            blockchain.find_header(&chain[1].hash),
+            Some((BlockHeight(1), chain[1].header()))
+        );
+        assert!(blockchain.find_block(&chain[1].hash).is_none());
+    }
+
+    #[test]
+    fn does_nothing_when_the_chain_is_not_deeper_than_the_depth() {
+        let mut blockchain = mined(2);
+        assert_eq!(blockchain.prune(2).unwrap(), None);
+        assert_eq!(blockchain.prune(5).unwrap(), None);
+        assert_eq!(blockchain.base_height(), BlockHeight(0));
+        assert!(blockchain.pruned_headers().is_empty());
+    }
+
+    #[test]
+    fn prunes_again_and_keeps_accepting_blocks() {
+        let mut blockchain = mined(3);
+        assert_eq!(blockchain.prune(2).unwrap(), Some(BlockHeight(1)));
+        blockchain.mine_transactions(&"cd".repeat(32)).unwrap();
+        assert_eq!(blockchain.prune(1).unwrap(), Some(BlockHeight(3)));
+        assert_eq!(blockchain.pruned_headers().len(), 3);
+        let mut headers = blockchain.pruned_headers().to_vec();
+        headers.push(blockchain.chain[0].header());
+        assert!(Blockchain::is_valid_header_chain(&headers));
+        assert!(blockchain.find_block(&blockchain.last_block().hash).is_some());
+        assert_eq!(blockchain.height(), BlockHeight(4));
+    }
+
+    #[test]
+    fn set_pruned_headers_rejects_headers_that_do_not_chain() {
+        let mut blockchain = mined(3);
+        blockchain.prune(1).unwrap();
+        let headers = blockchain.pruned_headers().to_vec();
+        assert!(blockchain.set_pruned_headers(headers[..1].to_vec()).is_err());
+
+        let mut tampered = headers.clone();
+        tampered[1].nonce = Nonce(tampered[1].nonce.0 + 1);
+        tampered[1].hash = Hash256([0; 32]);
+        assert!(blockchain.set_pruned_headers(tampered).is_err());
+        assert!(blockchain.set_pruned_headers(headers).is_ok());
+    }
 }
@@ -0,0 +1,56 @@
+use crate::{clock::*, types::Nonce};
+
+use std::time::{Duration, SystemTime};
+
+mod mock_clock {
+    use super::*;
+
+    #[test]
+    fn moves_only_when_told_to() {
+        let clock = MockClock::at_millis(1_000);
+        let start = SystemTime::UNIX_EPOCH + Duration::from_millis(1_000);
+        assert_eq!(clock.now(), start);
+        assert_eq!(clock.now(), start);
+        clock.advance(Duration::from_millis(250));
+        assert_eq!(clock.now(), start + Duration::from_millis(250));
+        clock.set(SystemTime::UNIX_EPOCH);
+        assert_eq!(clock.now(), SystemTime::UNIX_EPOCH);
+    }
+
+    #[test]
+    fn sleeps_by_jumping_to_the_deadline() {
+        let clock = MockClock::at_millis(1_000);
+        let deadline = SystemTime::UNIX_EPOCH + Duration::from_secs(3_600);
+        clock.sleep_until(deadline);
+        assert_eq!(clock.now(), deadline);
+        clock.sleep_until(SystemTime::UNIX_EPOCH);
+        assert_eq!(clock.now(), deadline);
+    }
+}
+
+mod system_clock {
+    use super::*;
+
+    #[test]
+    fn returns_at_once_from_a_past_deadline() {
+        let before = SystemTime::now();
+        SystemClock.sleep_until(SystemTime::UNIX_EPOCH);
+        assert!(SystemClock.now() >= before);
+    }
+}
+
+mod sequential_nonces {
+    use super::*;
+
+    #[test]
+    fn counts_up_from_the_start() {
+        let mut nonces = SequentialNonces::default();
+        assert_eq!(
+            (0..3).map(|_| nonces.next_nonce()).collect::<Vec<_>>(),
+            vec![Nonce(1), Nonce(2), Nonce(3)]
+        );
+        let mut nonces = SequentialNonces::starting_at(Nonce(u64::MAX));
+        assert_eq!(nonces.next_nonce(), Nonce(u64::MAX));
+        assert_eq!(nonces.next_nonce(), Nonce(0));
+    }
+}
@@ -0,0 +1,720 @@
+use crate::{
+    block::Block,
+    blockchain::Blockchain,
+    clock::{Clock, MockClock},
+    config::PARALLEL_VALIDATION_MIN,
+    consensus::*,
+    index::ChainIndex,
+    ledger::Ledger,
+    params::{ChainParams, Consensus},
+    sled_store::SledStore,
+    store::ChainStore,
+    transaction::{OutPoint, Transaction, TxOutput},
+    types::{BlockHeight, Hash256, Nonce},
+};
+
+use crypto::{
+    hasher::{HashAlgorithm, Sha256Hasher},
+    keys::KeyPair,
+};
+use std::{sync::Arc, time::Duration};
+use tempfile::tempdir;
+
+const SLOT_MS: u64 = 5;
+const START_MS: u64 = 1_700_000_000_000;
+
+fn stake_params(validators: &[&KeyPair]) -> StakeParams {
+    return StakeParams {
+        slot_ms: SLOT_MS,
+        min_stake: 50,
+        bootstrap_validators: validators.iter().map(|key| key.address()).collect(),
+    };
+}
+
+fn engine(validators: &[&KeyPair]) -> ProofOfStakeEngine {
+    return engine_at(validators, Arc::new(MockClock::at_millis(START_MS)));
+}
+
+fn engine_at(validators: &[&KeyPair], clock: Arc<MockClock>) -> ProofOfStakeEngine {
+    return ProofOfStakeEngine::with_clock(
+        Box::new(HashAlgorithm::Sha256),
+        stake_params(validators),
+        clock,
+    );
+}
+
+fn staked_chain(validators: &[&KeyPair], signer: &KeyPair) -> Blockchain {
+    let mut blockchain = Blockchain::new();
+    blockchain
+        .set_params(ChainParams {
+            consensus: Consensus::ProofOfStake(stake_params(validators)),
+            ..ChainParams::default()
+        })
+        .unwrap();
+    blockchain.set_validator_key(KeyPair::from_secret_bytes(&signer.secret_bytes()).unwrap());
+    return blockchain;
+}
+
+// Waits on `clock` for the slot `signer` leads, as a node does before
+// producing a block.
+fn produce_in_slot(
+    engine: &dyn ConsensusEngine,
+    clock: &dyn Clock,
+    last_block: &Block,
+    ledger: &Ledger,
+    contents: BlockContents,
+    signer: &KeyPair,
+) -> Result<Block, String> {
+    clock.sleep_until(engine.next_block_time(last_block, ledger, Some(signer))?);
+    return engine.produce_block(last_block, ledger, contents, Some(signer));
+}
+
+fn contents(data: &str) -> BlockContents {
+    return BlockContents {
+        data: String::from(data),
+        state_root: None,
+        evidence: Vec::new(),
+    };
+}
+
+// The same block's slot sealed over different data.
+fn resealed(block: &Block, data: &str, signer: &KeyPair) -> Block {
+    let mut block = block.clone();
+    block.data = String::from(data);
+    block.hash = Block::compute_hash(&HashAlgorithm::Sha256, &block);
+    let seal = block.seal.as_mut().unwrap();
+    seal.signature = signer
+        .sign(&BlockSeal::message(seal.slot, &block.hash))
+        .to_vec();
+    return block;
+}
+
+fn staked_ledger(stakes: &[(&KeyPair, u64)]) -> Ledger {
+    return Ledger::from_utxos(stakes.iter().enumerate().map(|(i, (key, amount))| {
+        let outpoint = OutPoint {
+            txid: [i as u8; 32],
+            index: 0,
+        };
+        let output = TxOutput {
+            amount: *amount,
+            address: key.address(),
+        };
+        return (outpoint, output);
+    }));
+}
+
+mod proof_of_work_engine {
+    use super::*;
+
+    #[test]
+    fn produces_blocks_that_validate() {
+        let engine = ProofOfWorkEngine::new(Box::new(Sha256Hasher));
+        let genesis = Block::genesis();
+        let block = engine
+            .produce_block(&genesis, &Ledger::new(), contents("Raccoons"), None)
+            .unwrap();
+        assert!(block.seal.is_none());
+        assert!(engine
+            .validate_block(&block, &genesis, &Ledger::new())
+            .is_ok());
+        assert!(engine.validate_header(&block.header(), &genesis.header()));
+    }
+
+    #[test]
+    fn rejects_sealed_blocks_and_evidence() {
+        let key_pair = KeyPair::generate();
+        let genesis = Block::genesis();
+        let sealed = engine(&[&key_pair])
+            .produce_block(
+                &genesis,
+                &Ledger::new(),
+                contents("Raccoons"),
+                Some(&key_pair),
+            )
+            .unwrap();
+        let engine = ProofOfWorkEngine::new(Box::new(Sha256Hasher));
+        assert!(engine
+            .validate_block(&sealed, &genesis, &Ledger::new())
+            .is_err());
+        assert!(!engine.validate_header(&sealed.header(), &genesis.header()));
+        let evidence = DoubleSign {
+            first: sealed.header(),
+            second: resealed(&sealed, "Skunks", &key_pair).header(),
+        };
+        let with_evidence = BlockContents {
+            evidence: vec![evidence],
+            ..contents("Raccoons")
+        };
+        assert!(engine
+            .produce_block(&genesis, &Ledger::new(), with_evidence, None)
+            .is_err());
+    }
+}
+
+mod validators {
+    use super::*;
+
+    #[test]
+    fn fall_back_to_bootstrap_validators_without_stake() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let validators = engine(&[&a, &b]).validators(&staked_ledger(&[(&a, 10)]));
+        let mut expected = vec![(a.address(), 1), (b.address(), 1)];
+        expected.sort();
+        assert_eq!(validators, expected);
+    }
+
+    #[test]
+    fn are_addresses_holding_the_minimum_stake() {
+        let (a, b, c) = (
+            KeyPair::generate(),
+            KeyPair::generate(),
+            KeyPair::generate(),
+        );
+        let ledger = staked_ledger(&[(&a, 50), (&b, 49), (&c, 120)]);
+        let mut expected = vec![(a.address(), 50), (c.address(), 120)];
+        expected.sort();
+        assert_eq!(engine(&[&b]).validators(&ledger), expected);
+    }
+
+    #[test]
+    fn exclude_slashed_validators() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let ledger = staked_ledger(&[(&a, 50), (&b, 50)]).with_slashed(vec![a.address()]);
+        assert_eq!(engine(&[]).validators(&ledger), vec![(b.address(), 50)]);
+        let bootstrap = Ledger::new().with_slashed(vec![a.address()]);
+        assert_eq!(
+            engine(&[&a, &b]).validators(&bootstrap),
+            vec![(b.address(), 1)]
+        );
+    }
+}
+
+mod leader {
+    use super::*;
+
+    #[test]
+    fn is_deterministic_per_parent_and_slot() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let engine = engine(&[&a, &b]);
+        for slot in 0..20 {
+            let leader = engine.leader(&Hash256([1; 32]), slot, &Ledger::new());
+            assert!(leader == Some(a.address()) || leader == Some(b.address()));
+            assert_eq!(
+                engine.leader(&Hash256([1; 32]), slot, &Ledger::new()),
+                leader
+            );
+        }
+    }
+
+    #[test]
+    fn is_weighted_by_stake() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let ledger = staked_ledger(&[(&a, 900), (&b, 100)]);
+        let engine = engine(&[]);
+        let led_by_a = (0..1000)
+            .filter(|slot| engine.leader(&Hash256([2; 32]), *slot, &ledger) == Some(a.address()))
+            .count();
+        assert!(led_by_a > 800 && led_by_a < 980);
+    }
+
+    #[test]
+    fn is_none_without_validators() {
+        assert_eq!(
+            engine(&[]).leader(&Hash256([3; 32]), 1, &Ledger::new()),
+            None
+        );
+    }
+}
+
+mod proof_of_stake_engine {
+    use super::*;
+
+    fn produced(validators: &[&KeyPair], signer: &KeyPair) -> (ProofOfStakeEngine, Block) {
+        let clock = Arc::new(MockClock::at_millis(START_MS));
+        let engine = engine_at(validators, clock.clone());
+        let block = produce_in_slot(
+            &engine,
+            clock.as_ref(),
+            &Block::genesis(),
+            &Ledger::new(),
+            contents("Raccoons"),
+            signer,
+        )
+        .unwrap();
+        return (engine, block);
+    }
+
+    #[test]
+    fn produces_sealed_blocks_in_a_slot_the_signer_leads() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let (engine, block) = produced(&[&a, &b], &a);
+        let seal = block.seal.as_ref().unwrap();
+        assert_eq!(seal.address(), a.address());
+        assert_eq!(block.timestamp, engine.slot_start(seal.slot));
+        assert_eq!(
+            engine.leader(&Block::genesis().hash, seal.slot, &Ledger::new()),
+            Some(a.address())
+        );
+        let genesis = Block::genesis();
+        assert!(engine
+            .validate_block(&block, &genesis, &Ledger::new())
+            .is_ok());
+        assert!(engine.validate_header(&block.header(), &genesis.header()));
+    }
+
+    #[test]
+    fn needs_a_validator_key_in_the_validator_set() {
+        let (a, outsider) = (KeyPair::generate(), KeyPair::generate());
+        let engine = engine(&[&a]);
+        let genesis = Block::genesis();
+        assert!(engine
+            .produce_block(&genesis, &Ledger::new(), contents("Raccoons"), None)
+            .is_err());
+        assert!(engine
+            .produce_block(
+                &genesis,
+                &Ledger::new(),
+                contents("Raccoons"),
+                Some(&outsider)
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_blocks_sealed_by_another_validator() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let (engine, block) = produced(&[&a, &b], &a);
+        let mut forged = block.clone();
+        forged.seal.as_mut().unwrap().validator = b.public_key();
+        let forged = resealed(&forged, &forged.data.clone(), &b);
+        let error = engine
+            .validate_block(&forged, &Block::genesis(), &Ledger::new())
+            .unwrap_err();
+        assert!(error.contains("is not the leader"));
+    }
+
+    #[test]
+    fn rejects_tampered_blocks() {
+        let a = KeyPair::generate();
+        let (engine, block) = produced(&[&a], &a);
+        let genesis = Block::genesis();
+        let mut unsealed = block.clone();
+        unsealed.seal = None;
+        assert!(engine
+            .validate_block(&unsealed, &genesis, &Ledger::new())
+            .is_err());
+        let mut tampered = block.clone();
+        tampered.data = String::from("Skunks");
+        assert!(engine
+            .validate_block(&tampered, &genesis, &Ledger::new())
+            .is_err());
+        let mut bad_signature = block.clone();
+        bad_signature.seal.as_mut().unwrap().signature[0] ^= 1;
+        assert!(engine
+            .validate_block(&bad_signature, &genesis, &Ledger::new())
+            .is_err());
+        assert!(!engine.validate_header(&bad_signature.header(), &genesis.header()));
+        let mut mined = block;
+        mined.nonce = Nonce(1);
+        assert!(engine
+            .validate_block(&mined, &genesis, &Ledger::new())
+            .is_err());
+    }
+
+    #[test]
+    fn checks_seals_before_the_ledger_and_leaders_after() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let (engine, block) = produced(&[&a, &b], &a);
+        let genesis = Block::genesis();
+        assert_eq!(engine.validate_proof(&block, &genesis), Ok(()));
+        let mut bad_signature = block.clone();
+        bad_signature.seal.as_mut().unwrap().signature[0] ^= 1;
+        assert_eq!(
+            engine.validate_proof(&bad_signature, &genesis),
+            Err(String::from("invalid block seal"))
+        );
+
+        let mut forged = block.clone();
+        forged.seal.as_mut().unwrap().validator = b.public_key();
+        let forged = resealed(&forged, &forged.data.clone(), &b);
+        assert_eq!(engine.validate_proof(&forged, &genesis), Ok(()));
+        let error = engine
+            .validate_state(&forged, &genesis, &Ledger::new())
+            .unwrap_err();
+        assert!(error.contains("is not the leader"));
+    }
+
+    #[test]
+    fn reports_the_first_bad_seal_of_a_long_chain() {
+        let a = KeyPair::generate();
+        let mut blockchain = staked_chain(&[&a], &a);
+        while blockchain.chain.len() <= PARALLEL_VALIDATION_MIN {
+            blockchain
+                .in_slot(|blockchain| blockchain.add_block(String::from("Raccoons")))
+                .unwrap();
+        }
+        let mut chain = blockchain.chain.clone();
+        for position in [30, 10].iter() {
+            chain[*position].seal.as_mut().unwrap().signature[0] ^= 1;
+        }
+        let peer = staked_chain(&[&a], &a);
+        assert_eq!(peer.first_invalid_extension(&chain), Some(10));
+        assert_eq!(peer.first_invalid_extension(&chain[..20]), Some(10));
+    }
+
+    #[test]
+    fn rejects_headers_whose_hash_does_not_cover_them() {
+        let a = KeyPair::generate();
+        let (engine, block) = produced(&[&a], &a);
+        let genesis = Block::genesis().header();
+        let mut other_body = block.header();
+        other_body.data_root = Block::compute_data_root("Skunks", &[]);
+        assert!(!engine.validate_header(&other_body, &genesis));
+        let mut retimed = block.header();
+        retimed.timestamp += Duration::from_millis(SLOT_MS);
+        assert!(!engine.validate_header(&retimed, &genesis));
+        assert!(engine.validate_header(&block.header(), &genesis));
+    }
+
+    #[test]
+    fn rejects_slots_that_do_not_advance() {
+        let a = KeyPair::generate();
+        let (engine, block) = produced(&[&a], &a);
+        let mut repeated = block.clone();
+        repeated.last_hash = block.hash;
+        let repeated = resealed(&repeated, "Skunks", &a);
+        let error = engine
+            .validate_block(&repeated, &block, &Ledger::new())
+            .unwrap_err();
+        assert!(error.contains("out of order"));
+    }
+
+    #[test]
+    fn refuses_to_seal_before_its_slot_starts() {
+        let a = KeyPair::generate();
+        let clock = Arc::new(MockClock::at_millis(START_MS));
+        let engine = engine_at(&[&a], clock.clone());
+        assert_eq!(engine.current_slot(), START_MS / SLOT_MS);
+        let genesis = Block::genesis();
+        let first = engine
+            .produce_block(&genesis, &Ledger::new(), contents("Raccoons"), Some(&a))
+            .unwrap();
+        assert_eq!(first.seal.as_ref().unwrap().slot, START_MS / SLOT_MS);
+        assert_eq!(clock.now(), first.timestamp);
+
+        // The current slot is taken, so the next block waits for the next one.
+        let error = engine
+            .produce_block(&first, &Ledger::new(), contents("Bears"), Some(&a))
+            .unwrap_err();
+        assert!(error.contains("is not the slot leader until"));
+        assert_eq!(clock.now(), first.timestamp);
+        let next = engine
+            .next_block_time(&first, &Ledger::new(), Some(&a))
+            .unwrap();
+        assert_eq!(next, engine.slot_start(START_MS / SLOT_MS + 1));
+        clock.sleep_until(next);
+        let second = engine
+            .produce_block(&first, &Ledger::new(), contents("Bears"), Some(&a))
+            .unwrap();
+        assert_eq!(second.timestamp, next);
+    }
+
+    #[test]
+    fn chains_report_their_next_slot_and_wait_for_it_outside_the_engine() {
+        let a = KeyPair::generate();
+        let clock = Arc::new(MockClock::at_millis(START_MS));
+        let mut blockchain = staked_chain(&[&a], &a);
+        blockchain.set_clock(clock.clone()).unwrap();
+        assert_eq!(blockchain.next_block_time(), Ok(clock.now()));
+        blockchain.add_block(String::from("Raccoons")).unwrap();
+        let next = blockchain.next_block_time().unwrap();
+        assert_eq!(next, clock.now() + Duration::from_millis(SLOT_MS));
+        assert!(blockchain.add_block(String::from("Bears")).is_err());
+        assert_eq!(blockchain.chain.len(), 2);
+        blockchain
+            .in_slot(|blockchain| blockchain.add_block(String::from("Bears")))
+            .unwrap();
+        assert_eq!(clock.now(), next);
+        assert_eq!(blockchain.last_block().timestamp, next);
+    }
+
+    #[test]
+    fn rejects_blocks_from_slots_its_clock_has_not_reached() {
+        let a = KeyPair::generate();
+        let ahead = Arc::new(MockClock::at_millis(START_MS));
+        ahead.advance(Duration::from_millis(10 * SLOT_MS));
+        let block = engine_at(&[&a], ahead.clone())
+            .produce_block(
+                &Block::genesis(),
+                &Ledger::new(),
+                contents("Raccoons"),
+                Some(&a),
+            )
+            .unwrap();
+        let clock = Arc::new(MockClock::at_millis(START_MS));
+        let engine = engine_at(&[&a], clock.clone());
+        let error = engine
+            .validate_block(&block, &Block::genesis(), &Ledger::new())
+            .unwrap_err();
+        assert!(error.contains("out of order"));
+        clock.advance(Duration::from_millis(10 * SLOT_MS));
+        assert!(engine
+            .validate_block(&block, &Block::genesis(), &Ledger::new())
+            .is_ok());
+    }
+}
+
+mod double_sign {
+    use super::*;
+
+    #[test]
+    fn names_the_validator_that_sealed_both_blocks() {
+        let a = KeyPair::generate();
+        let mut blockchain = staked_chain(&[&a], &a);
+        blockchain
+            .in_slot(|blockchain| blockchain.add_block(String::from("Raccoons")))
+            .unwrap();
+        let block = blockchain.last_block();
+        let evidence = DoubleSign {
+            first: block.header(),
+            second: resealed(block, "Skunks", &a).header(),
+        };
+        assert_eq!(evidence.offender(), Ok(a.address()));
+    }
+
+    #[test]
+    fn rejects_evidence_that_proves_nothing() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let mut blockchain = staked_chain(&[&a], &a);
+        blockchain
+            .in_slot(|blockchain| blockchain.add_block(String::from("Raccoons")))
+            .unwrap();
+        let block = blockchain.last_block().clone();
+        let same = DoubleSign {
+            first: block.header(),
+            second: block.header(),
+        };
+        assert!(same.offender().is_err());
+        let mut other_validator = block.clone();
+        other_validator.seal.as_mut().unwrap().validator = b.public_key();
+        let other_validator = DoubleSign {
+            first: block.header(),
+            second: resealed(&other_validator, "Skunks", &b).header(),
+        };
+        assert!(other_validator.offender().is_err());
+        let unsigned = DoubleSign {
+            first: block.header(),
+            second: resealed(&block, "Skunks", &b).header(),
+        };
+        assert!(unsigned.offender().is_err());
+        let unsealed = DoubleSign {
+            first: Block::genesis().header(),
+            second: block.header(),
+        };
+        assert!(unsealed.offender().is_err());
+    }
+}
+
+mod slashing {
+    use super::*;
+
+    // Chain on which `a` double signed its block at height 1 and `b` then
+    // included the evidence at height 2.
+    fn slashed_chain(a: &KeyPair, b: &KeyPair) -> Blockchain {
+        let mut blockchain = staked_chain(&[a, b], a);
+        blockchain
+            .in_slot(|blockchain| blockchain.add_block(String::from("Raccoons")))
+            .unwrap();
+        return slash_tip(blockchain, a, b);
+    }
+
+    // Like `slashed_chain`, but `a` paid itself the reward of the block it
+    // double signed. The minimum stake is out of reach so both keys keep
+    // leading as bootstrap validators.
+    fn slashed_chain_with_stake(a: &KeyPair, b: &KeyPair) -> Blockchain {
+        let mut blockchain = staked_chain(&[a, b], a);
+        blockchain
+            .set_params(ChainParams {
+                consensus: Consensus::ProofOfStake(StakeParams {
+                    min_stake: u64::MAX,
+                    ..stake_params(&[a, b])
+                }),
+                ..ChainParams::default()
+            })
+            .unwrap();
+        blockchain
+            .in_slot(|blockchain| blockchain.mine_transactions(&a.address()))
+            .unwrap();
+        assert_eq!(blockchain.ledger().unwrap().balance(&a.address()), 50);
+        return slash_tip(blockchain, a, b);
+    }
+
+    fn slash_tip(mut blockchain: Blockchain, a: &KeyPair, b: &KeyPair) -> Blockchain {
+        let conflicting = resealed(blockchain.last_block(), "Skunks", a);
+        assert!(!blockchain.submit_block(conflicting));
+        assert_eq!(blockchain.pending_evidence().len(), 1);
+        blockchain.set_validator_key(KeyPair::from_secret_bytes(&b.secret_bytes()).unwrap());
+        blockchain
+            .in_slot(|blockchain| blockchain.add_block(String::from("Bears")))
+            .unwrap();
+        return blockchain;
+    }
+
+    #[test]
+    fn submitting_a_conflicting_block_records_evidence_for_the_next_block() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let blockchain = slashed_chain(&a, &b);
+        assert!(blockchain.pending_evidence().is_empty());
+        let block = blockchain.last_block();
+        assert_eq!(block.evidence.len(), 1);
+        assert_eq!(block.evidence[0].offender(), Ok(a.address()));
+        assert_eq!(block.seal.as_ref().unwrap().address(), b.address());
+    }
+
+    #[test]
+    fn slashed_validators_are_committed_and_never_lead_again() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let blockchain = slashed_chain(&a, &b);
+        let ledger = blockchain.ledger().unwrap();
+        assert!(ledger.is_slashed(&a.address()));
+        assert_eq!(
+            blockchain.last_block().state_root,
+            Some(ledger.commitment())
+        );
+        assert_ne!(ledger.commitment(), Ledger::new().commitment());
+        assert_eq!(
+            engine(&[&a, &b]).validators(&ledger),
+            vec![(b.address(), 1)]
+        );
+
+        let mut slashed = staked_chain(&[&a, &b], &a);
+        slashed.replace_chain(blockchain.chain.clone()).unwrap();
+        assert!(slashed.add_block(String::from("Possums")).is_err());
+    }
+
+    #[test]
+    fn burns_the_offenders_stake() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let blockchain = slashed_chain_with_stake(&a, &b);
+        let ledger = blockchain.ledger().unwrap();
+        assert_eq!(ledger.balance(&a.address()), 0);
+        assert_eq!(
+            blockchain.last_block().state_root,
+            Some(ledger.commitment())
+        );
+
+        let stake = OutPoint {
+            txid: Transaction::decode_payload(&blockchain.chain[1].data).unwrap()[0].txid(),
+            index: 0,
+        };
+        let outputs = |address: String| {
+            vec![TxOutput {
+                amount: 50,
+                address,
+            }]
+        };
+        let spend = Transaction::new_signed(&[(stake, &a)], outputs("cd".repeat(32)), 0);
+        let restake = Transaction::new_signed(&[(stake, &a)], outputs(b.address()), 0);
+        for transaction in [spend, restake].iter() {
+            let error = ledger
+                .validate_transaction(transaction, BlockHeight(3))
+                .unwrap_err();
+            assert!(error.ends_with("input is missing or spent"));
+        }
+    }
+
+    #[test]
+    fn sled_store_burns_and_restores_stake_across_reorgs() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let blockchain = slashed_chain_with_stake(&a, &b);
+        store
+            .commit_chain(&blockchain.chain, blockchain.index())
+            .unwrap();
+        assert_eq!(
+            store.load_ledger(&Ledger::new()).unwrap(),
+            blockchain.ledger().unwrap()
+        );
+        let unslashed = &blockchain.chain[..2];
+        store
+            .commit_chain(unslashed, &ChainIndex::build(unslashed))
+            .unwrap();
+        let ledger = store.load_ledger(&Ledger::new()).unwrap();
+        assert_eq!(ledger, Ledger::from_chain(unslashed).unwrap());
+        assert_eq!(ledger.balance(&a.address()), 50);
+    }
+
+    #[test]
+    fn other_nodes_accept_the_evidence_once() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let blockchain = slashed_chain(&a, &b);
+        let mut peer = staked_chain(&[&a, &b], &b);
+        assert!(peer.is_valid_extension(&blockchain.chain));
+        peer.replace_chain(blockchain.chain.clone()).unwrap();
+        assert!(peer.ledger().unwrap().is_slashed(&a.address()));
+        let evidence = blockchain.last_block().evidence[0].clone();
+        assert!(peer.report_double_sign(evidence).is_err());
+    }
+
+    #[test]
+    fn refuses_blocks_slashing_an_already_slashed_validator() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let blockchain = slashed_chain(&a, &b);
+        let evidence = blockchain.last_block().evidence.clone();
+        let clock = Arc::new(MockClock::at_millis(START_MS));
+        let engine = engine_at(&[&a, &b], clock.clone());
+        let block = produce_in_slot(
+            &engine,
+            clock.as_ref(),
+            blockchain.last_block(),
+            &blockchain.ledger().unwrap(),
+            BlockContents {
+                evidence,
+                ..contents("Possums")
+            },
+            &b,
+        )
+        .unwrap();
+        let error = engine
+            .validate_block(
+                &block,
+                blockchain.last_block(),
+                &blockchain.ledger().unwrap(),
+            )
+            .unwrap_err();
+        assert!(error.contains("already slashed"));
+    }
+
+    #[test]
+    fn snapshots_carry_slashed_validators() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let blockchain = slashed_chain(&a, &b);
+        let snapshot = blockchain.snapshot(blockchain.height()).unwrap();
+        assert_eq!(snapshot.slashed, vec![a.address()]);
+        assert!(snapshot.verify().is_ok());
+        assert!(snapshot.ledger().is_slashed(&a.address()));
+    }
+
+    #[test]
+    fn sled_store_tracks_slashed_validators_across_reorgs() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let blockchain = slashed_chain(&a, &b);
+        store
+            .commit_chain(&blockchain.chain, blockchain.index())
+            .unwrap();
+        assert!(store
+            .load_ledger(&Ledger::new())
+            .unwrap()
+            .is_slashed(&a.address()));
+        let unslashed = &blockchain.chain[..2];
+        store.commit_chain(unslashed, &ChainIndex::new()).unwrap();
+        assert!(store
+            .load_ledger(&Ledger::new())
+            .unwrap()
+            .slashed()
+            .is_empty());
+    }
+}
@@ -0,0 +1,57 @@
+use crate::{
+    block::Block,
+    events::{ChainEvent, EventBus},
+    types::BlockHeight,
+};
+
+fn tip_event(height: u64) -> ChainEvent {
+    return ChainEvent::NewTip {
+        height: BlockHeight(height),
+        hash: Block::genesis().hash,
+    };
+}
+
+mod event_bus {
+    use super::*;
+
+    #[test]
+    fn delivers_events_to_every_subscriber() {
+        let bus = EventBus::new();
+        let first = bus.subscribe();
+        let second = bus.subscribe();
+        bus.publish(tip_event(0));
+        assert_eq!(first.try_recv().unwrap(), tip_event(0));
+        assert_eq!(second.try_recv().unwrap(), tip_event(0));
+    }
+
+    #[test]
+    fn delivers_events_in_publish_order() {
+        let bus = EventBus::new();
+        let receiver = bus.subscribe();
+        bus.publish(tip_event(1));
+        bus.publish(tip_event(2));
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<ChainEvent>>(),
+            vec![tip_event(1), tip_event(2)]
+        );
+    }
+
+    #[test]
+    fn drops_subscribers_that_hung_up() {
+        let bus = EventBus::new();
+        let receiver = bus.subscribe();
+        drop(bus.subscribe());
+        assert_eq!(bus.subscriber_count(), 2);
+        bus.publish(tip_event(0));
+        assert_eq!(bus.subscriber_count(), 1);
+        assert!(receiver.try_recv().is_ok());
+    }
+
+    #[test]
+    fn does_not_replay_past_events_to_new_subscribers() {
+        let bus = EventBus::new();
+        bus.publish(tip_event(0));
+        let receiver = bus.subscribe();
+        assert!(receiver.try_recv().is_err());
+    }
+}
@@ -0,0 +1,838 @@
+use crate::{
+    block::Block,
+    blockchain::Blockchain,
+    events::ChainEvent,
+    finality::*,
+    params::ChainParams,
+    sled_store::SledStore,
+    store::{ChainStore, FileStore},
+    types::{BlockHeight, Hash256},
+};
+
+use crypto::keys::KeyPair;
+use std::collections::BTreeMap;
+use tempfile::tempdir;
+
+fn key_pairs(count: usize) -> Vec<KeyPair> {
+    return (0..count).map(|_| KeyPair::generate()).collect();
+}
+
+fn finality_params(key_pairs: &[KeyPair]) -> FinalityParams {
+    return FinalityParams {
+        validators: key_pairs
+            .iter()
+            .map(|key_pair| hex::encode(key_pair.public_key()))
+            .collect(),
+    };
+}
+
+fn validator_set(key_pairs: &[KeyPair]) -> ValidatorSet {
+    return finality_params(key_pairs).validator_set().unwrap();
+}
+
+fn copy(key_pair: &KeyPair) -> KeyPair {
+    return KeyPair::from_secret_bytes(&key_pair.secret_bytes()).unwrap();
+}
+
+fn precommit(key_pair: &KeyPair, height: u64, hash: impl Into<Hash256>) -> FinalityVote {
+    return FinalityVote::new_signed(
+        key_pair,
+        VoteKind::Precommit,
+        BlockHeight(height),
+        0,
+        hash.into(),
+    );
+}
+
+fn certificate(signers: &[KeyPair], height: u64, hash: impl Into<Hash256>) -> FinalityCertificate {
+    let hash = hash.into();
+    return FinalityCertificate {
+        height: BlockHeight(height),
+        round: 0,
+        hash,
+        precommits: signers
+            .iter()
+            .map(|key_pair| precommit(key_pair, height, hash))
+            .collect(),
+    };
+}
+
+fn finality_chain(key_pairs: &[KeyPair], blocks: &[&str]) -> Blockchain {
+    let mut blockchain = Blockchain::new();
+    blockchain
+        .set_params(ChainParams {
+            finality: Some(finality_params(key_pairs)),
+            ..ChainParams::default()
+        })
+        .unwrap();
+    for data in blocks {
+        blockchain.add_block(data.to_string()).unwrap();
+    }
+    return blockchain;
+}
+
+// Validators that run the gadget honestly, each with its own copy of the
+// chain. The first `split` hold one fork and the rest another.
+struct Node {
+    gadget: FinalityGadget,
+    blockchain: Blockchain,
+    finalized: BTreeMap<BlockHeight, Hash256>,
+}
+
+// Delivers votes between honest nodes in a seeded pseudo-random order while
+// the faulty validators equivocate: every round, at whatever height each
+// honest node is voting on, they prevote and precommit that node's fork to
+// it and the other fork to the nodes holding it.
+struct Simulation {
+    nodes: Vec<Node>,
+    split: usize,
+    forks: [Vec<Block>; 2],
+    faulty: Vec<KeyPair>,
+    queue: Vec<(usize, FinalityVote)>,
+    seed: u64,
+}
+
+impl Simulation {
+    fn new(
+        forks: &[Vec<Block>; 2],
+        honest: usize,
+        split: usize,
+        faulty: usize,
+        seed: u64,
+    ) -> Simulation {
+        let key_pairs = key_pairs(honest + faulty);
+        let validators = validator_set(&key_pairs);
+        let mut nodes = Vec::new();
+        for (position, key_pair) in key_pairs[..honest].iter().enumerate() {
+            let fork = &forks[(position >= split) as usize];
+            let mut blockchain = Blockchain::from_chain(fork.clone());
+            blockchain
+                .set_params(ChainParams {
+                    finality: Some(finality_params(&key_pairs)),
+                    ..ChainParams::default()
+                })
+                .unwrap();
+            nodes.push(Node {
+                gadget: FinalityGadget::new(validators.clone(), Some(copy(key_pair)), None),
+                blockchain,
+                finalized: BTreeMap::new(),
+            });
+        }
+        return Simulation {
+            nodes,
+            split,
+            forks: forks.clone(),
+            faulty: key_pairs[honest..].iter().map(copy).collect(),
+            queue: Vec::new(),
+            seed,
+        };
+    }
+
+    fn run(&mut self, height: u64, max_rounds: usize) {
+        let height = BlockHeight(height);
+        for _ in 0..max_rounds {
+            for position in 0..self.nodes.len() {
+                let node = &mut self.nodes[position];
+                if node.gadget.height() > height {
+                    continue;
+                }
+                if let Some(header) = node.blockchain.header_at(node.gadget.height()) {
+                    let votes = node.gadget.propose(header.hash);
+                    self.broadcast(position, votes);
+                }
+            }
+            self.equivocate(height);
+            self.deliver();
+            self.share_certificates();
+            if self.nodes.iter().all(|node| node.gadget.height() > height) {
+                return;
+            }
+            for node in self.nodes.iter_mut() {
+                if node.gadget.height() <= height {
+                    node.gadget.next_round();
+                }
+            }
+        }
+    }
+
+    fn broadcast(&mut self, from: usize, votes: Vec<FinalityVote>) {
+        for vote in votes {
+            for to in 0..self.nodes.len() {
+                if to != from {
+                    self.queue.push((to, vote.clone()));
+                }
+            }
+        }
+    }
+
+    fn equivocate(&mut self, height: BlockHeight) {
+        for to in 0..self.nodes.len() {
+            let gadget = &self.nodes[to].gadget;
+            if gadget.height() > height {
+                continue;
+            }
+            let fork = &self.forks[(to >= self.split) as usize];
+            let hash = match fork.get(gadget.height().index()) {
+                Some(block) => block.hash,
+                None => Hash256([0xbb; 32]),
+            };
+            for key_pair in self.faulty.iter() {
+                for kind in [VoteKind::Prevote, VoteKind::Precommit].iter() {
+                    let vote = FinalityVote::new_signed(
+                        key_pair,
+                        *kind,
+                        gadget.height(),
+                        gadget.round(),
+                        hash,
+                    );
+                    self.queue.push((to, vote));
+                }
+            }
+        }
+    }
+
+    fn deliver(&mut self) {
+        while !self.queue.is_empty() {
+            self.seed = self
+                .seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let next = (self.seed >> 33) as usize % self.queue.len();
+            let (to, vote) = self.queue.swap_remove(next);
+            if let Ok(votes) = self.nodes[to].gadget.on_vote(vote) {
+                self.broadcast(to, votes);
+            }
+            self.record_finality(to);
+        }
+    }
+
+    fn share_certificates(&mut self) {
+        for from in 0..self.nodes.len() {
+            let certificate = match self.nodes[from].gadget.finalized() {
+                Some(certificate) => certificate.clone(),
+                None => continue,
+            };
+            for to in 0..self.nodes.len() {
+                if let Ok(true) = self.nodes[to].gadget.on_certificate(certificate.clone()) {
+                    self.record_finality(to);
+                }
+            }
+        }
+    }
+
+    // Syncs the fork a newly finalized block is on before finalizing it.
+    fn record_finality(&mut self, position: usize) {
+        let certificate = match self.nodes[position].gadget.finalized() {
+            Some(certificate) => certificate.clone(),
+            None => return,
+        };
+        let node = &mut self.nodes[position];
+        if node.finalized.contains_key(&certificate.height) {
+            return;
+        }
+        node.finalized.insert(certificate.height, certificate.hash);
+        for fork in self.forks.iter() {
+            let on_fork = fork.get(certificate.height.index()).map(|block| block.hash);
+            let synced = node
+                .blockchain
+                .header_at(certificate.height)
+                .map(|header| header.hash);
+            if on_fork == Some(certificate.hash) && synced != on_fork {
+                let _ = node.blockchain.replace_chain(fork.clone());
+            }
+        }
+        let _ = node.blockchain.finalize(certificate);
+    }
+
+    // Heights at which two honest nodes finalized different blocks.
+    fn conflicts(&self) -> Vec<BlockHeight> {
+        let mut finalized: BTreeMap<BlockHeight, Hash256> = BTreeMap::new();
+        let mut conflicts = Vec::new();
+        for node in self.nodes.iter() {
+            for (height, hash) in node.finalized.iter() {
+                match finalized.get(height) {
+                    Some(other) if other != hash => conflicts.push(*height),
+                    Some(_) => {}
+                    None => {
+                        finalized.insert(*height, *hash);
+                    }
+                }
+            }
+        }
+        return conflicts;
+    }
+}
+
+mod validator_set {
+    use super::*;
+
+    #[test]
+    fn needs_more_than_two_thirds_for_a_quorum() {
+        for (count, quorum, max_faulty) in
+            [(1, 1, 0), (3, 3, 0), (4, 3, 1), (6, 5, 1), (7, 5, 2)].iter()
+        {
+            let validators = validator_set(&key_pairs(*count));
+            assert_eq!(validators.quorum(), *quorum);
+            assert_eq!(validators.max_faulty(), *max_faulty);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_validators() {
+        let empty = FinalityParams::default();
+        assert!(empty.validator_set().is_err());
+        let short = FinalityParams {
+            validators: vec![String::from("abcd")],
+        };
+        assert_eq!(
+            short.validator_set(),
+            Err(String::from("invalid finality validator abcd"))
+        );
+    }
+
+    #[test]
+    fn chain_params_reject_invalid_validators() {
+        let mut blockchain = Blockchain::new();
+        let result = blockchain.set_params(ChainParams {
+            finality: Some(FinalityParams::default()),
+            ..ChainParams::default()
+        });
+        assert!(result.is_err());
+        assert_eq!(blockchain.params().finality, None);
+    }
+}
+
+mod finality_vote {
+    use super::*;
+
+    #[test]
+    fn verifies_its_signature() {
+        let key_pair = KeyPair::generate();
+        let mut vote = FinalityVote::new_signed(
+            &key_pair,
+            VoteKind::Prevote,
+            BlockHeight(3),
+            1,
+            Hash256([1; 32]),
+        );
+        assert!(vote.verify());
+        vote.kind = VoteKind::Precommit;
+        assert!(!vote.verify());
+    }
+}
+
+mod certificate {
+    use super::*;
+
+    #[test]
+    fn verifies_a_quorum_of_precommits() {
+        let key_pairs = key_pairs(4);
+        let certificate = certificate(&key_pairs[..3], 2, [5; 32]);
+        assert_eq!(certificate.verify(&validator_set(&key_pairs)), Ok(()));
+    }
+
+    #[test]
+    fn rejects_too_few_precommits() {
+        let key_pairs = key_pairs(4);
+        let certificate = certificate(&key_pairs[..2], 2, [5; 32]);
+        assert_eq!(
+            certificate.verify(&validator_set(&key_pairs)),
+            Err(String::from("certificate has 2 of the 3 precommits needed"))
+        );
+    }
+
+    #[test]
+    fn counts_each_validator_once() {
+        let key_pairs = key_pairs(4);
+        let mut certificate = certificate(&key_pairs[..2], 2, [5; 32]);
+        certificate
+            .precommits
+            .push(precommit(&key_pairs[0], 2, [5; 32]));
+        assert!(certificate.verify(&validator_set(&key_pairs)).is_err());
+    }
+
+    #[test]
+    fn rejects_precommits_from_outsiders() {
+        let key_pairs = key_pairs(4);
+        let certificate = certificate(&key_pairs[1..], 2, [5; 32]);
+        assert!(certificate.verify(&validator_set(&key_pairs[..3])).is_err());
+    }
+
+    #[test]
+    fn rejects_votes_for_another_block() {
+        let key_pairs = key_pairs(4);
+        let mut certificate = certificate(&key_pairs[..3], 2, [5; 32]);
+        certificate.precommits[0] = precommit(&key_pairs[0], 2, [6; 32]);
+        assert_eq!(
+            certificate.verify(&validator_set(&key_pairs)),
+            Err(String::from("certificate holds a vote for something else"))
+        );
+    }
+}
+
+mod gadget {
+    use super::*;
+
+    fn gadgets(key_pairs: &[KeyPair]) -> Vec<FinalityGadget> {
+        let validators = validator_set(key_pairs);
+        return key_pairs
+            .iter()
+            .map(|key_pair| FinalityGadget::new(validators.clone(), Some(copy(key_pair)), None))
+            .collect();
+    }
+
+    fn prevote(key_pair: &KeyPair, round: u64, hash: impl Into<Hash256>) -> FinalityVote {
+        return FinalityVote::new_signed(
+            key_pair,
+            VoteKind::Prevote,
+            BlockHeight(1),
+            round,
+            hash.into(),
+        );
+    }
+
+    #[test]
+    fn prevotes_the_proposed_block_once_a_round() {
+        let key_pairs = key_pairs(4);
+        let mut gadget = gadgets(&key_pairs).remove(0);
+        let votes = gadget.propose(Hash256([1; 32]));
+        assert_eq!(votes, vec![prevote(&key_pairs[0], 0, [1; 32])]);
+        assert!(gadget.propose(Hash256([2; 32])).is_empty());
+    }
+
+    #[test]
+    fn precommits_and_locks_on_a_prevote_quorum() {
+        let key_pairs = key_pairs(4);
+        let mut gadget = gadgets(&key_pairs).remove(0);
+        gadget.propose(Hash256([1; 32]));
+        assert!(gadget
+            .on_vote(prevote(&key_pairs[1], 0, [1; 32]))
+            .unwrap()
+            .is_empty());
+        let votes = gadget.on_vote(prevote(&key_pairs[2], 0, [1; 32])).unwrap();
+        assert_eq!(
+            votes,
+            vec![FinalityVote::new_signed(
+                &key_pairs[0],
+                VoteKind::Precommit,
+                BlockHeight(1),
+                0,
+                Hash256([1; 32])
+            )]
+        );
+        assert_eq!(gadget.locked(), Some(Hash256([1; 32])));
+    }
+
+    #[test]
+    fn finalizes_on_a_precommit_quorum() {
+        let key_pairs = key_pairs(4);
+        let mut gadget = gadgets(&key_pairs).remove(3);
+        for key_pair in key_pairs[..3].iter() {
+            gadget.on_vote(precommit(key_pair, 1, [1; 32])).unwrap();
+        }
+        let certificate = gadget.finalized().unwrap();
+        assert_eq!(
+            (certificate.height, certificate.hash),
+            (BlockHeight(1), Hash256([1; 32]))
+        );
+        assert_eq!(certificate.verify(&validator_set(&key_pairs)), Ok(()));
+        assert_eq!(gadget.height(), BlockHeight(2));
+        assert_eq!(gadget.locked(), None);
+    }
+
+    #[test]
+    fn prevotes_the_locked_block_in_later_rounds() {
+        let key_pairs = key_pairs(4);
+        let mut gadget = gadgets(&key_pairs).remove(0);
+        gadget.propose(Hash256([1; 32]));
+        for key_pair in key_pairs[1..3].iter() {
+            gadget.on_vote(prevote(key_pair, 0, [1; 32])).unwrap();
+        }
+        gadget.next_round();
+        assert_eq!(gadget.round(), 1);
+        assert_eq!(
+            gadget.propose(Hash256([2; 32])),
+            vec![prevote(&key_pairs[0], 1, [1; 32])]
+        );
+    }
+
+    #[test]
+    fn moves_its_lock_to_a_later_prevote_quorum() {
+        let key_pairs = key_pairs(4);
+        let mut gadget = gadgets(&key_pairs).remove(0);
+        gadget.propose(Hash256([1; 32]));
+        for key_pair in key_pairs[1..3].iter() {
+            gadget.on_vote(prevote(key_pair, 0, [1; 32])).unwrap();
+        }
+        gadget.next_round();
+        for key_pair in key_pairs[1..].iter() {
+            gadget.on_vote(prevote(key_pair, 1, [2; 32])).unwrap();
+        }
+        assert_eq!(gadget.locked(), Some(Hash256([2; 32])));
+    }
+
+    #[test]
+    fn refuses_equivocating_votes() {
+        let key_pairs = key_pairs(4);
+        let mut gadget = gadgets(&key_pairs).remove(0);
+        gadget.on_vote(prevote(&key_pairs[1], 0, [1; 32])).unwrap();
+        assert_eq!(
+            gadget.on_vote(prevote(&key_pairs[1], 0, [1; 32])),
+            Ok(Vec::new())
+        );
+        let result = gadget.on_vote(prevote(&key_pairs[1], 0, [2; 32]));
+        assert_eq!(
+            result,
+            Err(format!(
+                "{} equivocated in round 0",
+                hex::encode(key_pairs[1].public_key())
+            ))
+        );
+    }
+
+    #[test]
+    fn refuses_outsiders_and_forged_votes() {
+        let key_pairs = key_pairs(4);
+        let mut gadget = gadgets(&key_pairs[..3]).remove(0);
+        assert!(gadget.on_vote(prevote(&key_pairs[3], 0, [1; 32])).is_err());
+        let mut forged = prevote(&key_pairs[1], 0, [1; 32]);
+        forged.hash = Hash256([2; 32]);
+        assert_eq!(
+            gadget.on_vote(forged),
+            Err(String::from("invalid finality vote signature"))
+        );
+    }
+
+    #[test]
+    fn ignores_votes_for_other_heights() {
+        let key_pairs = key_pairs(4);
+        let mut gadget = gadgets(&key_pairs).remove(3);
+        for key_pair in key_pairs[..3].iter() {
+            gadget.on_vote(precommit(key_pair, 2, [1; 32])).unwrap();
+        }
+        assert_eq!(gadget.finalized(), None);
+        assert_eq!(gadget.height(), BlockHeight(1));
+    }
+
+    #[test]
+    fn follows_votes_without_a_validator_key() {
+        let key_pairs = key_pairs(4);
+        let mut gadget = FinalityGadget::new(validator_set(&key_pairs), None, None);
+        assert!(gadget.propose(Hash256([1; 32])).is_empty());
+        for key_pair in key_pairs[..3].iter() {
+            assert!(gadget
+                .on_vote(precommit(key_pair, 1, [1; 32]))
+                .unwrap()
+                .is_empty());
+        }
+        assert_eq!(gadget.height(), BlockHeight(2));
+    }
+
+    #[test]
+    fn adopts_certificates_for_its_height_or_above() {
+        let key_pairs = key_pairs(4);
+        let mut gadget = gadgets(&key_pairs).remove(0);
+        let third = certificate(&key_pairs[..3], 3, [3; 32]);
+        assert_eq!(gadget.on_certificate(third.clone()), Ok(true));
+        assert_eq!(gadget.height(), BlockHeight(4));
+        let first = certificate(&key_pairs[..3], 1, [1; 32]);
+        assert_eq!(gadget.on_certificate(first), Ok(false));
+        assert_eq!(gadget.finalized(), Some(&third));
+        let short = certificate(&key_pairs[..2], 5, [5; 32]);
+        assert!(gadget.on_certificate(short).is_err());
+    }
+
+    #[test]
+    fn resumes_above_the_last_certificate() {
+        let key_pairs = key_pairs(4);
+        let finalized = certificate(&key_pairs[..3], 6, [6; 32]);
+        let gadget = FinalityGadget::new(validator_set(&key_pairs), None, Some(finalized));
+        assert_eq!(gadget.height(), BlockHeight(7));
+    }
+}
+
+mod blockchain {
+    use super::*;
+
+    #[test]
+    fn finalizes_blocks_in_the_chain() {
+        let key_pairs = key_pairs(4);
+        let mut blockchain = finality_chain(&key_pairs, &["one", "two"]);
+        let events = blockchain.events().subscribe();
+        let hash = blockchain.chain[1].hash;
+        blockchain
+            .finalize(certificate(&key_pairs[..3], 1, hash))
+            .unwrap();
+        assert_eq!(blockchain.finalized().unwrap().hash, hash);
+        assert_eq!(
+            events.try_recv(),
+            Ok(ChainEvent::Finalized {
+                height: BlockHeight(1),
+                hash
+            })
+        );
+    }
+
+    #[test]
+    fn needs_finality_validators() {
+        let key_pairs = key_pairs(4);
+        let mut blockchain = Blockchain::new();
+        blockchain.add_block(String::from("one")).unwrap();
+        let hash = blockchain.chain[1].hash;
+        assert_eq!(
+            blockchain.finalize(certificate(&key_pairs[..3], 1, hash)),
+            Err(String::from("chain has no finality validators"))
+        );
+    }
+
+    #[test]
+    fn refuses_blocks_outside_the_chain() {
+        let key_pairs = key_pairs(4);
+        let mut blockchain = finality_chain(&key_pairs, &["one"]);
+        let result = blockchain.finalize(certificate(&key_pairs[..3], 1, [9; 32]));
+        assert_eq!(
+            result,
+            Err(format!(
+                "block 1 {} is not in the chain",
+                hex::encode([9; 32])
+            ))
+        );
+        let hash = blockchain.chain[1].hash;
+        assert!(blockchain
+            .finalize(certificate(&key_pairs[..3], 2, hash))
+            .is_err());
+        assert_eq!(blockchain.finalized(), None);
+    }
+
+    #[test]
+    fn refuses_certificates_at_or_below_the_finalized_height() {
+        let key_pairs = key_pairs(4);
+        let mut blockchain = finality_chain(&key_pairs, &["one", "two"]);
+        let second = blockchain.chain[2].hash;
+        blockchain
+            .finalize(certificate(&key_pairs[..3], 2, second))
+            .unwrap();
+        let first = blockchain.chain[1].hash;
+        assert_eq!(
+            blockchain.finalize(certificate(&key_pairs[..3], 1, first)),
+            Err(String::from("block 1 is already final"))
+        );
+        assert_eq!(blockchain.finalized().unwrap().height, BlockHeight(2));
+    }
+
+    #[test]
+    fn refuses_reorgs_past_the_finalized_block() {
+        let key_pairs = key_pairs(4);
+        let mut blockchain = finality_chain(&key_pairs, &["one", "two"]);
+        let hash = blockchain.chain[2].hash;
+        blockchain
+            .finalize(certificate(&key_pairs[..3], 2, hash))
+            .unwrap();
+        let mut fork = Blockchain::from_chain(blockchain.chain[..2].to_vec());
+        for data in ["other", "three", "four"].iter() {
+            fork.add_block(data.to_string()).unwrap();
+        }
+        assert_eq!(
+            blockchain.replace_chain(fork.chain.clone()),
+            Err(String::from("new chain reverts final block 2"))
+        );
+        assert_eq!(blockchain.chain[2].hash, hash);
+    }
+
+    #[test]
+    fn accepts_chains_extending_the_finalized_block() {
+        let key_pairs = key_pairs(4);
+        let mut blockchain = finality_chain(&key_pairs, &["one"]);
+        let mut longer = Blockchain::from_chain(blockchain.chain.clone());
+        longer.add_block(String::from("two")).unwrap();
+        let hash = blockchain.chain[1].hash;
+        blockchain
+            .finalize(certificate(&key_pairs[..3], 1, hash))
+            .unwrap();
+        assert_eq!(blockchain.replace_chain(longer.chain.clone()), Ok(()));
+        assert_eq!(blockchain.height(), BlockHeight(2));
+    }
+
+    #[test]
+    fn a_lone_validator_finalizes_the_blocks_it_adds() {
+        let key_pairs = key_pairs(1);
+        let mut blockchain = finality_chain(&key_pairs, &["one"]);
+        assert_eq!(blockchain.finalized(), None);
+        blockchain.set_validator_key(copy(&key_pairs[0]));
+        assert_eq!(blockchain.finalized().unwrap().height, BlockHeight(1));
+        blockchain.add_block(String::from("two")).unwrap();
+        let finalized = blockchain.finalized().unwrap();
+        assert_eq!(finalized.height, BlockHeight(2));
+        assert_eq!(finalized.hash, blockchain.chain[2].hash);
+        assert!(blockchain.finality_votes().is_empty());
+    }
+
+    // What a node pulls from a peer: its certificate if it is ahead, then
+    // the votes it counted.
+    fn exchange(from: &Blockchain, to: &mut Blockchain) {
+        if let Some(certificate) = from.finalized() {
+            if to.finalized().map(|finalized| finalized.height) < Some(certificate.height) {
+                to.finalize(certificate.clone()).unwrap();
+            }
+        }
+        for vote in from.finality_votes() {
+            to.add_finality_vote(vote).unwrap();
+        }
+    }
+
+    #[test]
+    fn validators_finalize_by_exchanging_votes() {
+        let key_pairs = key_pairs(2);
+        let mut first = finality_chain(&key_pairs, &["one", "two"]);
+        let mut second = Blockchain::from_chain(first.chain.clone());
+        second
+            .set_params(ChainParams {
+                finality: Some(finality_params(&key_pairs)),
+                ..ChainParams::default()
+            })
+            .unwrap();
+        first.set_validator_key(copy(&key_pairs[0]));
+        second.set_validator_key(copy(&key_pairs[1]));
+        assert_eq!(first.finalized(), None);
+
+        for _ in 0..4 {
+            exchange(&first, &mut second);
+            exchange(&second, &mut first);
+        }
+        assert_eq!(first.finalized().unwrap().height, BlockHeight(2));
+        assert_eq!(first.finalized(), second.finalized());
+        assert_eq!(first.finalized().unwrap().hash, first.chain[2].hash);
+    }
+
+    #[test]
+    fn holds_votes_until_the_finalized_block_is_in_the_chain() {
+        let key_pairs = key_pairs(1);
+        let mut blockchain = finality_chain(&key_pairs, &["one"]);
+        let mut fork = Blockchain::from_chain(blockchain.chain[..1].to_vec());
+        fork.add_block(String::from("other")).unwrap();
+        fork.add_block(String::from("two")).unwrap();
+        let vote =
+            |kind, hash| FinalityVote::new_signed(&key_pairs[0], kind, BlockHeight(1), 0, hash);
+        let hash = fork.chain[1].hash;
+        blockchain
+            .add_finality_vote(vote(VoteKind::Prevote, hash))
+            .unwrap();
+        blockchain
+            .add_finality_vote(vote(VoteKind::Precommit, hash))
+            .unwrap();
+        assert_eq!(blockchain.finalized(), None);
+
+        blockchain.replace_chain(fork.chain.clone()).unwrap();
+        assert_eq!(blockchain.finalized().unwrap().hash, hash);
+        assert_eq!(
+            blockchain.add_finality_vote(vote(VoteKind::Prevote, hash)),
+            Ok(())
+        );
+    }
+}
+
+mod simulation {
+    use super::*;
+
+    // Two blocks on the fork most validators hold, one on the other.
+    fn forks() -> [Vec<Block>; 2] {
+        let mut fork_a = Blockchain::new();
+        for data in ["a1", "a2"].iter() {
+            fork_a.add_block(data.to_string()).unwrap();
+        }
+        let mut fork_b = Blockchain::new();
+        fork_b.add_block(String::from("b1")).unwrap();
+        return [fork_a.chain, fork_b.chain];
+    }
+
+    #[test]
+    fn honest_nodes_agree_with_one_of_four_faulty() {
+        let forks = forks();
+        for seed in 0..3 {
+            let mut simulation = Simulation::new(&forks, 3, 2, 1, seed);
+            simulation.run(2, 10);
+            assert_eq!(simulation.conflicts(), Vec::<BlockHeight>::new());
+            for node in simulation.nodes.iter() {
+                assert_eq!(
+                    node.finalized.get(&BlockHeight(1)),
+                    Some(&simulation.forks[0][1].hash)
+                );
+                assert_eq!(node.blockchain.finalized().unwrap().height, BlockHeight(2));
+            }
+        }
+    }
+
+    #[test]
+    fn honest_nodes_agree_with_two_of_seven_faulty() {
+        let forks = forks();
+        for seed in 0..2 {
+            let mut simulation = Simulation::new(&forks, 5, 3, 2, seed);
+            simulation.run(2, 10);
+            assert_eq!(simulation.conflicts(), Vec::<BlockHeight>::new());
+            for node in simulation.nodes.iter() {
+                assert_eq!(node.gadget.height(), BlockHeight(3));
+                let tip = &simulation.forks[0][2];
+                assert_eq!(node.blockchain.finalized().unwrap().hash, tip.hash);
+            }
+        }
+    }
+
+    #[test]
+    fn stalls_rather_than_conflicts_when_no_fork_has_a_quorum() {
+        let forks = forks();
+        let mut simulation = Simulation::new(&forks, 4, 2, 1, 7);
+        simulation.run(1, 5);
+        assert_eq!(simulation.conflicts(), Vec::<BlockHeight>::new());
+        assert!(simulation
+            .nodes
+            .iter()
+            .all(|node| node.finalized.is_empty()));
+    }
+
+    #[test]
+    fn finalized_blocks_survive_longer_forks() {
+        let forks = forks();
+        let mut simulation = Simulation::new(&forks, 3, 2, 1, 3);
+        simulation.run(1, 10);
+        let fork_b = simulation.forks[1].clone();
+        let node = &mut simulation.nodes[2];
+        let mut longer = Blockchain::from_chain(fork_b);
+        for _ in 0..3 {
+            longer.add_block(String::from("b")).unwrap();
+        }
+        assert!(node.blockchain.replace_chain(longer.chain).is_err());
+        assert_eq!(node.blockchain.chain[1].hash, simulation.forks[0][1].hash);
+    }
+
+    #[test]
+    fn a_third_faulty_can_finalize_conflicting_blocks() {
+        let forks = forks();
+        let mut simulation = Simulation::new(&forks, 2, 1, 2, 0);
+        simulation.run(1, 3);
+        assert_eq!(simulation.conflicts(), vec![BlockHeight(1)]);
+    }
+}
+
+mod store {
+    use super::*;
+
+    #[test]
+    fn file_store_round_trips_the_certificate() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        assert_eq!(store.load_finality().unwrap(), None);
+        let certificate = certificate(&key_pairs(4)[..3], 1, [1; 32]);
+        store.commit_finality(&certificate).unwrap();
+        assert_eq!(store.load_finality().unwrap(), Some(certificate));
+    }
+
+    #[test]
+    fn sled_store_round_trips_the_certificate() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        assert_eq!(store.load_finality().unwrap(), None);
+        let certificate = certificate(&key_pairs(4)[..3], 1, [1; 32]);
+        store.commit_finality(&certificate).unwrap();
+        assert_eq!(store.load_finality().unwrap(), Some(certificate));
+    }
+}
@@ -0,0 +1,220 @@
+use crypto::keys::KeyPair;
+
+use crate::{
+    blockchain::Blockchain,
+    htlc::*,
+    script,
+    transaction::{OutPoint, Transaction, TxOutput},
+    types::BlockHeight,
+};
+
+const PREIMAGE: &[u8] = b"atomic swap secret";
+
+fn contract(recipient: &KeyPair, refund: &KeyPair, timeout: u64) -> Htlc {
+    return Htlc::new(
+        PREIMAGE,
+        recipient.public_key(),
+        refund.public_key(),
+        timeout,
+    );
+}
+
+fn fund(htlc: &Htlc) -> (Blockchain, OutPoint) {
+    let mut blockchain = Blockchain::new();
+    blockchain.mine_transactions(&htlc.address()).unwrap();
+    let (outpoint, _) = blockchain.ledger().unwrap().unspent(&htlc.address())[0].clone();
+    return (blockchain, outpoint);
+}
+
+fn payout(key_pair: &KeyPair) -> Vec<TxOutput> {
+    return vec![TxOutput {
+        amount: 49,
+        address: key_pair.address(),
+    }];
+}
+
+mod script_template {
+    use super::*;
+
+    #[test]
+    fn disassembles_to_hash_and_time_lock_branches() {
+        let htlc = contract(&KeyPair::generate(), &KeyPair::generate(), 20);
+        let asm = script::disassemble(&htlc.locking_script()).unwrap();
+        assert!(asm.starts_with("OP_IF OP_SHA256 "));
+        assert!(asm.contains(" OP_ELSE 14 OP_CHECKLOCKTIMEVERIFY OP_DROP "));
+        assert!(asm.ends_with(" OP_CHECKSIG OP_ENDIF"));
+        assert_eq!(htlc.address(), script::address(&htlc.locking_script()));
+    }
+
+    #[test]
+    fn from_script_round_trips() {
+        for timeout in [0, 5, 16, 17, 100_000].iter() {
+            let htlc = contract(&KeyPair::generate(), &KeyPair::generate(), *timeout);
+            assert_eq!(Htlc::from_script(&htlc.locking_script()), Some(htlc));
+        }
+    }
+
+    #[test]
+    fn saturates_timeouts_beyond_script_range() {
+        let htlc = contract(&KeyPair::generate(), &KeyPair::generate(), u64::MAX);
+        let asm = script::disassemble(&htlc.locking_script()).unwrap();
+        assert!(asm.contains(" OP_ELSE ffffffffffffff7f OP_CHECKLOCKTIMEVERIFY "));
+        let decoded = Htlc::from_script(&htlc.locking_script());
+        assert_eq!(decoded.map(|htlc| htlc.timeout), Some(i64::MAX as u64));
+    }
+
+    #[test]
+    fn from_script_rejects_other_scripts() {
+        let htlc = contract(&KeyPair::generate(), &KeyPair::generate(), 20);
+        let mut locking_script = htlc.locking_script();
+        locking_script.push(script::OP_NOP);
+        assert_eq!(Htlc::from_script(&locking_script), None);
+        assert_eq!(Htlc::from_script(&script::assemble("OP_1").unwrap()), None);
+        assert_eq!(Htlc::from_script(&[0xff]), None);
+    }
+}
+
+mod claim {
+    use super::*;
+
+    #[test]
+    fn recipient_claims_with_preimage() {
+        let recipient = KeyPair::generate();
+        let htlc = contract(&recipient, &KeyPair::generate(), 20);
+        let (blockchain, outpoint) = fund(&htlc);
+        let transaction = htlc
+            .claim(&[outpoint], payout(&recipient), &recipient, PREIMAGE)
+            .unwrap();
+        let ledger = blockchain.ledger().unwrap();
+        assert_eq!(
+            ledger.validate_transaction(&transaction, BlockHeight(2)),
+            Ok(1)
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_preimage_or_key() {
+        let recipient = KeyPair::generate();
+        let refund = KeyPair::generate();
+        let htlc = contract(&recipient, &refund, 20);
+        let outpoint = OutPoint {
+            txid: [1; 32],
+            index: 0,
+        };
+        assert!(htlc
+            .claim(&[outpoint], payout(&recipient), &recipient, b"wrong")
+            .is_err());
+        assert!(htlc
+            .claim(&[outpoint], payout(&refund), &refund, PREIMAGE)
+            .is_err());
+    }
+
+    #[test]
+    fn ledger_rejects_forged_claims() {
+        let recipient = KeyPair::generate();
+        let refund = KeyPair::generate();
+        let htlc = contract(&recipient, &refund, 20);
+        let (blockchain, outpoint) = fund(&htlc);
+        let ledger = blockchain.ledger().unwrap();
+
+        let mut forged = htlc
+            .claim(&[outpoint], payout(&recipient), &recipient, PREIMAGE)
+            .unwrap();
+        forged.outputs[0].address = refund.address();
+        assert!(ledger
+            .validate_transaction(&forged, BlockHeight(2))
+            .is_err());
+
+        let mut wrong_preimage = Transaction::new_script_spend(
+            &[(outpoint, htlc.locking_script())],
+            payout(&recipient),
+            0,
+        );
+        let mut unlocking_script = Vec::new();
+        script::push_data(
+            &mut unlocking_script,
+            &recipient.sign(&wrong_preimage.sighash()),
+        );
+        script::push_data(&mut unlocking_script, b"wrong");
+        unlocking_script.push(script::OP_1);
+        wrong_preimage.set_unlocking_script(0, unlocking_script);
+        assert!(ledger
+            .validate_transaction(&wrong_preimage, BlockHeight(2))
+            .is_err());
+    }
+}
+
+mod refund {
+    use super::*;
+
+    #[test]
+    fn refund_is_locked_until_timeout() {
+        let refund = KeyPair::generate();
+        let htlc = contract(&KeyPair::generate(), &refund, 5);
+        let (blockchain, outpoint) = fund(&htlc);
+        let transaction = htlc.refund(&[outpoint], payout(&refund), &refund).unwrap();
+        assert_eq!(transaction.lock_height, 5);
+        let ledger = blockchain.ledger().unwrap();
+        assert!(ledger
+            .validate_transaction(&transaction, BlockHeight(4))
+            .is_err());
+        assert_eq!(
+            ledger.validate_transaction(&transaction, BlockHeight(5)),
+            Ok(1)
+        );
+    }
+
+    #[test]
+    fn only_refund_key_can_refund() {
+        let recipient = KeyPair::generate();
+        let htlc = contract(&recipient, &KeyPair::generate(), 5);
+        let outpoint = OutPoint {
+            txid: [1; 32],
+            index: 0,
+        };
+        assert!(htlc
+            .refund(&[outpoint], payout(&recipient), &recipient)
+            .is_err());
+    }
+
+    #[test]
+    fn refund_with_earlier_lock_height_fails_script() {
+        let refund = KeyPair::generate();
+        let htlc = contract(&KeyPair::generate(), &refund, 5);
+        let (blockchain, outpoint) = fund(&htlc);
+        let mut transaction =
+            Transaction::new_script_spend(&[(outpoint, htlc.locking_script())], payout(&refund), 4);
+        let mut unlocking_script = Vec::new();
+        script::push_data(&mut unlocking_script, &refund.sign(&transaction.sighash()));
+        unlocking_script.push(script::OP_0);
+        transaction.set_unlocking_script(0, unlocking_script);
+        assert!(blockchain
+            .ledger()
+            .unwrap()
+            .validate_transaction(&transaction, BlockHeight(10))
+            .is_err());
+    }
+}
+
+mod extract_preimage {
+    use super::*;
+
+    #[test]
+    fn reveals_preimage_from_claim() {
+        let recipient = KeyPair::generate();
+        let refund = KeyPair::generate();
+        let htlc = contract(&recipient, &refund, 20);
+        let outpoint = OutPoint {
+            txid: [1; 32],
+            index: 0,
+        };
+        let claim = htlc
+            .claim(&[outpoint], payout(&recipient), &recipient, PREIMAGE)
+            .unwrap();
+        assert_eq!(htlc.extract_preimage(&claim), Some(PREIMAGE.to_vec()));
+        let refund_transaction = htlc.refund(&[outpoint], payout(&refund), &refund).unwrap();
+        assert_eq!(htlc.extract_preimage(&refund_transaction), None);
+        let other = contract(&recipient, &refund, 21);
+        assert_eq!(other.extract_preimage(&claim), None);
+    }
+}
@@ -0,0 +1,122 @@
+use crypto::keys::KeyPair;
+
+use crate::{
+    blockchain::Blockchain,
+    index::*,
+    transaction::{OutPoint, Transaction, TxOutput},
+    types::{BlockHeight, Hash256},
+};
+
+fn funded(key_pair: &KeyPair) -> (Blockchain, OutPoint) {
+    let mut blockchain = Blockchain::new();
+    blockchain.mine_transactions(&key_pair.address()).unwrap();
+    let (outpoint, _) = blockchain.ledger().unwrap().unspent(&key_pair.address())[0].clone();
+    return (blockchain, outpoint);
+}
+
+fn payment(outpoint: OutPoint, key_pair: &KeyPair, to: &str) -> Transaction {
+    let outputs = vec![TxOutput {
+        amount: 50,
+        address: String::from(to),
+    }];
+    return Transaction::new_signed(&[(outpoint, key_pair)], outputs, 0);
+}
+
+mod build {
+    use super::*;
+
+    #[test]
+    fn indexes_block_hashes_by_height() {
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .add_block(String::from("Raccoons are cool"))
+            .unwrap();
+        blockchain
+            .add_block(String::from("Skunks smell bad"))
+            .unwrap();
+        let index = ChainIndex::build(&blockchain.chain);
+        assert_eq!(index.len(), 3);
+        for (height, block) in blockchain.chain.iter().enumerate() {
+            assert_eq!(
+                index.block_height(&block.hash),
+                Some(BlockHeight::from(height))
+            );
+        }
+        assert_eq!(index.block_height(&Hash256([13; 32])), None);
+        assert_eq!(&index, blockchain.index());
+    }
+
+    #[test]
+    fn indexes_transactions_and_address_history() {
+        let sender = KeyPair::generate();
+        let recipient = "cd".repeat(32);
+        let (mut blockchain, outpoint) = funded(&sender);
+        let transaction = payment(outpoint, &sender, &recipient);
+        blockchain.submit_transaction(transaction.clone()).unwrap();
+        blockchain.mine_transactions(&"ab".repeat(32)).unwrap();
+        let index = blockchain.index();
+
+        let funding = TxLocation {
+            height: BlockHeight(1),
+            position: 0,
+        };
+        let spend = TxLocation {
+            height: BlockHeight(2),
+            position: 1,
+        };
+        assert_eq!(index.transaction_location(&outpoint.txid), Some(funding));
+        assert_eq!(index.transaction_location(&transaction.txid()), Some(spend));
+        assert_eq!(
+            index.address_transactions(&sender.address()),
+            &[funding, spend]
+        );
+        assert_eq!(index.address_transactions(&recipient), &[spend]);
+        assert!(index.address_transactions(&"ef".repeat(32)).is_empty());
+    }
+}
+
+mod sync {
+    use super::*;
+
+    #[test]
+    fn reorg_drops_disconnected_blocks() {
+        let sender = KeyPair::generate();
+        let (mut blockchain, outpoint) = funded(&sender);
+        let mut fork = Blockchain::from_chain(blockchain.chain.clone());
+        let transaction = payment(outpoint, &sender, &"cd".repeat(32));
+        blockchain.submit_transaction(transaction.clone()).unwrap();
+        blockchain.mine_transactions(&"ab".repeat(32)).unwrap();
+        let orphaned = blockchain.last_block().hash;
+
+        fork.add_block(String::from("Bears are big")).unwrap();
+        fork.add_block(String::from("Owls are wise")).unwrap();
+        blockchain.replace_chain(fork.chain.clone()).unwrap();
+        let index = blockchain.index();
+        assert_eq!(index, &ChainIndex::build(&fork.chain));
+        assert_eq!(index.block_height(&orphaned), None);
+        assert_eq!(index.transaction_location(&transaction.txid()), None);
+        assert!(index.address_transactions(&"cd".repeat(32)).is_empty());
+    }
+
+    #[test]
+    fn rebuilds_index_of_unrelated_chain() {
+        let mut other = Blockchain::new();
+        other.add_block(String::from("Foxes are quick")).unwrap();
+        other.add_block(String::from("Owls are wise")).unwrap();
+        let mut index = ChainIndex::build(&other.chain);
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .add_block(String::from("Raccoons are cool"))
+            .unwrap();
+        index.sync(&blockchain.chain);
+        assert_eq!(index, ChainIndex::build(&blockchain.chain));
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let (blockchain, _) = funded(&KeyPair::generate());
+        let json = serde_json::to_string(blockchain.index()).unwrap();
+        let index: ChainIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(&index, blockchain.index());
+    }
+}
@@ -0,0 +1,308 @@
+use crypto::keys::KeyPair;
+
+use crate::{
+    block::Block,
+    blockchain::Blockchain,
+    config::MINING_REWARD,
+    ledger::Ledger,
+    transaction::{OutPoint, Transaction, TxOutput},
+    types::{BlockHeight, Hash256},
+};
+
+fn funded_chain(key_pair: &KeyPair) -> (Blockchain, OutPoint) {
+    let mut blockchain = Blockchain::new();
+    blockchain.mine_transactions(&key_pair.address()).unwrap();
+    let coinbase = Transaction::decode_payload(&blockchain.chain[1].data).unwrap()[0].clone();
+    let outpoint = OutPoint {
+        txid: coinbase.txid(),
+        index: 0,
+    };
+    return (blockchain, outpoint);
+}
+
+fn payment(outpoint: OutPoint, key_pair: &KeyPair, to: &str, amount: u64) -> Transaction {
+    let outputs = vec![TxOutput {
+        amount,
+        address: String::from(to),
+    }];
+    return Transaction::new_signed(&[(outpoint, key_pair)], outputs, 0);
+}
+
+fn mine_payload(blockchain: &Blockchain, transactions: &[Transaction]) -> Block {
+    return Block::mine_block(
+        blockchain.last_block(),
+        Transaction::encode_payload(transactions),
+    );
+}
+
+mod from_chain {
+    use super::*;
+
+    #[test]
+    fn credits_mining_reward() {
+        let key_pair = KeyPair::generate();
+        let (blockchain, outpoint) = funded_chain(&key_pair);
+        let ledger = blockchain.ledger().unwrap();
+        assert_eq!(ledger.balance(&key_pair.address()), MINING_REWARD);
+        assert_eq!(ledger.get(&outpoint).unwrap().amount, MINING_REWARD);
+    }
+
+    #[test]
+    fn ignores_plain_data_blocks() {
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .add_block(String::from("Raccoons are cool"))
+            .unwrap();
+        assert!(blockchain.ledger().unwrap().is_empty());
+    }
+}
+
+mod validate_transaction {
+    use super::*;
+
+    #[test]
+    fn returns_fee_for_valid_spend() {
+        let key_pair = KeyPair::generate();
+        let (blockchain, outpoint) = funded_chain(&key_pair);
+        let transaction = payment(outpoint, &key_pair, &"cd".repeat(32), 45);
+        let ledger = blockchain.ledger().unwrap();
+        assert_eq!(
+            ledger.validate_transaction(&transaction, BlockHeight(2)),
+            Ok(5)
+        );
+    }
+
+    #[test]
+    fn rejects_spend_by_other_key() {
+        let key_pair = KeyPair::generate();
+        let (blockchain, outpoint) = funded_chain(&key_pair);
+        let thief = KeyPair::generate();
+        let transaction = payment(outpoint, &thief, &thief.address(), 50);
+        assert!(blockchain
+            .ledger()
+            .unwrap()
+            .validate_transaction(&transaction, BlockHeight(2))
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_tampered_output() {
+        let key_pair = KeyPair::generate();
+        let (blockchain, outpoint) = funded_chain(&key_pair);
+        let mut transaction = payment(outpoint, &key_pair, &"cd".repeat(32), 40);
+        transaction.outputs[0].address = "ef".repeat(32);
+        assert!(blockchain
+            .ledger()
+            .unwrap()
+            .validate_transaction(&transaction, BlockHeight(2))
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_overspend() {
+        let key_pair = KeyPair::generate();
+        let (blockchain, outpoint) = funded_chain(&key_pair);
+        let transaction = payment(outpoint, &key_pair, &"cd".repeat(32), MINING_REWARD + 1);
+        assert!(blockchain
+            .ledger()
+            .unwrap()
+            .validate_transaction(&transaction, BlockHeight(2))
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_or_duplicate_inputs() {
+        let key_pair = KeyPair::generate();
+        let (blockchain, outpoint) = funded_chain(&key_pair);
+        let ledger = blockchain.ledger().unwrap();
+        let unknown = OutPoint {
+            txid: [9; 32],
+            index: 0,
+        };
+        let transaction = payment(unknown, &key_pair, &"cd".repeat(32), 1);
+        assert!(ledger
+            .validate_transaction(&transaction, BlockHeight(2))
+            .is_err());
+        let outputs = vec![TxOutput {
+            amount: 60,
+            address: "cd".repeat(32),
+        }];
+        let doubled =
+            Transaction::new_signed(&[(outpoint, &key_pair), (outpoint, &key_pair)], outputs, 0);
+        assert!(ledger
+            .validate_transaction(&doubled, BlockHeight(2))
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_zero_amounts_and_bad_addresses() {
+        let key_pair = KeyPair::generate();
+        let (blockchain, outpoint) = funded_chain(&key_pair);
+        let ledger = blockchain.ledger().unwrap();
+        assert!(ledger
+            .validate_transaction(
+                &payment(outpoint, &key_pair, &"cd".repeat(32), 0),
+                BlockHeight(2)
+            )
+            .is_err());
+        assert!(ledger
+            .validate_transaction(&payment(outpoint, &key_pair, "nowhere", 1), BlockHeight(2))
+            .is_err());
+    }
+
+    #[test]
+    fn honours_lock_height() {
+        let key_pair = KeyPair::generate();
+        let (blockchain, outpoint) = funded_chain(&key_pair);
+        let outputs = vec![TxOutput {
+            amount: 50,
+            address: "cd".repeat(32),
+        }];
+        let transaction = Transaction::new_signed(&[(outpoint, &key_pair)], outputs, 5);
+        let ledger = blockchain.ledger().unwrap();
+        assert!(ledger
+            .validate_transaction(&transaction, BlockHeight(4))
+            .is_err());
+        assert!(ledger
+            .validate_transaction(&transaction, BlockHeight(5))
+            .is_ok());
+    }
+}
+
+mod apply_block {
+    use super::*;
+
+    #[test]
+    fn rejects_double_spend_within_block() {
+        let key_pair = KeyPair::generate();
+        let (blockchain, outpoint) = funded_chain(&key_pair);
+        let first = payment(outpoint, &key_pair, &"cd".repeat(32), 50);
+        let second = payment(outpoint, &key_pair, &"ef".repeat(32), 50);
+        let block = mine_payload(&blockchain, &[first, second]);
+        let mut ledger = blockchain.ledger().unwrap();
+        assert!(ledger.apply_block(BlockHeight(2), &block).is_err());
+        assert_eq!(ledger, blockchain.ledger().unwrap());
+    }
+
+    #[test]
+    fn rejects_excessive_coinbase() {
+        let blockchain = Blockchain::new();
+        let coinbase = Transaction::coinbase(BlockHeight(1), &"ab".repeat(32), MINING_REWARD + 1);
+        let block = mine_payload(&blockchain, &[coinbase]);
+        assert!(Ledger::new().apply_block(BlockHeight(1), &block).is_err());
+    }
+
+    #[test]
+    fn allows_coinbase_to_collect_fees() {
+        let key_pair = KeyPair::generate();
+        let (blockchain, outpoint) = funded_chain(&key_pair);
+        let transaction = payment(outpoint, &key_pair, &"cd".repeat(32), 40);
+        let coinbase = Transaction::coinbase(BlockHeight(2), &"ab".repeat(32), MINING_REWARD + 10);
+        let block = mine_payload(&blockchain, &[coinbase, transaction]);
+        let mut ledger = blockchain.ledger().unwrap();
+        ledger.apply_block(BlockHeight(2), &block).unwrap();
+        assert_eq!(ledger.balance(&"ab".repeat(32)), MINING_REWARD + 10);
+        assert_eq!(ledger.balance(&key_pair.address()), 0);
+    }
+
+    #[test]
+    fn rejects_misplaced_or_mismatched_coinbase() {
+        let blockchain = Blockchain::new();
+        let address = "ab".repeat(32);
+        let wrong_height = mine_payload(
+            &blockchain,
+            &[Transaction::coinbase(BlockHeight(7), &address, 1)],
+        );
+        assert!(Ledger::new()
+            .apply_block(BlockHeight(1), &wrong_height)
+            .is_err());
+        let two = mine_payload(
+            &blockchain,
+            &[
+                Transaction::coinbase(BlockHeight(1), &address, 1),
+                Transaction::coinbase(BlockHeight(1), &address, 2),
+            ],
+        );
+        assert!(Ledger::new().apply_block(BlockHeight(1), &two).is_err());
+    }
+}
+
+mod commitment {
+    use super::*;
+
+    #[test]
+    fn matches_for_equal_utxo_sets_and_changes_with_them() {
+        let key_pair = KeyPair::generate();
+        let (blockchain, outpoint) = funded_chain(&key_pair);
+        let ledger = blockchain.ledger().unwrap();
+        let rebuilt = Ledger::from_utxos(ledger.utxos().clone());
+        assert_eq!(ledger.commitment(), rebuilt.commitment());
+        assert_ne!(ledger.commitment(), Ledger::new().commitment());
+
+        let transaction = payment(outpoint, &key_pair, &"cd".repeat(32), 50);
+        let mut spent = ledger.clone();
+        spent.apply_transaction(&transaction);
+        assert_ne!(spent.commitment(), ledger.commitment());
+    }
+
+    #[test]
+    fn commitment_after_predicts_the_applied_block() {
+        let key_pair = KeyPair::generate();
+        let (blockchain, outpoint) = funded_chain(&key_pair);
+        let transaction = payment(outpoint, &key_pair, &"cd".repeat(32), 50);
+        let payload = Transaction::encode_payload(&[transaction]);
+        let mut ledger = blockchain.ledger().unwrap();
+        let state_root = ledger
+            .commitment_after(BlockHeight(2), &payload, &[])
+            .unwrap();
+        assert_eq!(ledger, blockchain.ledger().unwrap());
+        let block =
+            Block::mine_block_with_state(blockchain.last_block(), payload, Some(state_root));
+        let mut applied = ledger.clone();
+        applied.apply_block(BlockHeight(2), &block).unwrap();
+        assert_eq!(applied.commitment(), state_root);
+    }
+
+    #[test]
+    fn undoing_a_block_restores_the_ledger_and_its_commitment() {
+        let key_pair = KeyPair::generate();
+        let (blockchain, outpoint) = funded_chain(&key_pair);
+        let before = blockchain.ledger().unwrap();
+        let transaction = payment(outpoint, &key_pair, &key_pair.address(), MINING_REWARD);
+        let received = OutPoint {
+            txid: transaction.txid(),
+            index: 0,
+        };
+        let spend_received = payment(received, &key_pair, &"ef".repeat(32), MINING_REWARD);
+        let block = mine_payload(&blockchain, &[transaction, spend_received]);
+        let mut ledger = before.clone();
+        let undo = ledger.apply_block(BlockHeight(2), &block).unwrap();
+        assert_ne!(ledger.commitment(), before.commitment());
+        assert_eq!(
+            ledger.commitment(),
+            Ledger::from_utxos(ledger.utxos().clone()).commitment()
+        );
+        ledger.undo_block(undo);
+        assert_eq!(ledger, before);
+        assert_eq!(ledger.commitment(), before.commitment());
+    }
+
+    #[test]
+    fn a_block_that_fails_leaves_the_ledger_untouched() {
+        let key_pair = KeyPair::generate();
+        let (blockchain, outpoint) = funded_chain(&key_pair);
+        let before = blockchain.ledger().unwrap();
+        let transaction = payment(outpoint, &key_pair, &"cd".repeat(32), 50);
+        let double_spend = payment(outpoint, &key_pair, &"ef".repeat(32), 50);
+        let block = mine_payload(&blockchain, &[transaction.clone(), double_spend]);
+        let mut ledger = before.clone();
+        assert!(ledger.apply_block(BlockHeight(2), &block).is_err());
+        assert_eq!(ledger, before);
+
+        let payload = Transaction::encode_payload(&[transaction]);
+        let block =
+            Block::mine_block_with_state(blockchain.last_block(), payload, Some(Hash256([7; 32])));
+        assert!(ledger.apply_block(BlockHeight(2), &block).is_err());
+        assert_eq!(ledger, before);
+    }
+}
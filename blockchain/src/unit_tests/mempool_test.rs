@@ -0,0 +1,98 @@
+use crypto::keys::KeyPair;
+
+use crate::{
+    blockchain::Blockchain,
+    ledger::Ledger,
+    mempool::Mempool,
+    transaction::{OutPoint, Transaction, TxOutput},
+    types::BlockHeight,
+};
+
+fn setup() -> (KeyPair, Ledger, OutPoint) {
+    let key_pair = KeyPair::generate();
+    let mut blockchain = Blockchain::new();
+    blockchain.mine_transactions(&key_pair.address()).unwrap();
+    let coinbase = Transaction::decode_payload(&blockchain.chain[1].data).unwrap()[0].clone();
+    let outpoint = OutPoint {
+        txid: coinbase.txid(),
+        index: 0,
+    };
+    return (key_pair, blockchain.ledger().unwrap(), outpoint);
+}
+
+fn payment(outpoint: OutPoint, key_pair: &KeyPair, to: &str, amount: u64) -> Transaction {
+    let outputs = vec![TxOutput {
+        amount,
+        address: String::from(to),
+    }];
+    return Transaction::new_signed(&[(outpoint, key_pair)], outputs, 0);
+}
+
+mod add {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_transaction() {
+        let (key_pair, ledger, outpoint) = setup();
+        let mut mempool = Mempool::new();
+        let transaction = payment(outpoint, &key_pair, &"cd".repeat(32), 50);
+        assert_eq!(
+            mempool.add(transaction.clone(), &ledger, BlockHeight(2)),
+            Ok(transaction.txid())
+        );
+        assert_eq!(mempool.len(), 1);
+        assert!(mempool.contains(&transaction.txid()));
+        assert_eq!(mempool.size_bytes(), transaction.encode().len());
+    }
+
+    #[test]
+    fn rejects_duplicate_and_conflicting_transactions() {
+        let (key_pair, ledger, outpoint) = setup();
+        let mut mempool = Mempool::new();
+        let transaction = payment(outpoint, &key_pair, &"cd".repeat(32), 50);
+        mempool
+            .add(transaction.clone(), &ledger, BlockHeight(2))
+            .unwrap();
+        assert!(mempool.add(transaction, &ledger, BlockHeight(2)).is_err());
+        let conflicting = payment(outpoint, &key_pair, &"ef".repeat(32), 50);
+        assert!(mempool.add(conflicting, &ledger, BlockHeight(2)).is_err());
+        assert_eq!(mempool.len(), 1);
+    }
+
+    #[test]
+    fn accepts_spend_of_unconfirmed_output() {
+        let (key_pair, ledger, outpoint) = setup();
+        let mut mempool = Mempool::new();
+        let change = KeyPair::generate();
+        let first = payment(outpoint, &key_pair, &change.address(), 50);
+        let first_txid = mempool.add(first, &ledger, BlockHeight(2)).unwrap();
+        let unconfirmed = OutPoint {
+            txid: first_txid,
+            index: 0,
+        };
+        let second = payment(unconfirmed, &change, &"cd".repeat(32), 45);
+        assert!(mempool.add(second, &ledger, BlockHeight(2)).is_ok());
+        assert_eq!(mempool.fees(&ledger, BlockHeight(2)), 5);
+    }
+}
+
+mod revalidate {
+    use super::*;
+
+    #[test]
+    fn drops_transactions_whose_inputs_were_spent() {
+        let (key_pair, ledger, outpoint) = setup();
+        let mut mempool = Mempool::new();
+        mempool
+            .add(
+                payment(outpoint, &key_pair, &"cd".repeat(32), 50),
+                &ledger,
+                BlockHeight(2),
+            )
+            .unwrap();
+        let mut spent = ledger.clone();
+        spent.apply_transaction(&payment(outpoint, &key_pair, &"ef".repeat(32), 50));
+        mempool.revalidate(&spent, BlockHeight(3));
+        assert!(mempool.is_empty());
+    }
+}
@@ -1,2 +1,20 @@
+mod authority_test;
 mod block_test;
-mod blockchain_test;
\ No newline at end of file
+mod blockchain_test;
+mod clock_test;
+mod consensus_test;
+mod events_test;
+mod finality_test;
+mod htlc_test;
+mod index_test;
+mod ledger_test;
+mod mempool_test;
+mod multisig_test;
+mod params_test;
+mod properties_test;
+mod script_test;
+mod simulator_test;
+mod snapshot_test;
+mod store_test;
+mod transaction_test;
+mod types_test;
@@ -0,0 +1,202 @@
+use crypto::keys::{self, KeyPair};
+
+use crate::{
+    blockchain::Blockchain,
+    multisig::*,
+    transaction::{OutPoint, Transaction, TxOutput},
+    types::BlockHeight,
+};
+
+fn cosigners() -> (Vec<KeyPair>, MultisigPolicy) {
+    let key_pairs = vec![
+        KeyPair::generate(),
+        KeyPair::generate(),
+        KeyPair::generate(),
+    ];
+    let public_keys: Vec<[u8; 32]> = key_pairs.iter().map(KeyPair::public_key).collect();
+    return (key_pairs, MultisigPolicy::new(2, &public_keys));
+}
+
+fn funded(policy: &MultisigPolicy) -> (Blockchain, OutPoint) {
+    let mut blockchain = Blockchain::new();
+    blockchain.mine_transactions(&policy.address()).unwrap();
+    let (outpoint, _) = blockchain.ledger().unwrap().unspent(&policy.address())[0].clone();
+    return (blockchain, outpoint);
+}
+
+fn spend(outpoint: OutPoint, policy: &MultisigPolicy) -> Transaction {
+    let outputs = vec![TxOutput {
+        amount: 50,
+        address: "cd".repeat(32),
+    }];
+    return Transaction::new_multisig(&[(outpoint, policy.clone())], outputs, 0);
+}
+
+mod policy {
+    use super::*;
+
+    #[test]
+    fn validates_threshold_and_keys() {
+        let (key_pairs, policy) = cosigners();
+        assert!(policy.validate().is_ok());
+        let mut too_high = policy.clone();
+        too_high.threshold = 4;
+        assert!(too_high.validate().is_err());
+        let mut zero = policy.clone();
+        zero.threshold = 0;
+        assert!(zero.validate().is_err());
+        let duplicate = MultisigPolicy::new(1, &[key_pairs[0].public_key(); 2]);
+        assert!(duplicate.validate().is_err());
+        let mut malformed = policy;
+        malformed.public_keys[0] = String::from("abcd");
+        assert!(malformed.validate().is_err());
+    }
+
+    #[test]
+    fn address_depends_on_threshold_and_keys() {
+        let (_, policy) = cosigners();
+        let mut other = policy.clone();
+        other.threshold = 3;
+        assert_ne!(policy.address(), other.address());
+        assert_eq!(policy.address().len(), 64);
+    }
+
+    #[test]
+    fn address_ignores_key_case_and_is_valid() {
+        let (key_pairs, policy) = cosigners();
+        let mut upper = policy.clone();
+        upper.public_keys = policy
+            .public_keys
+            .iter()
+            .map(|key| key.to_uppercase())
+            .collect();
+        assert_eq!(upper.address(), policy.address());
+        assert!(keys::is_valid_address(&upper.address()));
+
+        let (blockchain, outpoint) = funded(&policy);
+        let mut transaction = spend(outpoint, &upper);
+        transaction.sign_multisig(&key_pairs[0]);
+        transaction.sign_multisig(&key_pairs[1]);
+        let ledger = blockchain.ledger().unwrap();
+        assert_eq!(
+            ledger.validate_transaction(&transaction, BlockHeight(2)),
+            Ok(0)
+        );
+    }
+}
+
+mod spending {
+    use super::*;
+
+    #[test]
+    fn requires_threshold_signatures() {
+        let (key_pairs, policy) = cosigners();
+        let (blockchain, outpoint) = funded(&policy);
+        let ledger = blockchain.ledger().unwrap();
+        let mut transaction = spend(outpoint, &policy);
+        assert!(ledger
+            .validate_transaction(&transaction, BlockHeight(2))
+            .is_err());
+        assert_eq!(transaction.sign_multisig(&key_pairs[2]), 1);
+        assert!(ledger
+            .validate_transaction(&transaction, BlockHeight(2))
+            .is_err());
+        assert_eq!(transaction.sign_multisig(&key_pairs[2]), 0);
+        transaction.sign_multisig(&key_pairs[0]);
+        assert_eq!(
+            ledger.validate_transaction(&transaction, BlockHeight(2)),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn ignores_keys_outside_policy() {
+        let (_, policy) = cosigners();
+        let (_, outpoint) = funded(&policy);
+        let mut transaction = spend(outpoint, &policy);
+        assert_eq!(transaction.sign_multisig(&KeyPair::generate()), 0);
+    }
+
+    #[test]
+    fn rejects_policy_that_does_not_own_output() {
+        let (key_pairs, policy) = cosigners();
+        let (blockchain, outpoint) = funded(&policy);
+        let mut other = policy.clone();
+        other.threshold = 1;
+        let mut transaction = spend(outpoint, &other);
+        transaction.sign_multisig(&key_pairs[0]);
+        assert!(blockchain
+            .ledger()
+            .unwrap()
+            .validate_transaction(&transaction, BlockHeight(2))
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_forged_or_duplicated_signatures() {
+        let (key_pairs, policy) = cosigners();
+        let (blockchain, outpoint) = funded(&policy);
+        let ledger = blockchain.ledger().unwrap();
+        let mut transaction = spend(outpoint, &policy);
+        transaction.sign_multisig(&key_pairs[0]);
+        let spend = transaction.inputs[0].multisig.as_mut().unwrap();
+        let mut copy = spend.signatures[0].clone();
+        spend.signatures.push(copy.clone());
+        assert!(ledger
+            .validate_transaction(&transaction, BlockHeight(2))
+            .is_err());
+
+        let spend = transaction.inputs[0].multisig.as_mut().unwrap();
+        spend.signatures.pop();
+        copy.key_index = 1;
+        spend.signatures.push(copy);
+        assert!(ledger
+            .validate_transaction(&transaction, BlockHeight(2))
+            .is_err());
+    }
+
+    #[test]
+    fn combines_partial_signatures() {
+        let (key_pairs, policy) = cosigners();
+        let (blockchain, outpoint) = funded(&policy);
+        let mut first = spend(outpoint, &policy);
+        let mut second = first.clone();
+        first.sign_multisig(&key_pairs[0]);
+        second.sign_multisig(&key_pairs[1]);
+        first.combine_signatures(&second).unwrap();
+        assert_eq!(
+            first.inputs[0].multisig.as_ref().unwrap().signatures.len(),
+            2
+        );
+        assert!(blockchain
+            .ledger()
+            .unwrap()
+            .validate_transaction(&first, BlockHeight(2))
+            .is_ok());
+
+        let mut different = second.clone();
+        different.outputs[0].amount = 10;
+        assert!(first.combine_signatures(&different).is_err());
+    }
+
+    #[test]
+    fn single_key_encoding_is_unchanged() {
+        let transaction = Transaction::new_signed(
+            &[(outpoint_of(1), &KeyPair::generate())],
+            vec![TxOutput {
+                amount: 1,
+                address: "cd".repeat(32),
+            }],
+            0,
+        );
+        let encoded = String::from_utf8(transaction.encode()).unwrap();
+        assert!(!encoded.contains("multisig"));
+    }
+
+    fn outpoint_of(byte: u8) -> OutPoint {
+        return OutPoint {
+            txid: [byte; 32],
+            index: 0,
+        };
+    }
+}
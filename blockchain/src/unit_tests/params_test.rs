@@ -0,0 +1,313 @@
+use crate::{
+    block::Block,
+    blockchain::Blockchain,
+    consensus::StakeParams,
+    params::{self, ChainParams, Checkpoint, Consensus, ProofOfWork, MAX_REORG_DEPTH},
+    types::{BlockHeight, Hash256},
+};
+
+use crypto::hasher::{Argon2Params, HashAlgorithm};
+
+fn chain() -> Vec<Block> {
+    let mut blockchain = Blockchain::new();
+    blockchain
+        .add_block(String::from("Raccoons are cool"))
+        .unwrap();
+    blockchain
+        .add_block(String::from("Skunks smell bad"))
+        .unwrap();
+    return blockchain.chain;
+}
+
+mod default {
+    use super::*;
+
+    #[test]
+    fn checkpoints_genesis_and_bounds_reorgs() {
+        let params = ChainParams::default();
+        assert_eq!(
+            params.checkpoint_at(BlockHeight(0)).unwrap().hash,
+            Block::genesis().hash
+        );
+        assert_eq!(params.max_reorg_depth, Some(MAX_REORG_DEPTH));
+    }
+}
+
+mod parse_checkpoints {
+    use super::*;
+
+    #[test]
+    fn reads_one_checkpoint_per_line() {
+        let hash = Hash256([0xab; 32]);
+        let contents = format!("# trusted blocks\n\n  2:{}\n7:{}\n", hash, Hash256::ZERO);
+        assert_eq!(
+            params::parse_checkpoints(&contents),
+            Ok(vec![
+                Checkpoint {
+                    height: BlockHeight(2),
+                    hash,
+                },
+                Checkpoint {
+                    height: BlockHeight(7),
+                    hash: Hash256::ZERO,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_a_malformed_line() {
+        assert_eq!(
+            params::parse_checkpoints("2:abcd"),
+            Err(String::from(
+                "invalid checkpoint 2:abcd (expected HEIGHT:HASH)"
+            ))
+        );
+    }
+}
+
+mod add_checkpoints {
+    use super::*;
+
+    #[test]
+    fn adds_new_heights_and_refuses_conflicts() {
+        let chain = chain();
+        let mut params = ChainParams::default();
+        let checkpoint = Checkpoint {
+            height: BlockHeight(2),
+            hash: chain[2].hash,
+        };
+        params
+            .add_checkpoints(vec![checkpoint, checkpoint])
+            .unwrap();
+        assert_eq!(params.checkpoints.len(), 2);
+        let conflicting = Checkpoint {
+            height: BlockHeight::GENESIS,
+            hash: chain[1].hash,
+        };
+        assert!(params
+            .add_checkpoints(vec![conflicting])
+            .unwrap_err()
+            .contains("conflicts with checkpoint 0:"));
+    }
+}
+
+mod check_chain {
+    use super::*;
+
+    #[test]
+    fn accepts_chain_matching_every_checkpoint_it_reaches() {
+        let chain = chain();
+        let mut params = ChainParams::default();
+        params.checkpoints.push(Checkpoint {
+            height: BlockHeight(2),
+            hash: chain[2].hash,
+        });
+        params.checkpoints.push(Checkpoint {
+            height: BlockHeight(9),
+            hash: Hash256([1; 32]),
+        });
+        assert!(params.check_chain(BlockHeight(0), &chain).is_ok());
+    }
+
+    #[test]
+    fn rejects_chain_with_a_different_block_at_a_checkpoint() {
+        let chain = chain();
+        let mut params = ChainParams::default();
+        params.checkpoints.push(Checkpoint {
+            height: BlockHeight(1),
+            hash: chain[2].hash,
+        });
+        assert!(params.check_chain(BlockHeight(0), &chain).is_err());
+        assert!(params.check_block(BlockHeight(1), &chain[1]).is_err());
+        assert!(params.check_block(BlockHeight(2), &chain[1]).is_ok());
+    }
+
+    #[test]
+    fn offsets_chains_that_start_above_genesis() {
+        let chain = chain();
+        let mut params = ChainParams::default();
+        params.checkpoints.push(Checkpoint {
+            height: BlockHeight(2),
+            hash: chain[2].hash,
+        });
+        assert!(params.check_chain(BlockHeight(1), &chain[1..]).is_ok());
+        assert!(params.check_chain(BlockHeight(2), &chain[1..]).is_err());
+    }
+}
+
+mod check_reorg_depth {
+    use super::*;
+
+    #[test]
+    fn refuses_only_reorgs_past_the_limit() {
+        let mut params = ChainParams {
+            max_reorg_depth: Some(3),
+            ..ChainParams::default()
+        };
+        assert!(params.check_reorg_depth(3).is_ok());
+        assert!(params.check_reorg_depth(4).is_err());
+        params.max_reorg_depth = None;
+        assert!(params.check_reorg_depth(1_000).is_ok());
+    }
+}
+
+mod hash_algorithm {
+    use super::*;
+
+    fn chain_hashed_with(hash_algorithm: HashAlgorithm) -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .set_params(ChainParams {
+                hash_algorithm,
+                ..ChainParams::default()
+            })
+            .unwrap();
+        blockchain
+            .add_block(String::from("Raccoons are cool"))
+            .unwrap();
+        blockchain
+            .add_block(String::from("Skunks smell bad"))
+            .unwrap();
+        return blockchain;
+    }
+
+    #[test]
+    fn defaults_to_sha256_and_reads_its_name() {
+        assert_eq!(ChainParams::default().hash_algorithm, HashAlgorithm::Sha256);
+        let params: ChainParams = serde_json::from_str(r#"{"hash_algorithm":"sha3-256"}"#).unwrap();
+        assert_eq!(params.hash_algorithm, HashAlgorithm::Sha3_256);
+        assert_eq!(params.max_reorg_depth, Some(MAX_REORG_DEPTH));
+    }
+
+    #[test]
+    fn chains_validate_only_under_the_algorithm_that_mined_them() {
+        for mined_with in HashAlgorithm::ALL.iter() {
+            let blockchain = chain_hashed_with(*mined_with);
+            for checked_with in HashAlgorithm::ALL.iter() {
+                let checker = chain_hashed_with(*checked_with);
+                assert_eq!(
+                    checker.is_valid_extension(&blockchain.chain),
+                    mined_with == checked_with,
+                    "mined with {}, checked with {}",
+                    mined_with,
+                    checked_with
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn submit_block_checks_proof_of_work_with_the_chain_algorithm() {
+        let mut blockchain = chain_hashed_with(HashAlgorithm::Blake3);
+        let hasher = HashAlgorithm::Sha256.hasher();
+        let block =
+            Block::mine_block_with(hasher, blockchain.last_block(), String::from("x"), None);
+        assert!(!blockchain.submit_block(block));
+        let hasher = HashAlgorithm::Blake3.hasher();
+        let block =
+            Block::mine_block_with(hasher, blockchain.last_block(), String::from("x"), None);
+        assert!(blockchain.submit_block(block));
+    }
+
+    #[test]
+    fn replace_chain_refuses_chain_mined_with_another_algorithm() {
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .set_params(ChainParams {
+                hash_algorithm: HashAlgorithm::DoubleSha256,
+                ..ChainParams::default()
+            })
+            .unwrap();
+        let other = chain_hashed_with(HashAlgorithm::Sha256);
+        assert!(blockchain.replace_chain(other.chain).is_err());
+        let same = chain_hashed_with(HashAlgorithm::DoubleSha256);
+        assert!(blockchain.replace_chain(same.chain).is_ok());
+    }
+}
+
+mod proof_of_work {
+    use super::*;
+
+    fn argon2(memory_kib: u32) -> ChainParams {
+        return ChainParams {
+            proof_of_work: ProofOfWork::Argon2(Argon2Params {
+                memory_kib,
+                ..Argon2Params::default()
+            }),
+            ..ChainParams::default()
+        };
+    }
+
+    fn chain_with(params: ChainParams) -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        blockchain.set_params(params).unwrap();
+        blockchain
+            .add_block(String::from("Raccoons are cool"))
+            .unwrap();
+        blockchain
+            .add_block(String::from("Skunks smell bad"))
+            .unwrap();
+        return blockchain;
+    }
+
+    #[test]
+    fn reads_argon2_parameters() {
+        let params: ChainParams =
+            serde_json::from_str(r#"{"proof_of_work":{"type":"argon2","memory_kib":64}}"#).unwrap();
+        assert_eq!(params, argon2(64));
+        assert_eq!(ChainParams::default().proof_of_work, ProofOfWork::Hash);
+    }
+
+    #[test]
+    fn argon2_chains_validate_only_under_the_same_parameters() {
+        let blockchain = chain_with(argon2(64));
+        assert!(chain_with(argon2(64)).is_valid_extension(&blockchain.chain));
+        assert!(!chain_with(argon2(128)).is_valid_extension(&blockchain.chain));
+        assert!(!Blockchain::new().is_valid_extension(&blockchain.chain));
+    }
+
+    #[test]
+    fn set_params_refuses_unbounded_verification_cost() {
+        let mut blockchain = chain_with(argon2(64));
+        assert!(blockchain.set_params(argon2(u32::MAX)).is_err());
+        assert_eq!(blockchain.params(), &argon2(64));
+        blockchain
+            .add_block(String::from("Possums play dead"))
+            .unwrap();
+        assert_eq!(blockchain.height(), BlockHeight(3));
+    }
+}
+
+mod consensus {
+    use super::*;
+
+    #[test]
+    fn defaults_to_proof_of_work_and_reads_stake_parameters() {
+        assert_eq!(ChainParams::default().consensus, Consensus::ProofOfWork);
+        let params: ChainParams = serde_json::from_str(
+            r#"{"consensus":{"type":"proof-of-stake","slot_ms":1000,"min_stake":10}}"#,
+        )
+        .unwrap();
+        let expected = StakeParams {
+            slot_ms: 1000,
+            min_stake: 10,
+            bootstrap_validators: Vec::new(),
+        };
+        assert_eq!(params.consensus, Consensus::ProofOfStake(expected));
+    }
+
+    #[test]
+    fn set_params_refuses_invalid_stake_parameters() {
+        let mut blockchain = Blockchain::new();
+        let params = ChainParams {
+            consensus: Consensus::ProofOfStake(StakeParams {
+                bootstrap_validators: vec![String::from("not an address")],
+                ..StakeParams::default()
+            }),
+            ..ChainParams::default()
+        };
+        assert!(blockchain.set_params(params).is_err());
+        assert_eq!(blockchain.params(), &ChainParams::default());
+    }
+}
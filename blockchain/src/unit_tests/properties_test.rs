@@ -0,0 +1,152 @@
+use crate::{
+    block::Block,
+    blockchain::Blockchain,
+    clock::{MockClock, SequentialNonces},
+    config::MINE_RATE,
+    types::{Hash256, Nonce},
+};
+
+use crypto::hasher::Sha256Hasher;
+use proptest::prelude::*;
+use std::time::Duration;
+
+const START_MS: u64 = 1_700_000_000_000;
+
+// Blocks mined at least `MINE_RATE` apart, so difficulty only falls and every
+// case mines quickly.
+fn mine_chain(blocks: &[(String, u64)]) -> Vec<Block> {
+    let mut chain = vec![Block::genesis()];
+    for (i, (data, elapsed_ms)) in blocks.iter().enumerate() {
+        let last_block = &chain[chain.len() - 1];
+        let timestamp = match i {
+            0 => Block::genesis().timestamp + Duration::from_millis(START_MS),
+            _ => last_block.timestamp + Duration::from_millis(*elapsed_ms),
+        };
+        let block = Block::mine_block_using(
+            &Sha256Hasher,
+            last_block,
+            data.clone(),
+            None,
+            &MockClock::new(timestamp),
+            &mut SequentialNonces::default(),
+        );
+        chain.push(block);
+    }
+    return chain;
+}
+
+fn arb_chain(max_blocks: usize) -> impl Strategy<Value = Vec<Block>> {
+    return prop::collection::vec(
+        ("[a-zA-Z ]{0,16}", MINE_RATE + 1..10 * MINE_RATE),
+        1..=max_blocks,
+    )
+    .prop_map(|blocks| mine_chain(&blocks));
+}
+
+#[derive(Debug, Clone)]
+enum Mutation {
+    Timestamp(u64),
+    LastHash(usize, u8),
+    Hash(usize, u8),
+    Data(String),
+    Nonce(u64),
+    Difficulty(bool),
+    StateRoot([u8; 32]),
+}
+
+fn arb_mutation() -> impl Strategy<Value = Mutation> {
+    let bit = || (0..32usize, 0..8u8);
+    return prop_oneof![
+        (1..u32::MAX as u64).prop_map(Mutation::Timestamp),
+        bit().prop_map(|(byte, bit)| Mutation::LastHash(byte, bit)),
+        bit().prop_map(|(byte, bit)| Mutation::Hash(byte, bit)),
+        "[a-z]{1,8}".prop_map(Mutation::Data),
+        (1..u64::MAX).prop_map(Mutation::Nonce),
+        any::<bool>().prop_map(Mutation::Difficulty),
+        any::<[u8; 32]>().prop_map(Mutation::StateRoot),
+    ];
+}
+
+fn mutate(block: &mut Block, mutation: &Mutation) {
+    match mutation {
+        Mutation::Timestamp(millis) => block.timestamp += Duration::from_millis(*millis),
+        Mutation::LastHash(byte, bit) => block.last_hash.0[*byte] ^= 1 << bit,
+        Mutation::Hash(byte, bit) => block.hash.0[*byte] ^= 1 << bit,
+        Mutation::Data(suffix) => block.data.push_str(suffix),
+        Mutation::Nonce(offset) => block.nonce = Nonce(block.nonce.0.wrapping_add(*offset)),
+        Mutation::Difficulty(raise) => match raise {
+            true => block.difficulty = block.difficulty + 1,
+            false => block.difficulty = block.difficulty - 1,
+        },
+        Mutation::StateRoot(state_root) => block.state_root = Some(Hash256(*state_root)),
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn mined_chains_are_valid(chain in arb_chain(4)) {
+        prop_assert!(Blockchain::is_valid_chain(&chain));
+        for i in 1..chain.len() {
+            prop_assert!(Block::is_valid_block(
+                &chain[i],
+                &chain[i - 1].hash,
+                chain[i - 1].difficulty
+            ));
+        }
+    }
+
+    #[test]
+    fn any_single_field_mutation_invalidates_a_block(
+        chain in arb_chain(3),
+        position in any::<prop::sample::Index>(),
+        mutation in arb_mutation(),
+    ) {
+        let i = 1 + position.index(chain.len() - 1);
+        let mut mutated = chain.clone();
+        mutate(&mut mutated[i], &mutation);
+        prop_assert!(!Block::is_valid_block(
+            &mutated[i],
+            &mutated[i - 1].hash,
+            mutated[i - 1].difficulty
+        ));
+        prop_assert!(!Blockchain::is_valid_chain(&mutated));
+    }
+
+    #[test]
+    fn blocks_survive_a_json_round_trip(chain in arb_chain(2)) {
+        let json = serde_json::to_string(&chain).unwrap();
+        let decoded: Vec<Block> = serde_json::from_str(&json).unwrap();
+        prop_assert_eq!(&decoded, &chain);
+        prop_assert!(Blockchain::is_valid_chain(&decoded));
+    }
+
+    #[test]
+    fn replace_chain_never_shortens_the_chain(
+        current in arb_chain(3),
+        candidate in arb_chain(3),
+        mutation in prop::option::of(arb_mutation()),
+    ) {
+        let mut candidate = candidate;
+        if let Some(mutation) = &mutation {
+            let last = candidate.len() - 1;
+            mutate(&mut candidate[last], mutation);
+        }
+        let mut blockchain = Blockchain::from_chain(current.clone());
+        let result = blockchain.replace_chain(candidate.clone());
+        prop_assert!(blockchain.chain.len() >= current.len());
+        if mutation.is_none() && candidate.len() > current.len() {
+            prop_assert_eq!(result, Ok(()));
+            prop_assert_eq!(&blockchain.chain, &candidate);
+        } else {
+            prop_assert!(result.is_err());
+            prop_assert_eq!(&blockchain.chain, &current);
+        }
+    }
+}
+
+#[test]
+fn empty_chains_are_invalid() {
+    assert!(!Blockchain::is_valid_chain(&[]));
+}
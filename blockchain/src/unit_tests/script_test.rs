@@ -0,0 +1,351 @@
+use crypto::keys::KeyPair;
+
+use crate::{
+    blockchain::Blockchain,
+    script::*,
+    transaction::{OutPoint, Transaction, TxOutput},
+    types::BlockHeight,
+};
+
+fn context(lock_height: u64) -> ScriptContext {
+    return ScriptContext {
+        sighash: [7; 32],
+        lock_height,
+    };
+}
+
+fn run(asm: &str) -> Result<Vec<Vec<u8>>, String> {
+    let mut stack = Vec::new();
+    execute(&assemble(asm).unwrap(), &mut stack, &context(10))?;
+    return Ok(stack);
+}
+
+fn evaluates_true(asm: &str) -> bool {
+    match run(asm) {
+        Ok(stack) => return stack.last().is_some_and(|top| cast_to_bool(top)),
+        Err(_) => return false,
+    }
+}
+
+fn pay_to_key_script(key_pair: &KeyPair) -> Vec<u8> {
+    let mut script = vec![OP_DUP, OP_SHA256];
+    push_data(&mut script, &hex::decode(key_pair.address()).unwrap());
+    script.extend_from_slice(&[OP_EQUALVERIFY, OP_CHECKSIG]);
+    return script;
+}
+
+mod numbers {
+    use super::*;
+
+    #[test]
+    fn encodes_minimally_with_sign_bit() {
+        assert_eq!(encode_number(0), Vec::<u8>::new());
+        assert_eq!(encode_number(1), vec![0x01]);
+        assert_eq!(encode_number(-1), vec![0x81]);
+        assert_eq!(encode_number(127), vec![0x7f]);
+        assert_eq!(encode_number(128), vec![0x80, 0x00]);
+        assert_eq!(encode_number(-128), vec![0x80, 0x80]);
+        assert_eq!(encode_number(256), vec![0x00, 0x01]);
+    }
+
+    #[test]
+    fn decode_round_trips() {
+        for number in [0, 1, -1, 16, 127, 128, -255, 65_536, i64::MAX, -i64::MAX].iter() {
+            assert_eq!(decode_number(&encode_number(*number)), Ok(*number));
+        }
+    }
+
+    #[test]
+    fn decode_rejects_non_minimal_and_oversized_numbers() {
+        assert!(decode_number(&[0x00]).is_err());
+        assert!(decode_number(&[0x01, 0x00]).is_err());
+        assert!(decode_number(&[0x80]).is_err());
+        assert!(decode_number(&[1; 9]).is_err());
+        assert_eq!(decode_number(&[0xff, 0x00]), Ok(255));
+    }
+
+    #[test]
+    fn casts_negative_zero_to_false() {
+        assert!(!cast_to_bool(&[]));
+        assert!(!cast_to_bool(&[0, 0]));
+        assert!(!cast_to_bool(&[0, 0x80]));
+        assert!(cast_to_bool(&[0, 1]));
+        assert!(cast_to_bool(&[0x80, 0]));
+    }
+}
+
+mod assembler {
+    use super::*;
+
+    #[test]
+    fn disassembles_pay_to_key_script() {
+        let key_pair = KeyPair::generate();
+        assert_eq!(
+            disassemble(&pay_to_key_script(&key_pair)).unwrap(),
+            format!(
+                "OP_DUP OP_SHA256 {} OP_EQUALVERIFY OP_CHECKSIG",
+                key_pair.address()
+            )
+        );
+    }
+
+    #[test]
+    fn round_trips_asm() {
+        let asm = "OP_IF OP_2 OP_ELSE OP_1NEGATE OP_0 OP_ENDIF 0102 OP_CHECKLOCKTIMEVERIFY";
+        assert_eq!(disassemble(&assemble(asm).unwrap()).unwrap(), asm);
+    }
+
+    #[test]
+    fn uses_pushdata_for_long_pushes() {
+        let data = vec![0xab; 300];
+        let mut script = Vec::new();
+        push_data(&mut script, &data);
+        assert_eq!(script[0], OP_PUSHDATA2);
+        assert_eq!(script.len(), 303);
+        assert_eq!(parse(&script).unwrap(), vec![Instruction::Push(data)]);
+        let mut script = Vec::new();
+        push_data(&mut script, &[1; 80]);
+        assert_eq!(&script[..2], &[OP_PUSHDATA1, 80]);
+    }
+
+    #[test]
+    fn push_number_uses_small_integer_opcodes() {
+        let mut script = Vec::new();
+        push_number(&mut script, 0);
+        push_number(&mut script, 16);
+        push_number(&mut script, -1);
+        push_number(&mut script, 17);
+        assert_eq!(script, vec![OP_0, OP_16, OP_1NEGATE, 0x01, 17]);
+    }
+
+    #[test]
+    fn reports_unknown_and_truncated_scripts() {
+        assert_eq!(disassemble(&[0xff]).unwrap(), "OP_UNKNOWN_0xff");
+        assert!(disassemble(&[0x05, 0x01]).is_err());
+        assert!(parse(&[OP_PUSHDATA1]).is_err());
+        assert!(assemble("OP_FROBNICATE").is_err());
+        assert!(assemble("xyz").is_err());
+    }
+}
+
+mod interpreter {
+    use super::*;
+
+    #[test]
+    fn evaluates_arithmetic() {
+        assert!(evaluates_true("OP_2 OP_3 OP_ADD OP_5 OP_NUMEQUAL"));
+        assert!(evaluates_true("OP_2 OP_3 OP_SUB OP_1NEGATE OP_NUMEQUAL"));
+        assert!(evaluates_true(
+            "OP_5 OP_1ADD OP_1SUB OP_NEGATE OP_ABS OP_5 OP_EQUAL"
+        ));
+        assert!(evaluates_true("OP_3 OP_7 OP_MIN OP_3 OP_EQUAL"));
+        assert!(evaluates_true("OP_3 OP_7 OP_MAX OP_7 OP_EQUAL"));
+        assert!(evaluates_true("OP_4 OP_2 OP_5 OP_WITHIN"));
+        assert!(!evaluates_true("OP_5 OP_2 OP_5 OP_WITHIN"));
+        assert!(evaluates_true("OP_2 OP_3 OP_LESSTHAN"));
+        assert!(evaluates_true("OP_3 OP_3 OP_GREATERTHANOREQUAL"));
+        assert!(evaluates_true("OP_0 OP_NOT"));
+        assert!(evaluates_true("OP_1 OP_0 OP_BOOLOR"));
+        assert!(!evaluates_true("OP_1 OP_0 OP_BOOLAND"));
+    }
+
+    #[test]
+    fn rejects_arithmetic_overflow() {
+        let mut script = Vec::new();
+        push_number(&mut script, i64::MAX);
+        script.push(OP_1ADD);
+        assert!(execute(&script, &mut Vec::new(), &context(0)).is_err());
+    }
+
+    #[test]
+    fn evaluates_conditionals() {
+        assert_eq!(
+            run("OP_1 OP_IF OP_2 OP_ELSE OP_3 OP_ENDIF").unwrap(),
+            vec![vec![2]]
+        );
+        assert_eq!(
+            run("OP_0 OP_IF OP_2 OP_ELSE OP_3 OP_ENDIF").unwrap(),
+            vec![vec![3]]
+        );
+        assert_eq!(run("OP_0 OP_NOTIF OP_4 OP_ENDIF").unwrap(), vec![vec![4]]);
+        assert_eq!(
+            run("OP_1 OP_0 OP_IF OP_IF OP_5 OP_ENDIF OP_ELSE OP_6 OP_ENDIF").unwrap(),
+            vec![vec![1], vec![6]]
+        );
+    }
+
+    #[test]
+    fn rejects_unbalanced_conditionals() {
+        assert!(run("OP_1 OP_IF OP_2").is_err());
+        assert!(run("OP_ENDIF").is_err());
+        assert!(run("OP_ELSE").is_err());
+    }
+
+    #[test]
+    fn skips_return_in_unexecuted_branch() {
+        assert!(evaluates_true("OP_0 OP_IF OP_RETURN OP_ENDIF OP_1"));
+        assert!(run("OP_1 OP_IF OP_RETURN OP_ENDIF").is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_opcodes_even_when_not_executed() {
+        let script = vec![OP_0, OP_IF, 0xff, OP_ENDIF, OP_1];
+        assert!(execute(&script, &mut Vec::new(), &context(0)).is_err());
+    }
+
+    #[test]
+    fn manipulates_stack() {
+        assert_eq!(run("OP_1 OP_2 OP_SWAP").unwrap(), vec![vec![2], vec![1]]);
+        assert_eq!(
+            run("OP_1 OP_2 OP_OVER").unwrap(),
+            vec![vec![1], vec![2], vec![1]]
+        );
+        assert_eq!(
+            run("OP_1 OP_2 OP_DROP OP_DEPTH").unwrap(),
+            vec![vec![1], vec![1]]
+        );
+        assert_eq!(run("aabbcc OP_SIZE").unwrap()[1], vec![3]);
+        assert!(run("OP_DROP").is_err());
+        assert!(run("OP_1 OP_SWAP").is_err());
+    }
+
+    #[test]
+    fn verify_fails_on_false() {
+        assert!(run("OP_1 OP_VERIFY").is_ok());
+        assert!(run("OP_0 OP_VERIFY").is_err());
+        assert!(run("OP_1 OP_2 OP_EQUALVERIFY").is_err());
+        assert!(run("OP_2 OP_2 OP_NUMEQUALVERIFY").is_ok());
+    }
+
+    #[test]
+    fn checks_hash_lock() {
+        let preimage = b"open sesame";
+        let hash = crypto::cryptohash::sha256(preimage);
+        let locking = assemble(&format!("OP_SHA256 {} OP_EQUAL", hex::encode(hash))).unwrap();
+        let mut unlocking = Vec::new();
+        push_data(&mut unlocking, preimage);
+        assert!(verify_spend(&unlocking, &locking, &context(0)).is_ok());
+        let mut wrong = Vec::new();
+        push_data(&mut wrong, b"open barley");
+        assert!(verify_spend(&wrong, &locking, &context(0)).is_err());
+    }
+
+    #[test]
+    fn checks_time_lock() {
+        let locking = assemble("0a OP_CHECKLOCKTIMEVERIFY OP_DROP OP_1").unwrap();
+        assert!(verify_spend(&[], &locking, &context(10)).is_ok());
+        assert!(verify_spend(&[], &locking, &context(9)).is_err());
+        let negative = assemble("OP_1NEGATE OP_CHECKLOCKTIMEVERIFY").unwrap();
+        assert!(verify_spend(&[], &negative, &context(10)).is_err());
+    }
+
+    #[test]
+    fn checks_signatures_against_sighash() {
+        let key_pair = KeyPair::generate();
+        let context = context(0);
+        let mut unlocking = Vec::new();
+        push_data(&mut unlocking, &key_pair.sign(&context.sighash));
+        push_data(&mut unlocking, &key_pair.public_key());
+        let locking = pay_to_key_script(&key_pair);
+        assert!(verify_spend(&unlocking, &locking, &context).is_ok());
+
+        let other = KeyPair::generate();
+        let mut forged = Vec::new();
+        push_data(&mut forged, &other.sign(&context.sighash));
+        push_data(&mut forged, &key_pair.public_key());
+        assert!(verify_spend(&forged, &locking, &context).is_err());
+    }
+
+    #[test]
+    fn requires_push_only_unlocking_script() {
+        let locking = assemble("OP_1").unwrap();
+        assert!(verify_spend(&[OP_1, OP_DROP], &locking, &context(0)).is_err());
+    }
+
+    #[test]
+    fn enforces_execution_limits() {
+        let many_ops = vec![OP_NOP; MAX_OPS + 1];
+        assert!(execute(&many_ops, &mut Vec::new(), &context(0)).is_err());
+        let deep_stack = vec![OP_1; MAX_STACK_SIZE + 1];
+        assert!(execute(&deep_stack, &mut Vec::new(), &context(0)).is_err());
+        let mut large_push = Vec::new();
+        push_data(&mut large_push, &[0; MAX_ELEMENT_SIZE + 1]);
+        assert!(execute(&large_push, &mut Vec::new(), &context(0)).is_err());
+        assert!(parse(&vec![OP_NOP; MAX_SCRIPT_SIZE + 1]).is_err());
+    }
+
+    #[test]
+    fn empty_or_false_result_fails() {
+        assert!(verify_spend(&[], &[], &context(0)).is_err());
+        assert!(verify_spend(&[], &[OP_0], &context(0)).is_err());
+    }
+}
+
+mod ledger {
+    use super::*;
+
+    fn fund_script(locking: &[u8]) -> (Blockchain, OutPoint) {
+        let mut blockchain = Blockchain::new();
+        blockchain.mine_transactions(&address(locking)).unwrap();
+        let (outpoint, _) = blockchain.ledger().unwrap().unspent(&address(locking))[0].clone();
+        return (blockchain, outpoint);
+    }
+
+    fn spend(outpoint: OutPoint, locking: &[u8], lock_height: u64) -> Transaction {
+        let outputs = vec![TxOutput {
+            amount: 50,
+            address: "cd".repeat(32),
+        }];
+        return Transaction::new_script_spend(
+            &[(outpoint, locking.to_vec())],
+            outputs,
+            lock_height,
+        );
+    }
+
+    #[test]
+    fn accepts_signed_script_spend() {
+        let key_pair = KeyPair::generate();
+        let locking = pay_to_key_script(&key_pair);
+        let (blockchain, outpoint) = fund_script(&locking);
+        let mut transaction = spend(outpoint, &locking, 0);
+        let mut unlocking = Vec::new();
+        push_data(&mut unlocking, &key_pair.sign(&transaction.sighash()));
+        push_data(&mut unlocking, &key_pair.public_key());
+        assert!(transaction.set_unlocking_script(0, unlocking));
+        let ledger = blockchain.ledger().unwrap();
+        assert_eq!(
+            ledger.validate_transaction(&transaction, BlockHeight(2)),
+            Ok(0)
+        );
+        transaction.outputs[0].amount = 49;
+        assert!(ledger
+            .validate_transaction(&transaction, BlockHeight(2))
+            .is_err());
+    }
+
+    #[test]
+    fn time_lock_uses_transaction_lock_height() {
+        let locking = assemble("03 OP_CHECKLOCKTIMEVERIFY").unwrap();
+        let (blockchain, outpoint) = fund_script(&locking);
+        let ledger = blockchain.ledger().unwrap();
+        assert!(ledger
+            .validate_transaction(&spend(outpoint, &locking, 2), BlockHeight(5))
+            .is_err());
+        assert!(ledger
+            .validate_transaction(&spend(outpoint, &locking, 3), BlockHeight(5))
+            .is_ok());
+    }
+
+    #[test]
+    fn rejects_script_that_does_not_own_output() {
+        let locking = assemble("OP_1").unwrap();
+        let (blockchain, outpoint) = fund_script(&locking);
+        let other = assemble("OP_2").unwrap();
+        assert!(blockchain
+            .ledger()
+            .unwrap()
+            .validate_transaction(&spend(outpoint, &other, 0), BlockHeight(2))
+            .is_err());
+    }
+}
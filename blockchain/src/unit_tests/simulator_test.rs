@@ -0,0 +1,262 @@
+use crate::{simulator::*, types::BlockHeight};
+
+const MINUTE_MS: u64 = 60_000;
+
+fn simulate(config: SimulationConfig, duration_ms: u64) -> Simulator {
+    let mut simulator = Simulator::new(config).unwrap();
+    simulator.run_until(duration_ms);
+    simulator.stop_mining();
+    simulator.run_for(MINUTE_MS);
+    return simulator;
+}
+
+mod sim_rng {
+    use super::*;
+
+    #[test]
+    fn replays_the_same_sequence_for_a_seed() {
+        let mut first = SimRng::new(42);
+        let mut second = SimRng::new(42);
+        let mut other = SimRng::new(43);
+        let sequence: Vec<u64> = (0..8).map(|_| first.next_u64()).collect();
+        assert_eq!(
+            sequence,
+            (0..8).map(|_| second.next_u64()).collect::<Vec<_>>()
+        );
+        assert_ne!(
+            sequence,
+            (0..8).map(|_| other.next_u64()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn stays_within_ranges() {
+        let mut rng = SimRng::new(7);
+        for _ in 0..1_000 {
+            assert!((10..=20).contains(&rng.range(10, 20)));
+            assert!((0.0..1.0).contains(&rng.next_f64()));
+        }
+        assert_eq!(rng.range(5, 5), 5);
+        assert!(!rng.chance(0.0));
+    }
+
+    #[test]
+    fn draws_exponential_waits_with_the_given_mean() {
+        let mut rng = SimRng::new(1);
+        let total: u64 = (0..10_000).map(|_| rng.exponential(1_000)).sum();
+        let mean = total as f64 / 10_000.0;
+        assert!((950.0..1_050.0).contains(&mean), "mean {}", mean);
+    }
+}
+
+mod simulation_config {
+    use super::*;
+
+    #[test]
+    fn accepts_the_default() {
+        assert_eq!(SimulationConfig::default().check(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_out_of_range_settings() {
+        let configs = vec![
+            SimulationConfig {
+                block_interval_ms: 0,
+                ..SimulationConfig::default()
+            },
+            SimulationConfig {
+                min_latency_ms: 300,
+                max_latency_ms: 200,
+                ..SimulationConfig::default()
+            },
+            SimulationConfig {
+                loss: 1.0,
+                ..SimulationConfig::default()
+            },
+            SimulationConfig {
+                sync_interval_ms: Some(0),
+                ..SimulationConfig::default()
+            },
+            SimulationConfig {
+                nodes: vec![SimNode::selfish(1.0)],
+                ..SimulationConfig::default()
+            },
+            SimulationConfig {
+                nodes: vec![SimNode::honest(0.0)],
+                ..SimulationConfig::default()
+            },
+        ];
+        for config in configs {
+            assert!(Simulator::new(config).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_partitions_of_unknown_nodes() {
+        let config = SimulationConfig {
+            partitions: vec![Partition {
+                start_ms: 0,
+                end_ms: 10,
+                groups: vec![vec![0, 4]],
+            }],
+            ..SimulationConfig::default()
+        };
+        assert_eq!(
+            config.check(),
+            Err(String::from("partition names unknown node 4"))
+        );
+    }
+}
+
+mod partition {
+    use super::*;
+
+    #[test]
+    fn separates_groups_only_while_active() {
+        let partition = Partition {
+            start_ms: 100,
+            end_ms: 200,
+            groups: vec![vec![0, 1], vec![2]],
+        };
+        assert!(partition.separates(150, 0, 2));
+        assert!(!partition.separates(150, 0, 1));
+        assert!(!partition.separates(99, 0, 2));
+        assert!(!partition.separates(200, 0, 2));
+        assert!(partition.separates(150, 2, 3));
+        assert!(!partition.separates(150, 3, 4));
+    }
+}
+
+mod simulator {
+    use super::*;
+
+    #[test]
+    fn replays_a_run_from_its_seed() {
+        let config = SimulationConfig {
+            seed: 9,
+            loss: 0.1,
+            sync_interval_ms: Some(20_000),
+            ..SimulationConfig::default()
+        };
+        let first = simulate(config.clone(), 10 * MINUTE_MS);
+        let second = simulate(config.clone(), 10 * MINUTE_MS);
+        assert_eq!(first.report(), second.report());
+        assert_eq!(first.mined(), second.mined());
+        assert_eq!(first.reorgs(), second.reorgs());
+        let other = simulate(SimulationConfig { seed: 10, ..config }, 10 * MINUTE_MS);
+        assert_ne!(first.mined(), other.mined());
+    }
+
+    #[test]
+    fn honest_nodes_converge() {
+        let simulator = simulate(SimulationConfig::default(), 20 * MINUTE_MS);
+        let report = simulator.report();
+        assert!(report.converged);
+        assert!(
+            report.best_height > BlockHeight(50),
+            "height {}",
+            report.best_height
+        );
+        assert!(
+            report.orphan_rate < 0.1,
+            "orphan rate {}",
+            report.orphan_rate
+        );
+        assert!(report.max_reorg_depth <= 1);
+        assert_eq!(simulator.blockchain(3).chain, simulator.best_chain());
+    }
+
+    #[test]
+    fn slow_networks_orphan_more_blocks() {
+        let fast = simulate(SimulationConfig::default(), 20 * MINUTE_MS);
+        let slow = simulate(
+            SimulationConfig {
+                min_latency_ms: 3_000,
+                max_latency_ms: 8_000,
+                ..SimulationConfig::default()
+            },
+            20 * MINUTE_MS,
+        );
+        assert!(slow.orphan_rate() > fast.orphan_rate());
+        assert!(!slow.reorgs().is_empty());
+    }
+
+    #[test]
+    fn partitioned_halves_diverge_and_reorg_when_healed() {
+        let heal_ms = 5 * MINUTE_MS;
+        let mut simulator = Simulator::new(SimulationConfig {
+            seed: 3,
+            partitions: vec![Partition {
+                start_ms: MINUTE_MS,
+                end_ms: heal_ms,
+                groups: vec![vec![0, 1], vec![2, 3]],
+            }],
+            ..SimulationConfig::default()
+        })
+        .unwrap();
+        simulator.run_until(heal_ms - 1);
+        assert_ne!(
+            simulator.blockchain(0).last_block(),
+            simulator.blockchain(2).last_block()
+        );
+        simulator.run_until(heal_ms + MINUTE_MS);
+        simulator.stop_mining();
+        simulator.run_for(MINUTE_MS);
+        assert!(simulator.converged());
+        let healing: Vec<&ReorgRecord> = simulator
+            .reorgs()
+            .iter()
+            .filter(|reorg| reorg.at_ms >= heal_ms)
+            .collect();
+        assert!(healing.iter().any(|reorg| reorg.depth >= 2));
+        assert!(simulator.report().messages_lost > 0);
+    }
+
+    #[test]
+    fn periodic_sync_recovers_from_message_loss() {
+        let simulator = simulate(
+            SimulationConfig {
+                seed: 5,
+                loss: 0.4,
+                sync_interval_ms: Some(15_000),
+                ..SimulationConfig::default()
+            },
+            10 * MINUTE_MS,
+        );
+        let report = simulator.report();
+        assert!(report.messages_lost > 0);
+        assert!(report.converged);
+    }
+
+    #[test]
+    fn selfish_mining_earns_more_than_its_hash_power() {
+        let nodes = |strategy: fn(f64) -> SimNode| {
+            return vec![
+                strategy(0.4),
+                SimNode::honest(0.2),
+                SimNode::honest(0.2),
+                SimNode::honest(0.2),
+            ];
+        };
+        let config = SimulationConfig {
+            seed: 11,
+            min_latency_ms: 10,
+            max_latency_ms: 50,
+            nodes: nodes(SimNode::selfish),
+            ..SimulationConfig::default()
+        };
+        let selfish = simulate(config.clone(), 60 * MINUTE_MS);
+        let honest = simulate(
+            SimulationConfig {
+                nodes: nodes(SimNode::honest),
+                ..config
+            },
+            60 * MINUTE_MS,
+        );
+        assert!(selfish.report().converged);
+        assert!(selfish.share_of(0) > 0.4, "share {}", selfish.share_of(0));
+        assert!(selfish.share_of(0) > honest.share_of(0));
+        assert!(selfish.orphan_rate() > honest.orphan_rate());
+        assert!(selfish.max_reorg_depth() >= 2);
+    }
+}
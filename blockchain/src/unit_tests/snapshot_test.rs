@@ -0,0 +1,190 @@
+use crate::{
+    block::Block,
+    blockchain::Blockchain,
+    index::ChainIndex,
+    snapshot::Snapshot,
+    transaction::{OutPoint, Transaction, TxOutput},
+    types::{BlockHeight, Hash256},
+};
+
+use crypto::keys::KeyPair;
+use tempfile::tempdir;
+
+fn spending_chain(key_pair: &KeyPair) -> Blockchain {
+    let mut blockchain = Blockchain::new();
+    blockchain.mine_transactions(&key_pair.address()).unwrap();
+    let coinbase = Transaction::decode_payload(&blockchain.chain[1].data).unwrap()[0].clone();
+    let outpoint = OutPoint {
+        txid: coinbase.txid(),
+        index: 0,
+    };
+    let outputs = vec![TxOutput {
+        amount: 20,
+        address: "cd".repeat(32),
+    }];
+    let payment = Transaction::new_signed(&[(outpoint, key_pair)], outputs, 0);
+    blockchain.submit_transaction(payment).unwrap();
+    blockchain.mine_transactions(&"ab".repeat(32)).unwrap();
+    return blockchain;
+}
+
+fn started_from(snapshot: &Snapshot) -> Blockchain {
+    let chain = vec![snapshot.block.clone()];
+    return Blockchain::from_snapshot(snapshot.clone(), chain, ChainIndex::new()).unwrap();
+}
+
+mod state_root {
+    use super::*;
+
+    #[test]
+    fn add_block_commits_to_the_ledger_after_the_block() {
+        let blockchain = spending_chain(&KeyPair::generate());
+        let expected = blockchain.ledger().unwrap().commitment();
+        assert_eq!(blockchain.last_block().state_root, Some(expected));
+    }
+
+    #[test]
+    fn blocks_without_a_state_root_are_still_accepted() {
+        let mut blockchain = Blockchain::new();
+        let block = Block::mine_block(blockchain.last_block(), String::from("no state"));
+        assert!(blockchain.submit_block(block));
+    }
+
+    #[test]
+    fn rejects_block_with_wrong_state_root() {
+        let mut blockchain = Blockchain::new();
+        let block = Block::mine_block_with_state(
+            blockchain.last_block(),
+            String::from("wrong state"),
+            Some(Hash256([7; 32])),
+        );
+        assert!(!blockchain.submit_block(block));
+    }
+
+    #[test]
+    fn state_root_is_covered_by_the_block_hash() {
+        let blockchain = spending_chain(&KeyPair::generate());
+        let mut chain = blockchain.chain.clone();
+        chain[2].state_root = None;
+        assert!(!Blockchain::is_valid_chain(&chain));
+    }
+}
+
+mod verify {
+    use super::*;
+
+    #[test]
+    fn accepts_snapshot_matching_the_state_root() {
+        let blockchain = spending_chain(&KeyPair::generate());
+        let snapshot = blockchain.snapshot(BlockHeight(2)).unwrap();
+        assert_eq!(snapshot.height, BlockHeight(2));
+        assert_eq!(snapshot.verify().unwrap(), blockchain.ledger().unwrap());
+    }
+
+    #[test]
+    fn takes_snapshots_below_the_tip() {
+        let blockchain = spending_chain(&KeyPair::generate());
+        let snapshot = blockchain.snapshot(BlockHeight(1)).unwrap();
+        assert_eq!(snapshot.block, blockchain.chain[1]);
+        assert_eq!(snapshot.utxos.len(), 1);
+        assert!(snapshot.verify().is_ok());
+    }
+
+    #[test]
+    fn rejects_tampered_utxos() {
+        let blockchain = spending_chain(&KeyPair::generate());
+        let mut snapshot = blockchain.snapshot(BlockHeight(2)).unwrap();
+        snapshot.utxos[0].1.amount += 1;
+        assert!(snapshot.verify().is_err());
+        snapshot = blockchain.snapshot(BlockHeight(2)).unwrap();
+        let duplicate = snapshot.utxos[0].clone();
+        snapshot.utxos.push(duplicate);
+        assert!(snapshot.verify().is_err());
+    }
+
+    #[test]
+    fn requires_a_block_with_a_state_root() {
+        let blockchain = Blockchain::new();
+        assert!(blockchain.snapshot(BlockHeight(0)).is_err());
+        assert!(blockchain.snapshot(BlockHeight(5)).is_err());
+    }
+
+    #[test]
+    fn checks_the_trusted_checkpoint() {
+        let blockchain = spending_chain(&KeyPair::generate());
+        let snapshot = blockchain.snapshot(BlockHeight(2)).unwrap();
+        let hash = blockchain.chain[2].hash;
+        assert!(snapshot.verify_checkpoint(BlockHeight(2), &hash).is_ok());
+        assert!(snapshot.verify_checkpoint(BlockHeight(1), &hash).is_err());
+        assert!(snapshot.verify_checkpoint(BlockHeight(2), &Hash256::ZERO).is_err());
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("snapshot.json");
+        let snapshot = spending_chain(&KeyPair::generate()).snapshot(BlockHeight(2)).unwrap();
+        snapshot.save(&path).unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap(), snapshot);
+    }
+}
+
+mod from_snapshot {
+    use super::*;
+
+    #[test]
+    fn continues_from_the_snapshot_height() {
+        let key_pair = KeyPair::generate();
+        let source = spending_chain(&key_pair);
+        let mut blockchain = started_from(&source.snapshot(BlockHeight(2)).unwrap());
+        assert_eq!(blockchain.base_height(), BlockHeight(2));
+        assert_eq!(blockchain.height(), BlockHeight(2));
+        assert_eq!(blockchain.ledger().unwrap(), source.ledger().unwrap());
+        assert!(blockchain.block_at(BlockHeight(1)).is_none());
+
+        blockchain.mine_transactions(&key_pair.address()).unwrap();
+        assert_eq!(blockchain.height(), BlockHeight(3));
+        let coinbase = Transaction::decode_payload(&blockchain.last_block().data).unwrap();
+        assert_eq!(coinbase[0].lock_height, 3);
+        let (location, _) = blockchain.find_transaction(&coinbase[0].txid()).unwrap();
+        assert_eq!(location.height, BlockHeight(3));
+        assert_eq!(
+            blockchain.find_block(&blockchain.chain[1].hash).unwrap().0,
+            BlockHeight(3)
+        );
+    }
+
+    #[test]
+    fn validates_blocks_forward_from_the_snapshot() {
+        let mut source = spending_chain(&KeyPair::generate());
+        let snapshot = source.snapshot(BlockHeight(1)).unwrap();
+        source.mine_transactions(&"ef".repeat(32)).unwrap();
+        let mut blockchain = started_from(&snapshot);
+        blockchain.replace_chain(source.chain[1..].to_vec()).unwrap();
+        assert_eq!(blockchain.height(), source.height());
+        assert_eq!(blockchain.ledger().unwrap(), source.ledger().unwrap());
+
+        let mut tampered = started_from(&snapshot);
+        let mut chain = source.chain[1..].to_vec();
+        chain[1].data = String::from("forged");
+        assert!(tampered.replace_chain(chain).is_err());
+        assert_eq!(tampered.height(), BlockHeight(1));
+    }
+
+    #[test]
+    fn rejects_chain_that_does_not_start_at_the_snapshot() {
+        let source = spending_chain(&KeyPair::generate());
+        let snapshot = source.snapshot(BlockHeight(2)).unwrap();
+        let result = Blockchain::from_snapshot(snapshot, source.chain.clone(), ChainIndex::new());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn snapshots_again_above_the_base() {
+        let source = spending_chain(&KeyPair::generate());
+        let mut blockchain = started_from(&source.snapshot(BlockHeight(1)).unwrap());
+        blockchain.replace_chain(source.chain[1..].to_vec()).unwrap();
+        assert_eq!(blockchain.snapshot(BlockHeight(2)).unwrap(), source.snapshot(BlockHeight(2)).unwrap());
+        assert!(blockchain.snapshot(BlockHeight(0)).is_err());
+    }
+}
@@ -0,0 +1,400 @@
+use crate::{
+    blockchain::Blockchain,
+    index::ChainIndex,
+    ledger::Ledger,
+    sled_store::{SledStore, SLED_DIR},
+    store::*,
+    transaction::{OutPoint, Transaction, TxOutput},
+    types::BlockHeight,
+};
+
+use crypto::keys::KeyPair;
+use tempfile::tempdir;
+
+fn setup() -> Blockchain {
+    let mut blockchain = Blockchain::new();
+    blockchain.add_block(String::from("Raccoons are cool")).unwrap();
+    blockchain.add_block(String::from("Skunks smell bad")).unwrap();
+    return blockchain;
+}
+
+mod file_store {
+    use super::*;
+
+    #[test]
+    fn save_then_load_round_trips_chain() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let blockchain = setup();
+        assert!(!store.exists());
+        store.save(&blockchain.chain).unwrap();
+        assert!(store.exists());
+        assert_eq!(store.load().unwrap(), blockchain.chain);
+    }
+
+    #[test]
+    fn save_overwrites_previous_chain() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        store.save(&setup().chain).unwrap();
+        let replacement = Blockchain::new();
+        store.save(&replacement.chain).unwrap();
+        assert_eq!(store.load().unwrap(), replacement.chain);
+    }
+
+    #[test]
+    fn append_adds_block_to_end_of_chain() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let mut blockchain = setup();
+        store.save(&blockchain.chain).unwrap();
+        blockchain.add_block(String::from("Bears are big")).unwrap();
+        store.append(blockchain.last_block()).unwrap();
+        assert_eq!(store.load().unwrap(), blockchain.chain);
+    }
+
+    #[test]
+    fn save_index_then_load_index_round_trips() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let blockchain = setup();
+        assert!(store.load_index().is_err());
+        store.save_index(blockchain.index()).unwrap();
+        assert!(store.index_path().exists());
+        assert_eq!(&store.load_index().unwrap(), blockchain.index());
+    }
+
+    #[test]
+    fn load_fails_for_missing_chain() {
+        let dir = tempdir().unwrap();
+        assert!(FileStore::new(dir.path()).load().is_err());
+    }
+
+    #[test]
+    fn load_fails_for_corrupt_chain() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        std::fs::write(store.path(), "not a block\n").unwrap();
+        assert!(store.load().is_err());
+    }
+}
+
+// Chain whose last block spends the coinbase mined at height 1.
+fn spending_chain() -> Blockchain {
+    let key_pair = KeyPair::generate();
+    let mut blockchain = Blockchain::new();
+    blockchain.mine_transactions(&key_pair.address()).unwrap();
+    let (outpoint, _) = blockchain.ledger().unwrap().unspent(&key_pair.address())[0].clone();
+    let outputs = vec![
+        TxOutput {
+            amount: 30,
+            address: "cd".repeat(32),
+        },
+        TxOutput {
+            amount: 20,
+            address: key_pair.address(),
+        },
+    ];
+    let payment = Transaction::new_signed(&[(outpoint, &key_pair)], outputs, 0);
+    blockchain.submit_transaction(payment).unwrap();
+    blockchain.mine_transactions(&"ab".repeat(32)).unwrap();
+    return blockchain;
+}
+
+fn assert_stored(store: &dyn ChainStore, blockchain: &Blockchain) {
+    assert!(store.exists());
+    assert_eq!(store.load().unwrap(), blockchain.chain);
+    let headers: Vec<_> = blockchain
+        .chain
+        .iter()
+        .map(|block| block.header())
+        .collect();
+    assert_eq!(store.load_headers().unwrap(), headers);
+    assert_eq!(&store.load_index().unwrap(), blockchain.index());
+    assert_eq!(store.load_ledger(&Ledger::new()).unwrap(), blockchain.ledger().unwrap());
+}
+
+fn commits_chain_and_reloads_everything(backend: StoreBackend) {
+    let dir = tempdir().unwrap();
+    let blockchain = spending_chain();
+    let store = open_store(backend, dir.path()).unwrap();
+    assert!(!store.exists());
+    store
+        .commit_chain(&blockchain.chain, blockchain.index())
+        .unwrap();
+    assert_stored(store.as_ref(), &blockchain);
+}
+
+fn commits_blocks_one_at_a_time(backend: StoreBackend) {
+    let dir = tempdir().unwrap();
+    let store = open_store(backend, dir.path()).unwrap();
+    let full = spending_chain();
+    let mut blockchain = Blockchain::new();
+    store
+        .commit_chain(&blockchain.chain, blockchain.index())
+        .unwrap();
+    for block in full.chain[1..].iter() {
+        assert!(blockchain.submit_block(block.clone()));
+        store
+            .commit_block(blockchain.last_block(), blockchain.index())
+            .unwrap();
+    }
+    assert_stored(store.as_ref(), &full);
+}
+
+fn commit_chain_rolls_back_reorged_blocks(backend: StoreBackend) {
+    let dir = tempdir().unwrap();
+    let store = open_store(backend, dir.path()).unwrap();
+    let mut blockchain = spending_chain();
+    store
+        .commit_chain(&blockchain.chain, blockchain.index())
+        .unwrap();
+    let mut fork = Blockchain::from_chain(blockchain.chain[..2].to_vec());
+    for _ in 0..3 {
+        fork.mine_transactions(&"ef".repeat(32)).unwrap();
+    }
+    blockchain.replace_chain(fork.chain.clone()).unwrap();
+    assert_eq!(blockchain.chain, fork.chain);
+    store
+        .commit_chain(&blockchain.chain, blockchain.index())
+        .unwrap();
+    assert_stored(store.as_ref(), &blockchain);
+    assert!(store
+        .load_index()
+        .unwrap()
+        .address_transactions(&"cd".repeat(32))
+        .is_empty());
+}
+
+// A block whose second payment spends an output of its first.
+fn rolls_back_spends_within_a_block(backend: StoreBackend) {
+    let dir = tempdir().unwrap();
+    let store = open_store(backend, dir.path()).unwrap();
+    let key_pair = KeyPair::generate();
+    let payee = KeyPair::generate();
+    let mut blockchain = Blockchain::new();
+    blockchain.mine_transactions(&key_pair.address()).unwrap();
+    let before = blockchain.ledger().unwrap();
+    let (outpoint, _) = before.unspent(&key_pair.address())[0].clone();
+    let payment = Transaction::new_signed(
+        &[(outpoint, &key_pair)],
+        vec![TxOutput {
+            amount: 50,
+            address: payee.address(),
+        }],
+        0,
+    );
+    let forward = Transaction::new_signed(
+        &[(
+            OutPoint {
+                txid: payment.txid(),
+                index: 0,
+            },
+            &payee,
+        )],
+        vec![TxOutput {
+            amount: 50,
+            address: "cd".repeat(32),
+        }],
+        0,
+    );
+    blockchain.submit_transaction(payment).unwrap();
+    blockchain.submit_transaction(forward).unwrap();
+    blockchain.mine_transactions(&"ab".repeat(32)).unwrap();
+    assert_eq!(
+        Transaction::decode_payload(&blockchain.last_block().data)
+            .unwrap()
+            .len(),
+        3
+    );
+    store
+        .commit_chain(&blockchain.chain, blockchain.index())
+        .unwrap();
+
+    let previous = Blockchain::from_chain(blockchain.chain[..2].to_vec());
+    store
+        .commit_chain(&previous.chain, previous.index())
+        .unwrap();
+    assert_eq!(store.load_ledger(&Ledger::new()).unwrap(), before);
+    assert_stored(store.as_ref(), &previous);
+}
+
+fn commits_snapshot_and_extends_it(backend: StoreBackend) {
+    let dir = tempdir().unwrap();
+    let source = spending_chain();
+    let snapshot = source.snapshot(BlockHeight(1)).unwrap();
+    let chain = vec![snapshot.block.clone()];
+    let mut blockchain =
+        Blockchain::from_snapshot(snapshot.clone(), chain, ChainIndex::new()).unwrap();
+    let store = open_store(backend, dir.path()).unwrap();
+    store
+        .commit_snapshot(&snapshot, blockchain.index())
+        .unwrap();
+    assert_eq!(store.load_snapshot().unwrap(), Some(snapshot.clone()));
+    assert_stored(store.as_ref(), &blockchain);
+    blockchain.replace_chain(source.chain[1..].to_vec()).unwrap();
+    store
+        .commit_chain(&blockchain.chain, blockchain.index())
+        .unwrap();
+    assert_eq!(store.load_snapshot().unwrap(), Some(snapshot));
+    assert_stored(store.as_ref(), &blockchain);
+    assert_eq!(store.load_ledger(&Ledger::new()).unwrap(), source.ledger().unwrap());
+}
+
+fn prunes_bodies_and_keeps_headers(backend: StoreBackend) {
+    let dir = tempdir().unwrap();
+    let mut blockchain = spending_chain();
+    blockchain.mine_transactions(&"ef".repeat(32)).unwrap();
+    let headers: Vec<_> = blockchain.chain.iter().map(|block| block.header()).collect();
+    let store = open_store(backend, dir.path()).unwrap();
+    store
+        .commit_chain(&blockchain.chain, blockchain.index())
+        .unwrap();
+    assert_eq!(blockchain.prune(1).unwrap(), Some(BlockHeight(2)));
+    store
+        .prune(blockchain.base().unwrap(), blockchain.index())
+        .unwrap();
+    blockchain.mine_transactions(&"ab".repeat(32)).unwrap();
+    store
+        .commit_block(blockchain.last_block(), blockchain.index())
+        .unwrap();
+    assert_eq!(store.load().unwrap(), blockchain.chain);
+    assert_eq!(store.load_snapshot().unwrap().as_ref(), blockchain.base());
+    let mut expected = headers[..2].to_vec();
+    expected.extend(blockchain.chain.iter().map(|block| block.header()));
+    assert_eq!(store.load_headers().unwrap(), expected);
+    assert_eq!(&store.load_index().unwrap(), blockchain.index());
+    assert_eq!(store.load_ledger(&Ledger::new()).unwrap(), blockchain.ledger().unwrap());
+}
+
+// A store fast synced from a snapshot has no headers below it, so after
+// pruning it must still reload the way the node does on startup.
+fn prunes_a_store_started_from_a_snapshot(backend: StoreBackend) {
+    let dir = tempdir().unwrap();
+    let source = spending_chain();
+    let snapshot = source.snapshot(BlockHeight(1)).unwrap();
+    let chain = vec![snapshot.block.clone()];
+    let mut blockchain =
+        Blockchain::from_snapshot(snapshot.clone(), chain, ChainIndex::new()).unwrap();
+    let store = open_store(backend, dir.path()).unwrap();
+    store
+        .commit_snapshot(&snapshot, blockchain.index())
+        .unwrap();
+    blockchain.replace_chain(source.chain[1..].to_vec()).unwrap();
+    blockchain.mine_transactions(&"ef".repeat(32)).unwrap();
+    blockchain.mine_transactions(&"ab".repeat(32)).unwrap();
+    store
+        .commit_chain(&blockchain.chain, blockchain.index())
+        .unwrap();
+    assert_eq!(blockchain.prune(1).unwrap(), Some(BlockHeight(3)));
+    store
+        .prune(blockchain.base().unwrap(), blockchain.index())
+        .unwrap();
+    assert_stored(store.as_ref(), &blockchain);
+    let chain = store.load().unwrap();
+    let headers = store.load_headers().unwrap();
+    let mut reloaded = Blockchain::from_snapshot(
+        store.load_snapshot().unwrap().unwrap(),
+        chain,
+        store.load_index().unwrap(),
+    )
+    .unwrap();
+    let pruned = headers.len() - reloaded.chain.len();
+    reloaded
+        .set_pruned_headers(headers[..pruned].to_vec())
+        .unwrap();
+    assert_eq!(reloaded.chain, blockchain.chain);
+    assert_eq!(reloaded.ledger().unwrap(), blockchain.ledger().unwrap());
+}
+
+macro_rules! conformance_tests {
+    ($name:ident, $backend:expr) => {
+        mod $name {
+            use super::*;
+
+            #[test]
+            fn commits_chain_and_reloads_everything() {
+                super::commits_chain_and_reloads_everything($backend);
+            }
+
+            #[test]
+            fn commits_blocks_one_at_a_time() {
+                super::commits_blocks_one_at_a_time($backend);
+            }
+
+            #[test]
+            fn commit_chain_rolls_back_reorged_blocks() {
+                super::commit_chain_rolls_back_reorged_blocks($backend);
+            }
+
+            #[test]
+            fn rolls_back_spends_within_a_block() {
+                super::rolls_back_spends_within_a_block($backend);
+            }
+
+            #[test]
+            fn commits_snapshot_and_extends_it() {
+                super::commits_snapshot_and_extends_it($backend);
+            }
+
+            #[test]
+            fn prunes_bodies_and_keeps_headers() {
+                super::prunes_bodies_and_keeps_headers($backend);
+            }
+
+            #[test]
+            fn prunes_a_store_started_from_a_snapshot() {
+                super::prunes_a_store_started_from_a_snapshot($backend);
+            }
+        }
+    };
+}
+
+conformance_tests!(file_conformance, StoreBackend::File);
+conformance_tests!(sled_conformance, StoreBackend::Sled);
+
+mod sled_store {
+    use super::*;
+
+    #[test]
+    fn rejects_block_that_does_not_extend_the_tip() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        let blockchain = setup();
+        store
+            .commit_chain(
+                &blockchain.chain[..2],
+                &ChainIndex::build(&blockchain.chain[..2]),
+            )
+            .unwrap();
+        assert_eq!(store.height().unwrap(), BlockHeight(2));
+        let mut other = Blockchain::new();
+        other.add_block(String::from("Bears are big")).unwrap();
+        other.add_block(String::from("Owls are wise")).unwrap();
+        assert!(store
+            .commit_block(other.last_block(), other.index())
+            .is_err());
+        assert_eq!(store.load().unwrap(), blockchain.chain[..2].to_vec());
+    }
+
+    #[test]
+    fn stores_chain_in_its_own_directory() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::open(dir.path()).unwrap();
+        assert_eq!(store.location(), dir.path().join(SLED_DIR));
+        assert!(store.location().is_dir());
+    }
+}
+
+mod store_backend {
+    use super::*;
+
+    #[test]
+    fn parses_and_displays_names() {
+        for backend in [StoreBackend::File, StoreBackend::Sled].iter() {
+            assert_eq!(backend.to_string().parse::<StoreBackend>(), Ok(*backend));
+        }
+        assert_eq!(StoreBackend::default(), StoreBackend::File);
+        assert!("rocksdb".parse::<StoreBackend>().is_err());
+    }
+}
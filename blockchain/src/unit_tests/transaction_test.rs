@@ -0,0 +1,110 @@
+use crypto::keys::{self, KeyPair};
+
+use crate::{transaction::*, types::BlockHeight};
+
+fn spend(key_pair: &KeyPair, to: &str) -> Transaction {
+    let outpoint = OutPoint {
+        txid: [7; 32],
+        index: 0,
+    };
+    let outputs = vec![TxOutput {
+        amount: 10,
+        address: String::from(to),
+    }];
+    return Transaction::new_signed(&[(outpoint, key_pair)], outputs, 0);
+}
+
+mod coinbase {
+    use super::*;
+
+    #[test]
+    fn has_no_inputs_and_pays_address() {
+        let coinbase = Transaction::coinbase(BlockHeight(3), &"ab".repeat(32), 50);
+        assert!(coinbase.is_coinbase());
+        assert_eq!(coinbase.lock_height, 3);
+        assert_eq!(coinbase.output_total(), Some(50));
+    }
+
+    #[test]
+    fn txid_differs_per_height() {
+        let address = "ab".repeat(32);
+        assert_ne!(
+            Transaction::coinbase(BlockHeight(1), &address, 50).txid(),
+            Transaction::coinbase(BlockHeight(2), &address, 50).txid()
+        );
+    }
+}
+
+mod new_signed {
+    use super::*;
+
+    #[test]
+    fn signs_sighash_with_each_input_key() {
+        let key_pair = KeyPair::generate();
+        let transaction = spend(&key_pair, &"cd".repeat(32));
+        let input = &transaction.inputs[0];
+        assert_eq!(input.public_key, key_pair.public_key().to_vec());
+        assert!(keys::verify(
+            &input.public_key,
+            &transaction.sighash(),
+            &input.signature
+        ));
+    }
+
+    #[test]
+    fn sighash_ignores_signatures_but_txid_does_not() {
+        let key_pair = KeyPair::generate();
+        let transaction = spend(&key_pair, &"cd".repeat(32));
+        let mut stripped = transaction.clone();
+        stripped.inputs[0].signature.clear();
+        assert_eq!(stripped.sighash(), transaction.sighash());
+        assert_ne!(stripped.txid(), transaction.txid());
+    }
+
+    #[test]
+    fn sighash_commits_to_outputs() {
+        let key_pair = KeyPair::generate();
+        let transaction = spend(&key_pair, &"cd".repeat(32));
+        let mut modified = transaction.clone();
+        modified.outputs[0].amount += 1;
+        assert_ne!(modified.sighash(), transaction.sighash());
+    }
+}
+
+mod encoding {
+    use super::*;
+
+    #[test]
+    fn hex_round_trips() {
+        let transaction = spend(&KeyPair::generate(), &"cd".repeat(32));
+        let decoded = Transaction::decode_hex(&transaction.encode_hex()).unwrap();
+        assert_eq!(decoded, transaction);
+    }
+
+    #[test]
+    fn decode_hex_rejects_garbage() {
+        assert!(Transaction::decode_hex("zz").is_none());
+        assert!(Transaction::decode_hex("00").is_none());
+    }
+
+    #[test]
+    fn payload_round_trips_and_ignores_plain_data() {
+        let transactions = vec![
+            Transaction::coinbase(BlockHeight(1), &"ab".repeat(32), 50),
+            spend(&KeyPair::generate(), &"cd".repeat(32)),
+        ];
+        let payload = Transaction::encode_payload(&transactions);
+        assert_eq!(Transaction::decode_payload(&payload).unwrap(), transactions);
+        assert!(Transaction::decode_payload("Raccoons are cool").is_none());
+    }
+
+    #[test]
+    fn output_total_detects_overflow() {
+        let mut transaction = Transaction::coinbase(BlockHeight(1), &"ab".repeat(32), u64::MAX);
+        transaction.outputs.push(TxOutput {
+            amount: 1,
+            address: "ab".repeat(32),
+        });
+        assert_eq!(transaction.output_total(), None);
+    }
+}
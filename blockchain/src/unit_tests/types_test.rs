@@ -0,0 +1,115 @@
+use crate::{block::Block, types::*};
+
+mod hash256 {
+    use super::*;
+
+    #[test]
+    fn serializes_as_a_hex_string() {
+        let hash = Hash256([0xab; 32]);
+        let encoded = serde_json::to_string(&hash).unwrap();
+        assert_eq!(encoded, format!("\"{}\"", "ab".repeat(32)));
+        assert_eq!(serde_json::from_str::<Hash256>(&encoded).unwrap(), hash);
+    }
+
+    #[test]
+    fn refuses_to_deserialize_bad_hex() {
+        assert!(serde_json::from_str::<Hash256>("\"abcd\"").is_err());
+        assert!(serde_json::from_str::<Hash256>(&format!("\"{}\"", "zz".repeat(32))).is_err());
+    }
+}
+
+mod block_height {
+    use super::*;
+
+    #[test]
+    fn counts_blocks_between_heights() {
+        let height = BlockHeight(7);
+        assert_eq!(height.next(), BlockHeight(8));
+        assert_eq!(height + 3, BlockHeight(10));
+        assert_eq!(height - BlockHeight(2), 5);
+        assert_eq!(height.checked_sub(7), Some(BlockHeight::GENESIS));
+        assert_eq!(height.checked_sub(8), None);
+        assert_eq!(BlockHeight::from(7usize), height);
+        assert_eq!(height.index(), 7);
+    }
+
+    #[test]
+    fn displays_and_parses_as_decimal() {
+        assert_eq!(BlockHeight(42).to_string(), "42");
+        assert_eq!("42".parse::<BlockHeight>(), Ok(BlockHeight(42)));
+        assert!("-1".parse::<BlockHeight>().is_err());
+        assert_eq!(
+            "ab".parse::<BlockHeight>(),
+            Err(String::from(
+                "invalid block height \"ab\": invalid digit found in string"
+            ))
+        );
+    }
+
+    #[test]
+    fn orders_by_height() {
+        let mut heights = vec![BlockHeight(3), BlockHeight::GENESIS, BlockHeight(1)];
+        heights.sort();
+        assert_eq!(
+            heights,
+            vec![BlockHeight::GENESIS, BlockHeight(1), BlockHeight(3)]
+        );
+    }
+}
+
+mod difficulty {
+    use super::*;
+
+    #[test]
+    fn steps_up_and_down() {
+        assert_eq!(Difficulty(8) + 1, Difficulty(9));
+        assert_eq!(Difficulty(8) - 1, Difficulty(7));
+        assert!(Difficulty(8) < Difficulty(9));
+        assert_eq!(Difficulty(8).bits(), 8);
+    }
+
+    #[test]
+    fn displays_and_parses_as_decimal() {
+        assert_eq!(Difficulty(12).to_string(), "12");
+        assert_eq!("12".parse::<Difficulty>(), Ok(Difficulty(12)));
+        assert!("twelve".parse::<Difficulty>().is_err());
+    }
+}
+
+mod nonce {
+    use super::*;
+
+    #[test]
+    fn holds_64_bits_on_every_platform() {
+        let nonce = Nonce(u64::MAX);
+        assert_eq!(nonce.to_string(), "18446744073709551615");
+        assert_eq!("18446744073709551615".parse::<Nonce>(), Ok(nonce));
+        assert!("18446744073709551616".parse::<Nonce>().is_err());
+    }
+}
+
+mod serialization {
+    use super::*;
+
+    #[test]
+    fn keeps_the_block_encoding() {
+        let mut block = Block::genesis();
+        block.nonce = Nonce(u64::MAX);
+        let encoded = serde_json::to_value(&block).unwrap();
+        assert_eq!(encoded["nonce"], serde_json::json!(u64::MAX));
+        assert_eq!(encoded["difficulty"], serde_json::json!(8));
+        assert_eq!(encoded["hash"], "ff".repeat(32));
+        let decoded: Block = serde_json::from_value(encoded).unwrap();
+        assert_eq!(decoded, block);
+    }
+
+    #[test]
+    fn encodes_heights_as_numbers() {
+        let encoded = serde_json::to_string(&BlockHeight(5)).unwrap();
+        assert_eq!(encoded, "5");
+        assert_eq!(
+            serde_json::from_str::<BlockHeight>(&encoded).unwrap(),
+            BlockHeight(5)
+        );
+    }
+}
@@ -0,0 +1,38 @@
+#![allow(clippy::needless_return)]
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crypto::{cryptohash, hasher::HashAlgorithm};
+use std::collections::BTreeMap;
+
+const SIZES: [usize; 4] = [64, 1024, 16 * 1024, 256 * 1024];
+
+fn data_map(size: usize) -> BTreeMap<String, String> {
+    let mut data_map = BTreeMap::<String, String>::new();
+    data_map.insert(String::from("data"), "a".repeat(size));
+    return data_map;
+}
+
+// Block hashes format the data map before digesting it, so this measures
+// both against the raw digest.
+fn throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hash_throughput");
+    for size in SIZES.iter() {
+        let data_map = data_map(*size);
+        let bytes = vec![0xab_u8; *size];
+        group.throughput(Throughput::Bytes(*size as u64));
+        group.bench_function(BenchmarkId::new("cryptohash_hash", size), |b| {
+            let mut hash: [u8; 32] = [0; 32];
+            b.iter(|| cryptohash::hash(black_box(&data_map), &mut hash))
+        });
+        for algorithm in HashAlgorithm::ALL.iter() {
+            let id = format!("digest_{}", algorithm);
+            group.bench_function(BenchmarkId::new(id, size), |b| {
+                b.iter(|| algorithm.hasher().digest(black_box(&bytes)))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
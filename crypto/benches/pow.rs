@@ -0,0 +1,53 @@
+#![allow(clippy::needless_return)]
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use crypto::{
+    cryptohash,
+    hasher::{Argon2Hasher, Argon2Params, ChainHasher, HashAlgorithm},
+};
+use std::collections::BTreeMap;
+
+fn block_data_map() -> BTreeMap<String, String> {
+    let mut data_map = BTreeMap::<String, String>::new();
+    data_map.insert(
+        String::from("timestamp"),
+        String::from("SystemTime { tv_sec: 1700000000, tv_nsec: 0 }"),
+    );
+    data_map.insert(String::from("last_hash"), format!("{:?}", [255u8; 32]));
+    data_map.insert(String::from("data"), "ab".repeat(512));
+    data_map.insert(String::from("nonce"), String::from("4242"));
+    data_map.insert(String::from("difficulty"), String::from("12"));
+    return data_map;
+}
+
+// Verifying a block's proof of work is one hash of its contents plus the
+// difficulty check, so this is the per-block cost of validating a chain.
+fn verify(hasher: &dyn ChainHasher, data_map: &BTreeMap<String, String>) -> bool {
+    let mut hash: [u8; 32] = [0; 32];
+    cryptohash::hash_with(hasher, data_map, &mut hash);
+    return cryptohash::is_valid_hash(&hash, 12);
+}
+
+fn verification(c: &mut Criterion) {
+    let data_map = block_data_map();
+    let mut group = c.benchmark_group("verify_block_pow");
+    for algorithm in HashAlgorithm::ALL.iter() {
+        group.bench_function(BenchmarkId::new("hash", algorithm), |b| {
+            b.iter(|| verify(algorithm.hasher(), black_box(&data_map)))
+        });
+    }
+    for memory_kib in [64, 1024, 4 * 1024, 16 * 1024].iter() {
+        let params = Argon2Params {
+            memory_kib: *memory_kib,
+            ..Argon2Params::default()
+        };
+        let hasher = Argon2Hasher::new(HashAlgorithm::Sha256, &params).unwrap();
+        group.bench_function(BenchmarkId::new("argon2_kib", memory_kib), |b| {
+            b.iter(|| verify(&hasher, black_box(&data_map)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, verification);
+criterion_main!(benches);
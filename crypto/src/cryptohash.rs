@@ -1,12 +1,10 @@
 // use crypto::{digest::Digest, sha2::Sha256};
+use crate::hasher::{ChainHasher, Sha256Hasher};
+
 use sha2::{Digest, Sha256};
 use std::collections::BTreeMap;
 
 
-pub struct Sha256Hash {
-    pub hash: [u8; 32]
-}
-
 pub fn is_valid_hash(hash: &[u8], difficulty: usize) -> bool {
     if hash.len() != 32 {
         return false;
@@ -28,14 +26,39 @@ pub fn is_valid_hash(hash: &[u8], difficulty: usize) -> bool {
 }
 
 pub fn hash(data_map: &BTreeMap<String, String>, hashed_data: &mut [u8]) {
-    let mut data_str = String::from("|");
+    hash_with(&Sha256Hasher, data_map, hashed_data);
+}
+
+pub fn hash_with(
+    hasher: &dyn ChainHasher,
+    data_map: &BTreeMap<String, String>,
+    hashed_data: &mut [u8],
+) {
+    hashed_data.copy_from_slice(&hasher.digest(&preimage(data_map)));
+}
+
+// What `hash_with` digests: "|" followed by " key:value |" for each entry in
+// key order.
+pub fn preimage(data_map: &BTreeMap<String, String>) -> Vec<u8> {
+    let mut preimage = b"|".to_vec();
     for (key, value) in data_map {
-        data_str.push_str(&format!(" {}:{} |", key, value));
+        push_entry(&mut preimage, key, value.as_bytes());
     }
-    println!("HASHING THE FOLLOWING STRING: {}", data_str);
-    let mut sha = Sha256::new();
-    sha.input(&data_str);
-    hashed_data.copy_from_slice(sha.result().as_slice());
+    return preimage;
+}
+
+pub fn push_entry(preimage: &mut Vec<u8>, key: &str, value: &[u8]) {
+    preimage.push(b' ');
+    preimage.extend_from_slice(key.as_bytes());
+    preimage.push(b':');
+    preimage.extend_from_slice(value);
+    preimage.extend_from_slice(b" |");
+}
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hashed_data: [u8; 32] = [0; 32];
+    hashed_data.copy_from_slice(Sha256::digest(data).as_slice());
+    return hashed_data;
 }
 
 
@@ -0,0 +1,75 @@
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+// A 256-bit digest such as a block hash. Displays, parses and serializes as
+// 64 lowercase hex digits.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hash256(pub [u8; 32]);
+
+impl Hash256 {
+    pub const ZERO: Hash256 = Hash256([0; 32]);
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        return &self.0;
+    }
+}
+
+impl From<[u8; 32]> for Hash256 {
+    fn from(bytes: [u8; 32]) -> Hash256 {
+        return Hash256(bytes);
+    }
+}
+
+impl From<Hash256> for [u8; 32] {
+    fn from(hash: Hash256) -> [u8; 32] {
+        return hash.0;
+    }
+}
+
+impl AsRef<[u8]> for Hash256 {
+    fn as_ref(&self) -> &[u8] {
+        return &self.0;
+    }
+}
+
+impl fmt::Display for Hash256 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", hex::encode(self.0));
+    }
+}
+
+impl fmt::Debug for Hash256 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "Hash256({})", self);
+    }
+}
+
+impl FromStr for Hash256 {
+    type Err = String;
+
+    fn from_str(hex_str: &str) -> Result<Hash256, String> {
+        let mut bytes: [u8; 32] = [0; 32];
+        match hex::decode_to_slice(hex_str, &mut bytes) {
+            Ok(()) => return Ok(Hash256(bytes)),
+            Err(_) => {
+                return Err(format!(
+                    "invalid hash {:?} (expected 64 hex digits)",
+                    hex_str
+                ))
+            }
+        }
+    }
+}
+
+impl Serialize for Hash256 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_str(&self.to_string());
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash256 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Hash256, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        return encoded.parse().map_err(D::Error::custom);
+    }
+}
@@ -0,0 +1,319 @@
+use crate::cryptohash::sha256;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sha3::{Digest, Sha3_256};
+use std::{fmt, str::FromStr};
+
+pub const ARGON2_MAX_MEMORY_KIB: u32 = 256 * 1024;
+pub const ARGON2_MAX_ITERATIONS: u32 = 16;
+pub const ARGON2_MAX_PARALLELISM: u32 = 8;
+const ARGON2_SALT: &[u8] = b"cryptochain proof of work";
+
+// Hashes block contents for a chain's block hashes and proof of work.
+pub trait ChainHasher: fmt::Debug + Send + Sync {
+    fn digest(&self, data: &[u8]) -> [u8; 32];
+
+    // The state after hashing `prefix`, for hashing many inputs that share
+    // it. `None` if this hasher cannot resume from a prefix.
+    fn midstate(&self, _prefix: &[u8]) -> Option<Box<dyn Midstate>> {
+        return None;
+    }
+}
+
+// A hash state that has absorbed a fixed prefix. Finishing it over `parts`
+// gives the digest of the prefix followed by `parts`, and leaves the state
+// ready to finish again.
+pub trait Midstate: Send + Sync {
+    fn finish(&self, parts: &[&[u8]]) -> [u8; 32];
+}
+
+#[derive(Debug)]
+pub struct Sha256Hasher;
+
+impl ChainHasher for Sha256Hasher {
+    fn digest(&self, data: &[u8]) -> [u8; 32] {
+        return sha256(data);
+    }
+
+    fn midstate(&self, prefix: &[u8]) -> Option<Box<dyn Midstate>> {
+        return Some(Box::new(Sha256Midstate(Sha256::new_with_prefix(prefix))));
+    }
+}
+
+struct Sha256Midstate(Sha256);
+
+impl Sha256Midstate {
+    fn finish_once(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut state = self.0.clone();
+        for part in parts.iter() {
+            state.update(part);
+        }
+        return state.finalize().into();
+    }
+}
+
+impl Midstate for Sha256Midstate {
+    fn finish(&self, parts: &[&[u8]]) -> [u8; 32] {
+        return self.finish_once(parts);
+    }
+}
+
+#[derive(Debug)]
+pub struct DoubleSha256Hasher;
+
+impl ChainHasher for DoubleSha256Hasher {
+    fn digest(&self, data: &[u8]) -> [u8; 32] {
+        return sha256(&sha256(data));
+    }
+
+    fn midstate(&self, prefix: &[u8]) -> Option<Box<dyn Midstate>> {
+        return Some(Box::new(DoubleSha256Midstate(Sha256Midstate(
+            Sha256::new_with_prefix(prefix),
+        ))));
+    }
+}
+
+struct DoubleSha256Midstate(Sha256Midstate);
+
+impl Midstate for DoubleSha256Midstate {
+    fn finish(&self, parts: &[&[u8]]) -> [u8; 32] {
+        return sha256(&self.0.finish_once(parts));
+    }
+}
+
+#[derive(Debug)]
+pub struct Sha3_256Hasher;
+
+impl ChainHasher for Sha3_256Hasher {
+    fn digest(&self, data: &[u8]) -> [u8; 32] {
+        let mut hashed_data: [u8; 32] = [0; 32];
+        hashed_data.copy_from_slice(Sha3_256::digest(data).as_slice());
+        return hashed_data;
+    }
+
+    fn midstate(&self, prefix: &[u8]) -> Option<Box<dyn Midstate>> {
+        return Some(Box::new(Sha3_256Midstate(Sha3_256::new_with_prefix(
+            prefix,
+        ))));
+    }
+}
+
+struct Sha3_256Midstate(Sha3_256);
+
+impl Midstate for Sha3_256Midstate {
+    fn finish(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut state = self.0.clone();
+        for part in parts.iter() {
+            state.update(part);
+        }
+        return state.finalize().into();
+    }
+}
+
+#[derive(Debug)]
+pub struct Blake3Hasher;
+
+impl ChainHasher for Blake3Hasher {
+    fn digest(&self, data: &[u8]) -> [u8; 32] {
+        return *blake3::hash(data).as_bytes();
+    }
+
+    fn midstate(&self, prefix: &[u8]) -> Option<Box<dyn Midstate>> {
+        let mut state = blake3::Hasher::new();
+        state.update(prefix);
+        return Some(Box::new(Blake3Midstate(state)));
+    }
+}
+
+struct Blake3Midstate(blake3::Hasher);
+
+impl Midstate for Blake3Midstate {
+    fn finish(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut state = self.0.clone();
+        for part in parts.iter() {
+            state.update(part);
+        }
+        return *state.finalize().as_bytes();
+    }
+}
+
+// Memory-hard proof of work: Argon2id over a digest of the block contents, so
+// verifying a block costs one Argon2 evaluation whatever its size.
+#[derive(Debug)]
+pub struct Argon2Hasher {
+    prehash: &'static dyn ChainHasher,
+    argon2: Argon2<'static>,
+}
+
+impl Argon2Hasher {
+    pub fn new(prehash: HashAlgorithm, params: &Argon2Params) -> Result<Argon2Hasher, String> {
+        params.check()?;
+        let params = match Params::new(
+            params.memory_kib,
+            params.iterations,
+            params.parallelism,
+            Some(32),
+        ) {
+            Ok(params) => params,
+            Err(e) => return Err(format!("invalid argon2 parameters: {}", e)),
+        };
+        return Ok(Argon2Hasher {
+            prehash: prehash.hasher(),
+            argon2: Argon2::new(Algorithm::Argon2id, Version::V0x13, params),
+        });
+    }
+}
+
+impl ChainHasher for Argon2Hasher {
+    fn digest(&self, data: &[u8]) -> [u8; 32] {
+        let mut hashed_data: [u8; 32] = [0; 32];
+        self.argon2
+            .hash_password_into(&self.prehash.digest(data), ARGON2_SALT, &mut hashed_data)
+            .expect("argon2 parameters are checked by Argon2Hasher::new");
+        return hashed_data;
+    }
+
+    fn midstate(&self, prefix: &[u8]) -> Option<Box<dyn Midstate>> {
+        return Some(Box::new(Argon2Midstate {
+            prehash: self.prehash.midstate(prefix)?,
+            argon2: self.argon2.clone(),
+        }));
+    }
+}
+
+struct Argon2Midstate {
+    prehash: Box<dyn Midstate>,
+    argon2: Argon2<'static>,
+}
+
+impl Midstate for Argon2Midstate {
+    fn finish(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut hashed_data: [u8; 32] = [0; 32];
+        self.argon2
+            .hash_password_into(&self.prehash.finish(parts), ARGON2_SALT, &mut hashed_data)
+            .expect("argon2 parameters are checked by Argon2Hasher::new");
+        return hashed_data;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Argon2Params {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for Argon2Params {
+    fn default() -> Argon2Params {
+        return Argon2Params {
+            memory_kib: 4 * 1024,
+            iterations: 1,
+            parallelism: 1,
+        };
+    }
+}
+
+impl Argon2Params {
+    // Bounds what a chain may demand of every node verifying its blocks.
+    pub fn check(&self) -> Result<(), String> {
+        if self.parallelism == 0 || self.parallelism > ARGON2_MAX_PARALLELISM {
+            return Err(format!(
+                "argon2 parallelism must be between 1 and {}",
+                ARGON2_MAX_PARALLELISM
+            ));
+        }
+        if self.iterations == 0 || self.iterations > ARGON2_MAX_ITERATIONS {
+            return Err(format!(
+                "argon2 iterations must be between 1 and {}",
+                ARGON2_MAX_ITERATIONS
+            ));
+        }
+        let min_memory_kib = 8 * self.parallelism;
+        if self.memory_kib < min_memory_kib || self.memory_kib > ARGON2_MAX_MEMORY_KIB {
+            return Err(format!(
+                "argon2 memory must be between {} and {} KiB",
+                min_memory_kib, ARGON2_MAX_MEMORY_KIB
+            ));
+        }
+        return Ok(());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+    #[serde(rename = "sha256")]
+    Sha256,
+    #[serde(rename = "sha256d")]
+    DoubleSha256,
+    #[serde(rename = "sha3-256")]
+    Sha3_256,
+    #[serde(rename = "blake3")]
+    Blake3,
+}
+
+impl HashAlgorithm {
+    pub const ALL: [HashAlgorithm; 4] = [
+        HashAlgorithm::Sha256,
+        HashAlgorithm::DoubleSha256,
+        HashAlgorithm::Sha3_256,
+        HashAlgorithm::Blake3,
+    ];
+
+    pub fn hasher(&self) -> &'static dyn ChainHasher {
+        match self {
+            HashAlgorithm::Sha256 => return &Sha256Hasher,
+            HashAlgorithm::DoubleSha256 => return &DoubleSha256Hasher,
+            HashAlgorithm::Sha3_256 => return &Sha3_256Hasher,
+            HashAlgorithm::Blake3 => return &Blake3Hasher,
+        }
+    }
+}
+
+impl ChainHasher for HashAlgorithm {
+    fn digest(&self, data: &[u8]) -> [u8; 32] {
+        return self.hasher().digest(data);
+    }
+
+    fn midstate(&self, prefix: &[u8]) -> Option<Box<dyn Midstate>> {
+        return self.hasher().midstate(prefix);
+    }
+}
+
+impl Default for HashAlgorithm {
+    fn default() -> HashAlgorithm {
+        return HashAlgorithm::Sha256;
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<HashAlgorithm, String> {
+        match name {
+            "sha256" => return Ok(HashAlgorithm::Sha256),
+            "sha256d" => return Ok(HashAlgorithm::DoubleSha256),
+            "sha3-256" => return Ok(HashAlgorithm::Sha3_256),
+            "blake3" => return Ok(HashAlgorithm::Blake3),
+            _ => {
+                return Err(format!(
+                    "unknown hash algorithm {} (expected sha256, sha256d, sha3-256 or blake3)",
+                    name
+                ))
+            }
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HashAlgorithm::Sha256 => return write!(f, "sha256"),
+            HashAlgorithm::DoubleSha256 => return write!(f, "sha256d"),
+            HashAlgorithm::Sha3_256 => return write!(f, "sha3-256"),
+            HashAlgorithm::Blake3 => return write!(f, "blake3"),
+        }
+    }
+}
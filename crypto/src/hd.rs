@@ -0,0 +1,234 @@
+use crate::keys::KeyPair;
+
+use bip39::Mnemonic;
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use std::{fmt, str::FromStr};
+
+pub const HARDENED_OFFSET: u32 = 0x8000_0000;
+pub const PURPOSE: u32 = 44;
+// Unregistered SLIP-44 coin type used for cryptochain addresses.
+pub const COIN_TYPE: u32 = 9_000;
+pub const GAP_LIMIT: u32 = 20;
+const MASTER_KEY_SALT: &[u8] = b"ed25519 seed";
+
+pub fn generate_mnemonic(word_count: usize) -> Result<String, String> {
+    match Mnemonic::generate(word_count) {
+        Ok(mnemonic) => return Ok(mnemonic.to_string()),
+        Err(e) => return Err(format!("cannot generate mnemonic: {}", e)),
+    }
+}
+
+pub fn mnemonic_from_entropy(entropy: &[u8]) -> Result<String, String> {
+    match Mnemonic::from_entropy(entropy) {
+        Ok(mnemonic) => return Ok(mnemonic.to_string()),
+        Err(e) => return Err(format!("invalid entropy: {}", e)),
+    }
+}
+
+pub fn mnemonic_to_seed(phrase: &str, passphrase: &str) -> Result<[u8; 64], String> {
+    match Mnemonic::parse(phrase) {
+        Ok(mnemonic) => return Ok(mnemonic.to_seed(passphrase)),
+        Err(e) => return Err(format!("invalid mnemonic: {}", e)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivationPath {
+    indexes: Vec<u32>,
+}
+
+impl DerivationPath {
+    pub fn master() -> DerivationPath {
+        return DerivationPath {
+            indexes: Vec::new(),
+        };
+    }
+
+    // m/44'/coin'/account'/0'/index' -- SLIP-10 ed25519 only allows hardened children.
+    pub fn for_address(account: u32, index: u32) -> DerivationPath {
+        return DerivationPath {
+            indexes: vec![
+                PURPOSE | HARDENED_OFFSET,
+                COIN_TYPE | HARDENED_OFFSET,
+                account | HARDENED_OFFSET,
+                HARDENED_OFFSET,
+                index | HARDENED_OFFSET,
+            ],
+        };
+    }
+
+    pub fn indexes(&self) -> &[u32] {
+        return &self.indexes;
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = String;
+
+    fn from_str(path: &str) -> Result<DerivationPath, String> {
+        let mut components = path.split('/');
+        if components.next() != Some("m") {
+            return Err(format!("derivation path {} must start with m", path));
+        }
+        let mut indexes = Vec::new();
+        for component in components {
+            let (number, hardened) = match component
+                .strip_suffix('\'')
+                .or_else(|| component.strip_suffix('H'))
+            {
+                Some(number) => (number, true),
+                None => (component, false),
+            };
+            let index = match number.parse::<u32>() {
+                Ok(index) if index < HARDENED_OFFSET => index,
+                _ => return Err(format!("invalid path component {} in {}", component, path)),
+            };
+            indexes.push(if hardened {
+                index | HARDENED_OFFSET
+            } else {
+                index
+            });
+        }
+        return Ok(DerivationPath { indexes });
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "m")?;
+        for index in self.indexes.iter() {
+            if index & HARDENED_OFFSET != 0 {
+                write!(f, "/{}'", index & !HARDENED_OFFSET)?;
+            } else {
+                write!(f, "/{}", index)?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+#[derive(Clone)]
+pub struct ExtendedKey {
+    secret: [u8; 32],
+    chain_code: [u8; 32],
+    depth: u8,
+}
+
+impl ExtendedKey {
+    pub fn master(seed: &[u8]) -> ExtendedKey {
+        return ExtendedKey::from_hmac(MASTER_KEY_SALT, seed, 0);
+    }
+
+    pub fn derive_child(&self, index: u32) -> Result<ExtendedKey, String> {
+        if index & HARDENED_OFFSET == 0 {
+            return Err(String::from(
+                "ed25519 keys only support hardened derivation",
+            ));
+        }
+        let mut data = Vec::with_capacity(37);
+        data.push(0);
+        data.extend_from_slice(&self.secret);
+        data.extend_from_slice(&index.to_be_bytes());
+        return Ok(ExtendedKey::from_hmac(
+            &self.chain_code,
+            &data,
+            self.depth.saturating_add(1),
+        ));
+    }
+
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<ExtendedKey, String> {
+        let mut key = self.clone();
+        for index in path.indexes() {
+            key = key.derive_child(*index)?;
+        }
+        return Ok(key);
+    }
+
+    pub fn secret_bytes(&self) -> [u8; 32] {
+        return self.secret;
+    }
+
+    pub fn chain_code(&self) -> [u8; 32] {
+        return self.chain_code;
+    }
+
+    pub fn depth(&self) -> u8 {
+        return self.depth;
+    }
+
+    pub fn key_pair(&self) -> KeyPair {
+        return KeyPair::from_secret_bytes(&self.secret).unwrap();
+    }
+
+    fn from_hmac(key: &[u8], data: &[u8], depth: u8) -> ExtendedKey {
+        let mut mac = Hmac::<Sha512>::new_from_slice(key).unwrap();
+        mac.update(data);
+        let output = mac.finalize().into_bytes();
+        let mut secret = [0; 32];
+        let mut chain_code = [0; 32];
+        secret.copy_from_slice(&output[..32]);
+        chain_code.copy_from_slice(&output[32..]);
+        return ExtendedKey {
+            secret,
+            chain_code,
+            depth,
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredAddress {
+    pub path: DerivationPath,
+    pub address: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredAccount {
+    pub account: u32,
+    pub used: Vec<DiscoveredAddress>,
+    pub next_index: u32,
+}
+
+// BIP-44 style discovery: scan each account until `gap_limit` consecutive unused
+// addresses, and stop at the first account without any used address.
+pub fn discover_accounts<F>(
+    master: &ExtendedKey,
+    gap_limit: u32,
+    is_used: F,
+) -> Vec<DiscoveredAccount>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut accounts = Vec::new();
+    for account in 0..HARDENED_OFFSET {
+        let mut used = Vec::new();
+        let mut next_index = 0;
+        let mut gap = 0;
+        let mut index = 0;
+        while gap < gap_limit && index < HARDENED_OFFSET {
+            let path = DerivationPath::for_address(account, index);
+            let address = match master.derive_path(&path) {
+                Ok(key) => key.key_pair().address(),
+                Err(_) => break,
+            };
+            if is_used(&address) {
+                used.push(DiscoveredAddress { path, address });
+                next_index = index + 1;
+                gap = 0;
+            } else {
+                gap += 1;
+            }
+            index += 1;
+        }
+        if used.is_empty() {
+            break;
+        }
+        accounts.push(DiscoveredAccount {
+            account,
+            used,
+            next_index,
+        });
+    }
+    return accounts;
+}
@@ -0,0 +1,77 @@
+use crate::cryptohash;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::rngs::OsRng;
+
+pub const SECRET_KEY_LENGTH: usize = 32;
+pub const PUBLIC_KEY_LENGTH: usize = 32;
+pub const SIGNATURE_LENGTH: usize = 64;
+
+#[derive(Debug)]
+pub struct KeyPair {
+    signing_key: SigningKey,
+}
+
+impl KeyPair {
+    pub fn generate() -> KeyPair {
+        return KeyPair {
+            signing_key: SigningKey::generate(&mut OsRng),
+        };
+    }
+
+    pub fn from_secret_bytes(secret: &[u8]) -> Option<KeyPair> {
+        if secret.len() != SECRET_KEY_LENGTH {
+            return None;
+        }
+        let mut secret_bytes: [u8; SECRET_KEY_LENGTH] = [0; SECRET_KEY_LENGTH];
+        secret_bytes.copy_from_slice(secret);
+        return Some(KeyPair {
+            signing_key: SigningKey::from_bytes(&secret_bytes),
+        });
+    }
+
+    pub fn secret_bytes(&self) -> [u8; SECRET_KEY_LENGTH] {
+        return self.signing_key.to_bytes();
+    }
+
+    pub fn public_key(&self) -> [u8; PUBLIC_KEY_LENGTH] {
+        return self.signing_key.verifying_key().to_bytes();
+    }
+
+    pub fn address(&self) -> String {
+        return address(&self.public_key());
+    }
+
+    pub fn sign(&self, message: &[u8]) -> [u8; SIGNATURE_LENGTH] {
+        return self.signing_key.sign(message).to_bytes();
+    }
+}
+
+pub fn verify(public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    if public_key.len() != PUBLIC_KEY_LENGTH || signature.len() != SIGNATURE_LENGTH {
+        return false;
+    }
+    let mut public_key_bytes: [u8; PUBLIC_KEY_LENGTH] = [0; PUBLIC_KEY_LENGTH];
+    public_key_bytes.copy_from_slice(public_key);
+    let verifying_key = match VerifyingKey::from_bytes(&public_key_bytes) {
+        Ok(verifying_key) => verifying_key,
+        Err(_) => return false,
+    };
+    let signature = match Signature::from_slice(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    return verifying_key.verify(message, &signature).is_ok();
+}
+
+pub fn address(public_key: &[u8]) -> String {
+    return hex::encode(cryptohash::sha256(public_key));
+}
+
+// Addresses are compared as strings, so only the lowercase form that `address` produces is valid.
+pub fn is_valid_address(address: &str) -> bool {
+    return address.len() == 64
+        && address
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte));
+}
@@ -1,7 +1,12 @@
+#![allow(clippy::needless_return)]
+
 pub mod cryptohash;
-pub mod sha256hash;
+pub mod hash256;
+pub mod hasher;
+pub mod hd;
+pub mod keys;
 // pub mod elliptic;
 
 
 #[cfg(test)]
-mod unit_tests;
\ No newline at end of file
+mod unit_tests;
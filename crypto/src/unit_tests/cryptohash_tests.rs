@@ -47,4 +47,44 @@ mod hash {
 
         assert_eq!(hash, expected_hash);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn hash_with_uses_the_given_hasher() {
+        use crate::hasher::HashAlgorithm;
+
+        let mut data_map = BTreeMap::<String, String>::new();
+        data_map.insert(String::from("foo"), String::from("data"));
+
+        let mut sha256_hash: [u8; 32] = [0; 32];
+        cryptohash::hash(&data_map, &mut sha256_hash);
+        let mut hash: [u8; 32] = [0; 32];
+        cryptohash::hash_with(HashAlgorithm::Sha256.hasher(), &data_map, &mut hash);
+        assert_eq!(hash, sha256_hash);
+        cryptohash::hash_with(HashAlgorithm::Blake3.hasher(), &data_map, &mut hash);
+        assert_ne!(hash, sha256_hash);
+    }
+}
+
+#[cfg(test)]
+mod preimage {
+    use super::*;
+
+    #[test]
+    fn lists_entries_in_key_order() {
+        let mut data_map = BTreeMap::<String, String>::new();
+        data_map.insert(String::from("nonce"), String::from("7"));
+        data_map.insert(String::from("data"), String::from("\"a b\""));
+        assert_eq!(cryptohash::preimage(&data_map), b"| data:\"a b\" | nonce:7 |".to_vec());
+        assert_eq!(cryptohash::preimage(&BTreeMap::new()), b"|".to_vec());
+    }
+}
+#[cfg(test)]
+mod sha256 {
+    use super::*;
+
+    #[test]
+    fn matches_known_digest() {
+        let expected = decode("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad").unwrap();
+        assert_eq!(cryptohash::sha256(b"abc").to_vec(), expected);
+    }
+}
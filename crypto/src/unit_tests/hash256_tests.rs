@@ -0,0 +1,76 @@
+use crate::hash256::Hash256;
+
+fn counting() -> Hash256 {
+    let mut bytes: [u8; 32] = [0; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    return Hash256(bytes);
+}
+
+#[cfg(test)]
+mod display {
+    use super::*;
+
+    #[test]
+    fn formats_as_lowercase_hex() {
+        assert_eq!(
+            counting().to_string(),
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"
+        );
+        assert_eq!(Hash256([0xab; 32]).to_string(), "ab".repeat(32));
+    }
+
+    #[test]
+    fn debug_names_the_type() {
+        assert_eq!(
+            format!("{:?}", Hash256::ZERO),
+            format!("Hash256({})", "00".repeat(32))
+        );
+    }
+}
+
+#[cfg(test)]
+mod from_str {
+    use super::*;
+
+    #[test]
+    fn parses_what_it_displays() {
+        let hash = counting();
+        assert_eq!(hash.to_string().parse::<Hash256>(), Ok(hash));
+        assert_eq!("AB".repeat(32).parse::<Hash256>(), Ok(Hash256([0xab; 32])));
+    }
+
+    #[test]
+    fn rejects_bad_hex_without_panicking() {
+        let cases = [
+            String::new(),
+            "ab".repeat(31),
+            "ab".repeat(33),
+            format!("{}a", "ab".repeat(31)),
+            format!("{}zz", "ab".repeat(31)),
+        ];
+        for case in cases.iter() {
+            assert_eq!(
+                case.parse::<Hash256>(),
+                Err(format!("invalid hash {:?} (expected 64 hex digits)", case))
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod ordering {
+    use super::*;
+
+    #[test]
+    fn orders_bytewise_from_the_first_byte() {
+        let mut low = [0xff; 32];
+        low[0] = 0;
+        let mut high = [0; 32];
+        high[0] = 1;
+        assert!(Hash256(low) < Hash256(high));
+        assert!(Hash256::ZERO < counting());
+        assert_eq!(Hash256::ZERO, Hash256::default());
+    }
+}
@@ -0,0 +1,173 @@
+use crate::hasher::*;
+
+use hex::decode;
+
+const ABC_DIGESTS: [(HashAlgorithm, &str); 4] = [
+    (
+        HashAlgorithm::Sha256,
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+    ),
+    (
+        HashAlgorithm::DoubleSha256,
+        "4f8b42c22dd3729b519ba6f68d2da7cc5b2d606d05daed5ad5128cc03e6c6358",
+    ),
+    (
+        HashAlgorithm::Sha3_256,
+        "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532",
+    ),
+    (
+        HashAlgorithm::Blake3,
+        "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+    ),
+];
+
+#[cfg(test)]
+mod digest {
+    use super::*;
+
+    #[test]
+    fn matches_known_digests() {
+        for (algorithm, expected) in ABC_DIGESTS.iter() {
+            let digest = algorithm.hasher().digest(b"abc");
+            assert_eq!(digest.to_vec(), decode(expected).unwrap(), "{}", algorithm);
+        }
+    }
+
+    #[test]
+    fn algorithms_disagree_on_the_same_input() {
+        for a in HashAlgorithm::ALL.iter() {
+            for b in HashAlgorithm::ALL.iter().filter(|b| *b != a) {
+                assert_ne!(a.hasher().digest(b"abc"), b.hasher().digest(b"abc"));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod hash_algorithm {
+    use super::*;
+
+    #[test]
+    fn parses_and_displays_names() {
+        for algorithm in HashAlgorithm::ALL.iter() {
+            let name = algorithm.to_string();
+            assert_eq!(name.parse::<HashAlgorithm>().unwrap(), *algorithm);
+        }
+        assert_eq!(HashAlgorithm::default(), HashAlgorithm::Sha256);
+        assert!("md5".parse::<HashAlgorithm>().is_err());
+    }
+}
+
+#[cfg(test)]
+mod argon2 {
+    use super::*;
+
+    const LIGHT: Argon2Params = Argon2Params {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn is_deterministic_and_depends_on_its_parameters() {
+        let hasher = Argon2Hasher::new(HashAlgorithm::Sha256, &LIGHT).unwrap();
+        let digest = hasher.digest(b"abc");
+        assert_eq!(digest, hasher.digest(b"abc"));
+        assert_ne!(digest, hasher.digest(b"abd"));
+        assert_ne!(digest, HashAlgorithm::Sha256.hasher().digest(b"abc"));
+
+        let heavier = Argon2Params {
+            iterations: 2,
+            ..LIGHT
+        };
+        let other = Argon2Hasher::new(HashAlgorithm::Sha256, &heavier).unwrap();
+        assert_ne!(digest, other.digest(b"abc"));
+        let other = Argon2Hasher::new(HashAlgorithm::Blake3, &LIGHT).unwrap();
+        assert_ne!(digest, other.digest(b"abc"));
+    }
+
+    #[test]
+    fn rejects_parameters_outside_the_verification_bounds() {
+        let too_much_memory = Argon2Params {
+            memory_kib: ARGON2_MAX_MEMORY_KIB + 1,
+            ..LIGHT
+        };
+        let too_little_memory = Argon2Params {
+            memory_kib: 8,
+            parallelism: 2,
+            ..LIGHT
+        };
+        let too_many_iterations = Argon2Params {
+            iterations: ARGON2_MAX_ITERATIONS + 1,
+            ..LIGHT
+        };
+        let no_lanes = Argon2Params {
+            parallelism: 0,
+            ..LIGHT
+        };
+        for params in [
+            too_much_memory,
+            too_little_memory,
+            too_many_iterations,
+            no_lanes,
+        ]
+        .iter()
+        {
+            assert!(params.check().is_err());
+            assert!(Argon2Hasher::new(HashAlgorithm::Sha256, params).is_err());
+        }
+        assert!(Argon2Params::default().check().is_ok());
+    }
+}
+
+#[cfg(test)]
+mod midstate {
+    use super::*;
+
+    #[test]
+    fn finishes_to_the_digest_of_prefix_and_parts() {
+        let argon2 = Argon2Hasher::new(
+            HashAlgorithm::Sha3_256,
+            &Argon2Params {
+                memory_kib: 64,
+                ..Argon2Params::default()
+            },
+        )
+        .unwrap();
+        let mut hashers: Vec<&dyn ChainHasher> = HashAlgorithm::ALL
+            .iter()
+            .map(|algorithm| algorithm.hasher())
+            .collect();
+        hashers.push(&argon2);
+        let prefix = "prefix".repeat(40);
+        for hasher in hashers {
+            let midstate = hasher.midstate(prefix.as_bytes()).unwrap();
+            for parts in [
+                vec![],
+                vec![&b"a"[..]],
+                vec![&b"ab"[..], &b""[..], &b"cde"[..]],
+            ]
+            .iter()
+            {
+                let whole = [prefix.as_bytes(), &parts.concat()].concat();
+                assert_eq!(
+                    midstate.finish(parts),
+                    hasher.digest(&whole),
+                    "{:?}",
+                    hasher
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn algorithms_resume_with_their_own_hasher() {
+        assert_eq!(
+            HashAlgorithm::Blake3
+                .midstate(b"ab")
+                .unwrap()
+                .finish(&[b"c"]),
+            HashAlgorithm::Blake3.digest(b"abc")
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use crate::hd::*;
+
+use std::collections::BTreeSet;
+
+const SLIP10_SEED: &str = "000102030405060708090a0b0c0d0e0f";
+
+fn derive(seed: &str, path: &str) -> ExtendedKey {
+    let master = ExtendedKey::master(&hex::decode(seed).unwrap());
+    return master.derive_path(&path.parse().unwrap()).unwrap();
+}
+
+#[cfg(test)]
+mod mnemonic {
+    use super::*;
+
+    #[test]
+    fn matches_bip39_test_vector() {
+        let phrase = mnemonic_from_entropy(&[0; 16]).unwrap();
+        assert_eq!(
+            phrase,
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+        );
+        assert_eq!(
+            hex::encode(&mnemonic_to_seed(&phrase, "TREZOR").unwrap()[..]),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+    }
+
+    #[test]
+    fn generated_mnemonic_round_trips() {
+        let phrase = generate_mnemonic(24).unwrap();
+        assert_eq!(phrase.split(' ').count(), 24);
+        assert!(mnemonic_to_seed(&phrase, "").is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_checksum_and_word_count() {
+        let phrase = "abandon ".repeat(12);
+        assert!(mnemonic_to_seed(phrase.trim(), "").is_err());
+        assert!(generate_mnemonic(13).is_err());
+    }
+}
+
+#[cfg(test)]
+mod derivation_path {
+    use super::*;
+
+    #[test]
+    fn parses_and_displays_hardened_components() {
+        let path: DerivationPath = "m/44'/9000H/0'/0'/7'".parse().unwrap();
+        assert_eq!(path, DerivationPath::for_address(0, 7));
+        assert_eq!(path.to_string(), "m/44'/9000'/0'/0'/7'");
+        assert_eq!(
+            "m".parse::<DerivationPath>().unwrap(),
+            DerivationPath::master()
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_paths() {
+        assert!("44'/0'".parse::<DerivationPath>().is_err());
+        assert!("m/x'".parse::<DerivationPath>().is_err());
+        assert!("m/2147483648".parse::<DerivationPath>().is_err());
+    }
+}
+
+#[cfg(test)]
+mod extended_key {
+    use super::*;
+
+    #[test]
+    fn matches_slip10_ed25519_test_vector_1() {
+        let master = derive(SLIP10_SEED, "m");
+        assert_eq!(
+            hex::encode(master.chain_code()),
+            "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb"
+        );
+        assert_eq!(
+            hex::encode(master.secret_bytes()),
+            "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7"
+        );
+        assert_eq!(
+            hex::encode(master.key_pair().public_key()),
+            "a4b2856bfec510abab89753fac1ac0e1112364e7d250545963f135f2a33188ed"
+        );
+
+        let child = derive(SLIP10_SEED, "m/0H/1H");
+        assert_eq!(child.depth(), 2);
+        assert_eq!(
+            hex::encode(child.chain_code()),
+            "a320425f77d1b5c2505a6b1b27382b37368ee640e3557c315416801243552f14"
+        );
+        assert_eq!(
+            hex::encode(child.secret_bytes()),
+            "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2"
+        );
+
+        let leaf = derive(SLIP10_SEED, "m/0H/1H/2H/2H/1000000000H");
+        assert_eq!(
+            hex::encode(leaf.secret_bytes()),
+            "8f94d394a8e8fd6b1bc2f3f49f5c47e385281d5c17e65324b0f62483e37e8793"
+        );
+        assert_eq!(
+            hex::encode(leaf.key_pair().public_key()),
+            "3c24da049451555d51a7014a37337aa4e12d41e485abccfa46b47dfb2af54b7a"
+        );
+    }
+
+    #[test]
+    fn rejects_non_hardened_child() {
+        let master = derive(SLIP10_SEED, "m");
+        assert!(master.derive_child(0).is_err());
+        assert!(master.derive_path(&"m/0".parse().unwrap()).is_err());
+    }
+}
+
+#[cfg(test)]
+mod discover_accounts {
+    use super::*;
+
+    fn address(master: &ExtendedKey, account: u32, index: u32) -> String {
+        let path = DerivationPath::for_address(account, index);
+        return master.derive_path(&path).unwrap().key_pair().address();
+    }
+
+    #[test]
+    fn scans_accounts_until_gap_limit() {
+        let master = derive(SLIP10_SEED, "m");
+        let used: BTreeSet<String> = vec![
+            address(&master, 0, 0),
+            address(&master, 0, 4),
+            address(&master, 1, 2),
+            address(&master, 3, 0),
+        ]
+        .into_iter()
+        .collect();
+        let accounts = discover_accounts(&master, 5, |address| used.contains(address));
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].account, 0);
+        assert_eq!(accounts[0].used.len(), 2);
+        assert_eq!(accounts[0].next_index, 5);
+        assert_eq!(accounts[1].used[0].path, DerivationPath::for_address(1, 2));
+    }
+
+    #[test]
+    fn misses_addresses_beyond_gap_limit() {
+        let master = derive(SLIP10_SEED, "m");
+        let far = address(&master, 0, 3);
+        assert!(discover_accounts(&master, 3, |address| address == far).is_empty());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::keys::*;
+
+#[cfg(test)]
+mod key_pair {
+    use super::*;
+
+    #[test]
+    fn generates_distinct_keys() {
+        let first = KeyPair::generate();
+        let second = KeyPair::generate();
+        assert_ne!(first.public_key(), second.public_key());
+        assert_ne!(first.address(), second.address());
+    }
+
+    #[test]
+    fn restores_from_secret_bytes() {
+        let key_pair = KeyPair::generate();
+        let restored = KeyPair::from_secret_bytes(&key_pair.secret_bytes()).unwrap();
+        assert_eq!(restored.public_key(), key_pair.public_key());
+    }
+
+    #[test]
+    fn rejects_secret_of_wrong_length() {
+        assert!(KeyPair::from_secret_bytes(&[1; 31]).is_none());
+    }
+
+    #[test]
+    fn matches_rfc8032_test_vector() {
+        let secret =
+            hex::decode("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60").unwrap();
+        let key_pair = KeyPair::from_secret_bytes(&secret).unwrap();
+        assert_eq!(
+            hex::encode(key_pair.public_key()),
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"
+        );
+        assert_eq!(
+            hex::encode(key_pair.sign(b"")),
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b"
+        );
+    }
+}
+
+#[cfg(test)]
+mod signatures {
+    use super::*;
+
+    #[test]
+    fn verify_accepts_valid_signature() {
+        let key_pair = KeyPair::generate();
+        let signature = key_pair.sign(b"some data");
+        assert!(verify(&key_pair.public_key(), b"some data", &signature));
+    }
+
+    #[test]
+    fn verify_rejects_modified_message() {
+        let key_pair = KeyPair::generate();
+        let signature = key_pair.sign(b"some data");
+        assert!(!verify(&key_pair.public_key(), b"other data", &signature));
+    }
+
+    #[test]
+    fn verify_rejects_signature_from_other_key() {
+        let key_pair = KeyPair::generate();
+        let signature = KeyPair::generate().sign(b"some data");
+        assert!(!verify(&key_pair.public_key(), b"some data", &signature));
+    }
+
+    #[test]
+    fn verify_rejects_malformed_inputs() {
+        let key_pair = KeyPair::generate();
+        let signature = key_pair.sign(b"some data");
+        assert!(!verify(&key_pair.public_key()[..31], b"some data", &signature));
+        assert!(!verify(&key_pair.public_key(), b"some data", &signature[..63]));
+    }
+}
+
+#[cfg(test)]
+mod addresses {
+    use super::*;
+
+    #[test]
+    fn address_is_hex_sha256_of_public_key() {
+        let key_pair = KeyPair::generate();
+        assert_eq!(key_pair.address(), address(&key_pair.public_key()));
+        assert!(is_valid_address(&key_pair.address()));
+    }
+
+    #[test]
+    fn rejects_malformed_address() {
+        assert!(!is_valid_address("abcd"));
+        assert!(!is_valid_address(&"zz".repeat(32)));
+    }
+
+    #[test]
+    fn accepts_only_lowercase_hex() {
+        let address = KeyPair::generate().address();
+        assert_eq!(address, address.to_lowercase());
+        assert!(!is_valid_address(&"AB".repeat(32)));
+        assert!(!is_valid_address(&address.to_uppercase()));
+    }
+}
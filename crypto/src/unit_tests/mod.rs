@@ -1 +1,5 @@
-mod cryptohash_tests;
\ No newline at end of file
+mod cryptohash_tests;
+mod hash256_tests;
+mod hasher_tests;
+mod hd_tests;
+mod keys_tests;
@@ -0,0 +1,185 @@
+use crate::rpc::decode_block;
+
+use blockchain::{
+    block::Block,
+    finality::{FinalityCertificate, FinalityVote},
+    snapshot::Snapshot,
+    transaction::{OutPoint, Transaction, TxOutput},
+    types::BlockHeight,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Utxo {
+    pub txid: String,
+    pub vout: u32,
+    pub address: String,
+    pub amount: u64,
+}
+
+impl Utxo {
+    pub fn outpoint(&self) -> Result<OutPoint, String> {
+        let mut txid = [0u8; 32];
+        if hex::decode_to_slice(&self.txid, &mut txid).is_err() {
+            return Err(format!("invalid txid {}", self.txid));
+        }
+        return Ok(OutPoint {
+            txid,
+            index: self.vout,
+        });
+    }
+
+    pub fn output(&self) -> TxOutput {
+        return TxOutput {
+            amount: self.amount,
+            address: self.address.clone(),
+        };
+    }
+}
+
+// The proof of authority signers and the epoch votes must be cast for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Authorities {
+    pub epoch: u64,
+    pub authorities: Vec<String>,
+}
+
+pub struct RpcClient {
+    url: String,
+}
+
+impl RpcClient {
+    pub fn new(address: &str) -> RpcClient {
+        let url = if address.starts_with("http://") {
+            String::from(address)
+        } else {
+            format!("http://{}", address)
+        };
+        return RpcClient { url };
+    }
+
+    pub fn url(&self) -> &str {
+        return &self.url;
+    }
+
+    pub fn call(&self, method: &str, params: Value) -> Result<Value, String> {
+        let request = json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": 1 });
+        let response: Value = match ureq::post(&self.url).send_json(request) {
+            Ok(response) => match response.into_json() {
+                Ok(response) => response,
+                Err(e) => return Err(format!("{}: invalid response: {}", self.url, e)),
+            },
+            Err(e) => return Err(format!("{}: {}", self.url, e)),
+        };
+        if let Some(error) = response.get("error") {
+            return Err(format!("{}: {} failed: {}", self.url, method, error));
+        }
+        return Ok(response["result"].clone());
+    }
+
+    pub fn block_count(&self) -> Result<BlockHeight, String> {
+        match self.call("getblockcount", json!([]))?.as_u64() {
+            Some(count) => return Ok(BlockHeight(count)),
+            None => return Err(format!("{}: invalid block count", self.url)),
+        }
+    }
+
+    pub fn block_at(&self, height: BlockHeight) -> Result<Block, String> {
+        let hash = self.call("getblockhash", json!([height]))?;
+        let raw = self.call("getblock", json!([hash, 0]))?;
+        match raw.as_str() {
+            Some(raw) => return decode_block(raw).map_err(|e| e.message),
+            None => return Err(format!("{}: invalid block at {}", self.url, height)),
+        }
+    }
+
+    pub fn fetch_chain(&self) -> Result<Vec<Block>, String> {
+        return self.fetch_chain_from(BlockHeight::GENESIS);
+    }
+
+    pub fn fetch_chain_from(&self, base_height: BlockHeight) -> Result<Vec<Block>, String> {
+        let count = self.block_count()?;
+        let mut chain = Vec::with_capacity((count.0 + 1).saturating_sub(base_height.0) as usize);
+        for height in base_height.0..=count.0 {
+            chain.push(self.block_at(BlockHeight(height))?);
+        }
+        return Ok(chain);
+    }
+
+    pub fn snapshot(&self, height: BlockHeight) -> Result<Snapshot, String> {
+        let snapshot = self.call("getsnapshot", json!([height]))?;
+        match serde_json::from_value(snapshot) {
+            Ok(snapshot) => return Ok(snapshot),
+            Err(e) => return Err(format!("{}: invalid snapshot: {}", self.url, e)),
+        }
+    }
+
+    pub fn authorities(&self) -> Result<Authorities, String> {
+        let authorities = self.call("getauthorities", json!([]))?;
+        match serde_json::from_value(authorities) {
+            Ok(authorities) => return Ok(authorities),
+            Err(e) => return Err(format!("{}: invalid authorities: {}", self.url, e)),
+        }
+    }
+
+    // The certificate of the node's highest final block, if any.
+    pub fn finality(&self) -> Result<Option<FinalityCertificate>, String> {
+        let certificate = self.call("getfinality", json!([]))?;
+        match serde_json::from_value(certificate) {
+            Ok(certificate) => return Ok(certificate),
+            Err(e) => return Err(format!("{}: invalid certificate: {}", self.url, e)),
+        }
+    }
+
+    // The finality votes the node counted at the height it is voting on.
+    pub fn finality_votes(&self) -> Result<Vec<FinalityVote>, String> {
+        let votes = self.call("getfinalityvotes", json!([]))?;
+        match serde_json::from_value(votes) {
+            Ok(votes) => return Ok(votes),
+            Err(e) => return Err(format!("{}: invalid finality votes: {}", self.url, e)),
+        }
+    }
+
+    pub fn send_raw_transaction(&self, transaction: &Transaction) -> Result<String, String> {
+        let txid = self.call("sendrawtransaction", json!([transaction.encode_hex()]))?;
+        match txid.as_str() {
+            Some(txid) => return Ok(String::from(txid)),
+            None => return Err(format!("{}: invalid txid", self.url)),
+        }
+    }
+
+    pub fn raw_mempool(&self) -> Result<Vec<String>, String> {
+        let txids = self.call("getrawmempool", json!([]))?;
+        match serde_json::from_value(txids) {
+            Ok(txids) => return Ok(txids),
+            Err(e) => return Err(format!("{}: invalid mempool: {}", self.url, e)),
+        }
+    }
+
+    pub fn raw_transaction(&self, txid: &str) -> Result<Transaction, String> {
+        let hexstring = self.call("getrawtransaction", json!([txid]))?;
+        match hexstring.as_str().and_then(Transaction::decode_hex) {
+            Some(transaction) => return Ok(transaction),
+            None => return Err(format!("{}: invalid transaction {}", self.url, txid)),
+        }
+    }
+
+    pub fn list_unspent(&self, addresses: &[String]) -> Result<Vec<Utxo>, String> {
+        let unspent = self.call("listunspent", json!([addresses]))?;
+        match serde_json::from_value(unspent) {
+            Ok(unspent) => return Ok(unspent),
+            Err(e) => return Err(format!("{}: invalid unspent outputs: {}", self.url, e)),
+        }
+    }
+
+    pub fn received_by_address(&self, address: &str) -> Result<u64, String> {
+        match self
+            .call("getreceivedbyaddress", json!([address]))?
+            .as_u64()
+        {
+            Some(amount) => return Ok(amount),
+            None => return Err(format!("{}: invalid amount", self.url)),
+        }
+    }
+}
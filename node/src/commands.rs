@@ -0,0 +1,558 @@
+use crate::{
+    client::RpcClient,
+    config::{NodeConfig, CONFIG_FILE, SNAPSHOT_DIR},
+    rpc::{block_json, RpcHandler},
+    server::{spawn_http, spawn_websocket},
+    sync::spawn_sync,
+};
+
+use blockchain::{
+    block::Block,
+    blockchain::Blockchain,
+    events::ChainEvent,
+    index::ChainIndex,
+    params::Checkpoint,
+    snapshot::Snapshot,
+    store::{self, ChainStore},
+    types::{BlockHeight, Hash256},
+};
+use crypto::keys::KeyPair;
+use serde_json::Value;
+use std::{
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+pub struct RunningNode {
+    pub blockchain: Arc<Mutex<Blockchain>>,
+    pub http_address: SocketAddr,
+    pub ws_address: SocketAddr,
+}
+
+pub fn init(config: &NodeConfig) -> Result<String, String> {
+    let store = create_store(config)?;
+    let blockchain = Blockchain::new();
+    save_chain(store.as_ref(), &blockchain.chain, blockchain.index())?;
+    return Ok(format!(
+        "initialized chain with genesis block in {}",
+        config.data_dir.display()
+    ));
+}
+
+// Starts a new data directory from a snapshot file instead of genesis. The
+// snapshot must be for the trusted `checkpoint`, given as HEIGHT:HASH.
+pub fn init_from_snapshot(
+    config: &NodeConfig,
+    path: &Path,
+    checkpoint: &str,
+) -> Result<String, String> {
+    let snapshot = match Snapshot::load(path) {
+        Ok(snapshot) => snapshot,
+        Err(e) => return Err(format!("cannot read snapshot {}: {}", path.display(), e)),
+    };
+    let (blockchain, _) = install_snapshot(config, snapshot, checkpoint)?;
+    return Ok(format!(
+        "initialized chain from snapshot at height {} in {}",
+        blockchain.base_height(),
+        config.data_dir.display()
+    ));
+}
+
+// Fetches the snapshot at `checkpoint` from `peer`, then downloads and
+// validates every block the peer has on top of it.
+pub fn fast_sync(config: &NodeConfig, peer: &str, checkpoint: &str) -> Result<String, String> {
+    let (height, _) = parse_checkpoint(checkpoint)?;
+    let peer = RpcClient::new(peer);
+    let (mut blockchain, store) = install_snapshot(config, peer.snapshot(height)?, checkpoint)?;
+    let chain = peer.fetch_chain_from(height)?;
+    let fetched = chain.len().saturating_sub(1);
+    if fetched > 0 {
+        if let Err(e) = blockchain.replace_chain(chain) {
+            return Err(format!(
+                "{} sent a chain that was refused: {}",
+                peer.url(),
+                e
+            ));
+        }
+        save_chain(store.as_ref(), &blockchain.chain, blockchain.index())?;
+    }
+    return Ok(format!(
+        "fast synced from snapshot at height {} and validated {} blocks up to height {}",
+        height,
+        fetched,
+        blockchain.height()
+    ));
+}
+
+pub fn parse_checkpoint(checkpoint: &str) -> Result<(BlockHeight, Hash256), String> {
+    let checkpoint: Checkpoint = checkpoint.parse()?;
+    return Ok((checkpoint.height, checkpoint.hash));
+}
+
+pub fn open_store(config: &NodeConfig) -> Result<Box<dyn ChainStore>, String> {
+    match store::open_store(config.store, &config.data_dir) {
+        Ok(store) => return Ok(store),
+        Err(e) => {
+            return Err(format!(
+                "cannot open {} store in {}: {}",
+                config.store,
+                config.data_dir.display(),
+                e
+            ))
+        }
+    }
+}
+
+pub fn load(config: &NodeConfig) -> Result<Blockchain, String> {
+    return load_from(open_store(config)?.as_ref(), config);
+}
+
+pub fn mine(config: &NodeConfig, count: usize, data: &str) -> Result<String, String> {
+    let store = open_store(config)?;
+    let mut blockchain = load_from(store.as_ref(), config)?;
+    let mut output = Vec::new();
+    for _ in 0..count {
+        blockchain.in_slot(|blockchain| blockchain.add_block(String::from(data)))?;
+        let height = blockchain.height();
+        output.push(format!(
+            "mined block {} {}",
+            height,
+            hex::encode(blockchain.last_block().hash)
+        ));
+    }
+    save_chain(store.as_ref(), &blockchain.chain, blockchain.index())?;
+    return Ok(output.join("\n"));
+}
+
+pub fn show(config: &NodeConfig, id: &str) -> Result<String, String> {
+    let blockchain = load(config)?;
+    let (found, pruned) = match id.parse::<BlockHeight>() {
+        Ok(height) if id.len() != 64 => (
+            blockchain.block_at(height).map(|block| (height, block)),
+            blockchain.is_pruned(height),
+        ),
+        _ => match id.parse::<Hash256>() {
+            Ok(hash) => (
+                blockchain.find_block(&hash),
+                blockchain.find_header(&hash).is_some(),
+            ),
+            Err(_) => return Err(format!("{} is neither a height nor a block hash", id)),
+        },
+    };
+    match found {
+        Some((height, block)) => {
+            return Ok(pretty(&Value::Object(block_json(height, block, true))));
+        }
+        None if pruned => return Err(format!("block {} has been pruned", id)),
+        None => return Err(format!("block {} not found", id)),
+    }
+}
+
+pub fn validate(config: &NodeConfig) -> Result<String, String> {
+    let blockchain = load(config)?;
+    if let Some(index) = blockchain.first_invalid_extension(&blockchain.chain) {
+        return Err(format!(
+            "chain is invalid at height {}",
+            blockchain.base_height() + index as u64
+        ));
+    }
+    blockchain
+        .params()
+        .check_chain(blockchain.base_height(), &blockchain.chain)?;
+    return Ok(format!(
+        "chain is valid ({} blocks)",
+        blockchain.chain.len()
+    ));
+}
+
+pub fn export(config: &NodeConfig) -> Result<String, String> {
+    let blockchain = load(config)?;
+    match serde_json::to_string_pretty(&blockchain.chain) {
+        Ok(exported) => return Ok(exported),
+        Err(e) => return Err(format!("cannot encode chain: {}", e)),
+    }
+}
+
+pub fn snapshot(config: &NodeConfig, height: Option<BlockHeight>) -> Result<String, String> {
+    let blockchain = load(config)?;
+    let snapshot = blockchain.snapshot(height.unwrap_or_else(|| blockchain.height()))?;
+    match serde_json::to_string(&snapshot) {
+        Ok(exported) => return Ok(exported),
+        Err(e) => return Err(format!("cannot encode snapshot: {}", e)),
+    }
+}
+
+pub fn prune(config: &NodeConfig, depth: Option<usize>) -> Result<String, String> {
+    let depth = match depth.or(config.prune_depth) {
+        Some(depth) => depth,
+        None => return Err(String::from("no prune depth given or configured")),
+    };
+    let store = open_store(config)?;
+    let mut blockchain = load_from(store.as_ref(), config)?;
+    match prune_chain(store.as_ref(), &mut blockchain, depth)? {
+        Some(height) => return Ok(format!("pruned block bodies below height {}", height)),
+        None => return Ok(String::from("nothing to prune")),
+    }
+}
+
+pub fn import(config: &NodeConfig, path: &Path) -> Result<String, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+    };
+    let chain: Vec<Block> = match serde_json::from_str(&contents) {
+        Ok(chain) => chain,
+        Err(e) => return Err(format!("invalid chain in {}: {}", path.display(), e)),
+    };
+    let store = open_store(config)?;
+    let mut blockchain = load_from(store.as_ref(), config)?;
+    if chain.is_empty() {
+        return Err(format!("{} does not contain a valid chain", path.display()));
+    }
+    let imported_length = chain.len();
+    if let Err(e) = blockchain.replace_chain(chain) {
+        return Err(format!("cannot import {}: {}", path.display(), e));
+    }
+    save_chain(store.as_ref(), &blockchain.chain, blockchain.index())?;
+    return Ok(format!("imported {} blocks", imported_length));
+}
+
+pub fn start(config: &NodeConfig) -> Result<RunningNode, String> {
+    let store = open_store(config)?;
+    let blockchain = Arc::new(Mutex::new(load_from(store.as_ref(), config)?));
+    let handler = Arc::new(RpcHandler::new(Arc::clone(&blockchain)));
+    let snapshots = match config.snapshot_interval {
+        0 => None,
+        interval => Some((config.data_dir.join(SNAPSHOT_DIR), interval)),
+    };
+    spawn_persistence(
+        Arc::clone(&blockchain),
+        store,
+        snapshots,
+        config.prune_depth,
+    );
+    let http_address = match spawn_http(&config.http_address, Arc::clone(&handler)) {
+        Ok(address) => address,
+        Err(e) => return Err(format!("cannot listen on {}: {}", config.http_address, e)),
+    };
+    let ws_address = match spawn_websocket(&config.ws_address, handler) {
+        Ok(address) => address,
+        Err(e) => return Err(format!("cannot listen on {}: {}", config.ws_address, e)),
+    };
+    spawn_sync(
+        Arc::clone(&blockchain),
+        config.peers.clone(),
+        Duration::from_millis(config.sync_interval_ms),
+    );
+    return Ok(RunningNode {
+        blockchain,
+        http_address,
+        ws_address,
+    });
+}
+
+// Writes the snapshot at `height` to `<dir>/snapshot-<height>.json`.
+pub fn write_snapshot(
+    dir: &Path,
+    blockchain: &Blockchain,
+    height: BlockHeight,
+) -> Result<PathBuf, String> {
+    return save_snapshot(dir, &blockchain.snapshot(height)?);
+}
+
+fn save_snapshot(dir: &Path, snapshot: &Snapshot) -> Result<PathBuf, String> {
+    let path = dir.join(format!("snapshot-{}.json", snapshot.height));
+    let result = fs::create_dir_all(dir).and_then(|_| snapshot.save(&path));
+    match result {
+        Ok(()) => return Ok(path),
+        Err(e) => return Err(format!("cannot write {}: {}", path.display(), e)),
+    }
+}
+
+fn create_store(config: &NodeConfig) -> Result<Box<dyn ChainStore>, String> {
+    if let Err(e) = fs::create_dir_all(&config.data_dir) {
+        return Err(format!(
+            "cannot create {}: {}",
+            config.data_dir.display(),
+            e
+        ));
+    }
+    let store = open_store(config)?;
+    if store.exists() {
+        return Err(format!("{} already exists", store.location().display()));
+    }
+    let config_path = config.data_dir.join(CONFIG_FILE);
+    if !config_path.exists() {
+        config.save(&config_path)?;
+    }
+    return Ok(store);
+}
+
+// Returns the new store along with the chain so callers can keep writing to
+// it without opening it again.
+fn install_snapshot(
+    config: &NodeConfig,
+    snapshot: Snapshot,
+    checkpoint: &str,
+) -> Result<(Blockchain, Box<dyn ChainStore>), String> {
+    let (height, hash) = parse_checkpoint(checkpoint)?;
+    snapshot.verify_checkpoint(height, &hash)?;
+    let chain = vec![snapshot.block.clone()];
+    let mut blockchain = Blockchain::from_snapshot(snapshot.clone(), chain, ChainIndex::new())?;
+    blockchain.set_params(config.chain_params()?)?;
+    let store = create_store(config)?;
+    if let Err(e) = store.commit_snapshot(&snapshot, blockchain.index()) {
+        return Err(format!(
+            "cannot write {}: {}",
+            store.location().display(),
+            e
+        ));
+    }
+    return Ok((blockchain, store));
+}
+
+fn load_from(store: &dyn ChainStore, config: &NodeConfig) -> Result<Blockchain, String> {
+    let snapshot = match store.load_snapshot() {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            return Err(format!(
+                "cannot load snapshot from {}: {}",
+                store.location().display(),
+                e
+            ))
+        }
+    };
+    match store.load() {
+        Ok(chain) if !chain.is_empty() => {
+            let index = store.load_index().unwrap_or_default();
+            let mut blockchain = match snapshot {
+                Some(snapshot) => Blockchain::from_snapshot(snapshot, chain, index)?,
+                None => Blockchain::with_index(chain, index),
+            };
+            if blockchain.base().is_some() {
+                let headers = match store.load_headers() {
+                    Ok(headers) => headers,
+                    Err(e) => {
+                        return Err(format!(
+                            "cannot load headers from {}: {}",
+                            store.location().display(),
+                            e
+                        ))
+                    }
+                };
+                let pruned = headers.len().saturating_sub(blockchain.chain.len());
+                blockchain.set_pruned_headers(headers[..pruned].to_vec())?;
+            }
+            blockchain.set_params(config.chain_params()?)?;
+            if let Some(secret) = &config.validator_key {
+                blockchain.set_validator_key(parse_validator_key(secret)?);
+            }
+            match store.load_finality() {
+                Ok(Some(certificate)) => blockchain.finalize(certificate)?,
+                Ok(None) => {}
+                Err(e) => {
+                    return Err(format!(
+                        "cannot load finality from {}: {}",
+                        store.location().display(),
+                        e
+                    ))
+                }
+            }
+            return Ok(blockchain);
+        }
+        Ok(_) => {
+            return Err(format!(
+                "{} is empty (run `cryptochain init` first?)",
+                store.location().display()
+            ))
+        }
+        Err(e) => {
+            return Err(format!(
+                "cannot load {} (run `cryptochain init` first?): {}",
+                store.location().display(),
+                e
+            ))
+        }
+    }
+}
+
+fn spawn_persistence(
+    blockchain: Arc<Mutex<Blockchain>>,
+    store: Box<dyn ChainStore>,
+    snapshots: Option<(PathBuf, usize)>,
+    prune_depth: Option<usize>,
+) {
+    let (events, mut stored_tip) = {
+        let blockchain = blockchain.lock().unwrap();
+        let tip = (blockchain.height(), blockchain.last_block().hash);
+        (blockchain.events().subscribe(), Some(tip))
+    };
+    thread::spawn(move || {
+        while let Ok(event) = events.recv() {
+            if let ChainEvent::NewTip { height, .. } = event {
+                let snapshot_dir = match &snapshots {
+                    Some((dir, interval))
+                        if height > BlockHeight::GENESIS && height.0 % *interval as u64 == 0 =>
+                    {
+                        Some(dir)
+                    }
+                    _ => None,
+                };
+                // Only copies are taken under the lock. The disk writes happen
+                // after it is released so RPC and sync are not held up.
+                let (update, index, snapshot, pruned) = {
+                    let mut blockchain = blockchain.lock().unwrap();
+                    let update = chain_update(&blockchain, stored_tip);
+                    stored_tip = Some((blockchain.height(), blockchain.last_block().hash));
+                    let index = blockchain.index().clone();
+                    // Tips that do not commit to a state root, such as blocks
+                    // submitted without one, cannot be snapshotted.
+                    let has_root = blockchain
+                        .block_at(height)
+                        .is_some_and(|block| block.state_root.is_some());
+                    let snapshot = snapshot_dir
+                        .filter(|_| has_root)
+                        .map(|dir| (dir, blockchain.snapshot(height)));
+                    let pruned = match prune_depth {
+                        Some(depth) => blockchain.prune(depth).map(|moved| {
+                            moved.map(|_| {
+                                (
+                                    blockchain.base().unwrap().clone(),
+                                    blockchain.index().clone(),
+                                )
+                            })
+                        }),
+                        None => Ok(None),
+                    };
+                    (update, index, snapshot, pruned)
+                };
+                let saved = match update {
+                    ChainUpdate::Extend(blocks) => blocks
+                        .iter()
+                        .try_for_each(|block| save_block(store.as_ref(), block, &index)),
+                    ChainUpdate::Replace(chain) => save_chain(store.as_ref(), &chain, &index),
+                };
+                if let Err(e) = saved {
+                    // The stored chain is now unknown, so the next tip rewrites it.
+                    stored_tip = None;
+                    eprintln!("{}", e);
+                }
+                if let Some((dir, snapshot)) = snapshot {
+                    if let Err(e) = snapshot.and_then(|snapshot| save_snapshot(dir, &snapshot)) {
+                        eprintln!("{}", e);
+                    }
+                }
+                let pruned = pruned.and_then(|pruned| match pruned {
+                    Some((base, index)) => save_pruned(store.as_ref(), &base, &index),
+                    None => Ok(()),
+                });
+                if let Err(e) = pruned {
+                    eprintln!("{}", e);
+                }
+            }
+            if let ChainEvent::Finalized { .. } = event {
+                let certificate = blockchain.lock().unwrap().finalized().cloned();
+                if let Some(certificate) = certificate {
+                    if let Err(e) = store.commit_finality(&certificate) {
+                        eprintln!("cannot save finality: {}", e);
+                    }
+                }
+            }
+        }
+    });
+}
+
+enum ChainUpdate {
+    // Blocks on top of the stored tip, oldest first.
+    Extend(Vec<Block>),
+    // The whole chain, after a reorg below the stored tip.
+    Replace(Vec<Block>),
+}
+
+// What to write so the store holds `blockchain`, given the tip it was last
+// brought up to. Only the new blocks are copied when the chain grew on top of
+// that tip.
+fn chain_update(
+    blockchain: &Blockchain,
+    stored_tip: Option<(BlockHeight, Hash256)>,
+) -> ChainUpdate {
+    if let Some((height, hash)) = stored_tip {
+        if blockchain.block_at(height).map(|block| block.hash) == Some(hash) {
+            let new_blocks = (height.0 + 1..=blockchain.height().0)
+                .filter_map(|height| blockchain.block_at(BlockHeight(height)).cloned())
+                .collect();
+            return ChainUpdate::Extend(new_blocks);
+        }
+    }
+    return ChainUpdate::Replace(blockchain.chain.clone());
+}
+
+fn prune_chain(
+    store: &dyn ChainStore,
+    blockchain: &mut Blockchain,
+    depth: usize,
+) -> Result<Option<BlockHeight>, String> {
+    let height = match blockchain.prune(depth)? {
+        Some(height) => height,
+        None => return Ok(None),
+    };
+    save_pruned(store, blockchain.base().unwrap(), blockchain.index())?;
+    return Ok(Some(height));
+}
+
+fn save_pruned(store: &dyn ChainStore, base: &Snapshot, index: &ChainIndex) -> Result<(), String> {
+    match store.prune(base, index) {
+        Ok(()) => return Ok(()),
+        Err(e) => {
+            return Err(format!(
+                "cannot prune {}: {}",
+                store.location().display(),
+                e
+            ))
+        }
+    }
+}
+
+fn parse_validator_key(secret: &str) -> Result<KeyPair, String> {
+    let key_pair = hex::decode(secret)
+        .ok()
+        .and_then(|secret| KeyPair::from_secret_bytes(&secret));
+    match key_pair {
+        Some(key_pair) => return Ok(key_pair),
+        None => return Err(String::from("validator_key is not a hex secret key")),
+    }
+}
+
+fn save_block(store: &dyn ChainStore, block: &Block, index: &ChainIndex) -> Result<(), String> {
+    match store.commit_block(block, index) {
+        Ok(()) => return Ok(()),
+        Err(e) => {
+            return Err(format!(
+                "cannot write {}: {}",
+                store.location().display(),
+                e
+            ))
+        }
+    }
+}
+
+fn save_chain(store: &dyn ChainStore, chain: &[Block], index: &ChainIndex) -> Result<(), String> {
+    match store.commit_chain(chain, index) {
+        Ok(()) => return Ok(()),
+        Err(e) => {
+            return Err(format!(
+                "cannot write {}: {}",
+                store.location().display(),
+                e
+            ))
+        }
+    }
+}
+
+fn pretty(value: &Value) -> String {
+    return serde_json::to_string_pretty(value).unwrap_or_default();
+}
@@ -0,0 +1,120 @@
+use blockchain::{
+    params::{self, ChainParams},
+    store::StoreBackend,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const CONFIG_FILE: &str = "cryptochain.toml";
+pub const DEFAULT_DATA_DIR: &str = "cryptochain-data";
+pub const SNAPSHOT_DIR: &str = "snapshots";
+// Checkpoints kept in the data directory are enforced on top of those in the
+// chain parameters.
+pub const CHECKPOINTS_FILE: &str = "checkpoints.txt";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NodeConfig {
+    pub data_dir: PathBuf,
+    pub http_address: String,
+    pub ws_address: String,
+    pub peers: Vec<String>,
+    pub sync_interval_ms: u64,
+    pub store: StoreBackend,
+    // Write a state snapshot every this many blocks; 0 disables snapshots.
+    pub snapshot_interval: usize,
+    // Delete block bodies more than this many blocks below the tip.
+    pub prune_depth: Option<usize>,
+    // Hex secret key this node signs blocks with under proof of stake, and
+    // votes blocks final with if it is a finality validator's.
+    pub validator_key: Option<String>,
+    pub chain: ChainParams,
+}
+
+impl Default for NodeConfig {
+    fn default() -> NodeConfig {
+        return NodeConfig {
+            data_dir: PathBuf::from(DEFAULT_DATA_DIR),
+            http_address: String::from("127.0.0.1:8332"),
+            ws_address: String::from("127.0.0.1:8333"),
+            peers: Vec::new(),
+            sync_interval_ms: 5_000,
+            store: StoreBackend::File,
+            snapshot_interval: 100,
+            prune_depth: None,
+            validator_key: None,
+            chain: ChainParams::default(),
+        };
+    }
+}
+
+impl NodeConfig {
+    pub fn load(path: &Path) -> Result<NodeConfig, String> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+        };
+        match toml::from_str(&contents) {
+            Ok(config) => return Ok(config),
+            Err(e) => return Err(format!("invalid config {}: {}", path.display(), e)),
+        }
+    }
+
+    // The chain parameters with the data directory's checkpoints file, if
+    // there is one, added.
+    pub fn chain_params(&self) -> Result<ChainParams, String> {
+        let mut chain = self.chain.clone();
+        let path = self.data_dir.join(CHECKPOINTS_FILE);
+        if !path.exists() {
+            return Ok(chain);
+        }
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+        };
+        let checkpoints = match params::parse_checkpoints(&contents) {
+            Ok(checkpoints) => checkpoints,
+            Err(e) => return Err(format!("invalid checkpoints in {}: {}", path.display(), e)),
+        };
+        chain.add_checkpoints(checkpoints)?;
+        return Ok(chain);
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = match toml::to_string_pretty(self) {
+            Ok(contents) => contents,
+            Err(e) => return Err(format!("cannot encode config: {}", e)),
+        };
+        match fs::write(path, contents) {
+            Ok(()) => return Ok(()),
+            Err(e) => return Err(format!("cannot write {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn resolve(
+        data_dir: Option<PathBuf>,
+        config_path: Option<PathBuf>,
+    ) -> Result<NodeConfig, String> {
+        let default_dir = data_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_DATA_DIR));
+        let mut config = match config_path {
+            Some(path) => NodeConfig::load(&path)?,
+            None => {
+                let path = default_dir.join(CONFIG_FILE);
+                if path.exists() {
+                    NodeConfig::load(&path)?
+                } else {
+                    NodeConfig::default()
+                }
+            }
+        };
+        if let Some(data_dir) = data_dir {
+            config.data_dir = data_dir;
+        }
+        return Ok(config);
+    }
+}
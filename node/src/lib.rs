@@ -0,0 +1,12 @@
+#![allow(clippy::needless_return)]
+
+pub mod client;
+pub mod commands;
+pub mod config;
+pub mod rpc;
+pub mod server;
+pub mod subscriptions;
+pub mod sync;
+
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,160 @@
+use blockchain::{store::StoreBackend, types::BlockHeight};
+use node::{commands, config::NodeConfig};
+
+use clap::{Parser, Subcommand};
+use std::{fs, path::PathBuf, process, thread};
+
+#[derive(Parser)]
+#[command(
+    name = "cryptochain",
+    version,
+    about = "Run and inspect a cryptochain node"
+)]
+struct Cli {
+    #[arg(
+        long,
+        global = true,
+        help = "Directory holding the chain and config file"
+    )]
+    data_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        help = "Config file (defaults to <data-dir>/cryptochain.toml)"
+    )]
+    config: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        help = "Chain store backend: file or sled (overrides the config file)"
+    )]
+    store: Option<StoreBackend>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Create the data directory, config file and genesis block")]
+    Init {
+        #[arg(long, help = "Start from this snapshot file instead of genesis")]
+        snapshot: Option<PathBuf>,
+        #[arg(
+            long,
+            conflicts_with = "snapshot",
+            help = "Fast sync from the snapshot this peer serves at the checkpoint"
+        )]
+        fast_sync: Option<String>,
+        #[arg(long, help = "Trusted HEIGHT:HASH the snapshot must be taken at")]
+        checkpoint: Option<String>,
+    },
+    #[command(about = "Start the node with its JSON-RPC servers and peer sync")]
+    Run {
+        #[arg(long, help = "HTTP JSON-RPC listen address")]
+        http: Option<String>,
+        #[arg(long, help = "WebSocket JSON-RPC listen address")]
+        ws: Option<String>,
+        #[arg(long = "peer", help = "JSON-RPC address of a peer to sync from")]
+        peers: Vec<String>,
+    },
+    #[command(about = "Mine blocks onto the stored chain")]
+    Mine {
+        #[arg(short = 'n', long, default_value_t = 1)]
+        count: usize,
+        #[arg(long, default_value = "")]
+        data: String,
+    },
+    #[command(about = "Print a block by height or hash")]
+    Show { block: String },
+    #[command(about = "Validate the stored chain")]
+    Validate,
+    #[command(about = "Export the stored chain as JSON")]
+    Export { file: Option<PathBuf> },
+    #[command(about = "Import a longer valid chain from a JSON export")]
+    Import { file: PathBuf },
+    #[command(about = "Delete block bodies deep below the tip, keeping headers and state")]
+    Prune {
+        #[arg(long, help = "Blocks to keep below the tip (defaults to prune_depth)")]
+        depth: Option<usize>,
+    },
+    #[command(about = "Export the UTXO set at a block as a state snapshot")]
+    Snapshot {
+        #[arg(long, help = "Block height to snapshot (defaults to the tip)")]
+        height: Option<BlockHeight>,
+        file: Option<PathBuf>,
+    },
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let mut config = match NodeConfig::resolve(cli.data_dir, cli.config) {
+        Ok(config) => config,
+        Err(e) => exit_with_error(&e),
+    };
+    if let Some(store) = cli.store {
+        config.store = store;
+    }
+    let result = match cli.command {
+        Command::Init {
+            snapshot,
+            fast_sync,
+            checkpoint,
+        } => match (snapshot, fast_sync, checkpoint) {
+            (None, None, _) => commands::init(&config),
+            (Some(file), _, Some(checkpoint)) => {
+                commands::init_from_snapshot(&config, &file, &checkpoint)
+            }
+            (_, Some(peer), Some(checkpoint)) => commands::fast_sync(&config, &peer, &checkpoint),
+            (_, _, None) => Err(String::from(
+                "--checkpoint is required when starting from a snapshot",
+            )),
+        },
+        Command::Run { http, ws, peers } => {
+            if let Some(http) = http {
+                config.http_address = http;
+            }
+            if let Some(ws) = ws {
+                config.ws_address = ws;
+            }
+            config.peers.extend(peers);
+            match commands::start(&config) {
+                Ok(node) => {
+                    println!("JSON-RPC over HTTP listening on {}", node.http_address);
+                    println!("JSON-RPC over WebSocket listening on {}", node.ws_address);
+                    loop {
+                        thread::park();
+                    }
+                }
+                Err(e) => Err(e),
+            }
+        }
+        Command::Mine { count, data } => commands::mine(&config, count, &data),
+        Command::Show { block } => commands::show(&config, &block),
+        Command::Validate => commands::validate(&config),
+        Command::Export { file } => match (commands::export(&config), file) {
+            (Ok(exported), Some(file)) => match fs::write(&file, exported) {
+                Ok(()) => Ok(format!("exported chain to {}", file.display())),
+                Err(e) => Err(format!("cannot write {}: {}", file.display(), e)),
+            },
+            (result, _) => result,
+        },
+        Command::Import { file } => commands::import(&config, &file),
+        Command::Prune { depth } => commands::prune(&config, depth),
+        Command::Snapshot { height, file } => match (commands::snapshot(&config, height), file) {
+            (Ok(exported), Some(file)) => match fs::write(&file, exported) {
+                Ok(()) => Ok(format!("exported snapshot to {}", file.display())),
+                Err(e) => Err(format!("cannot write {}: {}", file.display(), e)),
+            },
+            (result, _) => result,
+        },
+    };
+    match result {
+        Ok(output) => println!("{}", output),
+        Err(e) => exit_with_error(&e),
+    }
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
@@ -0,0 +1,530 @@
+use blockchain::{
+    block::{Block, BlockHeader},
+    blockchain::Blockchain,
+    events::EventBus,
+    finality::FinalityCertificate,
+    script,
+    transaction::Transaction,
+    types::{BlockHeight, Hash256},
+};
+use crypto::keys;
+
+use serde_json::{json, Map, Value};
+use std::{
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+pub const NOT_SUPPORTED: i64 = -32000;
+pub const BLOCK_NOT_FOUND: i64 = -5;
+pub const BLOCK_PRUNED: i64 = -1;
+pub const DESERIALIZATION_ERROR: i64 = -22;
+pub const INVALID_ADDRESS: i64 = -5;
+pub const TRANSACTION_REJECTED: i64 = -26;
+pub const TRANSACTION_NOT_FOUND: i64 = -5;
+pub const CERTIFICATE_REJECTED: i64 = -25;
+
+// The most blocks one generatetoaddress call mines. Each is mined while the
+// chain is locked, so larger batches would stall the rest of the node.
+pub const MAX_GENERATE_BLOCKS: u64 = 16;
+
+#[derive(Debug, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: &str) -> RpcError {
+        return RpcError {
+            code,
+            message: String::from(message),
+        };
+    }
+
+    pub fn to_json(&self) -> Value {
+        return json!({ "code": self.code, "message": self.message });
+    }
+}
+
+pub struct RpcHandler {
+    blockchain: Arc<Mutex<Blockchain>>,
+    events: Arc<EventBus>,
+}
+
+impl RpcHandler {
+    pub fn new(blockchain: Arc<Mutex<Blockchain>>) -> RpcHandler {
+        let events = blockchain.lock().unwrap().events();
+        return RpcHandler { blockchain, events };
+    }
+
+    pub fn events(&self) -> Arc<EventBus> {
+        return Arc::clone(&self.events);
+    }
+
+    pub fn handle_message(&self, message: &str) -> Option<String> {
+        let request: Value = match serde_json::from_str(message) {
+            Ok(request) => request,
+            Err(_) => {
+                let error = RpcError::new(PARSE_ERROR, "Parse error");
+                return Some(error_response(Value::Null, &error).to_string());
+            }
+        };
+        let response = match request {
+            Value::Array(batch) => {
+                if batch.is_empty() {
+                    let error = RpcError::new(INVALID_REQUEST, "Invalid Request");
+                    Some(error_response(Value::Null, &error))
+                } else {
+                    let responses: Vec<Value> = batch
+                        .iter()
+                        .filter_map(|request| self.handle_request(request))
+                        .collect();
+                    if responses.is_empty() {
+                        None
+                    } else {
+                        Some(Value::Array(responses))
+                    }
+                }
+            }
+            request => self.handle_request(&request),
+        };
+        return response.map(|response| response.to_string());
+    }
+
+    pub fn handle_request(&self, request: &Value) -> Option<Value> {
+        let request = match request.as_object() {
+            Some(request) => request,
+            None => {
+                let error = RpcError::new(INVALID_REQUEST, "Invalid Request");
+                return Some(error_response(Value::Null, &error));
+            }
+        };
+        let id = request.get("id").cloned();
+        let method = match (request.get("jsonrpc"), request.get("method")) {
+            (Some(Value::String(version)), Some(Value::String(method))) if version == "2.0" => {
+                method
+            }
+            _ => {
+                let error = RpcError::new(INVALID_REQUEST, "Invalid Request");
+                return Some(error_response(id.unwrap_or(Value::Null), &error));
+            }
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        let result = self.call(method, &params);
+        let id = id?;
+        return Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+            Err(error) => error_response(id, &error),
+        });
+    }
+
+    pub fn call(&self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        if !params.is_null() && !params.is_array() && !params.is_object() {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                "params must be an array or object",
+            ));
+        }
+        let mut blockchain = match self.blockchain.lock() {
+            Ok(blockchain) => blockchain,
+            Err(_) => return Err(RpcError::new(INTERNAL_ERROR, "blockchain lock poisoned")),
+        };
+        match method {
+            "getblockcount" => return Ok(json!(blockchain.height())),
+            "getbestblockhash" => return Ok(json!(hex::encode(blockchain.last_block().hash))),
+            "getdifficulty" => return Ok(json!(blockchain.last_block().difficulty)),
+            "getblockhash" => {
+                let height = match param(params, 0, "height").and_then(Value::as_u64) {
+                    Some(height) => BlockHeight(height),
+                    None => return Err(RpcError::new(INVALID_PARAMS, "missing height")),
+                };
+                match blockchain.header_at(height) {
+                    Some(header) => return Ok(json!(hex::encode(header.hash))),
+                    None => return Err(RpcError::new(INVALID_PARAMS, "Block height out of range")),
+                }
+            }
+            "getblock" => {
+                let hash = hash_param(params, 0, "blockhash")?;
+                let verbosity = match param(params, 1, "verbosity") {
+                    None => 1,
+                    Some(Value::Bool(verbose)) => *verbose as u64,
+                    Some(value) => match value.as_u64() {
+                        Some(verbosity) => verbosity,
+                        None => return Err(RpcError::new(INVALID_PARAMS, "invalid verbosity")),
+                    },
+                };
+                let (height, block) = lookup_block(&blockchain, &hash)?;
+                if verbosity == 0 {
+                    return Ok(json!(encode_block(block)?));
+                }
+                let mut block_json = header_json(&blockchain, height, &block.header());
+                block_json.insert(String::from("data"), json!(block.data));
+                return Ok(Value::Object(block_json));
+            }
+            "getblockheader" => {
+                let hash = hash_param(params, 0, "blockhash")?;
+                let verbose = match param(params, 1, "verbose") {
+                    None => true,
+                    Some(Value::Bool(verbose)) => *verbose,
+                    Some(_) => return Err(RpcError::new(INVALID_PARAMS, "invalid verbose flag")),
+                };
+                let (height, header) = match blockchain.find_header(&hash) {
+                    Some(found) => found,
+                    None => return Err(RpcError::new(BLOCK_NOT_FOUND, "Block not found")),
+                };
+                if !verbose {
+                    return Ok(json!(encode_header(&header)?));
+                }
+                return Ok(Value::Object(header_json(&blockchain, height, &header)));
+            }
+            "getsnapshot" => {
+                let height = match param(params, 0, "height") {
+                    None | Some(Value::Null) => blockchain.height(),
+                    Some(value) => match value.as_u64() {
+                        Some(height) => BlockHeight(height),
+                        None => return Err(RpcError::new(INVALID_PARAMS, "invalid height")),
+                    },
+                };
+                if blockchain.block_at(height).is_none() {
+                    return Err(RpcError::new(INVALID_PARAMS, "Block height out of range"));
+                }
+                let snapshot = match blockchain.snapshot(height) {
+                    Ok(snapshot) => snapshot,
+                    Err(e) => return Err(RpcError::new(NOT_SUPPORTED, &e)),
+                };
+                match serde_json::to_value(&snapshot) {
+                    Ok(snapshot) => return Ok(snapshot),
+                    Err(_) => return Err(RpcError::new(INTERNAL_ERROR, "snapshot encode failed")),
+                }
+            }
+            "getauthorities" => match blockchain.authority_set() {
+                Ok(authorities) => {
+                    return Ok(json!({
+                        "epoch": authorities.epoch(),
+                        "authorities": authorities.members(),
+                    }))
+                }
+                Err(e) => return Err(RpcError::new(NOT_SUPPORTED, &e)),
+            },
+            "getfinality" => {
+                if let Err(e) = blockchain.finality_validators() {
+                    return Err(RpcError::new(NOT_SUPPORTED, &e));
+                }
+                return Ok(json!(blockchain.finalized()));
+            }
+            "getfinalityvotes" => {
+                if let Err(e) = blockchain.finality_validators() {
+                    return Err(RpcError::new(NOT_SUPPORTED, &e));
+                }
+                return Ok(json!(blockchain.finality_votes()));
+            }
+            "submitfinality" => {
+                let certificate: FinalityCertificate = match param(params, 0, "certificate")
+                    .map(|certificate| serde_json::from_value(certificate.clone()))
+                {
+                    Some(Ok(certificate)) => certificate,
+                    Some(Err(_)) => {
+                        return Err(RpcError::new(
+                            DESERIALIZATION_ERROR,
+                            "certificate decode failed",
+                        ))
+                    }
+                    None => return Err(RpcError::new(INVALID_PARAMS, "missing certificate")),
+                };
+                match blockchain.finalize(certificate) {
+                    Ok(()) => return Ok(Value::Null),
+                    Err(e) => return Err(RpcError::new(CERTIFICATE_REJECTED, &e)),
+                }
+            }
+            "submitblock" => {
+                let hexdata = match param(params, 0, "hexdata") {
+                    Some(Value::String(hexdata)) => hexdata,
+                    _ => return Err(RpcError::new(INVALID_PARAMS, "missing hexdata")),
+                };
+                let block = decode_block(hexdata)?;
+                if blockchain.find_block(&block.hash).is_some() {
+                    return Ok(json!("duplicate"));
+                }
+                if !blockchain.submit_block(block) {
+                    return Ok(json!("rejected"));
+                }
+                return Ok(Value::Null);
+            }
+            "sendrawtransaction" => {
+                let transaction = match param(params, 0, "hexstring") {
+                    Some(Value::String(hexstring)) => match Transaction::decode_hex(hexstring) {
+                        Some(transaction) => transaction,
+                        None => {
+                            return Err(RpcError::new(DESERIALIZATION_ERROR, "TX decode failed"))
+                        }
+                    },
+                    _ => return Err(RpcError::new(INVALID_PARAMS, "missing hexstring")),
+                };
+                match blockchain.submit_transaction(transaction) {
+                    Ok(txid) => return Ok(json!(hex::encode(txid))),
+                    Err(e) => return Err(RpcError::new(TRANSACTION_REJECTED, &e)),
+                }
+            }
+            "getmempoolinfo" => {
+                let mempool = blockchain.mempool();
+                return Ok(json!({ "size": mempool.len(), "bytes": mempool.size_bytes() }));
+            }
+            "getrawmempool" => {
+                let txids: Vec<String> = blockchain
+                    .mempool()
+                    .transactions()
+                    .iter()
+                    .map(|transaction| hex::encode(transaction.txid()))
+                    .collect();
+                return Ok(json!(txids));
+            }
+            "getrawtransaction" => {
+                let txid = match param(params, 0, "txid")
+                    .and_then(Value::as_str)
+                    .map(hex::decode)
+                {
+                    Some(Ok(txid)) if txid.len() == 32 => txid,
+                    _ => {
+                        return Err(RpcError::new(
+                            INVALID_PARAMS,
+                            "txid must be 32 bytes of hex",
+                        ))
+                    }
+                };
+                let pending = blockchain
+                    .mempool()
+                    .transactions()
+                    .iter()
+                    .find(|transaction| transaction.txid()[..] == txid[..])
+                    .cloned();
+                let transaction = match pending {
+                    Some(transaction) => transaction,
+                    None => {
+                        let mut hash = [0; 32];
+                        hash.copy_from_slice(&txid);
+                        match blockchain.find_transaction(&hash) {
+                            Some((_, transaction)) => transaction,
+                            None => {
+                                return Err(RpcError::new(
+                                    TRANSACTION_NOT_FOUND,
+                                    "No such mempool or blockchain transaction",
+                                ))
+                            }
+                        }
+                    }
+                };
+                return Ok(json!(transaction.encode_hex()));
+            }
+            "listunspent" => {
+                let addresses = match param(params, 0, "addresses") {
+                    Some(Value::Array(addresses)) => addresses,
+                    _ => return Err(RpcError::new(INVALID_PARAMS, "missing addresses")),
+                };
+                let ledger = match blockchain.tip_ledger() {
+                    Ok(ledger) => ledger,
+                    Err(e) => return Err(RpcError::new(INTERNAL_ERROR, &e)),
+                };
+                let mut unspent = Vec::new();
+                for address in addresses.iter() {
+                    let address = match address.as_str() {
+                        Some(address) if keys::is_valid_address(address) => address,
+                        _ => return Err(RpcError::new(INVALID_ADDRESS, "Invalid address")),
+                    };
+                    for (outpoint, output) in ledger.unspent(address) {
+                        unspent.push(json!({
+                            "txid": hex::encode(outpoint.txid),
+                            "vout": outpoint.index,
+                            "address": output.address,
+                            "amount": output.amount,
+                        }));
+                    }
+                }
+                return Ok(json!(unspent));
+            }
+            "getreceivedbyaddress" => match param(params, 0, "address") {
+                Some(Value::String(address)) if keys::is_valid_address(address) => {
+                    return Ok(json!(blockchain.received_by_address(address)));
+                }
+                _ => return Err(RpcError::new(INVALID_ADDRESS, "Invalid address")),
+            },
+            "decodescript" => {
+                let bytes = match param(params, 0, "hexstring") {
+                    Some(Value::String(hexstring)) => match hex::decode(hexstring) {
+                        Ok(bytes) => bytes,
+                        Err(_) => return Err(RpcError::new(INVALID_PARAMS, "script must be hex")),
+                    },
+                    _ => return Err(RpcError::new(INVALID_PARAMS, "missing hexstring")),
+                };
+                match script::disassemble(&bytes) {
+                    Ok(asm) => {
+                        return Ok(json!({ "asm": asm, "address": script::address(&bytes) }));
+                    }
+                    Err(e) => return Err(RpcError::new(DESERIALIZATION_ERROR, &e)),
+                }
+            }
+            "generatetoaddress" => {
+                let nblocks = match param(params, 0, "nblocks").and_then(Value::as_u64) {
+                    Some(nblocks) if nblocks <= MAX_GENERATE_BLOCKS => nblocks,
+                    Some(_) => {
+                        return Err(RpcError::new(
+                            INVALID_PARAMS,
+                            &format!("nblocks cannot exceed {}", MAX_GENERATE_BLOCKS),
+                        ))
+                    }
+                    None => return Err(RpcError::new(INVALID_PARAMS, "missing nblocks")),
+                };
+                let address = match param(params, 1, "address") {
+                    Some(Value::String(address)) if keys::is_valid_address(address) => address,
+                    _ => return Err(RpcError::new(INVALID_ADDRESS, "Invalid address")),
+                };
+                let mut hashes = Vec::new();
+                for _ in 0..nblocks {
+                    if let Err(e) = blockchain.mine_transactions(address) {
+                        return Err(RpcError::new(INTERNAL_ERROR, &e));
+                    }
+                    hashes.push(hex::encode(blockchain.last_block().hash));
+                }
+                return Ok(json!(hashes));
+            }
+            _ => return Err(RpcError::new(METHOD_NOT_FOUND, "Method not found")),
+        }
+    }
+}
+
+pub fn encode_block(block: &Block) -> Result<String, RpcError> {
+    match serde_json::to_vec(block) {
+        Ok(bytes) => return Ok(hex::encode(bytes)),
+        Err(_) => return Err(RpcError::new(INTERNAL_ERROR, "block encode failed")),
+    }
+}
+
+pub fn encode_header(header: &BlockHeader) -> Result<String, RpcError> {
+    match serde_json::to_vec(header) {
+        Ok(bytes) => return Ok(hex::encode(bytes)),
+        Err(_) => return Err(RpcError::new(INTERNAL_ERROR, "header encode failed")),
+    }
+}
+
+pub fn decode_header(hexdata: &str) -> Result<BlockHeader, RpcError> {
+    let bytes = match hex::decode(hexdata) {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(RpcError::new(DESERIALIZATION_ERROR, "Header decode failed")),
+    };
+    match serde_json::from_slice(&bytes) {
+        Ok(header) => return Ok(header),
+        Err(_) => return Err(RpcError::new(DESERIALIZATION_ERROR, "Header decode failed")),
+    }
+}
+
+pub fn decode_block(hexdata: &str) -> Result<Block, RpcError> {
+    let bytes = match hex::decode(hexdata) {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(RpcError::new(DESERIALIZATION_ERROR, "Block decode failed")),
+    };
+    match serde_json::from_slice(&bytes) {
+        Ok(block) => return Ok(block),
+        Err(_) => return Err(RpcError::new(DESERIALIZATION_ERROR, "Block decode failed")),
+    }
+}
+
+fn error_response(id: Value, error: &RpcError) -> Value {
+    return json!({ "jsonrpc": "2.0", "error": error.to_json(), "id": id });
+}
+
+fn param<'a>(params: &'a Value, index: usize, name: &str) -> Option<&'a Value> {
+    match params {
+        Value::Array(params) => return params.get(index),
+        Value::Object(params) => return params.get(name),
+        _ => return None,
+    }
+}
+
+fn hash_param(params: &Value, index: usize, name: &str) -> Result<Hash256, RpcError> {
+    let hash = match param(params, index, name) {
+        Some(Value::String(hash)) => hash,
+        _ => return Err(RpcError::new(INVALID_PARAMS, &format!("missing {}", name))),
+    };
+    match hash.parse::<Hash256>() {
+        Ok(hash) => return Ok(hash),
+        Err(_) => {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                &format!("{} must be a 64 character hex string", name),
+            ))
+        }
+    }
+}
+
+fn lookup_block<'a>(
+    blockchain: &'a Blockchain,
+    hash: &Hash256,
+) -> Result<(BlockHeight, &'a Block), RpcError> {
+    match blockchain.find_block(hash) {
+        Some(found) => return Ok(found),
+        None if blockchain.find_header(hash).is_some() => {
+            return Err(RpcError::new(
+                BLOCK_PRUNED,
+                "Block not available (pruned data)",
+            ))
+        }
+        None => return Err(RpcError::new(BLOCK_NOT_FOUND, "Block not found")),
+    }
+}
+
+pub fn block_json(height: BlockHeight, block: &Block, include_data: bool) -> Map<String, Value> {
+    let mut block_json = header_fields(height, &block.header());
+    if include_data {
+        block_json.insert(String::from("data"), json!(block.data));
+    }
+    return block_json;
+}
+
+fn header_fields(height: BlockHeight, block: &BlockHeader) -> Map<String, Value> {
+    let time = match block.timestamp.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_secs(),
+        Err(_) => 0,
+    };
+    let mut block_json = Map::new();
+    block_json.insert(String::from("hash"), json!(hex::encode(block.hash)));
+    block_json.insert(String::from("height"), json!(height));
+    block_json.insert(String::from("time"), json!(time));
+    block_json.insert(String::from("nonce"), json!(block.nonce));
+    block_json.insert(String::from("difficulty"), json!(block.difficulty));
+    if let Some(state_root) = block.state_root {
+        block_json.insert(String::from("stateroot"), json!(hex::encode(state_root)));
+    }
+    if let Some(seal) = &block.seal {
+        block_json.insert(String::from("validator"), json!(seal.address()));
+        block_json.insert(String::from("slot"), json!(seal.slot));
+    }
+    if height > BlockHeight::GENESIS {
+        block_json.insert(
+            String::from("previousblockhash"),
+            json!(hex::encode(block.last_hash)),
+        );
+    }
+    return block_json;
+}
+
+fn header_json(
+    blockchain: &Blockchain,
+    height: BlockHeight,
+    block: &BlockHeader,
+) -> Map<String, Value> {
+    let tip_height = blockchain.height();
+    let mut header = header_fields(height, block);
+    header.insert(
+        String::from("confirmations"),
+        json!(tip_height - height + 1),
+    );
+    if let Some(next) = blockchain.header_at(height.next()) {
+        header.insert(String::from("nextblockhash"), json!(hex::encode(next.hash)));
+    }
+    return header;
+}
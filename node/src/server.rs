@@ -0,0 +1,166 @@
+use crate::{
+    rpc::RpcHandler,
+    subscriptions::{server_sent_event, Subscriptions, Topic},
+};
+
+use blockchain::events::ChainEvent;
+use serde_json::Value;
+use std::{
+    io::{self, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        mpsc::{Receiver, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+use tiny_http::{Header, Method, Request, Response, Server};
+use tungstenite::{Error, Message};
+
+const WEBSOCKET_POLL_INTERVAL_MS: u64 = 50;
+const EVENT_STREAM_KEEPALIVE_MS: u64 = 15_000;
+
+pub fn spawn_http(address: &str, handler: Arc<RpcHandler>) -> io::Result<SocketAddr> {
+    let server = match Server::http(address) {
+        Ok(server) => server,
+        Err(e) => return Err(io::Error::other(e.to_string())),
+    };
+    let local_addr = match server.server_addr().to_ip() {
+        Some(local_addr) => local_addr,
+        None => return Err(io::Error::other("not an ip listener")),
+    };
+    thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            if *request.method() == Method::Get && request.url().starts_with("/events") {
+                let topics = parse_topics(request.url());
+                let events = handler.events().subscribe();
+                thread::spawn(move || stream_events(request, topics, events));
+                continue;
+            }
+            if *request.method() != Method::Post {
+                let _ = request.respond(Response::empty(405));
+                continue;
+            }
+            let mut body = String::new();
+            if request.as_reader().read_to_string(&mut body).is_err() {
+                let _ = request.respond(Response::empty(400));
+                continue;
+            }
+            let _ = match handler.handle_message(&body) {
+                Some(response) => request
+                    .respond(Response::from_string(response).with_header(json_content_type())),
+                None => request.respond(Response::empty(204)),
+            };
+        }
+    });
+    return Ok(local_addr);
+}
+
+pub fn spawn_websocket(address: &str, handler: Arc<RpcHandler>) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    let local_addr = listener.local_addr()?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let handler = Arc::clone(&handler);
+            thread::spawn(move || serve_websocket_client(stream, handler));
+        }
+    });
+    return Ok(local_addr);
+}
+
+fn serve_websocket_client(stream: TcpStream, handler: Arc<RpcHandler>) {
+    let mut socket = match tungstenite::accept(stream) {
+        Ok(socket) => socket,
+        Err(_) => return,
+    };
+    let poll_interval = Duration::from_millis(WEBSOCKET_POLL_INTERVAL_MS);
+    if socket
+        .get_mut()
+        .set_read_timeout(Some(poll_interval))
+        .is_err()
+    {
+        return;
+    }
+    let events = handler.events().subscribe();
+    let mut subscriptions = Subscriptions::new();
+    loop {
+        match socket.read() {
+            Ok(Message::Text(message)) => {
+                let request: Option<Value> = serde_json::from_str(&message).ok();
+                let response = match request
+                    .as_ref()
+                    .and_then(|request| subscriptions.handle_request(request))
+                {
+                    Some(response) => Some(response.to_string()),
+                    None => handler.handle_message(&message),
+                };
+                if let Some(response) = response {
+                    if socket.send(Message::Text(response)).is_err() {
+                        return;
+                    }
+                }
+            }
+            Ok(Message::Close(_)) => return,
+            Ok(_) => {}
+            Err(Error::Io(ref e)) if is_timeout(e) => {}
+            Err(_) => return,
+        }
+        for event in events.try_iter() {
+            for notification in subscriptions.notifications(&event) {
+                if socket
+                    .send(Message::Text(notification.to_string()))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn stream_events(request: Request, topics: Vec<Topic>, events: Receiver<ChainEvent>) {
+    let mut writer = request.into_writer();
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n";
+    if writer.write_all(head.as_bytes()).is_err() || writer.flush().is_err() {
+        return;
+    }
+    let keepalive = Duration::from_millis(EVENT_STREAM_KEEPALIVE_MS);
+    loop {
+        let chunk = match events.recv_timeout(keepalive) {
+            Ok(event) => {
+                if !topics.contains(&Topic::of(&event)) {
+                    continue;
+                }
+                server_sent_event(&event)
+            }
+            Err(RecvTimeoutError::Timeout) => String::from(": keepalive\n\n"),
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        if writer.write_all(chunk.as_bytes()).is_err() || writer.flush().is_err() {
+            return;
+        }
+    }
+}
+
+fn parse_topics(url: &str) -> Vec<Topic> {
+    let query = url.split_once('?').map(|(_, query)| query).unwrap_or("");
+    let mut topics: Vec<Topic> = query
+        .split('&')
+        .filter_map(|pair| pair.strip_prefix("topics="))
+        .flat_map(|names| names.split(','))
+        .filter_map(Topic::parse)
+        .collect();
+    if topics.is_empty() {
+        topics = vec![Topic::NewTip, Topic::NewBlock, Topic::Reorg];
+    }
+    return topics;
+}
+
+fn is_timeout(error: &io::Error) -> bool {
+    return error.kind() == io::ErrorKind::WouldBlock || error.kind() == io::ErrorKind::TimedOut;
+}
+
+fn json_content_type() -> Header {
+    return Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+}
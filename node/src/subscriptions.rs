@@ -0,0 +1,182 @@
+use crate::rpc::{block_json, RpcError, INVALID_PARAMS};
+
+use blockchain::{block::Block, events::ChainEvent, types::BlockHeight};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Topic {
+    NewTip,
+    NewBlock,
+    Reorg,
+    Mempool,
+    Finalized,
+}
+
+impl Topic {
+    pub fn parse(name: &str) -> Option<Topic> {
+        match name {
+            "newTip" => return Some(Topic::NewTip),
+            "newBlock" => return Some(Topic::NewBlock),
+            "reorg" => return Some(Topic::Reorg),
+            "mempool" => return Some(Topic::Mempool),
+            "finalized" => return Some(Topic::Finalized),
+            _ => return None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Topic::NewTip => return "newTip",
+            Topic::NewBlock => return "newBlock",
+            Topic::Reorg => return "reorg",
+            Topic::Mempool => return "mempool",
+            Topic::Finalized => return "finalized",
+        }
+    }
+
+    pub fn of(event: &ChainEvent) -> Topic {
+        match event {
+            ChainEvent::NewTip { .. } => return Topic::NewTip,
+            ChainEvent::NewBlock { .. } => return Topic::NewBlock,
+            ChainEvent::Reorg { .. } => return Topic::Reorg,
+            ChainEvent::NewTransaction { .. } => return Topic::Mempool,
+            ChainEvent::Finalized { .. } => return Topic::Finalized,
+        }
+    }
+}
+
+pub fn event_json(event: &ChainEvent) -> Value {
+    match event {
+        ChainEvent::NewTip { height, hash } => {
+            return json!({ "height": height, "hash": hex::encode(hash) });
+        }
+        ChainEvent::NewBlock { height, block } => {
+            return Value::Object(block_json(*height, block, true));
+        }
+        ChainEvent::Reorg {
+            fork_height,
+            disconnected,
+            connected,
+        } => {
+            return json!({
+                "fork_height": fork_height,
+                "disconnected": blocks_json(*fork_height, disconnected),
+                "connected": blocks_json(*fork_height, connected),
+            });
+        }
+        ChainEvent::NewTransaction { transaction } => {
+            return json!({
+                "txid": hex::encode(transaction.txid()),
+                "transaction": transaction,
+            });
+        }
+        ChainEvent::Finalized { height, hash } => {
+            return json!({ "height": height, "hash": hex::encode(hash) });
+        }
+    }
+}
+
+fn blocks_json(first_height: BlockHeight, blocks: &[Block]) -> Vec<Value> {
+    return blocks
+        .iter()
+        .enumerate()
+        .map(|(offset, block)| Value::Object(block_json(first_height + offset as u64, block, true)))
+        .collect();
+}
+
+pub struct Subscriptions {
+    next_id: u64,
+    active: BTreeMap<u64, Topic>,
+}
+
+impl Subscriptions {
+    pub fn new() -> Subscriptions {
+        return Subscriptions {
+            next_id: 1,
+            active: BTreeMap::new(),
+        };
+    }
+
+    pub fn subscribe(&mut self, topic: Topic) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.active.insert(id, topic);
+        return id;
+    }
+
+    pub fn unsubscribe(&mut self, id: u64) -> bool {
+        return self.active.remove(&id).is_some();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.active.is_empty();
+    }
+
+    pub fn handle_request(&mut self, request: &Value) -> Option<Value> {
+        let method = request.get("method")?.as_str()?;
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        let first_param = match &params {
+            Value::Array(params) => params.first().cloned(),
+            Value::Object(params) => params
+                .get("topic")
+                .or_else(|| params.get("subscription"))
+                .cloned(),
+            _ => None,
+        };
+        let result = match method {
+            "subscribe" => match first_param.as_ref().and_then(Value::as_str) {
+                Some(name) => match Topic::parse(name) {
+                    Some(topic) => Ok(json!(self.subscribe(topic))),
+                    None => Err(RpcError::new(
+                        INVALID_PARAMS,
+                        &format!("unknown topic {}", name),
+                    )),
+                },
+                None => Err(RpcError::new(INVALID_PARAMS, "missing topic")),
+            },
+            "unsubscribe" => match first_param.as_ref().and_then(Value::as_u64) {
+                Some(id) => Ok(json!(self.unsubscribe(id))),
+                None => Err(RpcError::new(INVALID_PARAMS, "missing subscription id")),
+            },
+            _ => return None,
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        match result {
+            Ok(result) => return Some(json!({ "jsonrpc": "2.0", "result": result, "id": id })),
+            Err(error) => {
+                return Some(json!({ "jsonrpc": "2.0", "error": error.to_json(), "id": id }))
+            }
+        }
+    }
+
+    pub fn notifications(&self, event: &ChainEvent) -> Vec<Value> {
+        let topic = Topic::of(event);
+        return self
+            .active
+            .iter()
+            .filter(|(_, subscribed)| **subscribed == topic)
+            .map(|(id, _)| {
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "subscription",
+                    "params": { "subscription": id, "topic": topic.name(), "result": event_json(event) },
+                })
+            })
+            .collect();
+    }
+}
+
+impl Default for Subscriptions {
+    fn default() -> Subscriptions {
+        return Subscriptions::new();
+    }
+}
+
+pub fn server_sent_event(event: &ChainEvent) -> String {
+    return format!(
+        "event: {}\ndata: {}\n\n",
+        Topic::of(event).name(),
+        event_json(event)
+    );
+}
@@ -0,0 +1,76 @@
+use crate::client::RpcClient;
+
+use blockchain::blockchain::Blockchain;
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+pub fn sync_with_peer(blockchain: &Mutex<Blockchain>, peer: &RpcClient) -> Result<bool, String> {
+    let (local_height, base_height) = {
+        let blockchain = blockchain.lock().unwrap();
+        (blockchain.height(), blockchain.base_height())
+    };
+    if peer.block_count()? <= local_height {
+        return Ok(false);
+    }
+    let chain = peer.fetch_chain_from(base_height)?;
+    let mut blockchain = blockchain.lock().unwrap();
+    let tip = blockchain.last_block().hash;
+    if let Err(e) = blockchain.replace_chain(chain) {
+        return Err(format!("{}: refused chain: {}", peer.url(), e));
+    }
+    return Ok(blockchain.last_block().hash != tip);
+}
+
+// Adopts the peer's finality certificate if it is ahead, then counts the
+// votes it has seen. Returns whether a block became final.
+pub fn sync_finality(blockchain: &Mutex<Blockchain>, peer: &RpcClient) -> Result<bool, String> {
+    let certificate = peer.finality()?;
+    let votes = peer.finality_votes()?;
+    let mut blockchain = blockchain.lock().unwrap();
+    let finalized = blockchain.finalized().map(|certificate| certificate.height);
+    if let Some(certificate) = certificate {
+        if Some(certificate.height) > finalized && certificate.height <= blockchain.height() {
+            if let Err(e) = blockchain.finalize(certificate) {
+                return Err(format!("{}: refused certificate: {}", peer.url(), e));
+            }
+        }
+    }
+    let mut refused = None;
+    for vote in votes {
+        if let Err(e) = blockchain.add_finality_vote(vote) {
+            refused.get_or_insert(e);
+        }
+    }
+    if let Some(e) = refused {
+        return Err(format!("{}: refused finality vote: {}", peer.url(), e));
+    }
+    return Ok(blockchain.finalized().map(|certificate| certificate.height) != finalized);
+}
+
+pub fn spawn_sync(blockchain: Arc<Mutex<Blockchain>>, peers: Vec<String>, interval: Duration) {
+    if peers.is_empty() {
+        return;
+    }
+    let peers: Vec<RpcClient> = peers.iter().map(|peer| RpcClient::new(peer)).collect();
+    let finality = blockchain.lock().unwrap().finality_validators().is_ok();
+    thread::spawn(move || loop {
+        for peer in peers.iter() {
+            match sync_with_peer(&blockchain, peer) {
+                Ok(true) => println!("synced chain from {}", peer.url()),
+                Ok(false) => {}
+                Err(e) => eprintln!("sync failed: {}", e),
+            }
+            if finality {
+                match sync_finality(&blockchain, peer) {
+                    Ok(true) => println!("synced finality from {}", peer.url()),
+                    Ok(false) => {}
+                    Err(e) => eprintln!("finality sync failed: {}", e),
+                }
+            }
+        }
+        thread::sleep(interval);
+    });
+}
@@ -0,0 +1,670 @@
+use crate::{commands::*, config::NodeConfig};
+
+use blockchain::{
+    block::Block,
+    blockchain::Blockchain,
+    consensus::StakeParams,
+    finality::{FinalityCertificate, FinalityParams, FinalityVote, VoteKind},
+    params::{Consensus, ProofOfWork},
+    sled_store::SLED_DIR,
+    store::{ChainStore, FileStore, StoreBackend},
+    types::{BlockHeight, Hash256, Nonce},
+};
+use crypto::{
+    hasher::{Argon2Params, HashAlgorithm},
+    keys::KeyPair,
+};
+use std::{fs, thread, time::Duration};
+use tempfile::{tempdir, TempDir};
+
+fn setup() -> (TempDir, NodeConfig) {
+    let dir = tempdir().unwrap();
+    let config = NodeConfig {
+        data_dir: dir.path().join("data"),
+        http_address: String::from("127.0.0.1:0"),
+        ws_address: String::from("127.0.0.1:0"),
+        ..NodeConfig::default()
+    };
+    init(&config).unwrap();
+    return (dir, config);
+}
+
+// sled releases a store's file lock from a background thread shortly after
+// the store is dropped. Tests running commands one after another against a
+// sled store wait for that, as they would if each command were its own
+// process.
+fn wait_until_closed(config: &NodeConfig) {
+    let lock = config.data_dir.join(SLED_DIR).join("db");
+    for _ in 0..100 {
+        let file = match fs::File::open(&lock) {
+            Ok(file) => file,
+            Err(_) => return,
+        };
+        if file.try_lock().is_ok() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("{} is still locked", lock.display());
+}
+
+mod init {
+    use super::*;
+
+    #[test]
+    fn writes_genesis_chain_and_config() {
+        let (_dir, config) = setup();
+        assert_eq!(load(&config).unwrap().chain, Blockchain::new().chain);
+        assert!(config.data_dir.join(crate::config::CONFIG_FILE).exists());
+    }
+
+    #[test]
+    fn refuses_to_overwrite_existing_chain() {
+        let (_dir, config) = setup();
+        assert!(init(&config).is_err());
+    }
+}
+
+mod mine {
+    use super::*;
+
+    #[test]
+    fn appends_blocks_to_stored_chain() {
+        let (_dir, config) = setup();
+        let output = mine(&config, 2, "Raccoons are cool").unwrap();
+        assert_eq!(output.lines().count(), 2);
+        let blockchain = load(&config).unwrap();
+        assert_eq!(blockchain.chain.len(), 3);
+        assert_eq!(blockchain.chain[2].data, "Raccoons are cool");
+    }
+
+    #[test]
+    fn persists_index_alongside_chain() {
+        let (_dir, config) = setup();
+        mine(&config, 2, "Raccoons are cool").unwrap();
+        let store = FileStore::new(&config.data_dir);
+        let blockchain = load(&config).unwrap();
+        assert_eq!(&store.load_index().unwrap(), blockchain.index());
+        assert_eq!(
+            blockchain.find_block(&blockchain.chain[2].hash).unwrap().0,
+            BlockHeight(2)
+        );
+
+        fs::remove_file(store.index_path()).unwrap();
+        assert_eq!(load(&config).unwrap().index(), blockchain.index());
+    }
+
+    #[test]
+    fn sled_backend_round_trips_chain_and_index() {
+        let dir = tempdir().unwrap();
+        let config = NodeConfig {
+            data_dir: dir.path().join("data"),
+            store: StoreBackend::Sled,
+            ..NodeConfig::default()
+        };
+        init(&config).unwrap();
+        wait_until_closed(&config);
+        assert!(init(&config).is_err());
+        wait_until_closed(&config);
+        mine(&config, 2, "Raccoons are cool").unwrap();
+        wait_until_closed(&config);
+        let blockchain = load(&config).unwrap();
+        assert_eq!(blockchain.chain.len(), 3);
+        assert!(!FileStore::new(&config.data_dir).exists());
+        wait_until_closed(&config);
+        assert_eq!(
+            open_store(&config).unwrap().load_index().unwrap(),
+            *blockchain.index()
+        );
+        wait_until_closed(&config);
+        assert!(validate(&config).is_ok());
+    }
+
+    #[test]
+    fn requires_initialized_data_dir() {
+        let dir = tempdir().unwrap();
+        let config = NodeConfig {
+            data_dir: dir.path().to_path_buf(),
+            ..NodeConfig::default()
+        };
+        assert!(mine(&config, 1, "data").is_err());
+    }
+
+    #[test]
+    fn seals_blocks_with_the_validator_key_under_proof_of_stake() {
+        let (_dir, mut config) = setup();
+        let key_pair = KeyPair::generate();
+        config.chain.consensus = Consensus::ProofOfStake(StakeParams {
+            slot_ms: 5,
+            bootstrap_validators: vec![key_pair.address()],
+            ..StakeParams::default()
+        });
+        assert!(mine(&config, 1, "Raccoons are cool")
+            .unwrap_err()
+            .contains("validator key"));
+        config.validator_key = Some(hex::encode(key_pair.secret_bytes()));
+        mine(&config, 2, "Raccoons are cool").unwrap();
+        let blockchain = load(&config).unwrap();
+        let seal = blockchain.last_block().seal.as_ref().unwrap();
+        assert_eq!(seal.address(), key_pair.address());
+        assert!(validate(&config).is_ok());
+    }
+
+    #[test]
+    fn refuses_malformed_validator_key() {
+        let (_dir, mut config) = setup();
+        config.validator_key = Some(String::from("not hex"));
+        assert!(mine(&config, 1, "data").is_err());
+    }
+}
+
+mod finality {
+    use super::*;
+    use crate::sync::spawn_sync;
+    use std::sync::Arc;
+
+    fn certificate(key_pairs: &[KeyPair], height: u64, hash: Hash256) -> FinalityCertificate {
+        let height = BlockHeight(height);
+        return FinalityCertificate {
+            height,
+            round: 0,
+            hash,
+            precommits: key_pairs
+                .iter()
+                .map(|key_pair| {
+                    FinalityVote::new_signed(key_pair, VoteKind::Precommit, height, 0, hash)
+                })
+                .collect(),
+        };
+    }
+
+    #[test]
+    fn restores_the_finalized_block_on_load() {
+        let (_dir, mut config) = setup();
+        mine(&config, 2, "Raccoons are cool").unwrap();
+        let key_pairs: Vec<KeyPair> = (0..3).map(|_| KeyPair::generate()).collect();
+        config.chain.finality = Some(FinalityParams {
+            validators: key_pairs
+                .iter()
+                .map(|key_pair| hex::encode(key_pair.public_key()))
+                .collect(),
+        });
+        let hash = load(&config).unwrap().chain[1].hash;
+        let store = FileStore::new(&config.data_dir);
+        store
+            .commit_finality(&certificate(&key_pairs, 1, hash))
+            .unwrap();
+        let blockchain = load(&config).unwrap();
+        assert_eq!(blockchain.finalized().unwrap().hash, hash);
+
+        store
+            .commit_finality(&certificate(&key_pairs, 1, Hash256([7; 32])))
+            .unwrap();
+        assert!(load(&config).is_err());
+    }
+
+    #[test]
+    fn validators_finalize_blocks_by_syncing_with_each_other() {
+        let (_dir, mut first) = setup();
+        let (_other_dir, mut second) = setup();
+        mine(&first, 2, "Raccoons are cool").unwrap();
+        let key_pairs: Vec<KeyPair> = (0..2).map(|_| KeyPair::generate()).collect();
+        let finality = FinalityParams {
+            validators: key_pairs
+                .iter()
+                .map(|key_pair| hex::encode(key_pair.public_key()))
+                .collect(),
+        };
+        for (config, key_pair) in [&mut first, &mut second].iter_mut().zip(key_pairs.iter()) {
+            config.chain.finality = Some(finality.clone());
+            config.validator_key = Some(hex::encode(key_pair.secret_bytes()));
+            config.sync_interval_ms = 10;
+        }
+        let first_node = start(&first).unwrap();
+        second.peers = vec![first_node.http_address.to_string()];
+        let second_node = start(&second).unwrap();
+        spawn_sync(
+            Arc::clone(&first_node.blockchain),
+            vec![second_node.http_address.to_string()],
+            Duration::from_millis(10),
+        );
+
+        let hash = first_node.blockchain.lock().unwrap().chain[2].hash;
+        let store = FileStore::new(&first.data_dir);
+        for _ in 0..500 {
+            let finalized = |node: &RunningNode| {
+                let blockchain = node.blockchain.lock().unwrap();
+                blockchain.finalized().map(|certificate| certificate.hash)
+            };
+            let stored = store.load_finality().unwrap();
+            if finalized(&first_node) == Some(hash)
+                && finalized(&second_node) == Some(hash)
+                && stored.map(|certificate| certificate.hash) == Some(hash)
+            {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("block 2 was not finalized");
+    }
+}
+
+mod show {
+    use super::*;
+
+    #[test]
+    fn finds_block_by_height_and_hash() {
+        let (_dir, config) = setup();
+        mine(&config, 1, "Raccoons are cool").unwrap();
+        let hash = hex::encode(load(&config).unwrap().chain[1].hash);
+        let by_height = show(&config, "1").unwrap();
+        assert!(by_height.contains(&hash));
+        assert!(by_height.contains("Raccoons are cool"));
+        assert_eq!(show(&config, &hash).unwrap(), by_height);
+    }
+
+    #[test]
+    fn reports_unknown_block() {
+        let (_dir, config) = setup();
+        assert!(show(&config, "5").is_err());
+        assert!(show(&config, &"13".repeat(32)).is_err());
+        assert!(show(&config, "xyz").is_err());
+    }
+}
+
+mod validate {
+    use super::*;
+
+    #[test]
+    fn accepts_mined_chain() {
+        let (_dir, config) = setup();
+        mine(&config, 2, "data").unwrap();
+        assert!(validate(&config).is_ok());
+    }
+
+    #[test]
+    fn rejects_tampered_chain() {
+        let (_dir, config) = setup();
+        mine(&config, 2, "data").unwrap();
+        let mut chain = load(&config).unwrap().chain;
+        chain[1].data = String::from("tampered");
+        FileStore::new(&config.data_dir).save(&chain).unwrap();
+        assert_eq!(
+            validate(&config),
+            Err(String::from("chain is invalid at height 1"))
+        );
+    }
+
+    #[test]
+    fn checks_proof_of_work_with_the_configured_hash_algorithm() {
+        let (_dir, mut config) = setup();
+        config.chain.hash_algorithm = HashAlgorithm::Blake3;
+        mine(&config, 2, "data").unwrap();
+        assert!(validate(&config).is_ok());
+        config.chain.hash_algorithm = HashAlgorithm::Sha256;
+        assert!(validate(&config).is_err());
+    }
+
+    #[test]
+    fn refuses_proof_of_work_parameters_out_of_bounds() {
+        let (_dir, mut config) = setup();
+        config.chain.proof_of_work = ProofOfWork::Argon2(Argon2Params {
+            memory_kib: u32::MAX,
+            ..Argon2Params::default()
+        });
+        assert!(validate(&config).unwrap_err().contains("argon2 memory"));
+    }
+}
+
+mod export_import {
+    use super::*;
+
+    #[test]
+    fn import_replaces_shorter_chain() {
+        let (dir, source) = setup();
+        mine(&source, 2, "exported").unwrap();
+        let file = dir.path().join("export.json");
+        fs::write(&file, export(&source).unwrap()).unwrap();
+
+        let (_other_dir, target) = setup();
+        import(&target, &file).unwrap();
+        assert_eq!(load(&target).unwrap().chain, load(&source).unwrap().chain);
+    }
+
+    #[test]
+    fn import_refuses_chain_that_is_not_longer() {
+        let (dir, source) = setup();
+        let file = dir.path().join("export.json");
+        fs::write(&file, export(&source).unwrap()).unwrap();
+        let (_other_dir, target) = setup();
+        mine(&target, 1, "local").unwrap();
+        assert!(import(&target, &file).is_err());
+        assert_eq!(load(&target).unwrap().chain.len(), 2);
+    }
+
+    #[test]
+    fn import_refuses_invalid_chain() {
+        let (dir, source) = setup();
+        mine(&source, 2, "exported").unwrap();
+        let mut chain = load(&source).unwrap().chain;
+        chain[2].nonce = Nonce(chain[2].nonce.0 + 1);
+        let file = dir.path().join("export.json");
+        fs::write(&file, serde_json::to_string(&chain).unwrap()).unwrap();
+        let (_other_dir, target) = setup();
+        assert!(import(&target, &file)
+            .unwrap_err()
+            .ends_with("export.json: new chain is invalid at height 2"));
+    }
+}
+
+mod checkpoints {
+    use super::*;
+    use crate::config::CHECKPOINTS_FILE;
+
+    #[test]
+    fn import_refuses_reorg_below_a_checkpoint_in_the_data_dir() {
+        let (dir, config) = setup();
+        mine(&config, 3, "local").unwrap();
+        let (_other_dir, source) = setup();
+        mine(&source, 5, "fork").unwrap();
+        let file = dir.path().join("export.json");
+        fs::write(&file, export(&source).unwrap()).unwrap();
+
+        let local = load(&config).unwrap();
+        let checkpoints = config.data_dir.join(CHECKPOINTS_FILE);
+        fs::write(&checkpoints, format!("2:{}\n", local.chain[2].hash)).unwrap();
+        assert!(import(&config, &file)
+            .unwrap_err()
+            .contains("conflicts with checkpoint"));
+        assert_eq!(load(&config).unwrap().chain, local.chain);
+
+        fs::remove_file(&checkpoints).unwrap();
+        import(&config, &file).unwrap();
+        assert_eq!(load(&config).unwrap().chain, load(&source).unwrap().chain);
+    }
+
+    #[test]
+    fn refuses_a_malformed_checkpoints_file() {
+        let (_dir, config) = setup();
+        fs::write(config.data_dir.join(CHECKPOINTS_FILE), "2\n").unwrap();
+        assert!(load(&config)
+            .unwrap_err()
+            .contains("invalid checkpoint 2 (expected HEIGHT:HASH)"));
+    }
+}
+
+mod snapshot {
+    use super::*;
+    use crate::{config::SNAPSHOT_DIR, rpc::RpcHandler, server::spawn_http};
+    use std::sync::{Arc, Mutex};
+
+    fn empty_config(dir: &TempDir, store: StoreBackend) -> NodeConfig {
+        return NodeConfig {
+            data_dir: dir.path().join("fast"),
+            store,
+            ..NodeConfig::default()
+        };
+    }
+
+    fn checkpoint(config: &NodeConfig, height: u64) -> String {
+        let blockchain = load(config).unwrap();
+        let hash = blockchain.block_at(BlockHeight(height)).unwrap().hash;
+        return format!("{}:{}", height, hash);
+    }
+
+    #[test]
+    fn exports_and_initializes_from_snapshot() {
+        let (dir, config) = setup();
+        mine(&config, 2, "Raccoons are cool").unwrap();
+        let path = dir.path().join("snapshot.json");
+        fs::write(&path, snapshot(&config, Some(BlockHeight(1))).unwrap()).unwrap();
+
+        let fast = empty_config(&dir, StoreBackend::File);
+        init_from_snapshot(&fast, &path, &checkpoint(&config, 1)).unwrap();
+        let blockchain = load(&fast).unwrap();
+        assert_eq!(blockchain.base_height(), BlockHeight(1));
+        assert_eq!(blockchain.height(), BlockHeight(1));
+        mine(&fast, 1, "Skunks smell bad").unwrap();
+        assert_eq!(load(&fast).unwrap().height(), BlockHeight(2));
+        assert!(validate(&fast).is_ok());
+        assert!(show(&fast, "2").is_ok());
+        assert!(show(&fast, "0").is_err());
+    }
+
+    #[test]
+    fn refuses_snapshot_that_is_not_the_checkpoint() {
+        let (dir, config) = setup();
+        mine(&config, 2, "Raccoons are cool").unwrap();
+        let path = dir.path().join("snapshot.json");
+        fs::write(&path, snapshot(&config, Some(BlockHeight(1))).unwrap()).unwrap();
+        let fast = empty_config(&dir, StoreBackend::File);
+        assert!(init_from_snapshot(&fast, &path, &checkpoint(&config, 2)).is_err());
+        assert!(init_from_snapshot(&fast, &path, "1").is_err());
+        assert!(!fast.data_dir.exists());
+    }
+
+    #[test]
+    fn fast_syncs_from_peer_and_validates_forward() {
+        let (dir, config) = setup();
+        mine(&config, 3, "Raccoons are cool").unwrap();
+        let peer = Arc::new(Mutex::new(load(&config).unwrap()));
+        let handler = Arc::new(RpcHandler::new(Arc::clone(&peer)));
+        let address = spawn_http("127.0.0.1:0", handler).unwrap().to_string();
+
+        for store in [StoreBackend::File, StoreBackend::Sled].iter() {
+            let fast = NodeConfig {
+                data_dir: dir.path().join(format!("fast-{}", store)),
+                store: *store,
+                ..NodeConfig::default()
+            };
+            let output = fast_sync(&fast, &address, &checkpoint(&config, 1)).unwrap();
+            assert!(output.contains("validated 2 blocks"));
+            wait_until_closed(&fast);
+            let blockchain = load(&fast).unwrap();
+            assert_eq!(blockchain.chain[..], peer.lock().unwrap().chain[1..]);
+            assert_eq!(
+                blockchain.ledger().unwrap(),
+                peer.lock().unwrap().ledger().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn import_reports_heights_above_the_snapshot() {
+        let (dir, config) = setup();
+        mine(&config, 3, "Raccoons are cool").unwrap();
+        let peer = Arc::new(Mutex::new(load(&config).unwrap()));
+        let handler = Arc::new(RpcHandler::new(peer));
+        let address = spawn_http("127.0.0.1:0", handler).unwrap().to_string();
+        let fast = NodeConfig {
+            data_dir: dir.path().join("fast"),
+            ..NodeConfig::default()
+        };
+        fast_sync(&fast, &address, &checkpoint(&config, 1)).unwrap();
+        wait_until_closed(&fast);
+
+        mine(&config, 1, "Skunks smell bad").unwrap();
+        let mut chain = load(&config).unwrap().chain.split_off(1);
+        chain[3].nonce = Nonce(chain[3].nonce.0 + 1);
+        let file = dir.path().join("export.json");
+        fs::write(&file, serde_json::to_string(&chain).unwrap()).unwrap();
+        assert!(import(&fast, &file)
+            .unwrap_err()
+            .ends_with("export.json: new chain is invalid at height 4"));
+    }
+
+    #[test]
+    fn start_writes_periodic_snapshots() {
+        let (_dir, mut config) = setup();
+        config.snapshot_interval = 2;
+        let node = start(&config).unwrap();
+        for data in ["Raccoons are cool", "Skunks smell bad"].iter() {
+            node.blockchain
+                .lock()
+                .unwrap()
+                .add_block(String::from(*data))
+                .unwrap();
+        }
+        let path = config.data_dir.join(SNAPSHOT_DIR).join("snapshot-2.json");
+        for _ in 0..100 {
+            if path.exists() {
+                assert!(!config
+                    .data_dir
+                    .join(SNAPSHOT_DIR)
+                    .join("snapshot-1.json")
+                    .exists());
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("snapshot was not written");
+    }
+
+    #[test]
+    fn start_skips_snapshots_of_blocks_without_a_state_root() {
+        let (_dir, mut config) = setup();
+        config.snapshot_interval = 2;
+        let node = start(&config).unwrap();
+        {
+            let mut blockchain = node.blockchain.lock().unwrap();
+            blockchain
+                .add_block(String::from("Raccoons are cool"))
+                .unwrap();
+            let block =
+                Block::mine_block(blockchain.last_block(), String::from("Skunks smell bad"));
+            assert!(block.state_root.is_none());
+            assert!(blockchain.submit_block(block));
+            blockchain.add_block(String::from("Bears are big")).unwrap();
+            blockchain.add_block(String::from("Owls are wise")).unwrap();
+        }
+        let dir = config.data_dir.join(SNAPSHOT_DIR);
+        for _ in 0..100 {
+            if dir.join("snapshot-4.json").exists() {
+                assert!(!dir.join("snapshot-2.json").exists());
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("snapshot was not written");
+    }
+}
+
+mod prune {
+    use super::*;
+
+    #[test]
+    fn drops_old_bodies_and_keeps_validating() {
+        for store in [StoreBackend::File, StoreBackend::Sled].iter() {
+            let dir = tempdir().unwrap();
+            let config = NodeConfig {
+                data_dir: dir.path().join("data"),
+                store: *store,
+                ..NodeConfig::default()
+            };
+            init(&config).unwrap();
+            wait_until_closed(&config);
+            mine(&config, 3, "Raccoons are cool").unwrap();
+            wait_until_closed(&config);
+            let tip = load(&config).unwrap().last_block().hash;
+            wait_until_closed(&config);
+            assert!(prune(&config, None).is_err());
+            wait_until_closed(&config);
+            assert!(prune(&config, Some(1)).unwrap().contains("height 2"));
+            wait_until_closed(&config);
+            assert_eq!(prune(&config, Some(1)).unwrap(), "nothing to prune");
+            wait_until_closed(&config);
+
+            let blockchain = load(&config).unwrap();
+            assert_eq!(blockchain.base_height(), BlockHeight(2));
+            assert_eq!(blockchain.pruned_headers().len(), 2);
+            assert_eq!(blockchain.last_block().hash, tip);
+            wait_until_closed(&config);
+            assert!(validate(&config).is_ok());
+            wait_until_closed(&config);
+            assert!(show(&config, "3").is_ok());
+            wait_until_closed(&config);
+            assert!(show(&config, "1").unwrap_err().contains("pruned"));
+            wait_until_closed(&config);
+            mine(&config, 1, "Skunks smell bad").unwrap();
+            wait_until_closed(&config);
+            assert_eq!(load(&config).unwrap().height(), BlockHeight(4));
+        }
+    }
+
+    #[test]
+    fn start_prunes_to_the_configured_depth() {
+        let (_dir, mut config) = setup();
+        config.prune_depth = Some(1);
+        let node = start(&config).unwrap();
+        for data in ["Raccoons are cool", "Skunks smell bad"].iter() {
+            node.blockchain
+                .lock()
+                .unwrap()
+                .add_block(String::from(*data))
+                .unwrap();
+        }
+        for _ in 0..100 {
+            if load(&config).unwrap().base_height() == BlockHeight(1) {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("chain was not pruned");
+    }
+}
+
+mod start {
+    use super::*;
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn persists_new_blocks() {
+        let (_dir, config) = setup();
+        let node = start(&config).unwrap();
+        node.blockchain
+            .lock()
+            .unwrap()
+            .add_block(String::from("Raccoons are cool"))
+            .unwrap();
+        for _ in 0..100 {
+            if load(&config).unwrap().chain.len() == 2 {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("new block was not persisted");
+    }
+
+    #[test]
+    fn persists_reorgs() {
+        let (_dir, config) = setup();
+        let node = start(&config).unwrap();
+        node.blockchain
+            .lock()
+            .unwrap()
+            .add_block(String::from("Raccoons are cool"))
+            .unwrap();
+        let mut fork = Blockchain::new();
+        for data in ["Bears are big", "Owls are wise", "Foxes are quick"].iter() {
+            fork.add_block(String::from(*data)).unwrap();
+        }
+        node.blockchain
+            .lock()
+            .unwrap()
+            .replace_chain(fork.chain.clone())
+            .unwrap();
+        node.blockchain
+            .lock()
+            .unwrap()
+            .add_block(String::from("Cats are curious"))
+            .unwrap();
+        let expected = node.blockchain.lock().unwrap().chain.clone();
+        for _ in 0..100 {
+            if load(&config).unwrap().chain == expected {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("reorg was not persisted");
+    }
+}
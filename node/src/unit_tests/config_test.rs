@@ -0,0 +1,131 @@
+use crate::config::*;
+
+use blockchain::{
+    consensus::StakeParams,
+    params::{Consensus, ProofOfWork},
+    store::StoreBackend,
+    types::{BlockHeight, Hash256},
+};
+use crypto::hasher::{Argon2Params, HashAlgorithm};
+use std::{fs, path::PathBuf};
+use tempfile::tempdir;
+
+mod node_config {
+    use super::*;
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        let config = NodeConfig {
+            peers: vec![String::from("127.0.0.1:9000")],
+            ..NodeConfig::default()
+        };
+        config.save(&path).unwrap();
+        assert_eq!(NodeConfig::load(&path).unwrap(), config);
+    }
+
+    #[test]
+    fn missing_fields_take_defaults() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        fs::write(&path, "http_address = \"0.0.0.0:1234\"\n").unwrap();
+        let config = NodeConfig::load(&path).unwrap();
+        assert_eq!(config.http_address, "0.0.0.0:1234");
+        assert_eq!(config.ws_address, NodeConfig::default().ws_address);
+    }
+
+    #[test]
+    fn reads_store_backend() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        fs::write(&path, "store = \"sled\"\n").unwrap();
+        assert_eq!(NodeConfig::load(&path).unwrap().store, StoreBackend::Sled);
+        fs::write(&path, "store = \"rocksdb\"\n").unwrap();
+        assert!(NodeConfig::load(&path).is_err());
+    }
+
+    #[test]
+    fn reads_chain_params() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        let hash = "ab".repeat(32);
+        let contents = format!(
+            "[chain]\nmax_reorg_depth = 5\nhash_algorithm = \"blake3\"\n\n[[chain.checkpoints]]\nheight = 7\nhash = \"{}\"\n",
+            hash
+        );
+        fs::write(&path, contents).unwrap();
+        let params = NodeConfig::load(&path).unwrap().chain;
+        assert_eq!(params.max_reorg_depth, Some(5));
+        assert_eq!(params.checkpoints.len(), 1);
+        assert_eq!(
+            params.checkpoint_at(BlockHeight(7)).unwrap().hash,
+            Hash256([0xab; 32])
+        );
+        assert_eq!(params.hash_algorithm, HashAlgorithm::Blake3);
+    }
+
+    #[test]
+    fn reads_memory_hard_proof_of_work() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        let contents =
+            "[chain.proof_of_work]\ntype = \"argon2\"\nmemory_kib = 1024\niterations = 2\n";
+        fs::write(&path, contents).unwrap();
+        let params = NodeConfig::load(&path).unwrap().chain;
+        let expected = Argon2Params {
+            memory_kib: 1024,
+            iterations: 2,
+            parallelism: 1,
+        };
+        assert_eq!(params.proof_of_work, ProofOfWork::Argon2(expected));
+    }
+
+    #[test]
+    fn reads_proof_of_stake_and_validator_key() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        let validator = "ab".repeat(32);
+        let contents = format!(
+            "validator_key = \"{}\"\n[chain.consensus]\ntype = \"proof-of-stake\"\nslot_ms = 2000\nbootstrap_validators = [\"{}\"]\n",
+            "01".repeat(32),
+            validator
+        );
+        fs::write(&path, contents).unwrap();
+        let config = NodeConfig::load(&path).unwrap();
+        assert_eq!(config.validator_key, Some("01".repeat(32)));
+        let expected = StakeParams {
+            slot_ms: 2000,
+            bootstrap_validators: vec![validator],
+            ..StakeParams::default()
+        };
+        assert_eq!(config.chain.consensus, Consensus::ProofOfStake(expected));
+    }
+
+    #[test]
+    fn load_reports_invalid_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        fs::write(&path, "peers = 12").unwrap();
+        assert!(NodeConfig::load(&path).is_err());
+    }
+
+    #[test]
+    fn resolve_reads_config_from_data_dir_and_keeps_flag_data_dir() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        fs::write(&path, "data_dir = \"elsewhere\"\nsync_interval_ms = 10\n").unwrap();
+        let config = NodeConfig::resolve(Some(dir.path().to_path_buf()), None).unwrap();
+        assert_eq!(config.data_dir, dir.path());
+        assert_eq!(config.sync_interval_ms, 10);
+    }
+
+    #[test]
+    fn resolve_prefers_explicit_config_path() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("custom.toml");
+        fs::write(&path, "data_dir = \"from-config\"\n").unwrap();
+        let config = NodeConfig::resolve(None, Some(path)).unwrap();
+        assert_eq!(config.data_dir, PathBuf::from("from-config"));
+    }
+}
@@ -0,0 +1,6 @@
+mod commands_test;
+mod config_test;
+mod rpc_test;
+mod server_test;
+mod subscriptions_test;
+mod sync_test;
@@ -0,0 +1,607 @@
+use blockchain::{
+    block::Block,
+    blockchain::Blockchain,
+    types::{BlockHeight, Hash256},
+};
+
+use crate::rpc::*;
+
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+fn setup() -> (Arc<Mutex<Blockchain>>, RpcHandler) {
+    let mut blockchain = Blockchain::new();
+    blockchain
+        .add_block(String::from("Raccoons are cool"))
+        .unwrap();
+    blockchain
+        .add_block(String::from("Skunks smell bad"))
+        .unwrap();
+    let blockchain = Arc::new(Mutex::new(blockchain));
+    let handler = RpcHandler::new(Arc::clone(&blockchain));
+    return (blockchain, handler);
+}
+
+fn request(handler: &RpcHandler, method: &str, params: Value) -> Value {
+    let message = json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": 1 });
+    let response = handler.handle_message(&message.to_string()).unwrap();
+    return serde_json::from_str(&response).unwrap();
+}
+
+mod envelope {
+    use super::*;
+
+    #[test]
+    fn echoes_request_id() {
+        let (_, handler) = setup();
+        let message = json!({ "jsonrpc": "2.0", "method": "getblockcount", "id": "abc" });
+        let response: Value =
+            serde_json::from_str(&handler.handle_message(&message.to_string()).unwrap()).unwrap();
+        assert_eq!(response["jsonrpc"], "2.0");
+        assert_eq!(response["id"], "abc");
+        assert_eq!(response["result"], 2);
+    }
+
+    #[test]
+    fn reports_parse_error() {
+        let (_, handler) = setup();
+        let response: Value =
+            serde_json::from_str(&handler.handle_message("{not json").unwrap()).unwrap();
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+        assert_eq!(response["id"], Value::Null);
+    }
+
+    #[test]
+    fn reports_invalid_request() {
+        let (_, handler) = setup();
+        let message = json!({ "jsonrpc": "1.0", "method": "getblockcount", "id": 4 });
+        let response: Value =
+            serde_json::from_str(&handler.handle_message(&message.to_string()).unwrap()).unwrap();
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+        assert_eq!(response["id"], 4);
+    }
+
+    #[test]
+    fn reports_unknown_method() {
+        let (_, handler) = setup();
+        let response = request(&handler, "getfoo", json!([]));
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn does_not_respond_to_notifications() {
+        let (_, handler) = setup();
+        let message = json!({ "jsonrpc": "2.0", "method": "getblockcount" });
+        assert!(handler.handle_message(&message.to_string()).is_none());
+    }
+
+    #[test]
+    fn answers_batches_in_order() {
+        let (_, handler) = setup();
+        let message = json!([
+            { "jsonrpc": "2.0", "method": "getblockcount", "id": 1 },
+            { "jsonrpc": "2.0", "method": "getdifficulty" },
+            { "jsonrpc": "2.0", "method": "getfoo", "id": 2 },
+        ]);
+        let response: Value =
+            serde_json::from_str(&handler.handle_message(&message.to_string()).unwrap()).unwrap();
+        let responses = response.as_array().unwrap();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[1]["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn rejects_empty_batch() {
+        let (_, handler) = setup();
+        let response: Value = serde_json::from_str(&handler.handle_message("[]").unwrap()).unwrap();
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+    }
+}
+
+mod chain_queries {
+    use super::*;
+
+    #[test]
+    fn getblockcount_returns_tip_height() {
+        let (blockchain, handler) = setup();
+        assert_eq!(request(&handler, "getblockcount", json!([]))["result"], 2);
+        blockchain
+            .lock()
+            .unwrap()
+            .add_block(String::from("Bears are big"))
+            .unwrap();
+        assert_eq!(request(&handler, "getblockcount", json!([]))["result"], 3);
+    }
+
+    #[test]
+    fn getbestblockhash_returns_tip_hash() {
+        let (blockchain, handler) = setup();
+        let tip_hash = hex::encode(blockchain.lock().unwrap().last_block().hash);
+        assert_eq!(
+            request(&handler, "getbestblockhash", json!([]))["result"],
+            tip_hash
+        );
+    }
+
+    #[test]
+    fn getdifficulty_returns_tip_difficulty() {
+        let (blockchain, handler) = setup();
+        let difficulty = blockchain.lock().unwrap().last_block().difficulty;
+        assert_eq!(
+            request(&handler, "getdifficulty", json!([]))["result"],
+            json!(difficulty)
+        );
+    }
+}
+
+mod getblock {
+    use super::*;
+
+    #[test]
+    fn returns_verbose_block_by_default() {
+        let (blockchain, handler) = setup();
+        let chain = blockchain.lock().unwrap().chain.clone();
+        let result = &request(&handler, "getblock", json!([hex::encode(chain[1].hash)]))["result"];
+        assert_eq!(result["height"], 1);
+        assert_eq!(result["confirmations"], 2);
+        assert_eq!(result["data"], "Raccoons are cool");
+        assert_eq!(result["previousblockhash"], hex::encode(chain[0].hash));
+        assert_eq!(result["nextblockhash"], hex::encode(chain[2].hash));
+    }
+
+    #[test]
+    fn accepts_named_params() {
+        let (blockchain, handler) = setup();
+        let hash = hex::encode(blockchain.lock().unwrap().chain[2].hash);
+        let result = &request(&handler, "getblock", json!({ "blockhash": hash }))["result"];
+        assert_eq!(result["height"], 2);
+        assert!(result.get("nextblockhash").is_none());
+    }
+
+    #[test]
+    fn returns_raw_block_for_verbosity_zero() {
+        let (blockchain, handler) = setup();
+        let block = blockchain.lock().unwrap().chain[1].clone();
+        let result = &request(&handler, "getblock", json!([hex::encode(block.hash), 0]))["result"];
+        assert_eq!(decode_block(result.as_str().unwrap()).unwrap(), block);
+    }
+
+    #[test]
+    fn reports_unknown_block() {
+        let (_, handler) = setup();
+        let response = request(&handler, "getblock", json!([hex::encode([13; 32])]));
+        assert_eq!(response["error"]["code"], BLOCK_NOT_FOUND);
+    }
+
+    #[test]
+    fn rejects_malformed_hash() {
+        let (_, handler) = setup();
+        let response = request(&handler, "getblock", json!(["abcd"]));
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+}
+
+mod getblockheader {
+    use super::*;
+
+    #[test]
+    fn omits_block_data() {
+        let (blockchain, handler) = setup();
+        let hash = hex::encode(blockchain.lock().unwrap().chain[0].hash);
+        let result = &request(&handler, "getblockheader", json!([hash]))["result"];
+        assert_eq!(result["height"], 0);
+        assert_eq!(result["hash"], hash);
+        assert!(result.get("data").is_none());
+        assert!(result.get("previousblockhash").is_none());
+    }
+
+    #[test]
+    fn returns_hex_header_when_not_verbose() {
+        let (blockchain, handler) = setup();
+        let hash = hex::encode(blockchain.lock().unwrap().chain[1].hash);
+        let raw = request(&handler, "getblockheader", json!([hash, false]))["result"].clone();
+        assert_eq!(
+            decode_header(raw.as_str().unwrap()).unwrap(),
+            blockchain.lock().unwrap().chain[1].header()
+        );
+    }
+}
+
+mod submitblock {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_block() {
+        let (blockchain, handler) = setup();
+        let block = Block::mine_block(blockchain.lock().unwrap().last_block(), String::from("new"));
+        let response = request(
+            &handler,
+            "submitblock",
+            json!([encode_block(&block).unwrap()]),
+        );
+        assert_eq!(response["result"], Value::Null);
+        assert_eq!(blockchain.lock().unwrap().last_block(), &block);
+    }
+
+    #[test]
+    fn reports_duplicate_block() {
+        let (blockchain, handler) = setup();
+        let block = blockchain.lock().unwrap().chain[1].clone();
+        let response = request(
+            &handler,
+            "submitblock",
+            json!([encode_block(&block).unwrap()]),
+        );
+        assert_eq!(response["result"], "duplicate");
+    }
+
+    #[test]
+    fn rejects_invalid_block() {
+        let (blockchain, handler) = setup();
+        let mut block =
+            Block::mine_block(blockchain.lock().unwrap().last_block(), String::from("new"));
+        block.data = String::from("tampered");
+        let response = request(
+            &handler,
+            "submitblock",
+            json!([encode_block(&block).unwrap()]),
+        );
+        assert_eq!(response["result"], "rejected");
+        assert_eq!(blockchain.lock().unwrap().chain.len(), 3);
+    }
+
+    #[test]
+    fn reports_undecodable_block() {
+        let (_, handler) = setup();
+        let response = request(&handler, "submitblock", json!(["zz"]));
+        assert_eq!(response["error"]["code"], DESERIALIZATION_ERROR);
+    }
+}
+
+mod transactions {
+    use super::*;
+    use blockchain::transaction::{Transaction, TxOutput};
+    use crypto::keys::KeyPair;
+
+    fn funded_payment(blockchain: &Arc<Mutex<Blockchain>>, handler: &RpcHandler) -> Transaction {
+        let key_pair = KeyPair::generate();
+        request(handler, "generatetoaddress", json!([1, key_pair.address()]));
+        let (outpoint, output) = blockchain
+            .lock()
+            .unwrap()
+            .ledger()
+            .unwrap()
+            .unspent(&key_pair.address())[0]
+            .clone();
+        let outputs = vec![TxOutput {
+            amount: output.amount - 1,
+            address: "cd".repeat(32),
+        }];
+        return Transaction::new_signed(&[(outpoint, &key_pair)], outputs, 0);
+    }
+
+    #[test]
+    fn sendrawtransaction_adds_to_mempool() {
+        let (blockchain, handler) = setup();
+        let transaction = funded_payment(&blockchain, &handler);
+        let response = request(
+            &handler,
+            "sendrawtransaction",
+            json!([transaction.encode_hex()]),
+        );
+        assert_eq!(response["result"], hex::encode(transaction.txid()));
+        let info = request(&handler, "getmempoolinfo", json!([]))["result"].clone();
+        assert_eq!(info["size"], 1);
+        assert_eq!(info["bytes"], transaction.encode().len());
+        assert_eq!(
+            request(&handler, "getrawmempool", json!([]))["result"],
+            json!([hex::encode(transaction.txid())])
+        );
+    }
+
+    #[test]
+    fn sendrawtransaction_reports_rejection_and_decode_errors() {
+        let (blockchain, handler) = setup();
+        let mut transaction = funded_payment(&blockchain, &handler);
+        transaction.outputs[0].amount += 10;
+        let response = request(
+            &handler,
+            "sendrawtransaction",
+            json!([transaction.encode_hex()]),
+        );
+        assert_eq!(response["error"]["code"], TRANSACTION_REJECTED);
+        let response = request(&handler, "sendrawtransaction", json!(["00"]));
+        assert_eq!(response["error"]["code"], DESERIALIZATION_ERROR);
+    }
+
+    #[test]
+    fn getrawtransaction_finds_mempool_and_mined_transactions() {
+        let (blockchain, handler) = setup();
+        let transaction = funded_payment(&blockchain, &handler);
+        let txid = hex::encode(transaction.txid());
+        let response = request(&handler, "getrawtransaction", json!([txid]));
+        assert_eq!(response["error"]["code"], TRANSACTION_NOT_FOUND);
+        request(
+            &handler,
+            "sendrawtransaction",
+            json!([transaction.encode_hex()]),
+        );
+        let response = request(&handler, "getrawtransaction", json!([txid]));
+        assert_eq!(response["result"], json!(transaction.encode_hex()));
+        request(&handler, "generatetoaddress", json!([1, "ab".repeat(32)]));
+        let response = request(&handler, "getrawtransaction", json!({ "txid": txid }));
+        assert_eq!(response["result"], json!(transaction.encode_hex()));
+        let response = request(&handler, "getrawtransaction", json!(["abcd"]));
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn generatetoaddress_mines_reward_and_listunspent_reports_it() {
+        let (blockchain, handler) = setup();
+        let address = KeyPair::generate().address();
+        let response = request(&handler, "generatetoaddress", json!([2, address]));
+        assert_eq!(response["result"].as_array().unwrap().len(), 2);
+        assert_eq!(blockchain.lock().unwrap().chain.len(), 5);
+        let unspent = request(&handler, "listunspent", json!([[address]]))["result"].clone();
+        assert_eq!(unspent.as_array().unwrap().len(), 2);
+        assert_eq!(unspent[0]["address"], json!(address));
+        assert_eq!(unspent[0]["vout"], 0);
+    }
+
+    #[test]
+    fn generatetoaddress_caps_the_blocks_mined_per_call() {
+        let (blockchain, handler) = setup();
+        let address = KeyPair::generate().address();
+        let response = request(
+            &handler,
+            "generatetoaddress",
+            json!([MAX_GENERATE_BLOCKS + 1, address]),
+        );
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        assert_eq!(blockchain.lock().unwrap().chain.len(), 3);
+    }
+
+    #[test]
+    fn getreceivedbyaddress_sums_outputs_even_when_spent() {
+        let (blockchain, handler) = setup();
+        let transaction = funded_payment(&blockchain, &handler);
+        let ledger = blockchain.lock().unwrap().ledger().unwrap();
+        let funded = ledger.get(&transaction.inputs[0].previous_output).unwrap();
+        let recipient = transaction.outputs[0].address.clone();
+        blockchain
+            .lock()
+            .unwrap()
+            .submit_transaction(transaction)
+            .unwrap();
+        request(&handler, "generatetoaddress", json!([1, "ab".repeat(32)]));
+        let response = request(&handler, "getreceivedbyaddress", json!([funded.address]));
+        assert_eq!(response["result"], funded.amount);
+        let response = request(&handler, "getreceivedbyaddress", json!([recipient]));
+        assert_eq!(response["result"], funded.amount - 1);
+    }
+
+    #[test]
+    fn rejects_invalid_addresses() {
+        let (_, handler) = setup();
+        let response = request(&handler, "generatetoaddress", json!([1, "nowhere"]));
+        assert_eq!(response["error"]["code"], INVALID_ADDRESS);
+        let response = request(&handler, "listunspent", json!([["nowhere"]]));
+        assert_eq!(response["error"]["code"], INVALID_ADDRESS);
+    }
+}
+
+mod getblockhash {
+    use super::*;
+
+    #[test]
+    fn returns_hash_at_height() {
+        let (blockchain, handler) = setup();
+        let hash = hex::encode(blockchain.lock().unwrap().chain[1].hash);
+        assert_eq!(
+            request(&handler, "getblockhash", json!([1]))["result"],
+            hash
+        );
+    }
+
+    #[test]
+    fn rejects_height_beyond_tip() {
+        let (_, handler) = setup();
+        let response = request(&handler, "getblockhash", json!([3]));
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+}
+
+mod decodescript {
+    use super::*;
+
+    #[test]
+    fn returns_asm_and_script_address() {
+        let (_, handler) = setup();
+        let script = blockchain::script::assemble("OP_2 OP_3 OP_ADD OP_5 OP_EQUAL").unwrap();
+        let response = request(&handler, "decodescript", json!([hex::encode(&script)]));
+        assert_eq!(response["result"]["asm"], "OP_2 OP_3 OP_ADD OP_5 OP_EQUAL");
+        assert_eq!(
+            response["result"]["address"],
+            blockchain::script::address(&script)
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_script() {
+        let (_, handler) = setup();
+        let response = request(&handler, "decodescript", json!(["05aa"]));
+        assert_eq!(response["error"]["code"], DESERIALIZATION_ERROR);
+    }
+}
+
+mod getsnapshot {
+    use super::*;
+    use blockchain::snapshot::Snapshot;
+
+    #[test]
+    fn returns_snapshot_at_height_or_tip() {
+        let (blockchain, handler) = setup();
+        let response = request(&handler, "getsnapshot", json!([1]));
+        let snapshot: Snapshot = serde_json::from_value(response["result"].clone()).unwrap();
+        assert_eq!(
+            snapshot,
+            blockchain.lock().unwrap().snapshot(BlockHeight(1)).unwrap()
+        );
+        let response = request(&handler, "getsnapshot", json!([]));
+        assert_eq!(response["result"]["height"], 2);
+    }
+
+    #[test]
+    fn reports_heights_without_a_snapshot() {
+        let (_, handler) = setup();
+        let response = request(&handler, "getsnapshot", json!([9]));
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        let response = request(&handler, "getsnapshot", json!([0]));
+        assert_eq!(response["error"]["code"], NOT_SUPPORTED);
+    }
+}
+
+mod pruned {
+    use super::*;
+
+    fn pruned_setup() -> (Vec<Block>, RpcHandler) {
+        let (blockchain, handler) = setup();
+        let chain = blockchain.lock().unwrap().chain.clone();
+        blockchain.lock().unwrap().prune(1).unwrap();
+        return (chain, handler);
+    }
+
+    #[test]
+    fn getblock_refuses_pruned_blocks() {
+        let (chain, handler) = pruned_setup();
+        let response = request(&handler, "getblock", json!([hex::encode(chain[0].hash)]));
+        assert_eq!(response["error"]["code"], BLOCK_PRUNED);
+        let response = request(&handler, "getblock", json!([hex::encode(chain[1].hash)]));
+        assert_eq!(response["result"]["height"], 1);
+    }
+
+    #[test]
+    fn still_serves_pruned_headers_and_hashes() {
+        let (chain, handler) = pruned_setup();
+        let hash = hex::encode(chain[0].hash);
+        let result = &request(&handler, "getblockheader", json!([hash]))["result"];
+        assert_eq!(result["height"], 0);
+        assert_eq!(result["nextblockhash"], hex::encode(chain[1].hash));
+        let result = &request(&handler, "getblockhash", json!([0]))["result"];
+        assert_eq!(result, &json!(hash));
+    }
+}
+
+mod getauthorities {
+    use super::*;
+
+    #[test]
+    fn is_only_supported_under_proof_of_authority() {
+        let (_, handler) = setup();
+        let response = request(&handler, "getauthorities", json!([]));
+        assert_eq!(response["error"]["code"], NOT_SUPPORTED);
+    }
+}
+
+mod finality {
+    use super::*;
+
+    use blockchain::{
+        finality::{FinalityCertificate, FinalityParams, FinalityVote, VoteKind},
+        params::ChainParams,
+    };
+    use crypto::keys::KeyPair;
+
+    fn finality_setup() -> (Arc<Mutex<Blockchain>>, RpcHandler, Vec<KeyPair>) {
+        let (blockchain, handler) = setup();
+        let key_pairs: Vec<KeyPair> = (0..4).map(|_| KeyPair::generate()).collect();
+        let validators = key_pairs
+            .iter()
+            .map(|key_pair| hex::encode(key_pair.public_key()))
+            .collect();
+        blockchain
+            .lock()
+            .unwrap()
+            .set_params(ChainParams {
+                finality: Some(FinalityParams { validators }),
+                ..ChainParams::default()
+            })
+            .unwrap();
+        return (blockchain, handler, key_pairs);
+    }
+
+    fn certificate(key_pairs: &[KeyPair], height: u64, hash: Hash256) -> FinalityCertificate {
+        let height = BlockHeight(height);
+        return FinalityCertificate {
+            height,
+            round: 0,
+            hash,
+            precommits: key_pairs
+                .iter()
+                .map(|key_pair| {
+                    FinalityVote::new_signed(key_pair, VoteKind::Precommit, height, 0, hash)
+                })
+                .collect(),
+        };
+    }
+
+    #[test]
+    fn is_only_supported_with_finality_validators() {
+        let (_, handler) = setup();
+        let response = request(&handler, "getfinality", json!([]));
+        assert_eq!(response["error"]["code"], NOT_SUPPORTED);
+    }
+
+    #[test]
+    fn submits_and_returns_the_certificate() {
+        let (blockchain, handler, key_pairs) = finality_setup();
+        assert_eq!(
+            request(&handler, "getfinality", json!([]))["result"],
+            Value::Null
+        );
+        let hash = blockchain.lock().unwrap().chain[1].hash;
+        let certificate = certificate(&key_pairs[..3], 1, hash);
+        let response = request(&handler, "submitfinality", json!([certificate]));
+        assert_eq!(response["result"], Value::Null);
+        assert_eq!(response["error"], Value::Null);
+        let response = request(&handler, "getfinality", json!([]));
+        assert_eq!(response["result"], json!(certificate));
+    }
+
+    #[test]
+    fn rejects_certificates_without_a_quorum() {
+        let (blockchain, handler, key_pairs) = finality_setup();
+        let hash = blockchain.lock().unwrap().chain[1].hash;
+        let certificate = certificate(&key_pairs[..2], 1, hash);
+        let response = request(&handler, "submitfinality", json!([certificate]));
+        assert_eq!(response["error"]["code"], CERTIFICATE_REJECTED);
+        let response = request(&handler, "submitfinality", json!([{ "height": 1 }]));
+        assert_eq!(response["error"]["code"], DESERIALIZATION_ERROR);
+        let response = request(&handler, "submitfinality", json!([]));
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn returns_the_votes_counted_at_the_voting_height() {
+        let (_, handler) = setup();
+        let response = request(&handler, "getfinalityvotes", json!([]));
+        assert_eq!(response["error"]["code"], NOT_SUPPORTED);
+
+        let (blockchain, handler, key_pairs) = finality_setup();
+        assert_eq!(
+            request(&handler, "getfinalityvotes", json!([]))["result"],
+            json!([])
+        );
+        let hash = blockchain.lock().unwrap().chain[1].hash;
+        let vote =
+            FinalityVote::new_signed(&key_pairs[0], VoteKind::Prevote, BlockHeight(1), 0, hash);
+        blockchain
+            .lock()
+            .unwrap()
+            .add_finality_vote(vote.clone())
+            .unwrap();
+        let response = request(&handler, "getfinalityvotes", json!([]));
+        assert_eq!(response["result"], json!([vote]));
+    }
+}
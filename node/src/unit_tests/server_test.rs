@@ -0,0 +1,78 @@
+use blockchain::blockchain::Blockchain;
+
+use crate::{rpc::RpcHandler, server::*};
+
+use serde_json::{json, Value};
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    sync::{Arc, Mutex},
+};
+use tungstenite::Message;
+
+fn setup() -> Arc<RpcHandler> {
+    let mut blockchain = Blockchain::new();
+    blockchain
+        .add_block(String::from("Raccoons are cool"))
+        .unwrap();
+    return Arc::new(RpcHandler::new(Arc::new(Mutex::new(blockchain))));
+}
+
+fn http_post(address: &str, body: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "POST / HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        address,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    return response;
+}
+
+mod http {
+    use super::*;
+
+    #[test]
+    fn answers_json_rpc_post() {
+        let address = spawn_http("127.0.0.1:0", setup()).unwrap().to_string();
+        let body = json!({ "jsonrpc": "2.0", "method": "getblockcount", "id": 7 }).to_string();
+        let response = http_post(&address, &body);
+        assert!(response.starts_with("HTTP/1.1 200"));
+        let payload: Value =
+            serde_json::from_str(response.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(payload["result"], 1);
+        assert_eq!(payload["id"], 7);
+    }
+
+    #[test]
+    fn returns_no_content_for_notification() {
+        let address = spawn_http("127.0.0.1:0", setup()).unwrap().to_string();
+        let body = json!({ "jsonrpc": "2.0", "method": "getblockcount" }).to_string();
+        assert!(http_post(&address, &body).starts_with("HTTP/1.1 204"));
+    }
+}
+
+mod websocket {
+    use super::*;
+
+    #[test]
+    fn answers_json_rpc_messages() {
+        let address = spawn_websocket("127.0.0.1:0", setup()).unwrap();
+        let (mut socket, _) = tungstenite::connect(format!("ws://{}", address)).unwrap();
+        for id in 0..2 {
+            let request = json!({ "jsonrpc": "2.0", "method": "getblockcount", "id": id });
+            socket.send(Message::Text(request.to_string())).unwrap();
+            let response = match socket.read().unwrap() {
+                Message::Text(response) => response,
+                message => panic!("unexpected message {:?}", message),
+            };
+            let payload: Value = serde_json::from_str(&response).unwrap();
+            assert_eq!(payload["result"], 1);
+            assert_eq!(payload["id"], id);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use blockchain::{
+    block::Block,
+    blockchain::Blockchain,
+    events::ChainEvent,
+    transaction::Transaction,
+    types::{BlockHeight, Hash256},
+};
+
+use crate::{
+    rpc::{RpcHandler, INVALID_PARAMS},
+    server::{spawn_http, spawn_websocket},
+    subscriptions::*,
+};
+
+use serde_json::{json, Value};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use tungstenite::Message;
+
+fn new_block_event() -> ChainEvent {
+    let block = Block::mine_block(&Block::genesis(), String::from("Raccoons are cool"));
+    return ChainEvent::NewBlock {
+        height: BlockHeight(1),
+        block,
+    };
+}
+
+mod subscriptions {
+    use super::*;
+
+    #[test]
+    fn subscribe_returns_increasing_ids() {
+        let mut subscriptions = Subscriptions::new();
+        let first =
+            json!({ "jsonrpc": "2.0", "method": "subscribe", "params": ["newTip"], "id": 1 });
+        let second = json!({ "jsonrpc": "2.0", "method": "subscribe", "params": { "topic": "reorg" }, "id": 2 });
+        assert_eq!(subscriptions.handle_request(&first).unwrap()["result"], 1);
+        assert_eq!(subscriptions.handle_request(&second).unwrap()["result"], 2);
+    }
+
+    #[test]
+    fn rejects_unknown_topic() {
+        let mut subscriptions = Subscriptions::new();
+        let request =
+            json!({ "jsonrpc": "2.0", "method": "subscribe", "params": ["peers"], "id": 1 });
+        let response = subscriptions.handle_request(&request).unwrap();
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        assert!(subscriptions.is_empty());
+    }
+
+    #[test]
+    fn unsubscribe_removes_subscription() {
+        let mut subscriptions = Subscriptions::new();
+        let id = subscriptions.subscribe(Topic::NewBlock);
+        let request = json!({ "jsonrpc": "2.0", "method": "unsubscribe", "params": [id], "id": 1 });
+        assert_eq!(
+            subscriptions.handle_request(&request).unwrap()["result"],
+            true
+        );
+        assert_eq!(
+            subscriptions.handle_request(&request).unwrap()["result"],
+            false
+        );
+        assert!(subscriptions.notifications(&new_block_event()).is_empty());
+    }
+
+    #[test]
+    fn ignores_other_methods() {
+        let mut subscriptions = Subscriptions::new();
+        let request = json!({ "jsonrpc": "2.0", "method": "getblockcount", "id": 1 });
+        assert!(subscriptions.handle_request(&request).is_none());
+    }
+
+    #[test]
+    fn notifies_only_matching_topics() {
+        let mut subscriptions = Subscriptions::new();
+        subscriptions.subscribe(Topic::NewTip);
+        let id = subscriptions.subscribe(Topic::NewBlock);
+        let notifications = subscriptions.notifications(&new_block_event());
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0]["method"], "subscription");
+        assert_eq!(notifications[0]["params"]["subscription"], id);
+        assert_eq!(notifications[0]["params"]["result"]["height"], 1);
+        assert_eq!(
+            notifications[0]["params"]["result"]["data"],
+            "Raccoons are cool"
+        );
+    }
+}
+
+mod event_json {
+    use super::*;
+
+    #[test]
+    fn reorg_lists_disconnected_and_connected_blocks_with_heights() {
+        let first = Block::mine_block(&Block::genesis(), String::from("one"));
+        let second = Block::mine_block(&first, String::from("two"));
+        let other = Block::mine_block(&Block::genesis(), String::from("other"));
+        let event = ChainEvent::Reorg {
+            fork_height: BlockHeight(1),
+            disconnected: vec![other.clone()],
+            connected: vec![first, second.clone()],
+        };
+        let payload = event_json(&event);
+        assert_eq!(payload["fork_height"], 1);
+        assert_eq!(payload["disconnected"][0]["hash"], hex::encode(other.hash));
+        assert_eq!(payload["connected"][1]["hash"], hex::encode(second.hash));
+        assert_eq!(payload["connected"][1]["height"], 2);
+    }
+
+    #[test]
+    fn new_transaction_is_published_on_mempool_topic() {
+        let transaction = Transaction::coinbase(BlockHeight(1), &"ab".repeat(32), 50);
+        let event = ChainEvent::NewTransaction {
+            transaction: transaction.clone(),
+        };
+        assert_eq!(Topic::of(&event), Topic::Mempool);
+        let payload = event_json(&event);
+        assert_eq!(payload["txid"], hex::encode(transaction.txid()));
+        assert_eq!(payload["transaction"]["outputs"][0]["amount"], 50);
+    }
+
+    #[test]
+    fn server_sent_event_is_named_after_topic() {
+        let event = ChainEvent::NewTip {
+            height: BlockHeight(3),
+            hash: Hash256([1; 32]),
+        };
+        let message = server_sent_event(&event);
+        assert!(message.starts_with("event: newTip\ndata: {"));
+        assert!(message.ends_with("\n\n"));
+    }
+}
+
+mod transports {
+    use super::*;
+
+    fn setup() -> (Arc<Mutex<Blockchain>>, Arc<RpcHandler>) {
+        let blockchain = Arc::new(Mutex::new(Blockchain::new()));
+        let handler = Arc::new(RpcHandler::new(Arc::clone(&blockchain)));
+        return (blockchain, handler);
+    }
+
+    fn read_text(
+        socket: &mut tungstenite::WebSocket<tungstenite::stream::MaybeTlsStream<TcpStream>>,
+    ) -> Value {
+        loop {
+            match socket.read().unwrap() {
+                Message::Text(text) => return serde_json::from_str(&text).unwrap(),
+                Message::Ping(_) | Message::Pong(_) => continue,
+                message => panic!("unexpected message {:?}", message),
+            }
+        }
+    }
+
+    #[test]
+    fn websocket_pushes_notifications_for_subscribed_topics() {
+        let (blockchain, handler) = setup();
+        let address = spawn_websocket("127.0.0.1:0", handler).unwrap();
+        let (mut socket, _) = tungstenite::connect(format!("ws://{}", address)).unwrap();
+        let request =
+            json!({ "jsonrpc": "2.0", "method": "subscribe", "params": ["newTip"], "id": 1 });
+        socket.send(Message::Text(request.to_string())).unwrap();
+        let subscription = read_text(&mut socket)["result"].clone();
+
+        blockchain
+            .lock()
+            .unwrap()
+            .add_block(String::from("Raccoons are cool"))
+            .unwrap();
+        let notification = read_text(&mut socket);
+        let tip_hash = hex::encode(blockchain.lock().unwrap().last_block().hash);
+        assert_eq!(notification["params"]["subscription"], subscription);
+        assert_eq!(notification["params"]["topic"], "newTip");
+        assert_eq!(notification["params"]["result"]["height"], 1);
+        assert_eq!(notification["params"]["result"]["hash"], tip_hash);
+
+        let request = json!({ "jsonrpc": "2.0", "method": "getblockcount", "id": 2 });
+        socket.send(Message::Text(request.to_string())).unwrap();
+        assert_eq!(read_text(&mut socket)["result"], 1);
+    }
+
+    #[test]
+    fn http_streams_server_sent_events() {
+        let (blockchain, handler) = setup();
+        let events = handler.events();
+        let address = spawn_http("127.0.0.1:0", handler).unwrap();
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "GET /events?topics=newBlock HTTP/1.1\r\nHost: {}\r\nAccept: text/event-stream\r\n\r\n",
+            address
+        )
+        .unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert!(line.starts_with("HTTP/1.1 200"));
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+
+        while events.subscriber_count() == 0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        blockchain
+            .lock()
+            .unwrap()
+            .add_block(String::from("Raccoons are cool"))
+            .unwrap();
+        let mut event = String::new();
+        let mut data = String::new();
+        reader.read_line(&mut event).unwrap();
+        reader.read_line(&mut data).unwrap();
+        assert_eq!(event, "event: newBlock\n");
+        let payload: Value = serde_json::from_str(data.trim_start_matches("data: ")).unwrap();
+        assert_eq!(payload["height"], 1);
+        assert_eq!(payload["data"], "Raccoons are cool");
+    }
+}
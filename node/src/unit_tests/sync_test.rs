@@ -0,0 +1,105 @@
+use crate::{client::RpcClient, rpc::RpcHandler, server::spawn_http, sync::*};
+
+use blockchain::{blockchain::Blockchain, types::BlockHeight};
+use std::sync::{Arc, Mutex};
+
+fn spawn_peer(blocks: &[&str]) -> (Arc<Mutex<Blockchain>>, RpcClient) {
+    let mut blockchain = Blockchain::new();
+    for data in blocks {
+        blockchain.add_block(String::from(*data)).unwrap();
+    }
+    let blockchain = Arc::new(Mutex::new(blockchain));
+    let handler = Arc::new(RpcHandler::new(Arc::clone(&blockchain)));
+    let address = spawn_http("127.0.0.1:0", handler).unwrap();
+    return (blockchain, RpcClient::new(&address.to_string()));
+}
+
+mod rpc_client {
+    use super::*;
+
+    #[test]
+    fn fetches_full_chain_from_peer() {
+        let (blockchain, client) = spawn_peer(&["Raccoons are cool", "Skunks smell bad"]);
+        assert_eq!(client.block_count().unwrap(), BlockHeight(2));
+        assert_eq!(
+            client.fetch_chain().unwrap(),
+            blockchain.lock().unwrap().chain
+        );
+    }
+
+    #[test]
+    fn reports_rpc_errors() {
+        let (_, client) = spawn_peer(&[]);
+        assert!(client.block_at(BlockHeight(3)).is_err());
+    }
+
+    #[test]
+    fn reports_unreachable_peer() {
+        let client = RpcClient::new("127.0.0.1:1");
+        assert!(client.block_count().is_err());
+    }
+}
+
+mod sync_with_peer {
+    use super::*;
+
+    #[test]
+    fn adopts_longer_peer_chain() {
+        let (peer, client) = spawn_peer(&["Raccoons are cool", "Skunks smell bad"]);
+        let local = Mutex::new(Blockchain::new());
+        assert!(sync_with_peer(&local, &client).unwrap());
+        assert_eq!(local.lock().unwrap().chain, peer.lock().unwrap().chain);
+    }
+
+    #[test]
+    fn keeps_local_chain_when_peer_is_not_longer() {
+        let (_, client) = spawn_peer(&["Raccoons are cool"]);
+        let mut blockchain = Blockchain::new();
+        blockchain.add_block(String::from("Bears are big")).unwrap();
+        let original = blockchain.chain.clone();
+        let local = Mutex::new(blockchain);
+        assert!(!sync_with_peer(&local, &client).unwrap());
+        assert_eq!(local.lock().unwrap().chain, original);
+    }
+}
+
+mod chain_params {
+    use super::*;
+    use blockchain::params::ChainParams;
+
+    #[test]
+    fn reports_peer_chain_that_reorgs_too_deep() {
+        let (_, client) = spawn_peer(&["Raccoons are cool", "Skunks smell bad"]);
+        let mut blockchain = Blockchain::new();
+        blockchain.add_block(String::from("Bears are big")).unwrap();
+        blockchain
+            .set_params(ChainParams {
+                max_reorg_depth: Some(0),
+                ..ChainParams::default()
+            })
+            .unwrap();
+        let original = blockchain.chain.clone();
+        let local = Mutex::new(blockchain);
+        let error = sync_with_peer(&local, &client).unwrap_err();
+        assert!(error.contains("exceeds the maximum depth"));
+        assert_eq!(local.lock().unwrap().chain, original);
+    }
+}
+
+mod snapshot_base {
+    use super::*;
+    use blockchain::index::ChainIndex;
+
+    #[test]
+    fn fetches_and_validates_blocks_above_the_snapshot() {
+        let (peer, client) = spawn_peer(&["Raccoons are cool", "Skunks smell bad"]);
+        let snapshot = peer.lock().unwrap().snapshot(BlockHeight(1)).unwrap();
+        let chain = vec![snapshot.block.clone()];
+        let blockchain = Blockchain::from_snapshot(snapshot, chain, ChainIndex::new()).unwrap();
+        let local = Mutex::new(blockchain);
+        assert!(sync_with_peer(&local, &client).unwrap());
+        let local = local.lock().unwrap();
+        assert_eq!(local.height(), BlockHeight(2));
+        assert_eq!(local.chain[..], peer.lock().unwrap().chain[1..]);
+    }
+}
@@ -0,0 +1,123 @@
+use blockchain::{
+    multisig::MultisigPolicy,
+    transaction::{OutPoint, Transaction, TxOutput},
+};
+use crypto::keys::{self, KeyPair};
+use node::client::Utxo;
+
+pub fn select_coins(utxos: &[Utxo], target: u64) -> Result<(Vec<Utxo>, u64), String> {
+    let mut candidates = utxos.to_vec();
+    candidates.sort_by_key(|utxo| std::cmp::Reverse(utxo.amount));
+    let mut selected = Vec::new();
+    let mut total: u64 = 0;
+    for utxo in candidates {
+        if total >= target {
+            break;
+        }
+        total = match total.checked_add(utxo.amount) {
+            Some(total) => total,
+            None => return Err(String::from("unspent output total overflows")),
+        };
+        selected.push(utxo);
+    }
+    if total < target {
+        return Err(format!(
+            "insufficient funds: need {}, have {}",
+            target, total
+        ));
+    }
+    return Ok((selected, total));
+}
+
+pub fn build_transaction(
+    key_pairs: &[KeyPair],
+    utxos: &[Utxo],
+    to: &str,
+    amount: u64,
+    fee: u64,
+    change_address: &str,
+) -> Result<Transaction, String> {
+    let is_spendable = |address: &str| {
+        key_pairs
+            .iter()
+            .any(|key_pair| key_pair.address() == address)
+    };
+    let (selected, outputs) = select_payment(utxos, is_spendable, to, amount, fee, change_address)?;
+    let mut spends: Vec<(OutPoint, &KeyPair)> = Vec::new();
+    for utxo in selected.iter() {
+        let key_pair = key_pairs
+            .iter()
+            .find(|key_pair| key_pair.address() == utxo.address)
+            .unwrap();
+        spends.push((utxo.outpoint()?, key_pair));
+    }
+    return Ok(Transaction::new_signed(&spends, outputs, 0));
+}
+
+pub fn build_multisig_transaction(
+    policy: &MultisigPolicy,
+    utxos: &[Utxo],
+    to: &str,
+    amount: u64,
+    fee: u64,
+    change_address: &str,
+) -> Result<Transaction, String> {
+    policy.validate()?;
+    let policy_address = policy.address();
+    let (selected, outputs) = select_payment(
+        utxos,
+        |address| address == policy_address,
+        to,
+        amount,
+        fee,
+        change_address,
+    )?;
+    let mut spends = Vec::new();
+    for utxo in selected.iter() {
+        spends.push((utxo.outpoint()?, policy.clone()));
+    }
+    return Ok(Transaction::new_multisig(&spends, outputs, 0));
+}
+
+fn select_payment<F>(
+    utxos: &[Utxo],
+    is_spendable: F,
+    to: &str,
+    amount: u64,
+    fee: u64,
+    change_address: &str,
+) -> Result<(Vec<Utxo>, Vec<TxOutput>), String>
+where
+    F: Fn(&str) -> bool,
+{
+    if amount == 0 {
+        return Err(String::from("amount must be positive"));
+    }
+    if !keys::is_valid_address(to) {
+        return Err(format!("invalid address {}", to));
+    }
+    if !keys::is_valid_address(change_address) {
+        return Err(format!("invalid change address {}", change_address));
+    }
+    let spendable: Vec<Utxo> = utxos
+        .iter()
+        .filter(|utxo| is_spendable(&utxo.address))
+        .cloned()
+        .collect();
+    let target = match amount.checked_add(fee) {
+        Some(target) => target,
+        None => return Err(String::from("amount plus fee overflows")),
+    };
+    let (selected, total) = select_coins(&spendable, target)?;
+    let mut outputs = vec![TxOutput {
+        amount,
+        address: String::from(to),
+    }];
+    if total > target {
+        outputs.push(TxOutput {
+            amount: total - target,
+            address: String::from(change_address),
+        });
+    }
+    return Ok((selected, outputs));
+}
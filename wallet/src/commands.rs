@@ -0,0 +1,434 @@
+use crate::{
+    builder::{build_multisig_transaction, build_transaction},
+    keystore::Keystore,
+};
+
+use blockchain::{
+    authority::AuthorityVote,
+    htlc::Htlc,
+    multisig::MultisigPolicy,
+    transaction::{OutPoint, Transaction, TxOutput},
+};
+use crypto::{
+    cryptohash,
+    hd::{self, DerivationPath, ExtendedKey, GAP_LIMIT},
+    keys::KeyPair,
+};
+use node::client::{RpcClient, Utxo};
+use rand::{rngs::OsRng, RngCore};
+use std::{
+    cell::RefCell,
+    convert::TryFrom,
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub struct WalletOptions {
+    pub keystore: PathBuf,
+    pub passphrase: String,
+    pub kdf_log_n: u8,
+}
+
+pub fn new_key(options: &WalletOptions, label: &str) -> Result<String, String> {
+    let mut keystore = if options.keystore.exists() {
+        Keystore::open(&options.keystore, &options.passphrase)?
+    } else {
+        Keystore::create(&options.keystore, options.kdf_log_n)?
+    };
+    let address = keystore.generate(label)?;
+    keystore.save(&options.passphrase)?;
+    return Ok(address);
+}
+
+pub fn import_key(options: &WalletOptions, label: &str, secret: &str) -> Result<String, String> {
+    let mut keystore = if options.keystore.exists() {
+        Keystore::open(&options.keystore, &options.passphrase)?
+    } else {
+        Keystore::create(&options.keystore, options.kdf_log_n)?
+    };
+    let address = keystore.import(label, secret)?;
+    keystore.save(&options.passphrase)?;
+    return Ok(address);
+}
+
+pub fn restore(
+    options: &WalletOptions,
+    client: &RpcClient,
+    mnemonic: &str,
+    mnemonic_passphrase: &str,
+) -> Result<String, String> {
+    let seed = hd::mnemonic_to_seed(mnemonic, mnemonic_passphrase)?;
+    let master = ExtendedKey::master(&seed);
+    let error = RefCell::new(None);
+    let accounts = hd::discover_accounts(&master, GAP_LIMIT, |address| {
+        match client.received_by_address(address) {
+            Ok(amount) => return amount > 0,
+            Err(e) => {
+                error.borrow_mut().get_or_insert(e);
+                return false;
+            }
+        }
+    });
+    if let Some(e) = error.into_inner() {
+        return Err(e);
+    }
+    let mut paths: Vec<DerivationPath> = accounts
+        .iter()
+        .flat_map(|account| account.used.iter().map(|used| used.path.clone()))
+        .collect();
+    let next_index = accounts.first().map_or(0, |account| account.next_index);
+    paths.push(DerivationPath::for_address(0, next_index));
+
+    let mut keystore = if options.keystore.exists() {
+        Keystore::open(&options.keystore, &options.passphrase)?
+    } else {
+        Keystore::create(&options.keystore, options.kdf_log_n)?
+    };
+    let mut lines = Vec::new();
+    for path in paths {
+        let key_pair = master.derive_path(&path)?.key_pair();
+        if keystore.find(&key_pair.address()).is_none() {
+            keystore.import(&path.to_string(), &hex::encode(key_pair.secret_bytes()))?;
+        }
+        lines.push(format!("{} {}", key_pair.address(), path));
+    }
+    keystore.save(&options.passphrase)?;
+    return Ok(lines.join("\n"));
+}
+
+pub fn export_key(options: &WalletOptions, id: &str) -> Result<String, String> {
+    let keystore = Keystore::open(&options.keystore, &options.passphrase)?;
+    match keystore.export(id) {
+        Some(secret) => return Ok(secret),
+        None => return Err(format!("no key with label or address {}", id)),
+    }
+}
+
+pub fn public_key(options: &WalletOptions, id: &str) -> Result<String, String> {
+    let keystore = Keystore::open(&options.keystore, &options.passphrase)?;
+    match keystore.find(id) {
+        Some(key_pair) => return Ok(hex::encode(key_pair.public_key())),
+        None => return Err(format!("no key with label or address {}", id)),
+    }
+}
+
+pub fn multisig_policy(threshold: usize, public_keys: &[String]) -> Result<MultisigPolicy, String> {
+    let policy = MultisigPolicy {
+        threshold,
+        public_keys: public_keys.iter().map(|key| key.to_lowercase()).collect(),
+    };
+    policy.validate()?;
+    return Ok(policy);
+}
+
+pub fn create_multisig_transaction(
+    policy: &MultisigPolicy,
+    utxos: &[Utxo],
+    to: &str,
+    amount: u64,
+    fee: u64,
+    change_address: Option<&str>,
+) -> Result<Transaction, String> {
+    let change_address = match change_address {
+        Some(change_address) => String::from(change_address),
+        None => policy.address(),
+    };
+    return build_multisig_transaction(policy, utxos, to, amount, fee, &change_address);
+}
+
+pub fn sign_transaction(options: &WalletOptions, raw: &str) -> Result<Transaction, String> {
+    let mut transaction = decode_raw(raw)?;
+    let keystore = Keystore::open(&options.keystore, &options.passphrase)?;
+    let signed: usize = keystore
+        .key_pairs()
+        .iter()
+        .map(|key_pair| transaction.sign_multisig(key_pair))
+        .sum();
+    if signed == 0 {
+        return Err(String::from("no keystore key can add a signature"));
+    }
+    return Ok(transaction);
+}
+
+pub fn combine(raws: &[String]) -> Result<Transaction, String> {
+    let mut combined = match raws.first() {
+        Some(raw) => decode_raw(raw)?,
+        None => return Err(String::from("nothing to combine")),
+    };
+    for raw in raws[1..].iter() {
+        combined.combine_signatures(&decode_raw(raw)?)?;
+    }
+    return Ok(combined);
+}
+
+pub fn list(options: &WalletOptions) -> Result<String, String> {
+    let keystore = Keystore::open(&options.keystore, &options.passphrase)?;
+    let lines: Vec<String> = keystore
+        .entries()
+        .iter()
+        .map(|entry| format!("{} {}", entry.address, entry.label))
+        .collect();
+    return Ok(lines.join("\n"));
+}
+
+pub fn balance(options: &WalletOptions, client: &RpcClient) -> Result<String, String> {
+    let keystore = Keystore::open(&options.keystore, &options.passphrase)?;
+    let addresses = keystore.addresses();
+    let utxos = client.list_unspent(&addresses)?;
+    let mut lines = Vec::new();
+    let mut total: u64 = 0;
+    for address in addresses.iter() {
+        let amount: u64 = utxos
+            .iter()
+            .filter(|utxo| utxo.address == *address)
+            .map(|utxo| utxo.amount)
+            .sum();
+        total += amount;
+        lines.push(format!("{} {}", address, amount));
+    }
+    lines.push(format!("total {}", total));
+    return Ok(lines.join("\n"));
+}
+
+pub fn unspent(options: &WalletOptions, client: &RpcClient) -> Result<Vec<Utxo>, String> {
+    let keystore = Keystore::open(&options.keystore, &options.passphrase)?;
+    return client.list_unspent(&keystore.addresses());
+}
+
+pub fn load_utxos(path: &Path) -> Result<Vec<Utxo>, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+    };
+    match serde_json::from_str(&contents) {
+        Ok(utxos) => return Ok(utxos),
+        Err(e) => {
+            return Err(format!(
+                "invalid unspent outputs in {}: {}",
+                path.display(),
+                e
+            ))
+        }
+    }
+}
+
+pub fn create_transaction(
+    options: &WalletOptions,
+    utxos: &[Utxo],
+    to: &str,
+    amount: u64,
+    fee: u64,
+    change_address: Option<&str>,
+) -> Result<Transaction, String> {
+    let keystore = Keystore::open(&options.keystore, &options.passphrase)?;
+    let key_pairs = keystore.key_pairs();
+    let change_address = match change_address {
+        Some(change_address) => String::from(change_address),
+        None => match key_pairs.first() {
+            Some(key_pair) => key_pair.address(),
+            None => return Err(String::from("keystore has no keys")),
+        },
+    };
+    return build_transaction(&key_pairs, utxos, to, amount, fee, &change_address);
+}
+
+pub fn broadcast(client: &RpcClient, raw: &str) -> Result<String, String> {
+    return client.send_raw_transaction(&decode_raw(raw)?);
+}
+
+pub fn send(
+    options: &WalletOptions,
+    client: &RpcClient,
+    to: &str,
+    amount: u64,
+    fee: u64,
+) -> Result<String, String> {
+    let utxos = unspent(options, client)?;
+    let transaction = create_transaction(options, &utxos, to, amount, fee, None)?;
+    return client.send_raw_transaction(&transaction);
+}
+
+// Signs a proof of authority vote for the node's current epoch with an
+// authority key and broadcasts it.
+pub fn vote(
+    options: &WalletOptions,
+    client: &RpcClient,
+    id: &str,
+    candidate: &str,
+    add: bool,
+) -> Result<String, String> {
+    let keystore = Keystore::open(&options.keystore, &options.passphrase)?;
+    let key_pair = match keystore.find(id) {
+        Some(key_pair) => key_pair,
+        None => return Err(format!("no key with label or address {}", id)),
+    };
+    let epoch = client.authorities()?.epoch;
+    let vote = AuthorityVote::new_signed(&key_pair, candidate, add, epoch);
+    return client.send_raw_transaction(&Transaction::new_vote(vote));
+}
+
+pub fn htlc_secret() -> (String, String) {
+    let mut preimage = [0; 32];
+    OsRng.fill_bytes(&mut preimage);
+    return (
+        hex::encode(preimage),
+        hex::encode(cryptohash::sha256(&preimage)),
+    );
+}
+
+pub fn htlc_contract(
+    options: &WalletOptions,
+    recipient: &str,
+    hash: &str,
+    timeout: u64,
+    refund_key: Option<&str>,
+) -> Result<Htlc, String> {
+    if i64::try_from(timeout).is_err() {
+        return Err(format!("timeout {} is above {}", timeout, i64::MAX));
+    }
+    let keystore = Keystore::open(&options.keystore, &options.passphrase)?;
+    let refund = match refund_key {
+        Some(id) => keystore.find(id),
+        None => keystore.key_pairs().into_iter().next(),
+    };
+    let refund = match refund {
+        Some(key_pair) => key_pair.public_key(),
+        None => return Err(String::from("no refund key in keystore")),
+    };
+    return Ok(Htlc {
+        hash: decode_bytes32(hash, "hash")?,
+        recipient: decode_bytes32(recipient, "recipient public key")?,
+        refund,
+        timeout,
+    });
+}
+
+pub fn htlc_fund(
+    options: &WalletOptions,
+    client: &RpcClient,
+    htlc: &Htlc,
+    amount: u64,
+    fee: u64,
+) -> Result<String, String> {
+    return send(options, client, &htlc.address(), amount, fee);
+}
+
+pub fn htlc_claim(
+    options: &WalletOptions,
+    client: &RpcClient,
+    script: &str,
+    preimage: &str,
+    fee: u64,
+) -> Result<String, String> {
+    let htlc = decode_htlc(script)?;
+    let preimage = match hex::decode(preimage.trim()) {
+        Ok(preimage) => preimage,
+        Err(_) => return Err(String::from("preimage must be hex")),
+    };
+    let key_pair = htlc_key(options, &htlc.recipient)?;
+    let (outpoints, outputs) = htlc_sweep(client, &htlc, &key_pair, fee)?;
+    let transaction = htlc.claim(&outpoints, outputs, &key_pair, &preimage)?;
+    return client.send_raw_transaction(&transaction);
+}
+
+pub fn htlc_refund(
+    options: &WalletOptions,
+    client: &RpcClient,
+    script: &str,
+    fee: u64,
+) -> Result<String, String> {
+    let htlc = decode_htlc(script)?;
+    let key_pair = htlc_key(options, &htlc.refund)?;
+    let (outpoints, outputs) = htlc_sweep(client, &htlc, &key_pair, fee)?;
+    let transaction = htlc.refund(&outpoints, outputs, &key_pair)?;
+    return client.send_raw_transaction(&transaction);
+}
+
+// Finds the preimage revealed by a claim of the contract, checking the mempool
+// before the chain so a counterparty can react before the claim is mined.
+pub fn htlc_preimage(client: &RpcClient, script: &str) -> Result<Option<String>, String> {
+    let htlc = decode_htlc(script)?;
+    for txid in client.raw_mempool()? {
+        if let Some(preimage) = htlc.extract_preimage(&client.raw_transaction(&txid)?) {
+            return Ok(Some(hex::encode(preimage)));
+        }
+    }
+    for block in client.fetch_chain()? {
+        for transaction in Transaction::decode_payload(&block.data).unwrap_or_default() {
+            if let Some(preimage) = htlc.extract_preimage(&transaction) {
+                return Ok(Some(hex::encode(preimage)));
+            }
+        }
+    }
+    return Ok(None);
+}
+
+fn htlc_key(options: &WalletOptions, public_key: &[u8; 32]) -> Result<KeyPair, String> {
+    let keystore = Keystore::open(&options.keystore, &options.passphrase)?;
+    match keystore
+        .key_pairs()
+        .into_iter()
+        .find(|key_pair| key_pair.public_key() == *public_key)
+    {
+        Some(key_pair) => return Ok(key_pair),
+        None => return Err(String::from("keystore does not hold the HTLC key")),
+    }
+}
+
+fn htlc_sweep(
+    client: &RpcClient,
+    htlc: &Htlc,
+    key_pair: &KeyPair,
+    fee: u64,
+) -> Result<(Vec<OutPoint>, Vec<TxOutput>), String> {
+    let utxos = client.list_unspent(&[htlc.address()])?;
+    if utxos.is_empty() {
+        return Err(format!("no unspent outputs at {}", htlc.address()));
+    }
+    let mut outpoints = Vec::new();
+    let mut total: u64 = 0;
+    for utxo in utxos.iter() {
+        outpoints.push(utxo.outpoint()?);
+        total = total.saturating_add(utxo.amount);
+    }
+    if total <= fee {
+        return Err(format!(
+            "contract holds {}, not enough for fee {}",
+            total, fee
+        ));
+    }
+    let outputs = vec![TxOutput {
+        amount: total - fee,
+        address: key_pair.address(),
+    }];
+    return Ok((outpoints, outputs));
+}
+
+fn decode_htlc(script: &str) -> Result<Htlc, String> {
+    let bytes = match hex::decode(script.trim()) {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(String::from("script must be hex")),
+    };
+    match Htlc::from_script(&bytes) {
+        Some(htlc) => return Ok(htlc),
+        None => return Err(String::from("script is not an HTLC")),
+    }
+}
+
+fn decode_bytes32(value: &str, name: &str) -> Result<[u8; 32], String> {
+    match hex::decode(value.trim()) {
+        Ok(bytes) if bytes.len() == 32 => {
+            let mut array = [0; 32];
+            array.copy_from_slice(&bytes);
+            return Ok(array);
+        }
+        _ => return Err(format!("{} must be 32 bytes of hex", name)),
+    }
+}
+
+fn decode_raw(raw: &str) -> Result<Transaction, String> {
+    match Transaction::decode_hex(raw.trim()) {
+        Some(transaction) => return Ok(transaction),
+        None => return Err(String::from("invalid raw transaction")),
+    }
+}
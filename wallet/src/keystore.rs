@@ -0,0 +1,240 @@
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use crypto::keys::{KeyPair, SECRET_KEY_LENGTH};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const KEYSTORE_VERSION: u32 = 1;
+pub const DEFAULT_KDF_LOG_N: u8 = 15;
+const KDF_R: u32 = 8;
+const KDF_P: u32 = 1;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+    #[serde(with = "hex::serde")]
+    pub salt: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct KeystoreFile {
+    version: u32,
+    kdf: KdfParams,
+    #[serde(with = "hex::serde")]
+    nonce: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    ciphertext: Vec<u8>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct StoredKey {
+    label: String,
+    #[serde(with = "hex::serde")]
+    secret: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyEntry {
+    pub label: String,
+    pub address: String,
+}
+
+pub struct Keystore {
+    path: PathBuf,
+    log_n: u8,
+    keys: Vec<StoredKey>,
+}
+
+impl Keystore {
+    pub fn create(path: &Path, log_n: u8) -> Result<Keystore, String> {
+        if path.exists() {
+            return Err(format!("{} already exists", path.display()));
+        }
+        return Ok(Keystore {
+            path: path.to_path_buf(),
+            log_n,
+            keys: Vec::new(),
+        });
+    }
+
+    pub fn open(path: &Path, passphrase: &str) -> Result<Keystore, String> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+        };
+        let file: KeystoreFile = match serde_json::from_str(&contents) {
+            Ok(file) => file,
+            Err(e) => return Err(format!("invalid keystore {}: {}", path.display(), e)),
+        };
+        if file.version != KEYSTORE_VERSION {
+            return Err(format!(
+                "unsupported keystore version {} in {}",
+                file.version,
+                path.display()
+            ));
+        }
+        if file.nonce.len() != NONCE_LENGTH {
+            return Err(format!("invalid keystore {}: bad nonce", path.display()));
+        }
+        let key = derive_key(passphrase, &file.kdf)?;
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+        let plaintext =
+            match cipher.decrypt(Nonce::from_slice(&file.nonce), file.ciphertext.as_ref()) {
+                Ok(plaintext) => plaintext,
+                Err(_) => return Err(String::from("incorrect passphrase or corrupted keystore")),
+            };
+        let keys: Vec<StoredKey> = match serde_json::from_slice(&plaintext) {
+            Ok(keys) => keys,
+            Err(e) => return Err(format!("invalid keystore contents: {}", e)),
+        };
+        return Ok(Keystore {
+            path: path.to_path_buf(),
+            log_n: file.kdf.log_n,
+            keys,
+        });
+    }
+
+    pub fn save(&self, passphrase: &str) -> Result<(), String> {
+        let mut salt = vec![0u8; SALT_LENGTH];
+        let mut nonce = vec![0u8; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+        let kdf = KdfParams {
+            log_n: self.log_n,
+            r: KDF_R,
+            p: KDF_P,
+            salt,
+        };
+        let key = derive_key(passphrase, &kdf)?;
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+        let plaintext = serde_json::to_vec(&self.keys).unwrap();
+        let ciphertext = match cipher.encrypt(Nonce::from_slice(&nonce), plaintext.as_ref()) {
+            Ok(ciphertext) => ciphertext,
+            Err(_) => return Err(String::from("keystore encryption failed")),
+        };
+        let file = KeystoreFile {
+            version: KEYSTORE_VERSION,
+            kdf,
+            nonce,
+            ciphertext,
+        };
+        let tmp_path = self.path.with_extension("tmp");
+        let contents = serde_json::to_string_pretty(&file).unwrap();
+        if let Err(e) = fs::write(&tmp_path, contents) {
+            return Err(format!("cannot write {}: {}", tmp_path.display(), e));
+        }
+        if let Err(e) = fs::rename(&tmp_path, &self.path) {
+            return Err(format!("cannot write {}: {}", self.path.display(), e));
+        }
+        return Ok(());
+    }
+
+    pub fn path(&self) -> &Path {
+        return &self.path;
+    }
+
+    pub fn generate(&mut self, label: &str) -> Result<String, String> {
+        return self.insert(label, KeyPair::generate());
+    }
+
+    pub fn import(&mut self, label: &str, secret_hex: &str) -> Result<String, String> {
+        let key_pair = match hex::decode(secret_hex.trim()) {
+            Ok(secret) if secret.len() == SECRET_KEY_LENGTH => KeyPair::from_secret_bytes(&secret),
+            _ => None,
+        };
+        match key_pair {
+            Some(key_pair) => return self.insert(label, key_pair),
+            None => {
+                return Err(format!(
+                    "secret key must be {} hex encoded bytes",
+                    SECRET_KEY_LENGTH
+                ))
+            }
+        }
+    }
+
+    pub fn export(&self, id: &str) -> Option<String> {
+        return self
+            .find(id)
+            .map(|key_pair| hex::encode(key_pair.secret_bytes()));
+    }
+
+    pub fn entries(&self) -> Vec<KeyEntry> {
+        return self
+            .key_pairs()
+            .iter()
+            .zip(self.keys.iter())
+            .map(|(key_pair, stored)| KeyEntry {
+                label: stored.label.clone(),
+                address: key_pair.address(),
+            })
+            .collect();
+    }
+
+    pub fn addresses(&self) -> Vec<String> {
+        return self
+            .key_pairs()
+            .iter()
+            .map(|key_pair| key_pair.address())
+            .collect();
+    }
+
+    pub fn key_pairs(&self) -> Vec<KeyPair> {
+        return self
+            .keys
+            .iter()
+            .filter_map(|stored| KeyPair::from_secret_bytes(&stored.secret))
+            .collect();
+    }
+
+    pub fn find(&self, id: &str) -> Option<KeyPair> {
+        return self
+            .keys
+            .iter()
+            .filter_map(|stored| {
+                let key_pair = KeyPair::from_secret_bytes(&stored.secret)?;
+                if stored.label == id || key_pair.address() == id {
+                    return Some(key_pair);
+                }
+                return None;
+            })
+            .next();
+    }
+
+    fn insert(&mut self, label: &str, key_pair: KeyPair) -> Result<String, String> {
+        let address = key_pair.address();
+        if self.find(&address).is_some() {
+            return Err(format!("key for {} is already in the keystore", address));
+        }
+        if !label.is_empty() && self.keys.iter().any(|stored| stored.label == label) {
+            return Err(format!("label {} is already in use", label));
+        }
+        self.keys.push(StoredKey {
+            label: String::from(label),
+            secret: key_pair.secret_bytes().to_vec(),
+        });
+        return Ok(address);
+    }
+}
+
+fn derive_key(passphrase: &str, kdf: &KdfParams) -> Result<[u8; 32], String> {
+    let params = match scrypt::Params::new(kdf.log_n, kdf.r, kdf.p, 32) {
+        Ok(params) => params,
+        Err(_) => return Err(String::from("invalid key derivation parameters")),
+    };
+    let mut key = [0u8; 32];
+    if scrypt::scrypt(passphrase.as_bytes(), &kdf.salt, &params, &mut key).is_err() {
+        return Err(String::from("key derivation failed"));
+    }
+    return Ok(key);
+}
@@ -0,0 +1,8 @@
+#![allow(clippy::needless_return)]
+
+pub mod builder;
+pub mod commands;
+pub mod keystore;
+
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,348 @@
+#![allow(clippy::needless_return)]
+
+use crypto::hd;
+use node::client::RpcClient;
+use wallet::{
+    commands::{self, WalletOptions},
+    keystore::DEFAULT_KDF_LOG_N,
+};
+
+use clap::{Parser, Subcommand};
+use std::{path::PathBuf, process};
+
+#[derive(Parser)]
+#[command(
+    name = "cryptochain-wallet",
+    version,
+    about = "Manage keys and send cryptochain transactions"
+)]
+struct Cli {
+    #[arg(
+        long,
+        global = true,
+        default_value = "wallet.json",
+        help = "Encrypted keystore file"
+    )]
+    keystore: PathBuf,
+    #[arg(
+        long,
+        global = true,
+        default_value = "127.0.0.1:8332",
+        help = "JSON-RPC address of the node to query"
+    )]
+    node: String,
+    #[arg(
+        long,
+        global = true,
+        env = "CRYPTOCHAIN_WALLET_PASSPHRASE",
+        hide_env_values = true,
+        help = "Keystore passphrase (prompted for when omitted)"
+    )]
+    passphrase: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Generate a new key, creating the keystore if needed")]
+    New {
+        #[arg(long, default_value = "")]
+        label: String,
+    },
+    #[command(about = "Import a hex encoded secret key")]
+    Import {
+        secret: String,
+        #[arg(long, default_value = "")]
+        label: String,
+    },
+    #[command(about = "Print a new BIP-39 mnemonic (it is not stored)")]
+    Mnemonic {
+        #[arg(long, default_value_t = 24)]
+        words: usize,
+    },
+    #[command(about = "Recover used HD keys for a mnemonic by scanning the node's chain")]
+    Restore {
+        #[arg(long, env = "CRYPTOCHAIN_WALLET_MNEMONIC", hide_env_values = true)]
+        mnemonic: String,
+        #[arg(long, default_value = "", help = "Optional BIP-39 passphrase")]
+        mnemonic_passphrase: String,
+    },
+    #[command(about = "Print the hex encoded secret key for a label or address")]
+    Export { key: String },
+    #[command(about = "List keystore addresses")]
+    List,
+    #[command(about = "Show balances reported by the node")]
+    Balance,
+    #[command(about = "Build and sign a transaction without broadcasting it")]
+    CreateTx {
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        amount: u64,
+        #[arg(long, default_value_t = 0)]
+        fee: u64,
+        #[arg(long, help = "Change address (defaults to the first key)")]
+        change: Option<String>,
+        #[arg(
+            long,
+            help = "listunspent JSON to spend from instead of querying the node"
+        )]
+        utxos: Option<PathBuf>,
+    },
+    #[command(about = "Print the hex encoded public key for a label or address")]
+    PublicKey { key: String },
+    #[command(about = "Print the address of an M-of-N multisig policy")]
+    MultisigAddress {
+        #[arg(long)]
+        threshold: usize,
+        #[arg(long = "key", help = "Hex encoded public key of a cosigner")]
+        keys: Vec<String>,
+    },
+    #[command(about = "Build an unsigned transaction spending from a multisig address")]
+    CreateMultisigTx {
+        #[arg(long)]
+        threshold: usize,
+        #[arg(long = "key", help = "Hex encoded public key of a cosigner")]
+        keys: Vec<String>,
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        amount: u64,
+        #[arg(long, default_value_t = 0)]
+        fee: u64,
+        #[arg(long, help = "Change address (defaults to the multisig address)")]
+        change: Option<String>,
+        #[arg(
+            long,
+            help = "listunspent JSON to spend from instead of querying the node"
+        )]
+        utxos: Option<PathBuf>,
+    },
+    #[command(about = "Add this keystore's signatures to a multisig transaction")]
+    Sign { raw: String },
+    #[command(about = "Merge the signatures of partially signed transactions")]
+    Combine {
+        #[arg(required = true)]
+        raws: Vec<String>,
+    },
+    #[command(about = "Print a random HTLC preimage and its SHA-256 hash")]
+    HtlcSecret,
+    #[command(about = "Lock funds in a hash time-locked contract and print its script")]
+    HtlcFund {
+        #[arg(long, help = "Hex encoded public key that can claim with the preimage")]
+        recipient: String,
+        #[arg(long, help = "Hex encoded SHA-256 hash of the preimage")]
+        hash: String,
+        #[arg(
+            long,
+            help = "Block height from which the refund key can reclaim the funds"
+        )]
+        timeout: u64,
+        #[arg(long, help = "Refund key label or address (defaults to the first key)")]
+        refund: Option<String>,
+        #[arg(long)]
+        amount: u64,
+        #[arg(long, default_value_t = 0)]
+        fee: u64,
+    },
+    #[command(about = "Claim an HTLC with its preimage")]
+    HtlcClaim {
+        #[arg(long)]
+        script: String,
+        #[arg(long)]
+        preimage: String,
+        #[arg(long, default_value_t = 0)]
+        fee: u64,
+    },
+    #[command(about = "Reclaim an HTLC after its timeout")]
+    HtlcRefund {
+        #[arg(long)]
+        script: String,
+        #[arg(long, default_value_t = 0)]
+        fee: u64,
+    },
+    #[command(about = "Print the preimage revealed by a claim of an HTLC")]
+    HtlcPreimage {
+        #[arg(long)]
+        script: String,
+    },
+    #[command(about = "Broadcast a raw transaction to the node")]
+    Broadcast { raw: String },
+    #[command(about = "Vote with an authority key to add or remove a proof of authority signer")]
+    Vote {
+        #[arg(long, help = "Authority key label or address")]
+        key: String,
+        candidate: String,
+        #[arg(long, help = "Vote to remove the candidate instead of adding it")]
+        remove: bool,
+    },
+    #[command(about = "Build, sign and broadcast a payment")]
+    Send {
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        amount: u64,
+        #[arg(long, default_value_t = 0)]
+        fee: u64,
+    },
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let client = RpcClient::new(&cli.node);
+    match &cli.command {
+        Command::Broadcast { raw } => finish(commands::broadcast(&client, raw)),
+        Command::Mnemonic { words } => finish(hd::generate_mnemonic(*words)),
+        Command::MultisigAddress { threshold, keys } => {
+            finish(commands::multisig_policy(*threshold, keys).map(|policy| policy.address()))
+        }
+        Command::CreateMultisigTx {
+            threshold,
+            keys,
+            to,
+            amount,
+            fee,
+            change,
+            utxos,
+        } => {
+            let result = commands::multisig_policy(*threshold, keys).and_then(|policy| {
+                let utxos = match utxos {
+                    Some(path) => commands::load_utxos(path)?,
+                    None => client.list_unspent(&[policy.address()])?,
+                };
+                let transaction = commands::create_multisig_transaction(
+                    &policy,
+                    &utxos,
+                    to,
+                    *amount,
+                    *fee,
+                    change.as_deref(),
+                )?;
+                return Ok(transaction.encode_hex());
+            });
+            finish(result);
+        }
+        Command::Combine { raws } => {
+            finish(commands::combine(raws).map(|transaction| transaction.encode_hex()))
+        }
+        Command::HtlcSecret => {
+            let (preimage, hash) = commands::htlc_secret();
+            finish(Ok(format!("preimage {}\nhash {}", preimage, hash)));
+        }
+        Command::HtlcPreimage { script } => finish(
+            commands::htlc_preimage(&client, script).and_then(|preimage| match preimage {
+                Some(preimage) => return Ok(preimage),
+                None => return Err(String::from("preimage has not been revealed")),
+            }),
+        ),
+        _ => {}
+    }
+    let passphrase = match cli.passphrase {
+        Some(passphrase) => passphrase,
+        None => match rpassword::prompt_password("Keystore passphrase: ") {
+            Ok(passphrase) => passphrase,
+            Err(e) => exit_with_error(&format!("cannot read passphrase: {}", e)),
+        },
+    };
+    let options = WalletOptions {
+        keystore: cli.keystore,
+        passphrase,
+        kdf_log_n: DEFAULT_KDF_LOG_N,
+    };
+    let result = match cli.command {
+        Command::New { label } => commands::new_key(&options, &label),
+        Command::Import { secret, label } => commands::import_key(&options, &label, &secret),
+        Command::Restore {
+            mnemonic,
+            mnemonic_passphrase,
+        } => commands::restore(&options, &client, &mnemonic, &mnemonic_passphrase),
+        Command::Export { key } => commands::export_key(&options, &key),
+        Command::PublicKey { key } => commands::public_key(&options, &key),
+        Command::Sign { raw } => {
+            commands::sign_transaction(&options, &raw).map(|transaction| transaction.encode_hex())
+        }
+        Command::List => commands::list(&options),
+        Command::Balance => commands::balance(&options, &client),
+        Command::CreateTx {
+            to,
+            amount,
+            fee,
+            change,
+            utxos,
+        } => {
+            let utxos = match utxos {
+                Some(path) => commands::load_utxos(&path),
+                None => commands::unspent(&options, &client),
+            };
+            utxos
+                .and_then(|utxos| {
+                    commands::create_transaction(
+                        &options,
+                        &utxos,
+                        &to,
+                        amount,
+                        fee,
+                        change.as_deref(),
+                    )
+                })
+                .map(|transaction| transaction.encode_hex())
+        }
+        Command::HtlcFund {
+            recipient,
+            hash,
+            timeout,
+            refund,
+            amount,
+            fee,
+        } => commands::htlc_contract(&options, &recipient, &hash, timeout, refund.as_deref())
+            .and_then(|htlc| {
+                let txid = commands::htlc_fund(&options, &client, &htlc, amount, fee)?;
+                return Ok(format!(
+                    "script {}\naddress {}\ntxid {}",
+                    hex::encode(htlc.locking_script()),
+                    htlc.address(),
+                    txid
+                ));
+            }),
+        Command::HtlcClaim {
+            script,
+            preimage,
+            fee,
+        } => commands::htlc_claim(&options, &client, &script, &preimage, fee),
+        Command::HtlcRefund { script, fee } => {
+            commands::htlc_refund(&options, &client, &script, fee)
+        }
+        Command::Broadcast { .. }
+        | Command::HtlcSecret
+        | Command::HtlcPreimage { .. }
+        | Command::Mnemonic { .. }
+        | Command::MultisigAddress { .. }
+        | Command::CreateMultisigTx { .. }
+        | Command::Combine { .. } => unreachable!(),
+        Command::Send { to, amount, fee } => commands::send(&options, &client, &to, amount, fee),
+        Command::Vote {
+            key,
+            candidate,
+            remove,
+        } => commands::vote(&options, &client, &key, &candidate, !remove),
+    };
+    finish(result);
+}
+
+fn finish(result: Result<String, String>) -> ! {
+    match result {
+        Ok(output) => {
+            if !output.is_empty() {
+                println!("{}", output);
+            }
+            process::exit(0);
+        }
+        Err(e) => exit_with_error(&e),
+    }
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
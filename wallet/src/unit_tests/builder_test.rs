@@ -0,0 +1,98 @@
+use crate::builder::*;
+
+use blockchain::transaction::TxOutput;
+use crypto::keys::{self, KeyPair};
+use node::client::Utxo;
+
+fn utxo(key_pair: &KeyPair, txid_byte: u8, amount: u64) -> Utxo {
+    return Utxo {
+        txid: hex::encode([txid_byte; 32]),
+        vout: 0,
+        address: key_pair.address(),
+        amount,
+    };
+}
+
+mod select_coins {
+    use super::*;
+
+    #[test]
+    fn picks_largest_outputs_first() {
+        let key_pair = KeyPair::generate();
+        let utxos = vec![
+            utxo(&key_pair, 1, 5),
+            utxo(&key_pair, 2, 30),
+            utxo(&key_pair, 3, 10),
+        ];
+        let (selected, total) = select_coins(&utxos, 35).unwrap();
+        assert_eq!(total, 40);
+        assert_eq!(selected, vec![utxos[1].clone(), utxos[2].clone()]);
+    }
+
+    #[test]
+    fn reports_insufficient_funds() {
+        let key_pair = KeyPair::generate();
+        assert!(select_coins(&[utxo(&key_pair, 1, 5)], 6).is_err());
+    }
+}
+
+mod build_transaction {
+    use super::*;
+
+    #[test]
+    fn pays_recipient_returns_change_and_signs_inputs() {
+        let key_pair = KeyPair::generate();
+        let change = KeyPair::generate().address();
+        let to = "cd".repeat(32);
+        let utxos = vec![utxo(&key_pair, 1, 50)];
+        let transaction = build_transaction(&[key_pair], &utxos, &to, 30, 2, &change).unwrap();
+        assert_eq!(
+            transaction.outputs,
+            vec![
+                TxOutput {
+                    amount: 30,
+                    address: to,
+                },
+                TxOutput {
+                    amount: 18,
+                    address: change,
+                },
+            ]
+        );
+        let input = &transaction.inputs[0];
+        assert_eq!(input.previous_output, utxos[0].outpoint().unwrap());
+        assert!(keys::verify(
+            &input.public_key,
+            &transaction.sighash(),
+            &input.signature
+        ));
+    }
+
+    #[test]
+    fn omits_change_for_exact_amount() {
+        let key_pair = KeyPair::generate();
+        let change = key_pair.address();
+        let utxos = vec![utxo(&key_pair, 1, 32)];
+        let transaction =
+            build_transaction(&[key_pair], &utxos, &"cd".repeat(32), 30, 2, &change).unwrap();
+        assert_eq!(transaction.outputs.len(), 1);
+    }
+
+    #[test]
+    fn ignores_outputs_without_a_key() {
+        let key_pair = KeyPair::generate();
+        let stranger = KeyPair::generate();
+        let utxos = vec![utxo(&stranger, 1, 100), utxo(&key_pair, 2, 10)];
+        let change = key_pair.address();
+        assert!(build_transaction(&[key_pair], &utxos, &"cd".repeat(32), 20, 0, &change).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_addresses_and_zero_amount() {
+        let key_pair = KeyPair::generate();
+        let change = key_pair.address();
+        let utxos = vec![utxo(&key_pair, 1, 50)];
+        assert!(build_transaction(&[], &utxos, "nowhere", 1, 0, &change).is_err());
+        assert!(build_transaction(&[], &utxos, &"cd".repeat(32), 0, 0, &change).is_err());
+    }
+}
@@ -0,0 +1,390 @@
+use crate::commands::*;
+
+use blockchain::blockchain::Blockchain;
+use node::{
+    client::{RpcClient, Utxo},
+    rpc::RpcHandler,
+    server::spawn_http,
+};
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+
+fn options(dir: &TempDir) -> WalletOptions {
+    return WalletOptions {
+        keystore: dir.path().join("wallet.json"),
+        passphrase: String::from("correct horse"),
+        kdf_log_n: 4,
+    };
+}
+
+fn spawn_node() -> (Arc<Mutex<Blockchain>>, RpcClient) {
+    let blockchain = Arc::new(Mutex::new(Blockchain::new()));
+    let handler = Arc::new(RpcHandler::new(Arc::clone(&blockchain)));
+    let address = spawn_http("127.0.0.1:0", handler).unwrap();
+    return (blockchain, RpcClient::new(&address.to_string()));
+}
+
+mod key_management {
+    use super::*;
+
+    #[test]
+    fn new_key_creates_keystore_and_lists_address() {
+        let dir = TempDir::new().unwrap();
+        let options = options(&dir);
+        let address = new_key(&options, "main").unwrap();
+        assert!(options.keystore.exists());
+        assert_eq!(list(&options).unwrap(), format!("{} main", address));
+    }
+
+    #[test]
+    fn export_and_import_move_keys_between_keystores() {
+        let dir = TempDir::new().unwrap();
+        let other = TempDir::new().unwrap();
+        let address = new_key(&options(&dir), "").unwrap();
+        let secret = export_key(&options(&dir), &address).unwrap();
+        assert_eq!(import_key(&options(&other), "", &secret), Ok(address));
+    }
+
+    #[test]
+    fn wrong_passphrase_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        new_key(&options(&dir), "").unwrap();
+        let mut wrong = options(&dir);
+        wrong.passphrase = String::from("battery staple");
+        assert!(list(&wrong).is_err());
+        assert!(new_key(&wrong, "").is_err());
+    }
+}
+
+mod transactions {
+    use super::*;
+
+    #[test]
+    fn send_pays_recipient_through_node() {
+        let dir = TempDir::new().unwrap();
+        let options = options(&dir);
+        let (blockchain, client) = spawn_node();
+        let address = new_key(&options, "").unwrap();
+        client
+            .call("generatetoaddress", serde_json::json!([1, address]))
+            .unwrap();
+        assert_eq!(
+            balance(&options, &client).unwrap(),
+            format!("{} 50\ntotal 50", address)
+        );
+
+        let recipient = "cd".repeat(32);
+        let txid = send(&options, &client, &recipient, 20, 1).unwrap();
+        let mut blockchain = blockchain.lock().unwrap();
+        assert_eq!(
+            hex::encode(blockchain.mempool().transactions()[0].txid()),
+            txid
+        );
+        blockchain.mine_transactions(&recipient).unwrap();
+        let ledger = blockchain.ledger().unwrap();
+        assert_eq!(ledger.balance(&address), 29);
+    }
+
+    #[test]
+    fn create_transaction_works_offline_and_broadcast_submits_it() {
+        let dir = TempDir::new().unwrap();
+        let options = options(&dir);
+        let (_, client) = spawn_node();
+        let address = new_key(&options, "").unwrap();
+        client
+            .call("generatetoaddress", serde_json::json!([1, address]))
+            .unwrap();
+        let utxos_path = dir.path().join("utxos.json");
+        let utxos = client
+            .call("listunspent", serde_json::json!([[address]]))
+            .unwrap();
+        std::fs::write(&utxos_path, utxos.to_string()).unwrap();
+
+        let utxos = load_utxos(&utxos_path).unwrap();
+        let transaction =
+            create_transaction(&options, &utxos, &"cd".repeat(32), 10, 0, None).unwrap();
+        let txid = broadcast(&client, &transaction.encode_hex()).unwrap();
+        assert_eq!(txid, hex::encode(transaction.txid()));
+        assert!(broadcast(&client, "zz").is_err());
+    }
+}
+
+mod restore {
+    use super::*;
+    use crypto::hd::{self, DerivationPath, ExtendedKey};
+
+    const MNEMONIC: &str =
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn hd_address(account: u32, index: u32) -> String {
+        let master = ExtendedKey::master(&hd::mnemonic_to_seed(MNEMONIC, "").unwrap());
+        let path = DerivationPath::for_address(account, index);
+        return master.derive_path(&path).unwrap().key_pair().address();
+    }
+
+    #[test]
+    fn imports_used_addresses_and_next_receive_key() {
+        let dir = TempDir::new().unwrap();
+        let options = options(&dir);
+        let (_, client) = spawn_node();
+        for (account, index) in [(0, 0), (0, 2), (1, 0)].iter() {
+            client
+                .call(
+                    "generatetoaddress",
+                    serde_json::json!([1, hd_address(*account, *index)]),
+                )
+                .unwrap();
+        }
+        let output = restore(&options, &client, MNEMONIC, "").unwrap();
+        assert_eq!(output.lines().count(), 4);
+        let listed = list(&options).unwrap();
+        assert!(listed.contains(&format!("{} m/44'/9000'/0'/0'/2'", hd_address(0, 2))));
+        assert!(listed.contains(&format!("{} m/44'/9000'/1'/0'/0'", hd_address(1, 0))));
+        assert!(listed.contains(&hd_address(0, 3)));
+        assert!(balance(&options, &client).unwrap().ends_with("total 150"));
+    }
+
+    #[test]
+    fn fresh_mnemonic_gets_first_receive_key_and_restore_is_idempotent() {
+        let dir = TempDir::new().unwrap();
+        let options = options(&dir);
+        let (_, client) = spawn_node();
+        restore(&options, &client, MNEMONIC, "").unwrap();
+        restore(&options, &client, MNEMONIC, "").unwrap();
+        assert_eq!(
+            list(&options).unwrap(),
+            format!("{} m/44'/9000'/0'/0'/0'", hd_address(0, 0))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_mnemonic() {
+        let dir = TempDir::new().unwrap();
+        let (_, client) = spawn_node();
+        assert!(restore(&options(&dir), &client, "abandon abandon", "").is_err());
+    }
+}
+
+mod multisig {
+    use super::*;
+
+    #[test]
+    fn cosigners_sign_separately_and_combine() {
+        let (blockchain, client) = spawn_node();
+        let dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+        let public_keys: Vec<String> = dirs
+            .iter()
+            .map(|dir| {
+                let address = new_key(&options(dir), "").unwrap();
+                return public_key(&options(dir), &address).unwrap();
+            })
+            .collect();
+        let policy = multisig_policy(2, &public_keys).unwrap();
+        client
+            .call(
+                "generatetoaddress",
+                serde_json::json!([1, policy.address()]),
+            )
+            .unwrap();
+
+        let utxos = client.list_unspent(&[policy.address()]).unwrap();
+        let recipient = "cd".repeat(32);
+        let unsigned =
+            create_multisig_transaction(&policy, &utxos, &recipient, 30, 1, None).unwrap();
+        assert_eq!(unsigned.outputs[1].address, policy.address());
+        let first = sign_transaction(&options(&dirs[0]), &unsigned.encode_hex()).unwrap();
+        assert!(broadcast(&client, &first.encode_hex()).is_err());
+        let third = sign_transaction(&options(&dirs[2]), &unsigned.encode_hex()).unwrap();
+
+        let combined = combine(&[first.encode_hex(), third.encode_hex()]).unwrap();
+        broadcast(&client, &combined.encode_hex()).unwrap();
+        let mut blockchain = blockchain.lock().unwrap();
+        blockchain.mine_transactions(&recipient).unwrap();
+        let ledger = blockchain.ledger().unwrap();
+        assert_eq!(ledger.balance(&policy.address()), 19);
+    }
+
+    #[test]
+    fn signing_with_unrelated_keystore_fails() {
+        let dir = TempDir::new().unwrap();
+        let other = TempDir::new().unwrap();
+        let address = new_key(&options(&dir), "").unwrap();
+        new_key(&options(&other), "").unwrap();
+        let policy = multisig_policy(1, &[public_key(&options(&dir), &address).unwrap()]).unwrap();
+        let utxos = vec![Utxo {
+            txid: "00".repeat(32),
+            vout: 0,
+            address: policy.address(),
+            amount: 10,
+        }];
+        let unsigned =
+            create_multisig_transaction(&policy, &utxos, &"cd".repeat(32), 10, 0, None).unwrap();
+        assert!(sign_transaction(&options(&other), &unsigned.encode_hex()).is_err());
+        assert!(sign_transaction(&options(&dir), &unsigned.encode_hex()).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_policy() {
+        assert!(multisig_policy(2, &["ab".repeat(32)]).is_err());
+        assert!(combine(&[]).is_err());
+    }
+}
+
+mod htlc {
+    use super::*;
+
+    fn funded_key(dir: &TempDir, client: &RpcClient) -> (String, String) {
+        let address = new_key(&options(dir), "").unwrap();
+        client
+            .call("generatetoaddress", serde_json::json!([1, address]))
+            .unwrap();
+        return (
+            address.clone(),
+            public_key(&options(dir), &address).unwrap(),
+        );
+    }
+
+    fn mine(blockchain: &Arc<Mutex<Blockchain>>) {
+        blockchain
+            .lock()
+            .unwrap()
+            .mine_transactions(&"ab".repeat(32))
+            .unwrap();
+    }
+
+    #[test]
+    fn atomic_swap_between_two_chains() {
+        let (chain_a, node_a) = spawn_node();
+        let (chain_b, node_b) = spawn_node();
+        let alice = TempDir::new().unwrap();
+        let bob = TempDir::new().unwrap();
+        let (alice_address, alice_key) = funded_key(&alice, &node_a);
+        let (bob_address, bob_key) = funded_key(&bob, &node_b);
+        let (preimage, hash) = htlc_secret();
+
+        let alice_htlc = htlc_contract(&options(&alice), &bob_key, &hash, 20, None).unwrap();
+        htlc_fund(&options(&alice), &node_a, &alice_htlc, 30, 1).unwrap();
+        let bob_htlc = htlc_contract(&options(&bob), &alice_key, &hash, 10, None).unwrap();
+        htlc_fund(&options(&bob), &node_b, &bob_htlc, 30, 1).unwrap();
+        mine(&chain_a);
+        mine(&chain_b);
+        let alice_script = hex::encode(alice_htlc.locking_script());
+        let bob_script = hex::encode(bob_htlc.locking_script());
+        assert_eq!(htlc_preimage(&node_b, &bob_script), Ok(None));
+
+        htlc_claim(&options(&alice), &node_b, &bob_script, &preimage, 1).unwrap();
+        let revealed = htlc_preimage(&node_b, &bob_script).unwrap().unwrap();
+        assert_eq!(revealed, preimage);
+        mine(&chain_b);
+        assert_eq!(htlc_preimage(&node_b, &bob_script), Ok(Some(preimage)));
+
+        htlc_claim(&options(&bob), &node_a, &alice_script, &revealed, 1).unwrap();
+        mine(&chain_a);
+        let ledger_a = chain_a.lock().unwrap().ledger().unwrap();
+        let ledger_b = chain_b.lock().unwrap().ledger().unwrap();
+        assert_eq!(ledger_a.balance(&bob_address), 29);
+        assert_eq!(ledger_a.balance(&alice_address), 19);
+        assert_eq!(ledger_a.balance(&alice_htlc.address()), 0);
+        assert_eq!(ledger_b.balance(&alice_address), 29);
+        assert_eq!(ledger_b.balance(&bob_address), 19);
+    }
+
+    #[test]
+    fn refund_after_timeout() {
+        let (blockchain, client) = spawn_node();
+        let alice = TempDir::new().unwrap();
+        let bob = TempDir::new().unwrap();
+        let (alice_address, _) = funded_key(&alice, &client);
+        let bob_key = public_key(&options(&bob), &new_key(&options(&bob), "").unwrap()).unwrap();
+        let (preimage, hash) = htlc_secret();
+        let htlc = htlc_contract(&options(&alice), &bob_key, &hash, 4, None).unwrap();
+        htlc_fund(&options(&alice), &client, &htlc, 30, 1).unwrap();
+        mine(&blockchain);
+        let script = hex::encode(htlc.locking_script());
+
+        assert!(htlc_refund(&options(&alice), &client, &script, 1).is_err());
+        assert!(htlc_refund(&options(&bob), &client, &script, 1).is_err());
+        assert!(htlc_claim(&options(&alice), &client, &script, &preimage, 1).is_err());
+        assert!(htlc_claim(&options(&bob), &client, &script, &"00".repeat(32), 1).is_err());
+        mine(&blockchain);
+        htlc_refund(&options(&alice), &client, &script, 1).unwrap();
+        mine(&blockchain);
+        let ledger = blockchain.lock().unwrap().ledger().unwrap();
+        assert_eq!(ledger.balance(&alice_address), 48);
+        assert_eq!(htlc_preimage(&client, &script), Ok(None));
+    }
+
+    #[test]
+    fn rejects_malformed_contracts() {
+        let dir = TempDir::new().unwrap();
+        new_key(&options(&dir), "").unwrap();
+        assert!(htlc_contract(&options(&dir), "abcd", &"00".repeat(32), 5, None).is_err());
+        assert!(htlc_contract(&options(&dir), &"00".repeat(32), "zz", 5, None).is_err());
+        assert_eq!(
+            htlc_contract(
+                &options(&dir),
+                &"00".repeat(32),
+                &"00".repeat(32),
+                u64::MAX,
+                None
+            ),
+            Err(format!("timeout {} is above {}", u64::MAX, i64::MAX))
+        );
+        let (_, client) = spawn_node();
+        assert!(htlc_preimage(&client, "51").is_err());
+    }
+}
+
+mod authority {
+    use super::*;
+    use blockchain::{
+        authority::AuthorityParams,
+        params::{ChainParams, Consensus},
+    };
+    use crypto::keys::KeyPair;
+
+    #[test]
+    fn vote_adds_an_authority_through_the_node() {
+        let dir = TempDir::new().unwrap();
+        let options = options(&dir);
+        let address = new_key(&options, "authority").unwrap();
+        let secret = hex::decode(export_key(&options, &address).unwrap()).unwrap();
+        let (blockchain, client) = spawn_node();
+        {
+            let mut blockchain = blockchain.lock().unwrap();
+            let consensus = Consensus::ProofOfAuthority(AuthorityParams {
+                slot_ms: 5,
+                authorities: vec![address.clone()],
+            });
+            blockchain
+                .set_params(ChainParams {
+                    consensus,
+                    ..ChainParams::default()
+                })
+                .unwrap();
+            blockchain.set_validator_key(KeyPair::from_secret_bytes(&secret).unwrap());
+        }
+        let candidate = KeyPair::generate().address();
+        vote(&options, &client, "authority", &candidate, true).unwrap();
+        assert!(vote(&options, &client, "authority", &candidate, true).is_err());
+        blockchain
+            .lock()
+            .unwrap()
+            .mine_transactions(&address)
+            .unwrap();
+        let authorities = client.authorities().unwrap();
+        assert_eq!(authorities.epoch, 1);
+        assert!(authorities.authorities.contains(&candidate));
+        assert!(vote(&options, &client, "missing", &candidate, false).is_err());
+    }
+
+    #[test]
+    fn vote_needs_a_proof_of_authority_node() {
+        let dir = TempDir::new().unwrap();
+        let options = options(&dir);
+        new_key(&options, "authority").unwrap();
+        let (_, client) = spawn_node();
+        let candidate = KeyPair::generate().address();
+        assert!(vote(&options, &client, "authority", &candidate, true).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use crate::keystore::*;
+
+use crypto::keys::KeyPair;
+use std::fs;
+use tempfile::TempDir;
+
+const TEST_LOG_N: u8 = 4;
+
+fn saved_keystore(dir: &TempDir) -> (std::path::PathBuf, String) {
+    let path = dir.path().join("wallet.json");
+    let mut keystore = Keystore::create(&path, TEST_LOG_N).unwrap();
+    let address = keystore.generate("savings").unwrap();
+    keystore.save("correct horse").unwrap();
+    return (path, address);
+}
+
+mod open {
+    use super::*;
+
+    #[test]
+    fn round_trips_keys_with_passphrase() {
+        let dir = TempDir::new().unwrap();
+        let (path, address) = saved_keystore(&dir);
+        let keystore = Keystore::open(&path, "correct horse").unwrap();
+        assert_eq!(
+            keystore.entries(),
+            vec![KeyEntry {
+                label: String::from("savings"),
+                address: address.clone(),
+            }]
+        );
+        assert_eq!(keystore.find("savings").unwrap().address(), address);
+    }
+
+    #[test]
+    fn rejects_wrong_passphrase() {
+        let dir = TempDir::new().unwrap();
+        let (path, _) = saved_keystore(&dir);
+        assert!(Keystore::open(&path, "battery staple").is_err());
+    }
+
+    #[test]
+    fn does_not_store_secrets_in_plaintext() {
+        let dir = TempDir::new().unwrap();
+        let (path, _) = saved_keystore(&dir);
+        let keystore = Keystore::open(&path, "correct horse").unwrap();
+        let secret = keystore.export("savings").unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains(&secret));
+        assert!(!contents.contains("savings"));
+    }
+
+    #[test]
+    fn rejects_tampered_ciphertext() {
+        let dir = TempDir::new().unwrap();
+        let (path, _) = saved_keystore(&dir);
+        let mut file: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let ciphertext = file["ciphertext"].as_str().unwrap();
+        let flipped = if ciphertext.starts_with('0') {
+            "1"
+        } else {
+            "0"
+        };
+        file["ciphertext"] = serde_json::json!(format!("{}{}", flipped, &ciphertext[1..]));
+        fs::write(&path, file.to_string()).unwrap();
+        assert!(Keystore::open(&path, "correct horse").is_err());
+    }
+}
+
+mod create {
+    use super::*;
+
+    #[test]
+    fn refuses_to_overwrite_existing_file() {
+        let dir = TempDir::new().unwrap();
+        let (path, _) = saved_keystore(&dir);
+        assert!(Keystore::create(&path, TEST_LOG_N).is_err());
+    }
+}
+
+mod import {
+    use super::*;
+
+    #[test]
+    fn imports_exported_secret() {
+        let dir = TempDir::new().unwrap();
+        let key_pair = KeyPair::generate();
+        let mut keystore = Keystore::create(&dir.path().join("wallet.json"), TEST_LOG_N).unwrap();
+        let secret = hex::encode(key_pair.secret_bytes());
+        assert_eq!(keystore.import("", &secret), Ok(key_pair.address()));
+        assert_eq!(keystore.export(&key_pair.address()), Some(secret));
+    }
+
+    #[test]
+    fn rejects_duplicates_and_malformed_secrets() {
+        let dir = TempDir::new().unwrap();
+        let mut keystore = Keystore::create(&dir.path().join("wallet.json"), TEST_LOG_N).unwrap();
+        let secret = hex::encode(KeyPair::generate().secret_bytes());
+        keystore.import("main", &secret).unwrap();
+        assert!(keystore.import("other", &secret).is_err());
+        assert!(keystore.generate("main").is_err());
+        assert!(keystore.import("", "abcd").is_err());
+        assert!(keystore.import("", "not hex").is_err());
+    }
+}
@@ -0,0 +1,3 @@
+mod builder_test;
+mod commands_test;
+mod keystore_test;